@@ -19,6 +19,12 @@ in
       description = "WiFi interface to use for AP mode (or 'auto' to detect)";
     };
 
+    staInterface = mkOption {
+      type = types.str;
+      default = "auto";
+      description = "Second WiFi interface used for scanning while the AP is up ('auto', 'none', or an interface name)";
+    };
+
     ssid = mkOption {
       type = types.str;
       default = "HyperRecovery";
@@ -43,11 +49,277 @@ in
       description = "Seconds to wait for existing network before starting AP";
     };
 
+    fastReconnect = mkOption {
+      type = types.bool;
+      default = false;
+      description = "Before the grace period, rejoin the last connected network if it is saved and in range";
+    };
+
+    portalHostname = mkOption {
+      type = types.str;
+      default = "hyper.setup";
+      description = "Hostname that resolves to the captive portal on the setup AP (empty to disable)";
+    };
+
+    persist = mkOption {
+      type = types.bool;
+      default = false;
+      description = "Keep the daemon running after connecting and restart the AP if connectivity is lost";
+    };
+
+    keepApAfterConnect = mkOption {
+      type = types.bool;
+      default = false;
+      description = "With persist, leave the setup AP up after connecting; needs staInterface set to a second radio";
+    };
+
+    linkLossGrace = mkOption {
+      type = types.int;
+      default = 30;
+      description = "Seconds connectivity may be lost in persist mode before the AP is restarted";
+    };
+
+    useNmProfiles = mkOption {
+      type = types.bool;
+      default = false;
+      description = "Also auto-connect to networks NetworkManager already has saved WiFi profiles for";
+    };
+
+    autoConnectBudget = mkOption {
+      type = types.ints.unsigned;
+      default = 0;
+      description = "Seconds to spend trying every available saved network in priority order before raising the setup AP (0 tries only the best one)";
+    };
+
+    scanTtl = mkOption {
+      type = types.int;
+      default = 60;
+      description = "Seconds before the portal treats the network list as stale and rescans";
+    };
+
+    scanTimeout = mkOption {
+      type = types.ints.positive;
+      default = 6;
+      description = "Seconds a scan waits for fresh results; raise for slow radios";
+    };
+
+    dbus = mkOption {
+      type = types.bool;
+      default = false;
+      description = "Also expose the org.hyper.Recovery.WifiSetup service on the system bus";
+    };
+
+    dhcpStartOctet = mkOption {
+      type = types.ints.between 2 254;
+      default = 10;
+      description = "Last octet of the first DHCP address handed out on the AP subnet";
+    };
+
+    dhcpEndOctet = mkOption {
+      type = types.ints.between 2 254;
+      default = 250;
+      description = "Last octet of the final DHCP address handed out on the AP subnet";
+    };
+
+    dhcpLease = mkOption {
+      type = types.str;
+      default = "12h";
+      description = "DHCP lease time for AP clients, in dnsmasq syntax (e.g. '12h', '10m', 'infinite')";
+    };
+
+    apDnsMode = mkOption {
+      type = types.enum [ "captive" "passthrough" ];
+      default = "captive";
+      description = "DNS for AP clients: 'captive' resolves every name to the portal, 'passthrough' forwards queries to real resolvers";
+    };
+
+    apDnsUpstreams = mkOption {
+      type = types.listOf types.str;
+      default = [ ];
+      example = [ "1.1.1.1" "9.9.9.9" ];
+      description = "Resolvers AP clients' queries are forwarded to in passthrough mode; empty uses the system's resolv.conf";
+    };
+
+    audit = mkOption {
+      type = types.bool;
+      default = true;
+      description = "Record connection attempts in /var/log/hyper-connect/attempts.jsonl";
+    };
+
+    apBand = mkOption {
+      type = types.enum [ "2.4" "5" "auto" ];
+      default = "2.4";
+      description = "Band for the setup AP ('auto' prefers 5GHz when the adapter supports it on a non-DFS channel)";
+    };
+
+    apPasswordFile = mkOption {
+      type = types.nullOr types.str;
+      default = null;
+      example = "/run/secrets/hyper-connect-ap";
+      description = "Runtime path (e.g. under /run/secrets) of a file holding a WPA passphrase (8-63 characters) for the setup AP; read at startup and never copied into the Nix store, so don't pass a Nix path literal. null leaves the AP open";
+    };
+
+    apSecurity = mkOption {
+      type = types.enum [ "wpa2" "wpa3" "wpa2-wpa3" ];
+      default = "wpa2";
+      description = "Setup AP security when apPasswordFile is set; WPA3 modes fall back to WPA2 if hostapd lacks SAE support";
+    };
+
+    apIdleTimeout = mkOption {
+      type = types.int;
+      default = 0;
+      description = "Seconds without AP clients or portal requests before the setup AP is shut down (0 to never shut it down)";
+    };
+
+    apMaxClients = mkOption {
+      type = types.nullOr (types.ints.between 1 2007);
+      default = null;
+      description = "Most devices that may join the setup AP at once (hostapd max_num_sta); null keeps hostapd's default";
+    };
+
+    apBeaconInterval = mkOption {
+      type = types.nullOr (types.ints.between 15 65535);
+      default = null;
+      description = "Setup AP beacon interval in time units of 1.024ms (hostapd beacon_int); null keeps hostapd's default";
+    };
+
+    hostapdExtra = mkOption {
+      type = types.nullOr types.str;
+      default = null;
+      example = "/etc/hyper-connect/hostapd-extra.conf";
+      description = "Runtime path of a file whose lines are appended verbatim to the generated hostapd config, for adapters that need extra directives; read at startup rather than copied into the Nix store";
+    };
+
+    allowOpenSilently = mkOption {
+      type = types.bool;
+      default = false;
+      description = "Join unencrypted networks from the portal and TUI without an insecure-network confirmation";
+    };
+
+    portalTitle = mkOption {
+      type = types.str;
+      default = "Hyper Recovery";
+      description = "Title shown in the captive portal header and the TUI";
+    };
+
+    portalSubtitle = mkOption {
+      type = types.str;
+      default = "WiFi Setup Module";
+      description = "Subtitle shown under the portal title";
+    };
+
+    lang = mkOption {
+      type = types.nullOr (types.enum [ "en" "de" ]);
+      default = null;
+      description = "Portal language; null follows each browser's Accept-Language";
+    };
+
+    connectivityProbe = mkOption {
+      type = types.nullOr types.str;
+      default = null;
+      example = "http://updates.example.com/generate_204";
+      description = "HTTP URL probed when NetworkManager reports limited connectivity; any 2xx answer counts as online";
+    };
+
+    successUrl = mkOption {
+      type = types.nullOr types.str;
+      default = null;
+      example = "https://recovery.example.com/next-steps";
+      description = "http(s) URL captive-portal checks and the portal redirect to once connected; null answers checks as online";
+    };
+
+    minSignal = mkOption {
+      type = types.ints.between 0 100;
+      default = 0;
+      description = "Hide networks weaker than this signal percentage from the portal and TUI lists (they remain available for auto-connect)";
+    };
+
+    ipFamily = mkOption {
+      type = types.enum [ "dual" "v4" "v6" ];
+      default = "dual";
+      description = "IP families configured on the joined network; 'v4' or 'v6' keeps activation from waiting on a family the network doesn't offer";
+    };
+
+    portalMaxNetworks = mkOption {
+      type = types.ints.unsigned;
+      default = 20;
+      description = "Strongest networks the portal lists before a 'Show all' control (0 lists every network)";
+    };
+
+    tls = mkOption {
+      type = types.bool;
+      default = false;
+      description = "Also serve the portal over HTTPS with a self-signed certificate generated at boot (exported to /var/lib/hyper-connect/portal-cert.pem); captive-portal checks stay on HTTP";
+    };
+
+    tlsPort = mkOption {
+      type = types.port;
+      default = 443;
+      description = "Port for the HTTPS portal when tls is enabled";
+    };
+
+    portalPollMs = mkOption {
+      type = types.ints.between 500 60000;
+      default = 2500;
+      description = "Milliseconds between portal status polls while idle; connecting polls and the periodic page reload scale with it. Raise on low-power hardware";
+    };
+
+    logFile = mkOption {
+      type = types.nullOr types.str;
+      default = null;
+      example = "/var/log/hyper-connect/daemon.log";
+      description = "Also write the daemon log to this file so a failed setup can be debugged after a reboot (rotated at 10 MiB, mode 0600)";
+    };
+
+    onConnected = mkOption {
+      type = types.nullOr types.str;
+      default = null;
+      example = "touch /run/wifi-up";
+      description = "Shell command run once WiFi is connected, with HYPER_SSID, HYPER_IFACE and HYPER_IP set; killed after 30s";
+    };
+
+    socket = mkOption {
+      type = types.str;
+      default = "/run/hyper-connect.sock";
+      description = "IPC socket shared by the daemon and the TUI";
+    };
+
+    socketMode = mkOption {
+      type = types.str;
+      default = "0660";
+      description = "Octal permission mode of the IPC socket";
+    };
+
+    socketGroup = mkOption {
+      type = types.nullOr types.str;
+      default = null;
+      example = "wheel";
+      description = "Group owning the IPC socket so its members can run the TUI without root";
+    };
+
+    ipcToken = mkOption {
+      type = types.bool;
+      default = false;
+      description = "Require IPC clients to present a random token written next to the socket, readable by root and socketGroup";
+    };
+
+    ipcMaxClients = mkOption {
+      type = types.ints.positive;
+      default = 16;
+      description = "IPC connections served at once; further clients are refused";
+    };
+
     autoStartTui = mkOption {
       type = types.bool;
       default = false;
       description = "Automatically start TUI and focus tty1";
     };
+
+    tuiIdleDimSecs = mkOption {
+      type = types.nullOr types.ints.positive;
+      default = null;
+      description = "Dim the TUI after this many seconds without input to reduce burn-in on unattended screens; null never dims";
+    };
   };
 
   config = mkIf cfg.enable {
@@ -68,12 +340,14 @@ in
       wants = [ "NetworkManager.service" ];
 
       serviceConfig = {
-        Type = "simple";
-        ExecStart = "${hyperConnect}/bin/hyper-connect daemon --interface ${cfg.interface} --ssid ${cfg.ssid} --ap-ip ${cfg.apIp} --port ${toString cfg.port} --grace-period ${toString cfg.gracePeriod}";
+        Type = "notify";
+        ExecStart = "${hyperConnect}/bin/hyper-connect daemon --interface ${cfg.interface} --sta-interface ${cfg.staInterface} --ssid ${cfg.ssid} --ap-ip ${cfg.apIp} --port ${toString cfg.port} --grace-period ${toString cfg.gracePeriod}${optionalString cfg.fastReconnect " --fast-reconnect"} --portal-hostname=${cfg.portalHostname} --link-loss-grace ${toString cfg.linkLossGrace} --auto-connect-budget ${toString cfg.autoConnectBudget} --scan-ttl ${toString cfg.scanTtl} --scan-timeout ${toString cfg.scanTimeout} --dhcp-start-octet ${toString cfg.dhcpStartOctet} --dhcp-end-octet ${toString cfg.dhcpEndOctet} --dhcp-lease ${cfg.dhcpLease} --ap-dns-mode ${cfg.apDnsMode}${concatMapStrings (ip: " --ap-dns-upstream ${escapeShellArg ip}") cfg.apDnsUpstreams} --ap-band ${cfg.apBand}${optionalString (cfg.apPasswordFile != null) " --ap-password-file ${escapeShellArg cfg.apPasswordFile} --ap-security ${cfg.apSecurity}"} --ap-idle-timeout ${toString cfg.apIdleTimeout}${optionalString (cfg.apMaxClients != null) " --ap-max-clients ${toString cfg.apMaxClients}"}${optionalString (cfg.apBeaconInterval != null) " --ap-beacon-interval ${toString cfg.apBeaconInterval}"}${optionalString (cfg.hostapdExtra != null) " --hostapd-extra ${escapeShellArg cfg.hostapdExtra}"} --portal-title ${escapeShellArg cfg.portalTitle} --portal-subtitle ${escapeShellArg cfg.portalSubtitle}${optionalString (cfg.lang != null) " --lang ${cfg.lang}"} --min-signal ${toString cfg.minSignal} --ip-family ${cfg.ipFamily} --portal-max-networks ${toString cfg.portalMaxNetworks} --portal-poll-ms ${toString cfg.portalPollMs}${optionalString cfg.tls " --tls --tls-port ${toString cfg.tlsPort}"}${optionalString (cfg.connectivityProbe != null) " --connectivity-probe ${escapeShellArg cfg.connectivityProbe}"}${optionalString (cfg.successUrl != null) " --success-url ${escapeShellArg cfg.successUrl}"}${optionalString (cfg.logFile != null) " --log-file ${escapeShellArg cfg.logFile}"}${optionalString (cfg.onConnected != null) " --on-connected ${escapeShellArg cfg.onConnected}"} --socket ${escapeShellArg cfg.socket} --socket-mode ${cfg.socketMode}${optionalString (cfg.socketGroup != null) " --socket-group ${escapeShellArg cfg.socketGroup}"}${optionalString cfg.ipcToken " --ipc-token"} --ipc-max-clients ${toString cfg.ipcMaxClients}${optionalString cfg.persist " --persist"}${optionalString cfg.keepApAfterConnect " --keep-ap-after-connect"}${optionalString cfg.useNmProfiles " --use-nm-profiles"}${optionalString cfg.dbus " --dbus"}${optionalString (!cfg.audit) " --no-audit"}${optionalString cfg.allowOpenSilently " --allow-open-silently"}";
         Restart = "on-failure";
         RestartSec = "5s";
         RuntimeDirectory = "hyper-connect";
         RuntimeDirectoryMode = "0755";
+        LogsDirectory = "hyper-connect";
+        LogsDirectoryMode = "0700";
 
         # Security hardening (limited due to network requirements)
         ProtectSystem = "strict";
@@ -106,7 +380,7 @@ in
       serviceConfig = {
         Type = "simple";
         ExecStartPre = "${pkgs.kbd}/bin/chvt 1";
-        ExecStart = "${hyperConnect}/bin/hyper-connect tui";
+        ExecStart = "${hyperConnect}/bin/hyper-connect tui --socket ${escapeShellArg cfg.socket}${optionalString cfg.allowOpenSilently " --allow-open-silently"}${optionalString (cfg.tuiIdleDimSecs != null) " --idle-dim-secs ${toString cfg.tuiIdleDimSecs}"}";
         Restart = "no";
 
         StandardInput = "tty-force";
@@ -130,9 +404,23 @@ in
       "d /var/lib/hyper-connect 0700 root root -"
     ];
 
+    # Only root may own or call the service; it can change networks and forget credentials.
+    services.dbus.packages = mkIf cfg.dbus [
+      (pkgs.writeTextDir "share/dbus-1/system.d/org.hyper.Recovery.WifiSetup.conf" ''
+        <!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-BUS Bus Configuration 1.0//EN"
+         "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+        <busconfig>
+          <policy user="root">
+            <allow own="org.hyper.Recovery.WifiSetup"/>
+            <allow send_destination="org.hyper.Recovery.WifiSetup"/>
+          </policy>
+        </busconfig>
+      '')
+    ];
+
     # Firewall rules for captive portal
     networking.firewall = {
-      allowedTCPPorts = [ cfg.port 53 ];
+      allowedTCPPorts = [ cfg.port 53 ] ++ optional cfg.tls cfg.tlsPort;
       allowedUDPPorts = [ 53 67 68 ];
     };
   };
@@ -1,7 +1,15 @@
 //! Access Point management using hostapd and dnsmasq
 
+use super::backend;
+use super::network_manager;
+use super::{parse_bssid, ApBand, ApClient, ApDnsMode, ApSecurity, DaemonConfig, DhcpRange};
 use anyhow::{bail, Context, Result};
-use std::net::Ipv4Addr;
+use std::collections::VecDeque;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 use tokio::process::{Child, Command};
 use tokio::sync::{Mutex, OnceCell};
 use zbus::Connection;
@@ -12,13 +20,43 @@ static DNSMASQ_HANDLE: OnceCell<Mutex<Option<Child>>> = OnceCell::const_new();
 const RUNTIME_DIR: &str = "/run/hyper-connect";
 const HOSTAPD_CONF_PATH: &str = "/run/hyper-connect/hyper-hostapd.conf";
 const DNSMASQ_CONF_PATH: &str = "/run/hyper-connect/hyper-dnsmasq.conf";
+const DNSMASQ_LEASES_PATH: &str = "/run/hyper-connect/dnsmasq.leases";
 const NM_DEST: &str = "org.freedesktop.NetworkManager";
 const NM_PATH: &str = "/org/freedesktop/NetworkManager";
 const NM_IFACE: &str = "org.freedesktop.NetworkManager";
 const NM_DEVICE_IFACE: &str = "org.freedesktop.NetworkManager.Device";
+const NM_DEVICE_STATE_UNMANAGED: u32 = 10;
+/// How long NetworkManager gets to let go of the device after a failed connect.
+const STATION_RELEASE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(8);
+/// Child output lines kept for diagnosing an early exit.
+const CHILD_TAIL_LINES: usize = 12;
+/// Longest child output line forwarded; longer lines are skipped, not buffered.
+const CHILD_LINE_MAX: usize = 1024;
+/// 5GHz channels that never need radar detection, in order of preference.
+const NON_DFS_5GHZ_CHANNELS: [u8; 9] = [36, 40, 44, 48, 149, 153, 157, 161, 165];
+/// First hostapd release with usable WPA3-Personal (SAE) AP support.
+const SAE_MIN_HOSTAPD_VERSION: (u32, u32) = (2, 7);
+
+/// AP startup failures the controller reports distinctly.
+#[derive(Debug, thiserror::Error)]
+pub enum ApStartError {
+    #[error("This adapter cannot host a setup AP ({interface} does not support AP mode)")]
+    Unsupported { interface: String },
+    #[error("WiFi is disabled by hardware switch ({interface} is rfkill hard-blocked)")]
+    HardBlocked { interface: String },
+    #[error("{interface} cannot host a 5GHz AP on a channel without radar detection")]
+    No5GhzChannel { interface: String },
+    #[error("hostapd exited early ({status}):\n{output}")]
+    HostapdExited { status: String, output: String },
+    #[error("interface busy: {holders} still holds {interface}")]
+    InterfaceBusy { interface: String, holders: String },
+}
 
 /// Start the WiFi access point
-pub async fn start_ap(interface: &str, ssid: &str, ap_ip: &str) -> Result<()> {
+pub async fn start_ap(config: &DaemonConfig) -> Result<()> {
+    let interface = config.interface.as_str();
+    let ssid = config.ssid.as_str();
+    let ap_ip = config.ap_ip.as_str();
     tracing::info!(
         interface = %interface,
         ssid = %ssid,
@@ -26,19 +64,66 @@ pub async fn start_ap(interface: &str, ssid: &str, ap_ip: &str) -> Result<()> {
         "Starting access point"
     );
 
-    let result = start_ap_inner(interface, ssid, ap_ip).await;
+    sanitize_ap_ssid(ssid)?;
+
+    if network_manager::unblock_radio(interface).await == network_manager::RadioBlock::Hard {
+        return Err(ApStartError::HardBlocked {
+            interface: interface.to_string(),
+        }
+        .into());
+    }
+
+    // Bail before touching the device: tearing down the station side for a
+    // radio that can never host the AP would only leave it unusable.
+    let caps = probe_phy(interface).await;
+    if caps.as_ref().is_some_and(|caps| !caps.supports_ap) {
+        return Err(ApStartError::Unsupported {
+            interface: interface.to_string(),
+        }
+        .into());
+    }
+    let radio =
+        select_radio(config.ap_band, caps.as_ref()).ok_or_else(|| ApStartError::No5GhzChannel {
+            interface: interface.to_string(),
+        })?;
+    tracing::info!(
+        hw_mode = radio.hw_mode,
+        channel = radio.channel,
+        "Selected AP radio settings"
+    );
+
+    // hostapd expects exclusive control of the nl80211 interface, so both
+    // NetworkManager and its WiFi backend need to release the device.
+    release_device_from_nm(interface).await;
+
+    let backend = backend::active().await;
+    let release = backend::RadioRelease::for_config(config);
+    let result = backend::with_radio_released(&backend, release, interface, async {
+        let result = start_ap_inner(config, &radio).await;
+        if result.is_err() {
+            let _ = stop_ap().await;
+        }
+        result
+    })
+    .await;
+
     if let Err(err) = result {
-        tracing::warn!(error = %err, "AP start failed; attempting to restore WiFi services");
-        let _ = stop_ap().await;
-        let _ = restore_device_after_ap(interface).await;
+        tracing::warn!(error = %err, "AP start failed; restored WiFi services");
+        remove_ap_address(interface, &config.ap_ip).await;
+        set_device_managed(interface).await;
         return Err(err);
     }
 
     Ok(())
 }
 
-async fn start_ap_inner(interface: &str, ssid: &str, ap_ip: &str) -> Result<()> {
-    prepare_device_for_ap(interface).await?;
+async fn start_ap_inner(config: &DaemonConfig, radio: &ApRadio) -> Result<()> {
+    let interface = config.interface.as_str();
+    let ssid = config.ssid.as_str();
+    let ap_ip = config.ap_ip.as_str();
+
+    wait_for_station_disconnect(interface, std::time::Duration::from_secs(6)).await?;
+    ensure_interface_free(interface).await?;
 
     // Put the interface into a clean state before hostapd touches it.
     let _ = Command::new("ip")
@@ -50,21 +135,27 @@ async fn start_ap_inner(interface: &str, ssid: &str, ap_ip: &str) -> Result<()>
         .output()
         .await;
 
-    // Create hostapd config
-    let hostapd_conf = format!(
-        r#"interface={}
-driver=nl80211
-ssid={}
-hw_mode=g
-channel=6
-wmm_enabled=0
-macaddr_acl=0
-auth_algs=1
-ignore_broadcast_ssid=0
-wpa=0
-"#,
-        interface, ssid
+    let wpa = match &config.ap_password {
+        Some(password) => Some((
+            supported_security(config.ap_security).await,
+            password.as_str(),
+        )),
+        None => None,
+    };
+    let mut hostapd_conf = build_hostapd_config(
+        interface,
+        ssid,
+        radio,
+        wpa,
+        config.ap_max_clients,
+        config.ap_beacon_interval,
     );
+    // Last, so hostapd lets the extra directives override generated ones.
+    if let Some(extra) = &config.hostapd_extra {
+        hostapd_conf.push_str("# --hostapd-extra\n");
+        hostapd_conf.push_str(extra);
+    }
+    tracing::debug!(config = %redact_passphrase(&hostapd_conf), "hostapd config");
 
     tokio::fs::create_dir_all(RUNTIME_DIR)
         .await
@@ -73,26 +164,14 @@ wpa=0
         .await
         .context("Failed to write hostapd config")?;
 
-    let ap_ip_addr: Ipv4Addr = ap_ip
-        .parse()
-        .with_context(|| format!("Invalid AP IP address: '{}'", ap_ip))?;
-    let [a, b, c, _] = ap_ip_addr.octets();
-    let dhcp_start = format!("{}.{}.{}.10", a, b, c);
-    let dhcp_end = format!("{}.{}.{}.250", a, b, c);
-
-    // Create dnsmasq config
-    let dnsmasq_conf = format!(
-        r#"interface={}
-bind-dynamic
-dhcp-leasefile={}/dnsmasq.leases
-pid-file={}/dnsmasq.pid
-dhcp-range={},{},255.255.255.0,12h
-dhcp-option=option:router,{}
-dhcp-option=option:dns-server,{}
-address=/#/{}
-"#,
-        interface, RUNTIME_DIR, RUNTIME_DIR, dhcp_start, dhcp_end, ap_ip, ap_ip, ap_ip
-    );
+    let dnsmasq_conf = build_dnsmasq_config(
+        interface,
+        ap_ip,
+        &config.portal_hostname,
+        &config.dhcp,
+        config.ap_dns_mode,
+        &config.ap_dns_upstreams,
+    )?;
 
     tokio::fs::write(DNSMASQ_CONF_PATH, &dnsmasq_conf)
         .await
@@ -104,9 +183,16 @@ address=/#/{}
     let mut hostapd = Command::new("hostapd")
         .arg("-d")
         .arg(HOSTAPD_CONF_PATH)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()
         .context("Failed to start hostapd")?;
 
+    // hostapd -d is chatty; keep draining its pipes so it never blocks, and
+    // remember the tail in case it dies.
+    let tail = ChildTail::default();
+    let readers = forward_child_output("hostapd", &mut hostapd, &tail);
+
     // Wait for hostapd to initialize
     tokio::time::sleep(std::time::Duration::from_secs(2)).await;
 
@@ -114,7 +200,16 @@ address=/#/{}
         .try_wait()
         .context("Failed to check hostapd process")?
     {
-        bail!("hostapd exited early with status: {}", status);
+        // The pipes hit EOF once the process is gone; give the readers a moment to finish.
+        for reader in readers.into_iter().flatten() {
+            let _ = tokio::time::timeout(std::time::Duration::from_secs(1), reader).await;
+        }
+        let output = Vec::from(std::mem::take(&mut *tail.lock().unwrap())).join("\n");
+        return Err(ApStartError::HostapdExited {
+            status: status.to_string(),
+            output,
+        }
+        .into());
     }
 
     let hostapd_handle = HOSTAPD_HANDLE
@@ -146,9 +241,13 @@ address=/#/{}
     let mut dnsmasq = Command::new("dnsmasq")
         .arg("--keep-in-foreground")
         .arg("--no-daemon")
-        .arg(&format!("--conf-file={}", DNSMASQ_CONF_PATH))
+        .arg(format!("--conf-file={}", DNSMASQ_CONF_PATH))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()
         .context("Failed to start dnsmasq")?;
+    let tail = ChildTail::default();
+    let readers = forward_child_output("dnsmasq", &mut dnsmasq, &tail);
 
     tokio::time::sleep(std::time::Duration::from_millis(500)).await;
 
@@ -156,8 +255,20 @@ address=/#/{}
         .try_wait()
         .context("Failed to check dnsmasq process")?
     {
+        for reader in readers.into_iter().flatten() {
+            let _ = tokio::time::timeout(std::time::Duration::from_secs(1), reader).await;
+        }
+        let output = Vec::from(std::mem::take(&mut *tail.lock().unwrap())).join("\n");
         let _ = stop_ap().await;
-        bail!("dnsmasq exited early with status: {}", status);
+        bail!(
+            "dnsmasq exited early with status: {}{}",
+            status,
+            if output.is_empty() {
+                String::new()
+            } else {
+                format!("\n{}", output)
+            }
+        );
     }
 
     let dnsmasq_handle = DNSMASQ_HANDLE
@@ -172,14 +283,477 @@ address=/#/{}
     Ok(())
 }
 
-pub async fn restore_device_after_ap(interface: &str) -> Result<()> {
-    // Ensure iwd is available again for NetworkManager's WiFi backend.
-    let _ = Command::new("systemctl")
-        .args(["start", "iwd.service"])
+/// Last few output lines of a child process, shared by its pipe readers.
+type ChildTail = Arc<std::sync::Mutex<VecDeque<String>>>;
+
+/// Drain a child's stdout and stderr into the debug log so its pipes never
+/// fill up. Returns the reader tasks, which end when the child exits.
+fn forward_child_output(
+    source: &'static str,
+    child: &mut Child,
+    tail: &ChildTail,
+) -> [Option<tokio::task::JoinHandle<()>>; 2] {
+    [
+        child
+            .stdout
+            .take()
+            .map(|out| tokio::spawn(keep_tail(source, out, tail.clone()))),
+        child
+            .stderr
+            .take()
+            .map(|err| tokio::spawn(keep_tail(source, err, tail.clone()))),
+    ]
+}
+
+/// Forward a child's output to the debug log, keeping the last few lines.
+///
+/// Lines over `CHILD_LINE_MAX` bytes are skipped so a runaway child can't grow
+/// memory without bound.
+async fn keep_tail(source: &'static str, stream: impl AsyncRead + Unpin, tail: ChildTail) {
+    let record = |line: &[u8]| {
+        let line = String::from_utf8_lossy(line).into_owned();
+        tracing::debug!(source, "{}", line);
+        let mut tail = tail.lock().unwrap();
+        if tail.len() == CHILD_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    };
+
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    let mut overlong = false;
+    loop {
+        let buf = match reader.fill_buf().await {
+            Ok(buf) if !buf.is_empty() => buf,
+            _ => break,
+        };
+        let newline = buf.iter().position(|&b| b == b'\n');
+        let chunk = &buf[..newline.unwrap_or(buf.len())];
+        if !overlong {
+            if line.len() + chunk.len() > CHILD_LINE_MAX {
+                overlong = true;
+                line.clear();
+            } else {
+                line.extend_from_slice(chunk);
+            }
+        }
+        let used = chunk.len() + usize::from(newline.is_some());
+        reader.consume(used);
+
+        if newline.is_some() {
+            if overlong {
+                tracing::debug!(source, "<overlong line skipped>");
+            } else {
+                record(&line);
+            }
+            line.clear();
+            overlong = false;
+        }
+    }
+    if !line.is_empty() && !overlong {
+        record(&line);
+    }
+}
+
+/// What the radio behind an interface can do, from `iw phy <phy> info`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct PhyCaps {
+    supports_ap: bool,
+    /// 802.11n (HT) support on the 5GHz band
+    ht_5ghz: bool,
+    /// 802.11ac (VHT) support on the 5GHz band
+    vht_5ghz: bool,
+    /// 5GHz channels usable for an AP: not disabled, no-IR or radar-gated
+    usable_5ghz_channels: Vec<u8>,
+}
+
+/// hostapd radio settings for the AP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ApRadio {
+    hw_mode: &'static str,
+    channel: u8,
+    ieee80211n: bool,
+    ieee80211ac: bool,
+}
+
+impl ApRadio {
+    const DEFAULT_2_4GHZ: ApRadio = ApRadio {
+        hw_mode: "g",
+        channel: 6,
+        ieee80211n: false,
+        ieee80211ac: false,
+    };
+}
+
+/// Probe the radio behind `interface`.
+///
+/// Returns `None` when `iw` is unavailable or its output can't be read, in
+/// which case the caller should just try.
+async fn probe_phy(interface: &str) -> Option<PhyCaps> {
+    let dev_info = Command::new("iw")
+        .args(["dev", interface, "info"])
+        .output()
+        .await
+        .ok()?;
+    let phy = parse_wiphy_index(&String::from_utf8_lossy(&dev_info.stdout))?;
+
+    let phy_info = Command::new("iw")
+        .args(["phy", &format!("phy{}", phy), "info"])
+        .output()
+        .await
+        .ok()?;
+    if !phy_info.status.success() {
+        return None;
+    }
+
+    Some(parse_phy_caps(&String::from_utf8_lossy(&phy_info.stdout)))
+}
+
+/// Pick hostapd radio settings for the requested band.
+///
+/// Returns `None` when 5GHz was explicitly requested but the radio has no
+/// channel it may transmit on without radar detection.
+fn select_radio(band: ApBand, caps: Option<&PhyCaps>) -> Option<ApRadio> {
+    let five_ghz = match caps {
+        Some(caps) => NON_DFS_5GHZ_CHANNELS
+            .iter()
+            .find(|channel| caps.usable_5ghz_channels.contains(channel))
+            .map(|&channel| ApRadio {
+                hw_mode: "a",
+                channel,
+                ieee80211n: caps.ht_5ghz,
+                ieee80211ac: caps.vht_5ghz,
+            }),
+        // Capabilities unknown: only try 5GHz when asked to, on the safest channel.
+        None => (band == ApBand::Band5).then_some(ApRadio {
+            hw_mode: "a",
+            channel: NON_DFS_5GHZ_CHANNELS[0],
+            ieee80211n: false,
+            ieee80211ac: false,
+        }),
+    };
+
+    match band {
+        ApBand::Band2_4 => Some(ApRadio::DEFAULT_2_4GHZ),
+        ApBand::Band5 => five_ghz,
+        ApBand::Auto => Some(five_ghz.unwrap_or(ApRadio::DEFAULT_2_4GHZ)),
+    }
+}
+
+/// Check that `ssid` can be written verbatim as hostapd's `ssid=` line.
+///
+/// hostapd's config is line based, so a newline or other control character
+/// would let the SSID inject further directives; 802.11 caps SSIDs at 32 bytes.
+pub fn sanitize_ap_ssid(ssid: &str) -> Result<&str> {
+    if ssid.is_empty() {
+        bail!("AP SSID must not be empty");
+    }
+    if ssid.len() > 32 {
+        bail!("AP SSID is {} bytes long; the limit is 32", ssid.len());
+    }
+    if ssid.chars().any(char::is_control) {
+        bail!("AP SSID must not contain newlines or other control characters");
+    }
+    Ok(ssid)
+}
+
+/// Read a `--hostapd-extra` file, refusing lines hostapd would misparse.
+///
+/// Lines are kept as written, comments included; only control characters
+/// (tabs, stray carriage returns, escape sequences) are rejected.
+pub fn load_hostapd_extra(path: &Path) -> Result<String> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read hostapd extra config {}", path.display()))?;
+    let mut extra = String::new();
+    for (number, line) in contents.lines().enumerate() {
+        if line.chars().any(char::is_control) {
+            bail!(
+                "{}:{}: hostapd extra config lines must not contain control characters",
+                path.display(),
+                number + 1
+            );
+        }
+        extra.push_str(line);
+        extra.push('\n');
+    }
+    Ok(extra)
+}
+
+/// `conf` with the passphrase blanked out, for logging.
+fn redact_passphrase(conf: &str) -> String {
+    conf.lines()
+        .map(|line| match line.split_once('=') {
+            Some((key @ ("wpa_passphrase" | "sae_password"), _)) => format!("{}=<redacted>\n", key),
+            _ => format!("{}\n", line),
+        })
+        .collect()
+}
+
+/// Check that `password` can be written verbatim as hostapd's `wpa_passphrase=` line.
+///
+/// WPA passphrases are 8 to 63 printable ASCII characters; anything else
+/// would be rejected by hostapd or, with a newline, inject directives.
+pub fn sanitize_ap_password(password: &str) -> Result<&str> {
+    if !(8..=63).contains(&password.len()) {
+        bail!("AP password must be 8 to 63 characters long");
+    }
+    if !password
+        .chars()
+        .all(|c| c.is_ascii() && !c.is_ascii_control())
+    {
+        bail!("AP password must only contain printable ASCII characters");
+    }
+    Ok(password)
+}
+
+/// `requested`, or WPA2 when the installed hostapd is too old for SAE.
+async fn supported_security(requested: ApSecurity) -> ApSecurity {
+    if !requested.needs_sae() {
+        return requested;
+    }
+    let output = Command::new("hostapd").arg("-v").output().await;
+    // hostapd prints its version to stderr and exits non-zero.
+    let version = output.ok().and_then(|output| {
+        parse_hostapd_version(&String::from_utf8_lossy(&output.stderr))
+            .or_else(|| parse_hostapd_version(&String::from_utf8_lossy(&output.stdout)))
+    });
+    match version {
+        Some(version) if version >= SAE_MIN_HOSTAPD_VERSION => requested,
+        _ => {
+            tracing::warn!(
+                version = ?version,
+                "hostapd lacks WPA3 (SAE) support; the setup AP falls back to WPA2"
+            );
+            ApSecurity::Wpa2
+        }
+    }
+}
+
+/// (major, minor) from `hostapd -v` output like "hostapd v2.10".
+fn parse_hostapd_version(output: &str) -> Option<(u32, u32)> {
+    let version = output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("hostapd v"))?;
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// hostapd directives for the setup AP's security: open, or WPA with `passphrase`.
+fn security_directives(wpa: Option<(ApSecurity, &str)>) -> String {
+    let Some((security, passphrase)) = wpa else {
+        return "wpa=0\n".to_string();
+    };
+    // Management frame protection is mandatory for SAE and optional in transition mode.
+    let (key_mgmt, ieee80211w) = match security {
+        ApSecurity::Wpa2 => ("WPA-PSK", None),
+        ApSecurity::Wpa3 => ("SAE", Some(2)),
+        ApSecurity::Wpa2Wpa3 => ("WPA-PSK SAE", Some(1)),
+    };
+    let mut directives = format!("wpa=2\nwpa_key_mgmt={}\nrsn_pairwise=CCMP\n", key_mgmt);
+    if let Some(ieee80211w) = ieee80211w {
+        directives.push_str(&format!("ieee80211w={}\n", ieee80211w));
+    }
+    directives.push_str(&format!("wpa_passphrase={}\n", passphrase));
+    directives
+}
+
+/// Render the hostapd config for the setup AP, open unless `wpa` is given.
+fn build_hostapd_config(
+    interface: &str,
+    ssid: &str,
+    radio: &ApRadio,
+    wpa: Option<(ApSecurity, &str)>,
+    max_clients: Option<u16>,
+    beacon_interval: Option<u16>,
+) -> String {
+    let mut conf = format!(
+        r#"interface={}
+driver=nl80211
+ssid={}
+hw_mode={}
+channel={}
+macaddr_acl=0
+auth_algs=1
+ignore_broadcast_ssid=0
+"#,
+        interface, ssid, radio.hw_mode, radio.channel
+    );
+    conf.push_str(&security_directives(wpa));
+
+    // hostapd refuses HT/VHT without WMM.
+    if radio.ieee80211n || radio.ieee80211ac {
+        conf.push_str("wmm_enabled=1\n");
+    } else {
+        conf.push_str("wmm_enabled=0\n");
+    }
+    if radio.ieee80211n {
+        conf.push_str("ieee80211n=1\n");
+    }
+    if radio.ieee80211ac {
+        conf.push_str("ieee80211ac=1\n");
+    }
+
+    // Left to hostapd's defaults unless configured.
+    if let Some(max_clients) = max_clients {
+        conf.push_str(&format!("max_num_sta={}\n", max_clients));
+    }
+    if let Some(beacon_interval) = beacon_interval {
+        conf.push_str(&format!("beacon_int={}\n", beacon_interval));
+    }
+
+    conf
+}
+
+/// Parse AP mode and 5GHz capabilities out of `iw phy <phy> info`.
+fn parse_phy_caps(phy_info: &str) -> PhyCaps {
+    let mut caps = PhyCaps {
+        supports_ap: phy_supports_ap(phy_info),
+        ..PhyCaps::default()
+    };
+
+    // iw numbers bands by nl80211 index: Band 2 is 5GHz. Band contents are
+    // indented below the band header; the next top-level entry ends the band.
+    let mut in_5ghz = false;
+    for raw in phy_info.lines() {
+        let depth = raw.len() - raw.trim_start_matches('\t').len();
+        let line = raw.trim();
+        if depth <= 1 {
+            in_5ghz = line
+                .strip_prefix("Band ")
+                .is_some_and(|band| band.trim_end_matches(':') == "2");
+            continue;
+        }
+        if !in_5ghz {
+            continue;
+        }
+        if line.starts_with("Capabilities:") {
+            caps.ht_5ghz = true;
+        } else if line.starts_with("VHT Capabilities") {
+            caps.vht_5ghz = true;
+        } else if let Some(channel) = line.strip_prefix("* ").and_then(usable_channel) {
+            caps.usable_5ghz_channels.push(channel);
+        }
+    }
+
+    caps
+}
+
+/// Channel number of a frequency line like "5180 MHz [36] (20.0 dBm)", unless
+/// the channel is disabled or may not initiate radiation.
+fn usable_channel(entry: &str) -> Option<u8> {
+    let flags = ["disabled", "no IR", "radar detection", "passive scanning"];
+    if flags.iter().any(|flag| entry.contains(flag)) {
+        return None;
+    }
+    let start = entry.find('[')? + 1;
+    let end = start + entry[start..].find(']')?;
+    entry[start..end].parse().ok()
+}
+
+/// Extract the wiphy index from `iw dev <iface> info`.
+fn parse_wiphy_index(dev_info: &str) -> Option<u32> {
+    dev_info
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("wiphy "))
+        .and_then(|index| index.trim().parse().ok())
+}
+
+/// Check the "Supported interface modes" list of `iw phy <phy> info` for AP.
+fn phy_supports_ap(phy_info: &str) -> bool {
+    let mut lines = phy_info.lines().map(str::trim);
+    if !lines.any(|line| line == "Supported interface modes:") {
+        return false;
+    }
+    lines
+        .map_while(|line| line.strip_prefix("* "))
+        .any(|mode| mode.trim() == "AP")
+}
+
+/// Render the dnsmasq config for the AP subnet.
+///
+/// Every DNS name resolves to the AP so captive portal detection triggers, and the
+/// portal hostname (if any) gets an explicit mapping so it keeps working for clients
+/// that bypass the wildcard.
+fn build_dnsmasq_config(
+    interface: &str,
+    ap_ip: &str,
+    portal_hostname: &str,
+    dhcp: &DhcpRange,
+    dns_mode: ApDnsMode,
+    dns_upstreams: &[IpAddr],
+) -> Result<String> {
+    let ap_ip_addr: Ipv4Addr = ap_ip
+        .parse()
+        .with_context(|| format!("Invalid AP IP address: '{}'", ap_ip))?;
+    let [a, b, c, _] = ap_ip_addr.octets();
+    let dhcp_start = format!("{}.{}.{}.{}", a, b, c, dhcp.start_octet);
+    let dhcp_end = format!("{}.{}.{}.{}", a, b, c, dhcp.end_octet);
+
+    let mut conf = format!(
+        r#"interface={}
+bind-dynamic
+dhcp-leasefile={}
+pid-file={}/dnsmasq.pid
+dhcp-range={},{},255.255.255.0,{}
+dhcp-option=option:router,{}
+dhcp-option=option:dns-server,{}
+"#,
+        interface, DNSMASQ_LEASES_PATH, RUNTIME_DIR, dhcp_start, dhcp_end, dhcp.lease, ap_ip, ap_ip
+    );
+
+    let portal_hostname = portal_hostname.trim();
+    if !portal_hostname.is_empty() {
+        conf.push_str(&format!("address=/{}/{}\n", portal_hostname, ap_ip));
+    }
+    match dns_mode {
+        ApDnsMode::Captive => conf.push_str(&format!("address=/#/{}\n", ap_ip)),
+        // No upstreams: dnsmasq falls back to the system's resolv.conf.
+        ApDnsMode::Passthrough if !dns_upstreams.is_empty() => {
+            if dns_upstreams.contains(&IpAddr::V4(ap_ip_addr)) {
+                bail!("DNS upstream {} is the AP itself", ap_ip);
+            }
+            conf.push_str("no-resolv\n");
+            for upstream in dns_upstreams {
+                conf.push_str(&format!("server={}\n", upstream));
+            }
+        }
+        ApDnsMode::Passthrough => {}
+    }
+
+    Ok(conf)
+}
+
+/// Hand the radio back to NetworkManager for normal client operation.
+///
+/// Every step is a no-op when there is nothing to undo, so this is safe to call
+/// repeatedly and when no AP was ever started.
+pub async fn restore_device_after_ap(config: &DaemonConfig) -> Result<()> {
+    let interface = config.interface.as_str();
+    remove_ap_address(interface, &config.ap_ip).await;
+
+    // Ensure the configured backend is available again for NetworkManager.
+    let release = backend::RadioRelease::for_config(config);
+    if let Err(e) = backend::restore(&backend::active().await, release, interface).await {
+        tracing::warn!(error = %e, "Failed to restart WiFi backend");
+    }
+
+    set_device_managed(interface).await;
+    Ok(())
+}
+
+/// Best-effort: drop the AP address, leaving any station addressing alone.
+async fn remove_ap_address(interface: &str, ap_ip: &str) {
+    let _ = Command::new("ip")
+        .args(["addr", "del", &format!("{}/24", ap_ip), "dev", interface])
         .output()
         .await;
+}
 
-    // Best-effort: re-enable NetworkManager management of this device.
+/// Best-effort: re-enable NetworkManager management of this device.
+async fn set_device_managed(interface: &str) {
     if let Ok(connection) = Connection::system().await {
         if let Ok(nm_proxy) = zbus::Proxy::new(&connection, NM_DEST, NM_PATH, NM_IFACE).await {
             if let Ok(device_path) = nm_proxy
@@ -196,8 +770,6 @@ pub async fn restore_device_after_ap(interface: &str) -> Result<()> {
             }
         }
     }
-
-    Ok(())
 }
 
 /// Stop the access point
@@ -235,7 +807,7 @@ pub async fn stop_ap() -> Result<()> {
     // Clean up temp files
     let _ = tokio::fs::remove_file(HOSTAPD_CONF_PATH).await;
     let _ = tokio::fs::remove_file(DNSMASQ_CONF_PATH).await;
-    let _ = tokio::fs::remove_file("/run/hyper-connect/dnsmasq.leases").await;
+    let _ = tokio::fs::remove_file(DNSMASQ_LEASES_PATH).await;
     let _ = tokio::fs::remove_file("/run/hyper-connect/dnsmasq.pid").await;
 
     tracing::info!("Access point stopped");
@@ -268,11 +840,56 @@ async fn wait_for_ip_assignment(interface: &str, expected_ip: &str) -> Result<()
     );
 }
 
-async fn prepare_device_for_ap(interface: &str) -> Result<()> {
-    // hostapd expects exclusive control of the nl80211 interface. In our images,
-    // NetworkManager uses iwd as the WiFi backend, so both need to release the device.
+/// Take the radio back from NetworkManager after a failed station connect.
+///
+/// NetworkManager may still be retrying the activation when the connect gives
+/// up; if `start_ap` flushes and re-addresses the interface before NM has let
+/// go, NM can wipe the AP address again. Wait until the device is unmanaged
+/// and off the air before the AP is brought back.
+pub async fn release_station(interface: &str) -> Result<()> {
+    tracing::info!(interface = %interface, "Releasing device from NetworkManager");
+    release_device_from_nm(interface).await;
+
+    wait_for_device_unmanaged(interface, STATION_RELEASE_TIMEOUT).await?;
+    tracing::info!(interface = %interface, "NetworkManager released the device");
+
+    wait_for_station_disconnect(interface, STATION_RELEASE_TIMEOUT).await?;
+    tracing::info!(interface = %interface, "Station disconnected, radio free for the AP");
+    Ok(())
+}
+
+/// Poll NetworkManager until it reports the device as unmanaged.
+async fn wait_for_device_unmanaged(interface: &str, timeout: std::time::Duration) -> Result<()> {
+    let connection = Connection::system()
+        .await
+        .context("Failed to connect to system D-Bus")?;
+    let nm_proxy = zbus::Proxy::new(&connection, NM_DEST, NM_PATH, NM_IFACE).await?;
+    let device_path: zvariant::OwnedObjectPath = nm_proxy
+        .call("GetDeviceByIpIface", &(interface,))
+        .await
+        .with_context(|| format!("NetworkManager does not know {}", interface))?;
+    let device_proxy =
+        zbus::Proxy::new(&connection, NM_DEST, device_path.as_str(), NM_DEVICE_IFACE).await?;
 
-    // Best-effort: tell NetworkManager to disconnect and stop managing this device.
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        let state: u32 = device_proxy.get_property("State").await.unwrap_or(0);
+        if state == NM_DEVICE_STATE_UNMANAGED {
+            return Ok(());
+        }
+        if std::time::Instant::now() >= deadline {
+            bail!(
+                "Timed out waiting for NetworkManager to release {} (device state {})",
+                interface,
+                state
+            );
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+}
+
+/// Best-effort: tell NetworkManager to disconnect and stop managing this device.
+async fn release_device_from_nm(interface: &str) {
     if let Ok(connection) = Connection::system().await {
         if let Ok(nm_proxy) = zbus::Proxy::new(&connection, NM_DEST, NM_PATH, NM_IFACE).await {
             if let Ok(device_path) = nm_proxy
@@ -290,36 +907,190 @@ async fn prepare_device_for_ap(interface: &str) -> Result<()> {
             }
         }
     }
+}
 
-    // Stop iwd and wait until it is actually inactive.
-    tracing::debug!("Stopping iwd to release interface for hostapd");
-    let _ = Command::new("systemctl")
-        .args(["stop", "iwd.service"])
+/// Whether any client is currently associated with the AP on `interface`.
+pub async fn has_associated_stations(interface: &str) -> bool {
+    match Command::new("iw")
+        .args(["dev", interface, "station", "dump"])
         .output()
-        .await;
+        .await
+    {
+        Ok(output) => count_stations(&String::from_utf8_lossy(&output.stdout)) > 0,
+        Err(e) => {
+            tracing::debug!(error = %e, "Failed to query AP stations");
+            false
+        }
+    }
+}
 
-    wait_for_systemd_inactive("iwd.service", std::time::Duration::from_secs(6)).await?;
-    wait_for_station_disconnect(interface, std::time::Duration::from_secs(6)).await?;
-    Ok(())
+/// Number of "Station <mac>" entries in `iw dev <iface> station dump`.
+fn count_stations(station_dump: &str) -> usize {
+    station_macs(station_dump).len()
 }
 
-async fn wait_for_systemd_inactive(unit: &str, timeout: std::time::Duration) -> Result<()> {
-    let deadline = std::time::Instant::now() + timeout;
-    while std::time::Instant::now() < deadline {
-        let status = Command::new("systemctl")
-            .args(["is-active", "--quiet", unit])
-            .status()
-            .await
-            .with_context(|| format!("Failed to query systemd unit status for {}", unit))?;
+/// MACs of the "Station <mac> (on <iface>)" entries in a station dump.
+fn station_macs(station_dump: &str) -> Vec<String> {
+    station_dump
+        .lines()
+        .filter_map(|line| line.strip_prefix("Station "))
+        .filter_map(|rest| rest.split_whitespace().next())
+        .map(str::to_ascii_lowercase)
+        .collect()
+}
 
-        if !status.success() {
-            return Ok(());
+/// Devices on the AP: associated stations, with their DHCP lease when they
+/// have one. Without `iw`, the lease file is the best guess available.
+pub async fn list_ap_clients(interface: &str) -> Vec<ApClient> {
+    let leases = tokio::fs::read_to_string(DNSMASQ_LEASES_PATH)
+        .await
+        .map(|content| parse_dnsmasq_leases(&content))
+        .unwrap_or_default();
+
+    match Command::new("iw")
+        .args(["dev", interface, "station", "dump"])
+        .output()
+        .await
+    {
+        Ok(output) if output.status.success() => {
+            let stations = station_macs(&String::from_utf8_lossy(&output.stdout));
+            stations
+                .into_iter()
+                .map(|mac| {
+                    leases
+                        .iter()
+                        .find(|lease| lease.mac == mac)
+                        .cloned()
+                        .unwrap_or(ApClient {
+                            mac,
+                            ip: None,
+                            lease_expiry: None,
+                        })
+                })
+                .collect()
         }
+        _ => leases,
+    }
+}
 
-        tokio::time::sleep(std::time::Duration::from_millis(120)).await;
+/// Parse dnsmasq's lease file (`<expiry> <mac> <ip> <hostname> <client-id>`
+/// per line), skipping anything that doesn't look like an IPv4 lease.
+fn parse_dnsmasq_leases(content: &str) -> Vec<ApClient> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let expiry: u64 = fields.next()?.parse().ok()?;
+            let mac = fields.next()?;
+            parse_bssid(mac)?;
+            let ip: Ipv4Addr = fields.next()?.parse().ok()?;
+            Some(ApClient {
+                mac: mac.to_ascii_lowercase(),
+                ip: Some(ip.to_string()),
+                // dnsmasq writes 0 for leases that never expire.
+                lease_expiry: (expiry != 0).then_some(expiry),
+            })
+        })
+        .collect()
+}
+
+/// A process holding an interface hostapd needs for itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct InterfaceHolder {
+    pid: u32,
+    comm: String,
+    /// Left behind by an earlier run of ours, so safe to kill
+    stale: bool,
+}
+
+/// Kill hostapd instances an earlier run left on `interface`, and refuse to
+/// start while anything else still has it.
+async fn ensure_interface_free(interface: &str) -> Result<()> {
+    let holders = interface_holders(&read_processes(), interface, |path| {
+        std::fs::read_to_string(path).ok()
+    });
+
+    let mut busy = Vec::new();
+    for holder in holders {
+        if holder.stale {
+            tracing::warn!(
+                pid = holder.pid,
+                interface = %interface,
+                "Killing stale hostapd from an earlier run"
+            );
+            let _ = Command::new("kill")
+                .args(["-9", &holder.pid.to_string()])
+                .output()
+                .await;
+        } else {
+            busy.push(format!("{} {}", holder.pid, holder.comm));
+        }
+    }
+
+    if busy.is_empty() {
+        return Ok(());
+    }
+    Err(ApStartError::InterfaceBusy {
+        interface: interface.to_string(),
+        holders: busy.join(", "),
     }
+    .into())
+}
+
+/// `(pid, comm, argv)` for every process visible in /proc.
+fn read_processes() -> Vec<(u32, String, Vec<String>)> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let comm = std::fs::read_to_string(entry.path().join("comm")).ok()?;
+            let cmdline = std::fs::read(entry.path().join("cmdline")).ok()?;
+            let args = cmdline
+                .split(|&b| b == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect();
+            Some((pid, comm.trim().to_string(), args))
+        })
+        .collect()
+}
 
-    bail!("Timed out waiting for systemd unit {} to stop", unit);
+/// hostapd and wpa_supplicant processes bound to `interface`, either with
+/// `-i` or (hostapd) through an `interface=` line in a config file argument.
+fn interface_holders(
+    processes: &[(u32, String, Vec<String>)],
+    interface: &str,
+    read_config: impl Fn(&str) -> Option<String>,
+) -> Vec<InterfaceHolder> {
+    processes
+        .iter()
+        .filter(|(_, comm, _)| comm == "hostapd" || comm == "wpa_supplicant")
+        .filter(|(_, comm, args)| {
+            let args = args.get(1..).unwrap_or_default();
+            let by_flag = args.iter().enumerate().any(|(i, arg)| {
+                arg.strip_prefix("-i") == Some(interface)
+                    || (arg == "-i" && args.get(i + 1).map(String::as_str) == Some(interface))
+            });
+            let by_config = comm == "hostapd"
+                && args
+                    .iter()
+                    .filter(|arg| !arg.starts_with('-'))
+                    .filter_map(|path| read_config(path))
+                    .any(|conf| {
+                        conf.lines()
+                            .any(|line| line.trim().strip_prefix("interface=") == Some(interface))
+                    });
+            by_flag || by_config
+        })
+        .map(|(pid, comm, args)| InterfaceHolder {
+            pid: *pid,
+            comm: comm.clone(),
+            stale: comm == "hostapd" && args.iter().any(|arg| arg == HOSTAPD_CONF_PATH),
+        })
+        .collect()
 }
 
 async fn wait_for_station_disconnect(interface: &str, timeout: std::time::Duration) -> Result<()> {
@@ -344,3 +1115,430 @@ async fn wait_for_station_disconnect(interface: &str, timeout: std::time::Durati
         interface
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interface_holders_from_flags_and_configs() {
+        let process = |pid, comm: &str, args: &[&str]| {
+            (
+                pid,
+                comm.to_string(),
+                args.iter().map(|a| a.to_string()).collect::<Vec<_>>(),
+            )
+        };
+        let processes = [
+            process(
+                10,
+                "NetworkManager",
+                &["/usr/sbin/NetworkManager", "-i", "wlan0"],
+            ),
+            process(11, "wpa_supplicant", &["wpa_supplicant", "-u", "-s"]),
+            process(
+                12,
+                "wpa_supplicant",
+                &["wpa_supplicant", "-iwlan0", "-c", "/etc/wpa.conf"],
+            ),
+            process(13, "hostapd", &["hostapd", "-d", HOSTAPD_CONF_PATH]),
+            process(14, "hostapd", &["hostapd", "-B", "/etc/hostapd/other.conf"]),
+            process(15, "hostapd", &["hostapd", "/etc/hostapd/wlan1.conf"]),
+            process(16, "wpa_supplicant", &["wpa_supplicant", "-i", "wlan0"]),
+        ];
+        let read_config = |path: &str| match path {
+            HOSTAPD_CONF_PATH => Some("interface=wlan0\nssid=HyperRecovery\n".to_string()),
+            "/etc/hostapd/other.conf" => Some("driver=nl80211\ninterface=wlan0\n".to_string()),
+            "/etc/hostapd/wlan1.conf" => Some("interface=wlan1\n".to_string()),
+            _ => None,
+        };
+
+        let holders = interface_holders(&processes, "wlan0", read_config);
+        let summary: Vec<_> = holders.iter().map(|h| (h.pid, h.stale)).collect();
+        assert_eq!(summary, [(12, false), (13, true), (14, false), (16, false)]);
+        assert!(interface_holders(&processes, "wlan2", read_config).is_empty());
+    }
+
+    #[test]
+    fn test_dnsmasq_config_maps_portal_hostname() {
+        let conf = build_dnsmasq_config(
+            "wlan0",
+            "192.168.42.1",
+            "hyper.setup",
+            &DhcpRange::default(),
+            ApDnsMode::Captive,
+            &[],
+        )
+        .unwrap();
+        assert!(conf.contains("address=/hyper.setup/192.168.42.1\n"));
+        assert!(conf.contains("address=/#/192.168.42.1\n"));
+        assert!(conf.contains("dhcp-range=192.168.42.10,192.168.42.250,255.255.255.0,12h"));
+    }
+
+    #[test]
+    fn test_dnsmasq_config_without_portal_hostname() {
+        let conf = build_dnsmasq_config(
+            "wlan0",
+            "10.42.0.1",
+            "",
+            &DhcpRange::default(),
+            ApDnsMode::Captive,
+            &[],
+        )
+        .unwrap();
+        assert!(!conf.contains("address=//"));
+        assert!(conf.contains("address=/#/10.42.0.1\n"));
+    }
+
+    #[test]
+    fn test_dnsmasq_config_custom_dhcp_range() {
+        let dhcp = DhcpRange {
+            start_octet: 100,
+            end_octet: 120,
+            lease: "5m".to_string(),
+        };
+        let conf =
+            build_dnsmasq_config("wlan0", "10.42.0.1", "", &dhcp, ApDnsMode::Captive, &[]).unwrap();
+        assert!(conf.contains("dhcp-range=10.42.0.100,10.42.0.120,255.255.255.0,5m\n"));
+    }
+
+    #[test]
+    fn test_dnsmasq_config_passthrough_dns() {
+        let upstreams: [IpAddr; 2] = [
+            "1.1.1.1".parse().unwrap(),
+            "2606:4700::1111".parse().unwrap(),
+        ];
+        let passthrough = |upstreams: &[IpAddr]| {
+            build_dnsmasq_config(
+                "wlan0",
+                "10.42.0.1",
+                "hyper.setup",
+                &DhcpRange::default(),
+                ApDnsMode::Passthrough,
+                upstreams,
+            )
+        };
+
+        let conf = passthrough(&upstreams).unwrap();
+        assert!(!conf.contains("address=/#/"));
+        assert!(conf.contains("no-resolv\nserver=1.1.1.1\nserver=2606:4700::1111\n"));
+        // The portal name still resolves locally.
+        assert!(conf.contains("address=/hyper.setup/10.42.0.1\n"));
+
+        // Without upstreams dnsmasq reads the system's resolv.conf.
+        let conf = passthrough(&[]).unwrap();
+        assert!(!conf.contains("address=/#/"));
+        assert!(!conf.contains("no-resolv"));
+        assert!(!conf.contains("server="));
+
+        // Forwarding to ourselves would loop.
+        assert!(passthrough(&["10.42.0.1".parse().unwrap()]).is_err());
+    }
+
+    #[test]
+    fn test_dhcp_range_validation() {
+        let range = |start_octet, end_octet, lease: &str| DhcpRange {
+            start_octet,
+            end_octet,
+            lease: lease.to_string(),
+        };
+        assert!(DhcpRange::default().validate().is_ok());
+        assert!(range(2, 254, "infinite").validate().is_ok());
+        assert!(range(50, 50, "12h").validate().is_err());
+        assert!(range(200, 100, "12h").validate().is_err());
+        assert!(range(1, 100, "12h").validate().is_err());
+        assert!(range(10, 255, "12h").validate().is_err());
+        assert!(range(10, 250, "12x").validate().is_err());
+        assert!(range(10, 250, "h").validate().is_err());
+    }
+
+    const PHY_INFO: &str = "Wiphy phy0
+\twiphy index: 0
+\tmax # scan SSIDs: 4
+\tSupported interface modes:
+\t\t * IBSS
+\t\t * managed
+\t\t * AP
+\t\t * AP/VLAN
+\t\t * monitor
+\tBand 1:
+\t\tCapabilities: 0x1072
+";
+
+    #[test]
+    fn test_phy_supports_ap() {
+        assert!(phy_supports_ap(PHY_INFO));
+
+        let managed_only = PHY_INFO.replace("\t\t * AP\n", "");
+        assert!(!phy_supports_ap(&managed_only));
+
+        // AP listed outside the interface modes block doesn't count.
+        assert!(!phy_supports_ap(
+            "\tSupported interface modes:\n\t\t * managed\n\tBand 1:\n\t\t * AP\n"
+        ));
+    }
+
+    const PHY_INFO_DUAL_BAND: &str = "Wiphy phy1
+\tSupported interface modes:
+\t\t * managed
+\t\t * AP
+\tBand 1:
+\t\tCapabilities: 0x1072
+\t\tFrequencies:
+\t\t\t* 2412 MHz [1] (20.0 dBm)
+\tBand 2:
+\t\tCapabilities: 0x1062
+\t\t\tHT20/HT40
+\t\tVHT Capabilities (0x038071b0):
+\t\tFrequencies:
+\t\t\t* 5180 MHz [36] (20.0 dBm) (no IR)
+\t\t\t* 5200 MHz [40] (20.0 dBm)
+\t\t\t* 5260 MHz [52] (20.0 dBm) (radar detection)
+\t\t\t* 5745 MHz [149] (disabled)
+\tSupported commands:
+\t\t * new_interface
+";
+
+    #[test]
+    fn test_parse_phy_caps_5ghz() {
+        let caps = parse_phy_caps(PHY_INFO_DUAL_BAND);
+        assert!(caps.supports_ap);
+        assert!(caps.ht_5ghz);
+        assert!(caps.vht_5ghz);
+        assert_eq!(caps.usable_5ghz_channels, vec![40]);
+
+        let two_four_only = parse_phy_caps(PHY_INFO);
+        assert!(two_four_only.usable_5ghz_channels.is_empty());
+        assert!(!two_four_only.ht_5ghz);
+    }
+
+    #[test]
+    fn test_select_radio() {
+        let dual = parse_phy_caps(PHY_INFO_DUAL_BAND);
+        let single = parse_phy_caps(PHY_INFO);
+
+        let auto = select_radio(ApBand::Auto, Some(&dual)).unwrap();
+        assert_eq!((auto.hw_mode, auto.channel), ("a", 40));
+        assert!(auto.ieee80211n && auto.ieee80211ac);
+
+        assert_eq!(
+            select_radio(ApBand::Auto, Some(&single)),
+            Some(ApRadio::DEFAULT_2_4GHZ)
+        );
+        assert_eq!(select_radio(ApBand::Band5, Some(&single)), None);
+        assert_eq!(
+            select_radio(ApBand::Band2_4, Some(&dual)),
+            Some(ApRadio::DEFAULT_2_4GHZ)
+        );
+        assert_eq!(
+            select_radio(ApBand::Auto, None),
+            Some(ApRadio::DEFAULT_2_4GHZ)
+        );
+    }
+
+    #[test]
+    fn test_hostapd_config_for_band() {
+        let conf = build_hostapd_config(
+            "wlan0",
+            "HyperRecovery",
+            &ApRadio::DEFAULT_2_4GHZ,
+            None,
+            None,
+            None,
+        );
+        assert!(conf.contains("hw_mode=g\nchannel=6\n"));
+        assert!(conf.contains("wmm_enabled=0\n"));
+        assert!(!conf.contains("ieee80211n"));
+
+        let radio = select_radio(ApBand::Band5, Some(&parse_phy_caps(PHY_INFO_DUAL_BAND))).unwrap();
+        let conf = build_hostapd_config("wlan0", "HyperRecovery", &radio, None, None, None);
+        assert!(conf.contains("hw_mode=a\nchannel=40\n"));
+        assert!(conf.contains("wmm_enabled=1\nieee80211n=1\nieee80211ac=1\n"));
+    }
+
+    #[test]
+    fn test_sanitize_ap_ssid() {
+        assert_eq!(sanitize_ap_ssid("HyperRecovery").unwrap(), "HyperRecovery");
+        assert!(sanitize_ap_ssid("Café Setup").is_ok());
+
+        let injected = sanitize_ap_ssid("Setup\nwpa=0\nctrl_interface=/tmp").unwrap_err();
+        assert!(injected.to_string().contains("control characters"));
+        assert!(sanitize_ap_ssid("Tab\there").is_err());
+
+        let long = "x".repeat(33);
+        assert!(sanitize_ap_ssid(&long)
+            .unwrap_err()
+            .to_string()
+            .contains("32"));
+        assert!(sanitize_ap_ssid(&"x".repeat(32)).is_ok());
+        assert!(sanitize_ap_ssid("").is_err());
+    }
+
+    #[test]
+    fn test_count_stations() {
+        let dump = "Station aa:bb:cc:dd:ee:01 (on wlan0)
+\tinactive time:\t1200 ms
+\tsignal:  \t-48 dBm
+Station aa:bb:cc:dd:ee:02 (on wlan0)
+\tinactive time:\t30 ms
+";
+        assert_eq!(count_stations(dump), 2);
+        assert_eq!(count_stations(""), 0);
+    }
+
+    #[test]
+    fn test_parse_wiphy_index() {
+        let dev_info = "Interface wlan0
+\tifindex 3
+\twdev 0x1
+\taddr 00:11:22:33:44:55
+\ttype managed
+\twiphy 1
+";
+        assert_eq!(parse_wiphy_index(dev_info), Some(1));
+        assert_eq!(
+            parse_wiphy_index("command failed: No such device (-19)"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keep_tail_caps_lines_and_length() {
+        let mut output = String::new();
+        for i in 0..20 {
+            output.push_str(&format!("line {}\n", i));
+        }
+        output.push_str(&"x".repeat(CHILD_LINE_MAX + 1));
+        output.push_str("\nlast\n");
+
+        let tail = ChildTail::default();
+        keep_tail("hostapd", output.as_bytes(), tail.clone()).await;
+
+        let tail = tail.lock().unwrap();
+        assert_eq!(tail.len(), CHILD_TAIL_LINES);
+        assert_eq!(tail.back().map(String::as_str), Some("last"));
+        assert_eq!(tail.front().map(String::as_str), Some("line 9"));
+        assert!(tail.iter().all(|line| line.len() <= CHILD_LINE_MAX));
+    }
+
+    #[test]
+    fn test_parse_dnsmasq_leases_skips_malformed_lines() {
+        let leases = "1760700000 AA:BB:CC:DD:EE:01 192.168.42.50 phone 01:aa:bb:cc:dd:ee:01
+0 aa:bb:cc:dd:ee:02 192.168.42.51 * *
+garbage
+1760700000 not-a-mac 192.168.42.52 * *
+1760700000 aa:bb:cc:dd:ee:03 fe80::1 * *
+duid 00:01:00:01:2c:aa:bb:cc
+1760700000 aa:bb:cc:dd:ee:04
+";
+        assert_eq!(
+            parse_dnsmasq_leases(leases),
+            vec![
+                ApClient {
+                    mac: "aa:bb:cc:dd:ee:01".to_string(),
+                    ip: Some("192.168.42.50".to_string()),
+                    lease_expiry: Some(1760700000),
+                },
+                ApClient {
+                    mac: "aa:bb:cc:dd:ee:02".to_string(),
+                    ip: Some("192.168.42.51".to_string()),
+                    lease_expiry: None,
+                },
+            ]
+        );
+        assert!(parse_dnsmasq_leases("").is_empty());
+    }
+
+    #[test]
+    fn test_station_macs() {
+        let dump = "Station AA:BB:CC:DD:EE:01 (on wlan0)
+\tinactive time:\t1200 ms
+Station aa:bb:cc:dd:ee:02 (on wlan0)
+";
+        assert_eq!(
+            station_macs(dump),
+            ["aa:bb:cc:dd:ee:01", "aa:bb:cc:dd:ee:02"]
+        );
+    }
+
+    #[test]
+    fn test_hostapd_config_client_limit_and_beacon() {
+        let radio = ApRadio::DEFAULT_2_4GHZ;
+        let conf = build_hostapd_config("wlan0", "Kiosk", &radio, None, Some(4), Some(200));
+        assert!(conf.contains("max_num_sta=4\n"));
+        assert!(conf.contains("beacon_int=200\n"));
+
+        let conf = build_hostapd_config("wlan0", "Kiosk", &radio, None, None, None);
+        assert!(!conf.contains("max_num_sta"));
+        assert!(!conf.contains("beacon_int"));
+    }
+
+    #[test]
+    fn test_hostapd_config_security_modes() {
+        let radio = ApRadio::DEFAULT_2_4GHZ;
+        let conf = |wpa| build_hostapd_config("wlan0", "Kiosk", &radio, wpa, None, None);
+
+        let open = conf(None);
+        assert!(open.contains("wpa=0\n"));
+        assert!(!open.contains("wpa_passphrase"));
+
+        let wpa2 = conf(Some((ApSecurity::Wpa2, "hunter22")));
+        assert!(wpa2
+            .contains("wpa=2\nwpa_key_mgmt=WPA-PSK\nrsn_pairwise=CCMP\nwpa_passphrase=hunter22\n"));
+        assert!(!wpa2.contains("ieee80211w"));
+
+        let wpa3 = conf(Some((ApSecurity::Wpa3, "hunter22")));
+        assert!(wpa3.contains("wpa=2\nwpa_key_mgmt=SAE\nrsn_pairwise=CCMP\nieee80211w=2\n"));
+
+        let transition = conf(Some((ApSecurity::Wpa2Wpa3, "hunter22")));
+        assert!(transition
+            .contains("wpa=2\nwpa_key_mgmt=WPA-PSK SAE\nrsn_pairwise=CCMP\nieee80211w=1\n"));
+        assert!(transition.contains("wpa_passphrase=hunter22\n"));
+    }
+
+    #[test]
+    fn test_load_hostapd_extra() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hostapd-extra.conf");
+
+        std::fs::write(&path, "# Realtek quirk\r\nht_capab=[HT40+]\nwmm_enabled=0").unwrap();
+        assert_eq!(
+            load_hostapd_extra(&path).unwrap(),
+            "# Realtek quirk\nht_capab=[HT40+]\nwmm_enabled=0\n"
+        );
+
+        std::fs::write(&path, "country_code=DE\nht_capab=\t[HT40-]\n").unwrap();
+        let error = load_hostapd_extra(&path).unwrap_err().to_string();
+        assert!(
+            error.ends_with(":2: hostapd extra config lines must not contain control characters")
+        );
+
+        assert!(load_hostapd_extra(&dir.path().join("missing.conf")).is_err());
+    }
+
+    #[test]
+    fn test_redact_passphrase() {
+        assert_eq!(
+            redact_passphrase("ssid=Kiosk\nwpa_passphrase=hunter22\nsae_password=x=y\n"),
+            "ssid=Kiosk\nwpa_passphrase=<redacted>\nsae_password=<redacted>\n"
+        );
+    }
+
+    #[test]
+    fn test_sanitize_ap_password_and_hostapd_version() {
+        assert!(sanitize_ap_password("hunter22").is_ok());
+        assert!(sanitize_ap_password("short").is_err());
+        assert!(sanitize_ap_password(&"x".repeat(64)).is_err());
+        assert!(sanitize_ap_password("hunter22\nwpa=0").is_err());
+        assert!(sanitize_ap_password("pässwörd").is_err());
+
+        assert_eq!(
+            parse_hostapd_version("hostapd v2.10\nUser space daemon"),
+            Some((2, 10))
+        );
+        assert_eq!(parse_hostapd_version("hostapd v2.6-devel"), Some((2, 6)));
+        assert_eq!(parse_hostapd_version("command not found"), None);
+        assert!((2, 10) >= SAE_MIN_HOSTAPD_VERSION);
+        assert!((2, 6) < SAE_MIN_HOSTAPD_VERSION);
+    }
+}
@@ -0,0 +1,186 @@
+//! Audit log of connection attempts
+//!
+//! Each terminal connect outcome is appended as one JSON line for field
+//! debugging. Passwords and key material are never recorded. The file is
+//! rotated to `<name>.1` once it reaches a size cap.
+
+use super::{ConnectError, WifiBackend};
+use crate::log_file;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Default location of the attempt log
+pub const AUDIT_LOG_PATH: &str = "/var/log/hyper-connect/attempts.jsonl";
+/// Size at which the log is rotated
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+/// One connect attempt as written to the log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectAttempt {
+    /// Unix time the attempt finished
+    pub timestamp: u64,
+    pub ssid: String,
+    pub interface: String,
+    /// Pinned BSSID, or the strongest scanned AP for the SSID
+    pub bssid: Option<String>,
+    pub backend: Option<WifiBackend>,
+    pub success: bool,
+    /// `ConnectError` variant name on failure
+    pub error_code: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl ConnectAttempt {
+    /// Describe a finished attempt from its connect result.
+    pub fn from_result(
+        ssid: &str,
+        interface: &str,
+        bssid: Option<String>,
+        backend: Option<WifiBackend>,
+        duration: Duration,
+        result: &Result<()>,
+    ) -> Self {
+        let error = result.as_ref().err();
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            ssid: ssid.to_string(),
+            interface: interface.to_string(),
+            bssid,
+            backend,
+            success: error.is_none(),
+            error_code: error.map(|e| {
+                e.downcast_ref::<ConnectError>()
+                    .map(ConnectError::code)
+                    .unwrap_or("Other")
+                    .to_string()
+            }),
+            error: error.map(|e| format!("{:#}", e)),
+            duration_ms: duration.as_millis() as u64,
+        }
+    }
+}
+
+/// Appends connect attempts to a size-capped JSONL file
+#[derive(Debug, Clone)]
+pub struct AuditLogger {
+    path: PathBuf,
+    max_bytes: u64,
+}
+
+impl Default for AuditLogger {
+    fn default() -> Self {
+        Self::new(AUDIT_LOG_PATH, MAX_LOG_BYTES)
+    }
+}
+
+impl AuditLogger {
+    pub fn new<P: AsRef<Path>>(path: P, max_bytes: u64) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            max_bytes,
+        }
+    }
+
+    /// Append an attempt, logging (not returning) any failure.
+    pub fn record(&self, attempt: &ConnectAttempt) {
+        if let Err(e) = self.append(attempt) {
+            tracing::warn!(error = %format!("{:#}", e), "Failed to write connect audit log");
+        }
+    }
+
+    fn append(&self, attempt: &ConnectAttempt) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).context("Failed to create audit log directory")?;
+        }
+        self.rotate_if_full()?;
+
+        let line = serde_json::to_string(attempt)? + "\n";
+        let mut file = log_file::open_private(&self.path).context("Failed to open audit log")?;
+        file.write_all(line.as_bytes())
+            .context("Failed to append to audit log")?;
+        Ok(())
+    }
+
+    fn rotate_if_full(&self) -> Result<()> {
+        let size = match fs::metadata(&self.path) {
+            Ok(meta) => meta.len(),
+            Err(_) => return Ok(()),
+        };
+        if size >= self.max_bytes {
+            log_file::rotate(&self.path).context("Failed to rotate audit log")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    fn attempt(ssid: &str, result: Result<()>) -> ConnectAttempt {
+        ConnectAttempt::from_result(
+            ssid,
+            "wlan0",
+            Some("00:11:22:aa:bb:cc".to_string()),
+            Some(WifiBackend::WpaSupplicant),
+            Duration::from_millis(1500),
+            &result,
+        )
+    }
+
+    #[test]
+    fn test_entries_are_jsonl_without_password() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit").join("attempts.jsonl");
+        let logger = AuditLogger::new(&path, MAX_LOG_BYTES);
+
+        logger.record(&attempt("Home", Ok(())));
+        logger.record(&attempt(
+            "Office",
+            Err(anyhow::Error::new(ConnectError::WrongPassword)),
+        ));
+        logger.record(&attempt("Cafe", Err(anyhow::anyhow!("boom"))));
+
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = content
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["success"], true);
+        assert_eq!(lines[0]["duration_ms"], 1500);
+        assert_eq!(lines[0]["bssid"], "00:11:22:aa:bb:cc");
+        assert_eq!(lines[1]["error_code"], "WrongPassword");
+        assert_eq!(lines[2]["error_code"], "Other");
+        assert!(lines.iter().all(|l| l.get("password").is_none()));
+        assert!(!content.contains("password\""));
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_rotates_at_size_cap() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("attempts.jsonl");
+        let logger = AuditLogger::new(&path, 64);
+
+        logger.record(&attempt("First", Ok(())));
+        logger.record(&attempt("Second", Ok(())));
+
+        let current = fs::read_to_string(&path).unwrap();
+        let rotated = fs::read_to_string(dir.path().join("attempts.jsonl.1")).unwrap();
+        assert!(current.contains("Second") && !current.contains("First"));
+        assert!(rotated.contains("First"));
+    }
+}
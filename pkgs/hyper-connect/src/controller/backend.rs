@@ -0,0 +1,432 @@
+//! Supplicant lifecycle around handing the radio to hostapd
+//!
+//! NetworkManager drives the radio through either iwd or wpa_supplicant. Both
+//! have to let go of the device while hostapd runs the setup AP and come back
+//! afterwards; the steps differ only in which service is involved.
+//!
+//! With a single radio the supplicant is simply stopped. When a second radio
+//! keeps working as a station it needs the supplicant, so only the AP
+//! interface is taken away from it.
+
+use super::{network_manager, DaemonConfig, WifiBackend};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+use tokio::process::Command;
+use zbus::Connection;
+use zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+/// How long a supplicant gets to stop before AP startup gives up.
+const STOP_TIMEOUT: Duration = Duration::from_secs(6);
+
+const IWD_DEST: &str = "net.connman.iwd";
+const IWD_DEVICE_IFACE: &str = "net.connman.iwd.Device";
+const WPAS_DEST: &str = "fi.w1.wpa_supplicant1";
+const WPAS_PATH: &str = "/fi/w1/wpa_supplicant1";
+const WPAS_IFACE: &str = "fi.w1.wpa_supplicant1";
+
+/// How much of the supplicant the setup AP takes from the rest of the machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RadioRelease {
+    /// One radio: stop the supplicant outright while hostapd runs.
+    Supplicant,
+    /// A station radio stays in use: only the AP interface is taken from the
+    /// supplicant, which keeps serving the other radio.
+    Interface,
+}
+
+impl RadioRelease {
+    pub fn for_config(config: &DaemonConfig) -> Self {
+        if config.sta_interface().is_some() {
+            Self::Interface
+        } else {
+            Self::Supplicant
+        }
+    }
+}
+
+/// Service lifecycle hooks for a NetworkManager WiFi backend.
+pub trait WifiBackendOps {
+    /// Make sure the supplicant is up so NetworkManager can drive the radio.
+    async fn ensure_running(&self) -> Result<()>;
+    /// Stop the supplicant so hostapd gets exclusive control of the radio.
+    async fn pre_ap_release(&self) -> Result<()>;
+    /// Bring the supplicant back once the AP is gone.
+    async fn post_ap_restore(&self) -> Result<()>;
+    /// Take only `interface` from the running supplicant.
+    async fn release_interface(&self, interface: &str) -> Result<()>;
+    /// Let the supplicant drive `interface` again.
+    async fn restore_interface(&self, interface: &str) -> Result<()>;
+}
+
+pub struct Iwd;
+pub struct WpaSupplicant;
+
+impl WifiBackendOps for Iwd {
+    async fn ensure_running(&self) -> Result<()> {
+        start_unit("iwd.service").await
+    }
+
+    async fn pre_ap_release(&self) -> Result<()> {
+        stop_unit("iwd.service").await
+    }
+
+    async fn post_ap_restore(&self) -> Result<()> {
+        start_unit("iwd.service").await
+    }
+
+    // iwd leaves a powered-off device alone, so hostapd can have it.
+    async fn release_interface(&self, interface: &str) -> Result<()> {
+        set_iwd_powered(interface, false).await
+    }
+
+    async fn restore_interface(&self, interface: &str) -> Result<()> {
+        set_iwd_powered(interface, true).await
+    }
+}
+
+impl WifiBackendOps for WpaSupplicant {
+    async fn ensure_running(&self) -> Result<()> {
+        start_unit("wpa_supplicant.service").await
+    }
+
+    async fn pre_ap_release(&self) -> Result<()> {
+        stop_unit("wpa_supplicant.service").await
+    }
+
+    async fn post_ap_restore(&self) -> Result<()> {
+        start_unit("wpa_supplicant.service").await
+    }
+
+    async fn release_interface(&self, interface: &str) -> Result<()> {
+        let connection = Connection::system()
+            .await
+            .context("Failed to connect to system D-Bus")?;
+        let proxy = zbus::Proxy::new(&connection, WPAS_DEST, WPAS_PATH, WPAS_IFACE).await?;
+        let path: OwnedObjectPath = match proxy.call("GetInterface", &(interface,)).await {
+            Ok(path) => path,
+            // Not driving it, so there is nothing to release.
+            Err(_) => return Ok(()),
+        };
+        proxy
+            .call::<_, _, ()>("RemoveInterface", &(&path,))
+            .await
+            .with_context(|| format!("Failed to remove {} from wpa_supplicant", interface))
+    }
+
+    async fn restore_interface(&self, _interface: &str) -> Result<()> {
+        // NetworkManager adds the interface back once it manages the device again.
+        Ok(())
+    }
+}
+
+/// Dispatch to the implementation for the configured backend.
+impl WifiBackendOps for WifiBackend {
+    async fn ensure_running(&self) -> Result<()> {
+        match self {
+            WifiBackend::Iwd => Iwd.ensure_running().await,
+            WifiBackend::WpaSupplicant => WpaSupplicant.ensure_running().await,
+        }
+    }
+
+    async fn pre_ap_release(&self) -> Result<()> {
+        match self {
+            WifiBackend::Iwd => Iwd.pre_ap_release().await,
+            WifiBackend::WpaSupplicant => WpaSupplicant.pre_ap_release().await,
+        }
+    }
+
+    async fn post_ap_restore(&self) -> Result<()> {
+        match self {
+            WifiBackend::Iwd => Iwd.post_ap_restore().await,
+            WifiBackend::WpaSupplicant => WpaSupplicant.post_ap_restore().await,
+        }
+    }
+
+    async fn release_interface(&self, interface: &str) -> Result<()> {
+        match self {
+            WifiBackend::Iwd => Iwd.release_interface(interface).await,
+            WifiBackend::WpaSupplicant => WpaSupplicant.release_interface(interface).await,
+        }
+    }
+
+    async fn restore_interface(&self, interface: &str) -> Result<()> {
+        match self {
+            WifiBackend::Iwd => Iwd.restore_interface(interface).await,
+            WifiBackend::WpaSupplicant => WpaSupplicant.restore_interface(interface).await,
+        }
+    }
+}
+
+/// The backend NetworkManager is configured with, defaulting to iwd.
+pub async fn active() -> WifiBackend {
+    network_manager::current_wifi_backend()
+        .await
+        .unwrap_or(WifiBackend::Iwd)
+}
+
+/// Run `launch` with `interface` released from `backend`, handing it back if
+/// either step fails.
+pub async fn with_radio_released<B, F>(
+    backend: &B,
+    release: RadioRelease,
+    interface: &str,
+    launch: F,
+) -> Result<()>
+where
+    B: WifiBackendOps,
+    F: Future<Output = Result<()>>,
+{
+    let released = match release {
+        RadioRelease::Supplicant => backend.pre_ap_release().await,
+        RadioRelease::Interface => backend.release_interface(interface).await,
+    };
+    let result = match released {
+        Ok(()) => launch.await,
+        Err(e) => Err(e),
+    };
+
+    if result.is_err() {
+        if let Err(e) = restore(backend, release, interface).await {
+            tracing::warn!(error = %e, "Failed to restore WiFi backend");
+        }
+    }
+
+    result
+}
+
+/// Undo [`with_radio_released`] once the AP is gone.
+pub async fn restore<B: WifiBackendOps>(
+    backend: &B,
+    release: RadioRelease,
+    interface: &str,
+) -> Result<()> {
+    match release {
+        RadioRelease::Supplicant => backend.post_ap_restore().await,
+        RadioRelease::Interface => backend.restore_interface(interface).await,
+    }
+}
+
+/// Hand `interface` back to the supplicant for a station connect.
+///
+/// A supplicant stopped for a single-radio AP is started again. With a
+/// station radio it never stopped, and starting it for the whole machine
+/// would let it grab the radio hostapd still holds, so only `interface`
+/// is handed back.
+pub async fn reclaim_for_station<B: WifiBackendOps>(
+    backend: &B,
+    release: RadioRelease,
+    interface: &str,
+) -> Result<()> {
+    match release {
+        RadioRelease::Supplicant => backend.ensure_running().await,
+        RadioRelease::Interface => backend.restore_interface(interface).await,
+    }
+}
+
+/// Power an iwd device on or off; a device iwd doesn't know is left alone.
+async fn set_iwd_powered(interface: &str, powered: bool) -> Result<()> {
+    let connection = Connection::system()
+        .await
+        .context("Failed to connect to system D-Bus")?;
+    let manager = zbus::Proxy::new(
+        &connection,
+        IWD_DEST,
+        "/",
+        "org.freedesktop.DBus.ObjectManager",
+    )
+    .await?;
+    let objects: HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>> = manager
+        .call("GetManagedObjects", &())
+        .await
+        .context("Failed to list iwd devices")?;
+    let device = objects.into_iter().find_map(|(path, interfaces)| {
+        let name = interfaces.get(IWD_DEVICE_IFACE)?.get("Name")?;
+        matches!(&**name, Value::Str(name) if name.as_str() == interface).then_some(path)
+    });
+    let Some(device) = device else {
+        tracing::debug!(interface = %interface, "iwd does not drive this interface");
+        return Ok(());
+    };
+    zbus::Proxy::new(&connection, IWD_DEST, device.as_str(), IWD_DEVICE_IFACE)
+        .await?
+        .set_property("Powered", powered)
+        .await
+        .with_context(|| format!("Failed to power {} in iwd", interface))
+}
+
+async fn start_unit(unit: &str) -> Result<()> {
+    let status = Command::new("systemctl")
+        .args(["start", unit])
+        .status()
+        .await
+        .with_context(|| format!("Failed to run systemctl start {}", unit))?;
+    if !status.success() {
+        bail!("systemctl start {} failed: {}", unit, status);
+    }
+    Ok(())
+}
+
+/// Stop `unit` and wait until systemd reports it inactive.
+async fn stop_unit(unit: &str) -> Result<()> {
+    tracing::debug!(
+        unit,
+        "Stopping WiFi backend to release interface for hostapd"
+    );
+    let _ = Command::new("systemctl")
+        .args(["stop", unit])
+        .output()
+        .await;
+
+    let deadline = std::time::Instant::now() + STOP_TIMEOUT;
+    while std::time::Instant::now() < deadline {
+        let status = Command::new("systemctl")
+            .args(["is-active", "--quiet", unit])
+            .status()
+            .await
+            .with_context(|| format!("Failed to query systemd unit status for {}", unit))?;
+
+        if !status.success() {
+            return Ok(());
+        }
+
+        tokio::time::sleep(Duration::from_millis(120)).await;
+    }
+
+    bail!("Timed out waiting for systemd unit {} to stop", unit);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MockBackend {
+        calls: Mutex<Vec<&'static str>>,
+        fail_release: bool,
+    }
+
+    impl MockBackend {
+        fn calls(&self) -> Vec<&'static str> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    impl WifiBackendOps for MockBackend {
+        async fn ensure_running(&self) -> Result<()> {
+            self.calls.lock().unwrap().push("ensure_running");
+            Ok(())
+        }
+
+        async fn pre_ap_release(&self) -> Result<()> {
+            self.calls.lock().unwrap().push("pre_ap_release");
+            if self.fail_release {
+                bail!("backend would not stop");
+            }
+            Ok(())
+        }
+
+        async fn post_ap_restore(&self) -> Result<()> {
+            self.calls.lock().unwrap().push("post_ap_restore");
+            Ok(())
+        }
+
+        async fn release_interface(&self, _interface: &str) -> Result<()> {
+            self.calls.lock().unwrap().push("release_interface");
+            Ok(())
+        }
+
+        async fn restore_interface(&self, _interface: &str) -> Result<()> {
+            self.calls.lock().unwrap().push("restore_interface");
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_release_before_launch() {
+        let backend = MockBackend::default();
+        let result = with_radio_released(&backend, RadioRelease::Supplicant, "wlan0", async {
+            backend.calls.lock().unwrap().push("launch");
+            Ok(())
+        })
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(backend.calls(), ["pre_ap_release", "launch"]);
+    }
+
+    #[tokio::test]
+    async fn test_restore_after_failed_launch() {
+        let backend = MockBackend::default();
+        let result = with_radio_released(&backend, RadioRelease::Supplicant, "wlan0", async {
+            backend.calls.lock().unwrap().push("launch");
+            bail!("hostapd exited early")
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            backend.calls(),
+            ["pre_ap_release", "launch", "post_ap_restore"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_release_skips_launch() {
+        let backend = MockBackend {
+            fail_release: true,
+            ..Default::default()
+        };
+        let result = with_radio_released(&backend, RadioRelease::Supplicant, "wlan0", async {
+            backend.calls.lock().unwrap().push("launch");
+            Ok(())
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(backend.calls(), ["pre_ap_release", "post_ap_restore"]);
+    }
+
+    #[tokio::test]
+    async fn test_station_radio_keeps_supplicant() {
+        let (state, _rx) =
+            crate::controller::test_app_state_with(|config| config.sta_interface = "wlan1".into());
+        let release = RadioRelease::for_config(&state.config);
+        assert_eq!(release, RadioRelease::Interface);
+
+        let backend = MockBackend::default();
+        let result = with_radio_released(&backend, release, "wlan0", async {
+            backend.calls.lock().unwrap().push("launch");
+            bail!("hostapd exited early")
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            backend.calls(),
+            ["release_interface", "launch", "restore_interface"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_kept_ap_join_leaves_supplicant_alone() {
+        let (state, _rx) = crate::controller::test_app_state_with(|config| {
+            config.sta_interface = "wlan1".into();
+            config.keep_ap_after_connect = true;
+        });
+        let config = &state.config;
+
+        let backend = MockBackend::default();
+        reclaim_for_station(
+            &backend,
+            RadioRelease::for_config(config),
+            config.station_interface(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(backend.calls(), ["restore_interface"]);
+    }
+}
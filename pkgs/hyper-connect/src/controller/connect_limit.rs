@@ -0,0 +1,98 @@
+//! Per-SSID cooldown after repeated connect failures
+//!
+//! Every connect tears the AP down and rebuilds it, so a user retrying a bad
+//! password in a loop keeps the radio thrashing. After `MAX_RECENT_FAILURES`
+//! failures for one SSID inside `FAILURE_WINDOW`, further attempts are refused
+//! until the oldest of those failures ages out of the window.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Failures for one SSID that trigger the cooldown.
+const MAX_RECENT_FAILURES: usize = 3;
+/// How far back failures are counted.
+const FAILURE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Recent connect failures per SSID, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct FailureWindow {
+    failures: HashMap<String, VecDeque<Instant>>,
+}
+
+impl FailureWindow {
+    /// Count a failed attempt on `ssid`.
+    pub fn record(&mut self, ssid: &str, now: Instant) {
+        let times = self.failures.entry(ssid.to_string()).or_default();
+        if times.len() == MAX_RECENT_FAILURES {
+            times.pop_front();
+        }
+        times.push_back(now);
+    }
+
+    /// Forget the failures of a network that has since connected.
+    pub fn clear(&mut self, ssid: &str) {
+        self.failures.remove(ssid);
+    }
+
+    /// Time left before `ssid` may be tried again, if it is cooling down.
+    pub fn cooldown(&self, ssid: &str, now: Instant) -> Option<Duration> {
+        let times = self.failures.get(ssid)?;
+        if times.len() < MAX_RECENT_FAILURES {
+            return None;
+        }
+        let oldest = *times.front()?;
+        let remaining = FAILURE_WINDOW.saturating_sub(now.saturating_duration_since(oldest));
+        (!remaining.is_zero()).then_some(remaining)
+    }
+
+    /// Every SSID still cooling down, with the time left.
+    pub fn cooldowns(&self, now: Instant) -> Vec<(String, Duration)> {
+        let mut cooling: Vec<_> = self
+            .failures
+            .keys()
+            .filter_map(|ssid| Some((ssid.clone(), self.cooldown(ssid, now)?)))
+            .collect();
+        cooling.sort();
+        cooling
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cooldown_after_repeated_failures() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut window = FailureWindow::default();
+
+        window.record("Home", at(0));
+        window.record("Home", at(10));
+        window.record("Other", at(10));
+        assert_eq!(window.cooldown("Home", at(10)), None);
+
+        // Third failure inside the window: blocked until the first ages out.
+        window.record("Home", at(20));
+        assert_eq!(
+            window.cooldown("Home", at(20)),
+            Some(Duration::from_secs(40))
+        );
+        assert_eq!(window.cooldown("Other", at(20)), None);
+        assert_eq!(
+            window.cooldowns(at(20)),
+            [("Home".to_string(), Duration::from_secs(40))]
+        );
+
+        // The window slides: once the oldest failure expires, one more try.
+        assert_eq!(window.cooldown("Home", at(60)), None);
+        window.record("Home", at(61));
+        assert_eq!(
+            window.cooldown("Home", at(61)),
+            Some(Duration::from_secs(9))
+        );
+
+        window.clear("Home");
+        assert_eq!(window.cooldown("Home", at(61)), None);
+    }
+}
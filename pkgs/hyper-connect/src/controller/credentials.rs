@@ -15,8 +15,9 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs;
-use std::os::unix::fs::PermissionsExt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
 use std::path::Path;
 
 /// Default path for saved credentials
@@ -26,6 +27,8 @@ pub const CREDENTIALS_PATH: &str = "/var/lib/hyper-connect/credentials.json";
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SavedCredential {
     pub ssid: String,
+    /// Empty in a redacted export
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub password: String,
     /// When the credential was last used successfully
     #[serde(default)]
@@ -33,6 +36,9 @@ pub struct SavedCredential {
     /// Number of successful connections
     #[serde(default)]
     pub success_count: u32,
+    /// Auto-connect preference; higher wins over signal strength
+    #[serde(default)]
+    pub priority: i32,
 }
 
 /// Credentials store
@@ -49,6 +55,26 @@ fn default_version() -> u32 {
     1
 }
 
+/// What `CredentialsStore::merge` did with an imported bundle
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MergeSummary {
+    /// SSIDs that weren't saved before
+    pub added: usize,
+    /// Saved SSIDs replaced by the bundle's entry
+    pub updated: usize,
+    /// Saved SSIDs whose entry was kept
+    pub kept: usize,
+    /// Bundle entries without a password (from a redacted export)
+    pub skipped: usize,
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 impl CredentialsStore {
     /// Load credentials from disk
     pub fn load() -> Result<Self> {
@@ -94,14 +120,23 @@ impl CredentialsStore {
         let content =
             serde_json::to_string_pretty(self).context("Failed to serialize credentials")?;
 
-        // Write to temp file first, then rename for atomicity
+        // Write to temp file first, then rename for atomicity. The path may
+        // be in a shared directory, so the temp file is created fresh and
+        // private: create_new refuses symlinks and anything already there,
+        // and the mode applies from the first byte. A leftover from an
+        // interrupted save is removed first; that drops a symlink itself,
+        // never its target.
         let temp_path = path.with_extension("tmp");
-        fs::write(&temp_path, &content).context("Failed to write credentials file")?;
-
-        // Set restrictive permissions (root only)
-        let mut perms = fs::metadata(&temp_path)?.permissions();
-        perms.set_mode(0o600);
-        fs::set_permissions(&temp_path, perms)?;
+        let _ = fs::remove_file(&temp_path);
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&temp_path)
+            .context("Failed to create credentials file")?;
+        file.write_all(content.as_bytes())
+            .and_then(|()| file.sync_all())
+            .context("Failed to write credentials file")?;
 
         // Atomic rename
         fs::rename(&temp_path, path).context("Failed to finalize credentials file")?;
@@ -117,10 +152,7 @@ impl CredentialsStore {
 
     /// Add or update credentials for a network
     pub fn save_credential(&mut self, ssid: &str, password: &str) {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map(|d| d.as_secs())
-            .unwrap_or(0);
+        let now = unix_now();
 
         if let Some(existing) = self.networks.get_mut(ssid) {
             existing.password = password.to_string();
@@ -134,19 +166,48 @@ impl CredentialsStore {
                     password: password.to_string(),
                     last_used: Some(now),
                     success_count: 1,
+                    priority: 0,
                 },
             );
         }
     }
 
+    /// Count a successful connection to a saved network made without the
+    /// portal, e.g. an auto-connect. Returns false if `ssid` isn't saved.
+    pub fn mark_connected(&mut self, ssid: &str) -> bool {
+        match self.networks.get_mut(ssid) {
+            Some(credential) => {
+                credential.last_used = Some(unix_now());
+                credential.success_count += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The saved network connected to most recently, if any ever was.
+    pub fn last_connected(&self) -> Option<&SavedCredential> {
+        self.networks
+            .values()
+            .filter(|credential| credential.last_used.is_some())
+            .max_by_key(|credential| credential.last_used)
+    }
+
     /// Get saved password for a network
     pub fn get_password(&self, ssid: &str) -> Option<&str> {
         self.networks.get(ssid).map(|c| c.password.as_str())
     }
 
-    /// Check if we have credentials for a network
-    pub fn has_credentials(&self, ssid: &str) -> bool {
-        self.networks.contains_key(ssid)
+    /// Set the auto-connect priority of a saved network.
+    /// Returns false if there are no credentials for `ssid`.
+    pub fn set_priority(&mut self, ssid: &str, priority: i32) -> bool {
+        match self.networks.get_mut(ssid) {
+            Some(credential) => {
+                credential.priority = priority;
+                true
+            }
+            None => false,
+        }
     }
 
     /// Remove credentials for a network
@@ -154,6 +215,46 @@ impl CredentialsStore {
         self.networks.remove(ssid).is_some()
     }
 
+    /// A copy without passwords, for sharing which networks are known and
+    /// how they rank.
+    pub fn redacted(&self) -> Self {
+        let mut store = self.clone();
+        store
+            .networks
+            .values_mut()
+            .for_each(|credential| credential.password.clear());
+        store
+    }
+
+    /// Merge an exported bundle into this store.
+    ///
+    /// A network saved in both keeps the entry with more successful
+    /// connections, unless `overwrite` makes the bundle win. Entries without
+    /// a password can't connect to anything and are skipped.
+    pub fn merge(&mut self, bundle: CredentialsStore, overwrite: bool) -> MergeSummary {
+        let mut summary = MergeSummary::default();
+        for (ssid, credential) in bundle.networks {
+            if credential.password.is_empty() {
+                summary.skipped += 1;
+                continue;
+            }
+            match self.networks.get_mut(&ssid) {
+                None => {
+                    self.networks.insert(ssid, credential);
+                    summary.added += 1;
+                }
+                Some(existing)
+                    if overwrite || credential.success_count > existing.success_count =>
+                {
+                    *existing = credential;
+                    summary.updated += 1;
+                }
+                Some(_) => summary.kept += 1,
+            }
+        }
+        summary
+    }
+
     /// Find known networks from a list of available networks
     pub fn find_known_networks<'a>(
         &self,
@@ -166,15 +267,30 @@ impl CredentialsStore {
     }
 
     /// Get the best known network to auto-connect to
-    /// Prioritizes by: signal strength, then success count
     pub fn best_known_network<'a>(
         &self,
         available: &'a [super::NetworkInfo],
     ) -> Option<&'a super::NetworkInfo> {
+        self.ranked_known_networks(available).into_iter().next()
+    }
+
+    /// Known networks in auto-connect order
+    /// Prioritizes by: priority, then signal strength, then success count
+    pub fn ranked_known_networks<'a>(
+        &self,
+        available: &'a [super::NetworkInfo],
+    ) -> Vec<&'a super::NetworkInfo> {
         let mut known: Vec<_> = self.find_known_networks(available);
 
         known.sort_by(|a, b| {
-            // First by signal strength (descending)
+            // First by configured priority (descending)
+            let priority = |ssid: &str| self.networks.get(ssid).map(|c| c.priority).unwrap_or(0);
+            let priority_cmp = priority(&b.ssid).cmp(&priority(&a.ssid));
+            if priority_cmp != std::cmp::Ordering::Equal {
+                return priority_cmp;
+            }
+
+            // Then by signal strength (descending)
             let signal_cmp = b.signal_strength.cmp(&a.signal_strength);
             if signal_cmp != std::cmp::Ordering::Equal {
                 return signal_cmp;
@@ -194,13 +310,14 @@ impl CredentialsStore {
             b_count.cmp(&a_count)
         });
 
-        known.into_iter().next()
+        known
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::controller::NetworkInfo;
     use tempfile::tempdir;
 
     #[test]
@@ -216,6 +333,25 @@ mod tests {
         assert_eq!(loaded.get_password("TestNetwork"), Some("password123"));
     }
 
+    #[test]
+    fn test_save_is_private_and_ignores_planted_symlink() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("export.json");
+        let victim = dir.path().join("victim");
+        fs::write(&victim, "keep").unwrap();
+        std::os::unix::fs::symlink(&victim, dir.path().join("export.tmp")).unwrap();
+
+        let mut store = CredentialsStore::default();
+        store.save_credential("TestNetwork", "password123");
+        store.save_to(&path).unwrap();
+
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "keep");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
     #[test]
     fn test_update_credential() {
         let mut store = CredentialsStore::default();
@@ -225,4 +361,173 @@ mod tests {
         assert_eq!(store.get_password("TestNetwork"), Some("password2"));
         assert_eq!(store.networks.get("TestNetwork").unwrap().success_count, 2);
     }
+
+    #[test]
+    fn test_last_connected() {
+        let mut store = CredentialsStore::default();
+        assert!(store.last_connected().is_none());
+
+        store.save_credential("Home", "pw");
+        store.save_credential("Office", "pw");
+        store.networks.get_mut("Home").unwrap().last_used = Some(100);
+        store.networks.get_mut("Office").unwrap().last_used = Some(200);
+        assert_eq!(store.last_connected().unwrap().ssid, "Office");
+
+        assert!(store.mark_connected("Home"));
+        assert_eq!(store.last_connected().unwrap().ssid, "Home");
+        assert_eq!(store.networks["Home"].success_count, 2);
+        assert!(!store.mark_connected("Stranger"));
+
+        // Credentials saved by older versions may never have been used.
+        store.networks.values_mut().for_each(|c| c.last_used = None);
+        assert!(store.last_connected().is_none());
+    }
+
+    #[test]
+    fn test_best_known_network_by_signal_then_success() {
+        let mut store = CredentialsStore::default();
+        store.save_credential("Office", "pw");
+        store.save_credential("Home", "pw");
+        store.save_credential("Home", "pw");
+        store.save_credential("Cafe", "pw");
+
+        let available = [
+            NetworkInfo::test_fixture("Office", 60),
+            NetworkInfo::test_fixture("Stranger", 95),
+            NetworkInfo::test_fixture("Home", 60),
+            NetworkInfo::test_fixture("Cafe", 40),
+        ];
+        assert_eq!(store.best_known_network(&available).unwrap().ssid, "Home");
+
+        let available = [
+            NetworkInfo::test_fixture("Cafe", 80),
+            NetworkInfo::test_fixture("Home", 60),
+        ];
+        assert_eq!(store.best_known_network(&available).unwrap().ssid, "Cafe");
+    }
+
+    #[test]
+    fn test_priority_beats_signal() {
+        let mut store = CredentialsStore::default();
+        store.save_credential("Home", "pw");
+        store.save_credential("Hotspot", "pw");
+        assert!(store.set_priority("Hotspot", 10));
+        assert!(!store.set_priority("Unknown", 10));
+
+        let available = [
+            NetworkInfo::test_fixture("Home", 90),
+            NetworkInfo::test_fixture("Hotspot", 30),
+        ];
+        assert_eq!(
+            store.best_known_network(&available).unwrap().ssid,
+            "Hotspot"
+        );
+    }
+
+    #[test]
+    fn test_ranked_known_networks_lists_every_fallback() {
+        let mut store = CredentialsStore::default();
+        store.save_credential("Home", "pw");
+        store.save_credential("Office", "pw");
+        store.save_credential("Hotspot", "pw");
+        store.set_priority("Hotspot", 10);
+
+        let available = [
+            NetworkInfo::test_fixture("Office", 80),
+            NetworkInfo::test_fixture("Stranger", 95),
+            NetworkInfo::test_fixture("Home", 50),
+            NetworkInfo::test_fixture("Hotspot", 20),
+        ];
+        let ranked: Vec<_> = store
+            .ranked_known_networks(&available)
+            .iter()
+            .map(|n| n.ssid.as_str())
+            .collect();
+        assert_eq!(ranked, ["Hotspot", "Office", "Home"]);
+    }
+
+    #[test]
+    fn test_redacted_export_has_no_passwords() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("bundle.json");
+
+        let mut store = CredentialsStore::default();
+        store.save_credential("Home", "hunter22");
+        store.set_priority("Home", 5);
+        store.redacted().save_to(&path).unwrap();
+
+        let exported = fs::read_to_string(&path).unwrap();
+        assert!(!exported.contains("hunter22"));
+        assert!(!exported.contains("password"));
+        let loaded = CredentialsStore::load_from(&path).unwrap();
+        assert_eq!(loaded.networks["Home"].priority, 5);
+        assert_eq!(loaded.networks["Home"].success_count, 1);
+        assert_eq!(loaded.get_password("Home"), Some(""));
+
+        // Importing it back changes nothing.
+        let summary = store.merge(loaded, true);
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(store.get_password("Home"), Some("hunter22"));
+    }
+
+    #[test]
+    fn test_merge_prefers_more_successful_entry() {
+        let credential = |ssid: &str, password: &str, success_count| SavedCredential {
+            ssid: ssid.to_string(),
+            password: password.to_string(),
+            last_used: None,
+            success_count,
+            priority: 0,
+        };
+        let store_of = |credentials: Vec<SavedCredential>| CredentialsStore {
+            networks: credentials
+                .into_iter()
+                .map(|c| (c.ssid.clone(), c))
+                .collect(),
+            version: 1,
+        };
+        let local = store_of(vec![
+            credential("Home", "local-home", 5),
+            credential("Office", "local-office", 1),
+        ]);
+        let bundle = store_of(vec![
+            credential("Home", "bundle-home", 2),
+            credential("Office", "bundle-office", 3),
+            credential("Lab", "bundle-lab", 0),
+        ]);
+
+        let mut merged = local.clone();
+        let summary = merged.merge(bundle.clone(), false);
+        assert_eq!(
+            summary,
+            MergeSummary {
+                added: 1,
+                updated: 1,
+                kept: 1,
+                skipped: 0
+            }
+        );
+        assert_eq!(merged.get_password("Home"), Some("local-home"));
+        assert_eq!(merged.get_password("Office"), Some("bundle-office"));
+        assert_eq!(merged.get_password("Lab"), Some("bundle-lab"));
+
+        let mut merged = local;
+        let summary = merged.merge(bundle, true);
+        assert_eq!((summary.added, summary.updated, summary.kept), (1, 2, 0));
+        assert_eq!(merged.get_password("Home"), Some("bundle-home"));
+    }
+
+    #[test]
+    fn test_missing_priority_defaults_to_zero() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("creds.json");
+        fs::write(
+            &path,
+            r#"{"networks":{"Home":{"ssid":"Home","password":"pw","success_count":3}},"version":1}"#,
+        )
+        .unwrap();
+
+        let loaded = CredentialsStore::load_from(&path).unwrap();
+        assert_eq!(loaded.networks["Home"].priority, 0);
+    }
 }
@@ -0,0 +1,156 @@
+//! Optional D-Bus service mirroring the IPC commands
+//!
+//! Exposes `org.hyper.Recovery.WifiSetup` on the system bus for other system
+//! components. Requests go through the same handler as the Unix socket, so
+//! both front-ends share the command channel and snapshot types.
+
+use super::ipc::{handle_request, IpcRequest, IpcResponse};
+use super::{AppState, ControlCommand, WifiState, WifiStateSnapshot};
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use tokio::sync::watch;
+use zbus::object_server::SignalContext;
+
+/// Well-known bus name claimed by the daemon.
+pub const BUS_NAME: &str = "org.hyper.Recovery.WifiSetup";
+/// Object path the interface is served at.
+pub const OBJECT_PATH: &str = "/org/hyper/Recovery/WifiSetup";
+
+pub struct WifiSetup {
+    state: Arc<AppState>,
+}
+
+#[zbus::interface(name = "org.hyper.Recovery.WifiSetup")]
+impl WifiSetup {
+    /// Current state as the JSON-encoded `WifiStateSnapshot`.
+    async fn get_status(&self) -> zbus::fdo::Result<String> {
+        let snapshot = match handle_request(&self.state, IpcRequest::GetStatus).await {
+            IpcResponse::Status(snapshot) => snapshot,
+            other => return Err(into_fdo_error(other)),
+        };
+        serde_json::to_string(&snapshot).map_err(|e| zbus::fdo::Error::Failed(e.to_string()))
+    }
+
+    async fn connect(&self, ssid: String, password: String, save: bool) -> zbus::fdo::Result<()> {
+        let request = IpcRequest::Connect {
+            ssid,
+            password,
+            save,
+            hidden: false,
+            bssid: None,
+            eap_tls: None,
+            dry_run: false,
+            ssid_bytes: None,
+            security: None,
+            use_saved: false,
+        };
+        into_fdo_result(handle_request(&self.state, request).await)
+    }
+
+    /// Abort the connect attempt in progress.
+    async fn cancel_connect(&self) -> zbus::fdo::Result<()> {
+        into_fdo_result(handle_request(&self.state, IpcRequest::CancelConnect).await)
+    }
+
+    async fn scan(&self) -> zbus::fdo::Result<()> {
+        into_fdo_result(handle_request(&self.state, IpcRequest::Scan).await)
+    }
+
+    /// Drop saved credentials for `ssid`; returns whether any were stored.
+    async fn forget(&self, ssid: String) -> zbus::fdo::Result<bool> {
+        if !self
+            .state
+            .wifi_state
+            .read()
+            .await
+            .saved_ssids
+            .contains(&ssid)
+        {
+            return Ok(false);
+        }
+        // The control loop is the only writer of the credentials store.
+        self.state
+            .command_tx
+            .send(ControlCommand::ForgetCredential { ssid })
+            .await
+            .map_err(|_| zbus::fdo::Error::Failed("Controller is not running".to_string()))?;
+        Ok(true)
+    }
+
+    #[zbus(signal)]
+    async fn state_changed(ctxt: &SignalContext<'_>, status: &str) -> zbus::Result<()>;
+}
+
+fn into_fdo_result(response: IpcResponse) -> zbus::fdo::Result<()> {
+    match response {
+        IpcResponse::Ok => Ok(()),
+        other => Err(into_fdo_error(other)),
+    }
+}
+
+fn into_fdo_error(response: IpcResponse) -> zbus::fdo::Error {
+    match response {
+        IpcResponse::Error(message) => zbus::fdo::Error::Failed(message),
+        other => zbus::fdo::Error::Failed(format!("Unexpected response: {:?}", other)),
+    }
+}
+
+/// Claim the bus name and emit `StateChanged` for every state update.
+pub async fn serve(state: Arc<AppState>, mut state_rx: watch::Receiver<WifiState>) -> Result<()> {
+    let connection = zbus::connection::Builder::system()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, WifiSetup { state })?
+        .build()
+        .await
+        .context("Failed to register D-Bus service")?;
+    tracing::info!(name = BUS_NAME, "D-Bus service registered");
+
+    let ctxt = SignalContext::new(&connection, OBJECT_PATH)?;
+    while state_rx.changed().await.is_ok() {
+        let snapshot = WifiStateSnapshot::from(&*state_rx.borrow_and_update());
+        let status = serde_json::to_string(&snapshot)?;
+        if let Err(e) = WifiSetup::state_changed(&ctxt, &status).await {
+            tracing::warn!(error = %e, "Failed to emit StateChanged");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{test_app_state, ControlCommand};
+
+    #[tokio::test]
+    async fn test_connect_uses_command_channel() {
+        let (state, mut command_rx) = test_app_state();
+        let service = WifiSetup { state };
+
+        service
+            .connect("Home".to_string(), "secret".to_string(), false)
+            .await
+            .unwrap();
+
+        match command_rx.recv().await {
+            Some(ControlCommand::Connect {
+                ssid, save, bssid, ..
+            }) => {
+                assert_eq!(ssid, "Home");
+                assert!(!save);
+                assert!(bssid.is_none());
+            }
+            _ => panic!("expected Connect command"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_status_is_snapshot_json() {
+        let (state, _command_rx) = test_app_state();
+        let service = WifiSetup { state };
+
+        let json = service.get_status().await.unwrap();
+        let snapshot: WifiStateSnapshot = serde_json::from_str(&json).unwrap();
+        assert!(snapshot.available_networks.is_empty());
+    }
+}
@@ -0,0 +1,315 @@
+//! Environment and adapter diagnostics
+//!
+//! Gathers what field support usually asks for first: wireless interfaces and
+//! their drivers, the NetworkManager backend, required tools, and the
+//! regulatory domain. Runs standalone; the daemon doesn't need to be up. The
+//! adapter part is also served by a running daemon through `GetDiagnostics`.
+
+use super::network_manager;
+use super::WifiBackend;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// External tools the daemon shells out to.
+const REQUIRED_BINARIES: &[&str] = &[
+    "hostapd",
+    "dnsmasq",
+    "iw",
+    "ip",
+    "pkill",
+    "systemctl",
+    "NetworkManager",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterfaceReport {
+    pub name: String,
+    pub driver_bound: bool,
+    /// Bus address of the backing device (e.g. PCI slot)
+    pub device: String,
+}
+
+/// Wireless adapters and whether their drivers loaded
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AdapterReport {
+    pub interfaces: Vec<InterfaceReport>,
+    /// PCI wireless controllers with no driver attached
+    pub unbound_pci_devices: Vec<String>,
+}
+
+impl AdapterReport {
+    pub fn collect() -> Self {
+        Self {
+            interfaces: network_manager::list_wireless_interfaces()
+                .into_iter()
+                .map(|iface| InterfaceReport {
+                    name: iface.name,
+                    driver_bound: iface.driver_bound,
+                    device: iface.device_hint,
+                })
+                .collect(),
+            unbound_pci_devices: network_manager::detect_unbound_pci_wifi_devices(),
+        }
+    }
+
+    /// One line per adapter that can't work, for clients to show in place of
+    /// an empty network list.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems: Vec<String> = self
+            .interfaces
+            .iter()
+            .filter(|iface| !iface.driver_bound)
+            .map(|iface| {
+                format!(
+                    "No driver for {} (device {}); firmware may be missing",
+                    iface.name, iface.device
+                )
+            })
+            .collect();
+        problems.extend(self.unbound_pci_devices.iter().map(|device| {
+            format!(
+                "No driver for PCI WiFi device {}; firmware may be missing",
+                device
+            )
+        }));
+        if problems.is_empty() && self.interfaces.is_empty() {
+            problems.push("No wireless interfaces detected".to_string());
+        }
+        problems
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BinaryReport {
+    pub name: String,
+    /// Where it was found on `PATH`, if anywhere
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsReport {
+    pub version: String,
+    pub interfaces: Vec<InterfaceReport>,
+    /// PCI wireless controllers with no driver attached
+    pub unbound_pci_devices: Vec<String>,
+    pub network_manager_running: bool,
+    pub network_manager_error: Option<String>,
+    pub wifi_backend: Option<WifiBackend>,
+    pub wifi_backend_error: Option<String>,
+    pub binaries: Vec<BinaryReport>,
+    /// Country code from `iw reg get` ("00" is the world domain)
+    pub regulatory_domain: Option<String>,
+}
+
+impl DiagnosticsReport {
+    /// Run every probe; failures are recorded in the report rather than returned.
+    pub async fn collect() -> Self {
+        let adapters = AdapterReport::collect();
+
+        let (network_manager_running, network_manager_error) =
+            match network_manager::network_manager_running().await {
+                Ok(running) => (running, None),
+                Err(e) => (false, Some(format!("{:#}", e))),
+            };
+        let (wifi_backend, wifi_backend_error) = match network_manager::current_wifi_backend().await
+        {
+            Ok(backend) => (Some(backend), None),
+            Err(e) => (None, Some(format!("{:#}", e))),
+        };
+
+        let search_path = std::env::var_os("PATH").unwrap_or_default();
+        let binaries = REQUIRED_BINARIES
+            .iter()
+            .map(|name| BinaryReport {
+                name: name.to_string(),
+                path: find_in_path(name, &search_path),
+            })
+            .collect();
+
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            interfaces: adapters.interfaces,
+            unbound_pci_devices: adapters.unbound_pci_devices,
+            network_manager_running,
+            network_manager_error,
+            wifi_backend,
+            wifi_backend_error,
+            binaries,
+            regulatory_domain: regulatory_domain().await,
+        }
+    }
+
+    /// Human-readable report for support tickets.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        out.push_str("Hyper Connect Diagnostics\n");
+        out.push_str("=========================\n");
+        out.push_str(&format!("Version: {}\n", self.version));
+
+        out.push_str("\nWireless interfaces:\n");
+        if self.interfaces.is_empty() {
+            out.push_str("  (none)\n");
+        }
+        for iface in &self.interfaces {
+            out.push_str(&format!(
+                "  - {} [{}] device {}\n",
+                iface.name,
+                if iface.driver_bound {
+                    "driver bound"
+                } else {
+                    "no driver"
+                },
+                iface.device
+            ));
+        }
+        if !self.unbound_pci_devices.is_empty() {
+            out.push_str("Unbound PCI WiFi devices:\n");
+            for device in &self.unbound_pci_devices {
+                out.push_str(&format!("  - {}\n", device));
+            }
+        }
+
+        out.push_str("\nNetworkManager: ");
+        match (&self.network_manager_error, self.network_manager_running) {
+            (Some(e), _) => out.push_str(&format!("unknown ({})\n", e)),
+            (None, true) => out.push_str("running\n"),
+            (None, false) => out.push_str("not running\n"),
+        }
+        out.push_str("WiFi backend: ");
+        match (&self.wifi_backend, &self.wifi_backend_error) {
+            (Some(backend), _) => out.push_str(&format!("{}\n", backend.as_nm_value())),
+            (None, Some(e)) => out.push_str(&format!("unknown ({})\n", e)),
+            (None, None) => out.push_str("unknown\n"),
+        }
+        out.push_str(&format!(
+            "Regulatory domain: {}\n",
+            self.regulatory_domain.as_deref().unwrap_or("unknown")
+        ));
+
+        out.push_str("\nRequired tools:\n");
+        for binary in &self.binaries {
+            match &binary.path {
+                Some(path) => out.push_str(&format!("  - {}: {}\n", binary.name, path.display())),
+                None => out.push_str(&format!("  - {}: MISSING\n", binary.name)),
+            }
+        }
+
+        out
+    }
+}
+
+/// Print the diagnostics report as text or JSON.
+pub async fn print_diagnostics(json: bool) -> Result<()> {
+    let report = DiagnosticsReport::collect().await;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report.to_text());
+    }
+    Ok(())
+}
+
+/// Locate an executable `name` in a `PATH`-style list of directories.
+fn find_in_path(name: &str, search_path: &std::ffi::OsStr) -> Option<PathBuf> {
+    std::env::split_paths(search_path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+async fn regulatory_domain() -> Option<String> {
+    let output = Command::new("iw")
+        .args(["reg", "get"])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_regulatory_domain(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// First `country XX:` entry of `iw reg get` output (the global domain).
+fn parse_regulatory_domain(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let rest = line.trim().strip_prefix("country ")?;
+        let (code, _) = rest.split_once(':')?;
+        Some(code.trim().to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_regulatory_domain() {
+        let output = "global\ncountry DE: DFS-ETSI\n\t(2400 - 2483 @ 40), (N/A, 20), (N/A)\n\n\
+                      phy#0 (self-managed)\ncountry US: DFS-FCC\n";
+        assert_eq!(parse_regulatory_domain(output).as_deref(), Some("DE"));
+        assert_eq!(parse_regulatory_domain("global\n"), None);
+    }
+
+    #[test]
+    fn test_adapter_problems() {
+        let report = AdapterReport {
+            interfaces: vec![
+                InterfaceReport {
+                    name: "wlan0".to_string(),
+                    driver_bound: true,
+                    device: "0000:02:00.0".to_string(),
+                },
+                InterfaceReport {
+                    name: "wlan1".to_string(),
+                    driver_bound: false,
+                    device: "1-1:1.0".to_string(),
+                },
+            ],
+            unbound_pci_devices: vec!["0000:03:00.0 (vendor 0x8086, device 0x2725)".to_string()],
+        };
+        assert_eq!(
+            report.problems(),
+            [
+                "No driver for wlan1 (device 1-1:1.0); firmware may be missing",
+                "No driver for PCI WiFi device 0000:03:00.0 (vendor 0x8086, device 0x2725); \
+                 firmware may be missing",
+            ]
+        );
+
+        assert_eq!(
+            AdapterReport::default().problems(),
+            ["No wireless interfaces detected"]
+        );
+        let report = AdapterReport {
+            interfaces: report.interfaces[..1].to_vec(),
+            unbound_pci_devices: Vec::new(),
+        };
+        assert!(report.problems().is_empty());
+    }
+
+    #[test]
+    fn test_find_in_path_requires_executable() {
+        let first = tempdir().unwrap();
+        let second = tempdir().unwrap();
+
+        let plain = first.path().join("hostapd");
+        std::fs::write(&plain, "").unwrap();
+        let tool = second.path().join("hostapd");
+        std::fs::write(&tool, "").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let search_path = std::env::join_paths([first.path(), second.path()]).unwrap();
+        assert_eq!(find_in_path("hostapd", &search_path), Some(tool));
+        assert_eq!(find_in_path("dnsmasq", &search_path), None);
+    }
+}
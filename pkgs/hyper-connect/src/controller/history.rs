@@ -0,0 +1,125 @@
+//! Recent status transitions, for reviewing a flaky connect after the fact
+//!
+//! Every published state is offered to [`StateHistory::record`]; only changes
+//! of `status` are kept, in a ring of the last [`HISTORY_LEN`] entries.
+
+use super::{ConnectionStatus, WifiState};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Transitions kept before the oldest is dropped
+pub const HISTORY_LEN: usize = 50;
+
+/// One status change as seen by clients
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StateTransition {
+    /// Seconds since the Unix epoch
+    pub at: u64,
+    pub from: ConnectionStatus,
+    pub to: ConnectionStatus,
+    /// Why the attempt failed, when the transition is into `Failed`
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl StateTransition {
+    /// Seconds between the transition and `now` (Unix seconds).
+    pub fn age_secs(&self, now: u64) -> u64 {
+        now.saturating_sub(self.at)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct StateHistory {
+    entries: VecDeque<StateTransition>,
+    last: ConnectionStatus,
+}
+
+impl StateHistory {
+    /// Note `state` if its status differs from the last one recorded.
+    pub fn record(&mut self, state: &WifiState, at: u64) {
+        if state.status == self.last {
+            return;
+        }
+        let error = match state.status {
+            ConnectionStatus::Failed => state
+                .connect_error
+                .as_ref()
+                .map(|e| e.to_string())
+                .or_else(|| state.last_error.clone()),
+            _ => None,
+        };
+        if self.entries.len() == HISTORY_LEN {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(StateTransition {
+            at,
+            from: std::mem::replace(&mut self.last, state.status.clone()),
+            to: state.status.clone(),
+            error,
+        });
+    }
+
+    /// Recorded transitions, oldest first.
+    pub fn entries(&self) -> Vec<StateTransition> {
+        self.entries.iter().cloned().collect()
+    }
+}
+
+/// Current time in Unix seconds.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::ConnectError;
+
+    #[test]
+    fn test_records_status_changes_only() {
+        let mut history = StateHistory::default();
+        let mut state = WifiState::default();
+
+        for (at, status) in [
+            (1, ConnectionStatus::Initializing),
+            (2, ConnectionStatus::AwaitingCredentials),
+            (3, ConnectionStatus::AwaitingCredentials),
+            (4, ConnectionStatus::Connecting),
+        ] {
+            state.status = status;
+            history.record(&state, at);
+        }
+        state.status = ConnectionStatus::Failed;
+        state.connect_error = Some(ConnectError::WrongPassword);
+        history.record(&state, 5);
+
+        let entries = history.entries();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].from, ConnectionStatus::Initializing);
+        assert_eq!(entries[0].to, ConnectionStatus::AwaitingCredentials);
+        assert_eq!(entries[1].at, 4);
+        assert_eq!(entries[2].to, ConnectionStatus::Failed);
+        assert_eq!(
+            entries[2].error.as_deref(),
+            Some("Incorrect password or missing secrets")
+        );
+        assert_eq!(entries[2].age_secs(17), 12);
+
+        for at in 0..HISTORY_LEN as u64 {
+            state.status = if at % 2 == 0 {
+                ConnectionStatus::Connecting
+            } else {
+                ConnectionStatus::Scanning
+            };
+            history.record(&state, 100 + at);
+        }
+        let entries = history.entries();
+        assert_eq!(entries.len(), HISTORY_LEN);
+        assert_eq!(entries[0].at, 100);
+    }
+}
@@ -0,0 +1,134 @@
+//! Integrator hook run once WiFi comes up (`--on-connected`)
+//!
+//! The command runs through `sh -c` with `HYPER_SSID`, `HYPER_IFACE` and
+//! `HYPER_IP` in its environment. It never sees the passphrase. Runs are
+//! bounded by [`HOOK_TIMEOUT`], so a hung hook can only delay shutdown by
+//! that long.
+
+use std::net::Ipv4Addr;
+use std::process::Stdio;
+use std::sync::Mutex;
+use tokio::process::Command;
+use tokio::task::JoinSet;
+
+/// Longest a hook may run before it is killed
+pub const HOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Starts configured hooks in the background and keeps track of them.
+pub struct HookRunner {
+    on_connected: Option<String>,
+    runs: Mutex<JoinSet<()>>,
+}
+
+impl HookRunner {
+    pub fn new(on_connected: Option<String>) -> Self {
+        Self {
+            on_connected,
+            runs: Mutex::default(),
+        }
+    }
+
+    /// Start the on-connected hook, if configured, for the network just joined.
+    pub async fn connected(&self, ssid: &str, interface: &str) {
+        let Some(command) = self.on_connected.clone() else {
+            return;
+        };
+        let ip = interface_ipv4(interface).await;
+        let env = hook_env(ssid, interface, ip);
+        if let Ok(mut runs) = self.runs.lock() {
+            // Drop finished runs so a long-lived daemon doesn't keep every one.
+            while runs.try_join_next().is_some() {}
+            runs.spawn(run_hook(command, env));
+        }
+    }
+
+    /// Let hooks that are still running finish (or time out) before exit.
+    pub async fn finish(&self) {
+        let mut runs = match self.runs.lock() {
+            Ok(mut runs) => std::mem::take(&mut *runs),
+            Err(_) => return,
+        };
+        while runs.join_next().await.is_some() {}
+    }
+}
+
+/// Environment handed to the on-connected hook.
+fn hook_env(ssid: &str, interface: &str, ip: Option<Ipv4Addr>) -> Vec<(&'static str, String)> {
+    vec![
+        ("HYPER_SSID", ssid.to_string()),
+        ("HYPER_IFACE", interface.to_string()),
+        ("HYPER_IP", ip.map(|ip| ip.to_string()).unwrap_or_default()),
+    ]
+}
+
+async fn run_hook(command: String, env: Vec<(&'static str, String)>) {
+    tracing::info!(command = %command, "Running on-connected hook");
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .envs(env)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to start on-connected hook");
+            return;
+        }
+    };
+
+    match tokio::time::timeout(HOOK_TIMEOUT, child.wait()).await {
+        Ok(Ok(status)) if status.success() => tracing::info!("On-connected hook finished"),
+        Ok(Ok(status)) => tracing::warn!(status = %status, "On-connected hook failed"),
+        Ok(Err(e)) => tracing::warn!(error = %e, "Failed to wait for on-connected hook"),
+        Err(_) => {
+            tracing::warn!(
+                seconds = HOOK_TIMEOUT.as_secs(),
+                "On-connected hook timed out, killing it"
+            );
+            let _ = child.kill().await;
+        }
+    }
+}
+
+/// First IPv4 address on the interface, if it has one yet.
+async fn interface_ipv4(interface: &str) -> Option<Ipv4Addr> {
+    let output = Command::new("ip")
+        .args(["-4", "-o", "addr", "show", "dev", interface])
+        .output()
+        .await
+        .ok()?;
+    parse_ipv4(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Pull the address out of `ip -4 -o addr` output (`inet 10.0.0.5/24 ...`).
+fn parse_ipv4(output: &str) -> Option<Ipv4Addr> {
+    let mut tokens = output.split_whitespace();
+    tokens.find(|token| *token == "inet")?;
+    tokens.next()?.split('/').next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hook_env() {
+        let env = hook_env("Home", "wlan0", Some(Ipv4Addr::new(10, 0, 0, 5)));
+        assert_eq!(
+            env,
+            [
+                ("HYPER_SSID", "Home".to_string()),
+                ("HYPER_IFACE", "wlan0".to_string()),
+                ("HYPER_IP", "10.0.0.5".to_string()),
+            ]
+        );
+        assert_eq!(hook_env("Home", "wlan0", None)[2].1, "");
+
+        let line =
+            "3: wlan0    inet 192.168.1.23/24 brd 192.168.1.255 scope global dynamic wlan0\n";
+        assert_eq!(parse_ipv4(line), Some(Ipv4Addr::new(192, 168, 1, 23)));
+        assert_eq!(parse_ipv4(""), None);
+    }
+}
@@ -0,0 +1,84 @@
+//! One daemon per machine
+//!
+//! Two daemons would fight over the radio and the IPC socket, so
+//! `run_daemon` takes an exclusive flock on a pidfile first. The kernel drops
+//! the lock when the process exits, however it exits, so a stale pidfile left
+//! behind by a crash never blocks the next start.
+
+use anyhow::{bail, Context, Result};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+pub const DEFAULT_PID_FILE: &str = "/run/hyper-connect.pid";
+
+/// Held for the daemon's lifetime; dropping it releases the lock.
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Lock `path` and write our pid into it, or name the pid holding it.
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(0o644)
+            .open(path)
+            .with_context(|| format!("Failed to open pid file {}", path.display()))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut holder = String::new();
+                let _ = file.read_to_string(&mut holder);
+                match holder.trim().parse::<u32>() {
+                    Ok(pid) => bail!("another instance is running (pid {})", pid),
+                    Err(_) => bail!("another instance is running ({} is locked)", path.display()),
+                }
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+            }
+        }
+
+        // Only the lock holder rewrites the file, so readers see a whole pid
+        // or, briefly, nothing.
+        file.set_len(0)?;
+        writeln!(file, "{}", std::process::id())
+            .with_context(|| format!("Failed to write pid file {}", path.display()))?;
+        Ok(Self { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_instance_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hyper-connect.pid");
+
+        let first = InstanceLock::acquire(&path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{}\n", std::process::id())
+        );
+
+        // flock is per open file, so a second open in this process conflicts
+        // just like another daemon would.
+        let error = InstanceLock::acquire(&path).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("another instance is running (pid {})", std::process::id())
+        );
+
+        drop(first);
+        InstanceLock::acquire(&path).unwrap();
+    }
+}
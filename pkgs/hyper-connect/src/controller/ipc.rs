@@ -1,27 +1,134 @@
 //! IPC server for TUI client communication
+//!
+//! Newline-delimited JSON over a Unix socket. A client may open with `Hello` to
+//! learn the daemon version and which requests it understands; clients that
+//! skip the handshake keep working as before.
+//!
+//! When the daemon runs with an IPC token, every connection must open with a
+//! `Hello` carrying the token from `<socket>.token` before anything else is served.
 
-use super::state::WifiStateSnapshot;
+use super::history::StateTransition;
+use super::state::{ConnectionStatus, WifiStateSnapshot};
 use super::{AppState, ControlCommand};
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::time::Duration;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
 use tokio::net::{UnixListener, UnixStream};
 
+/// Socket the daemon listens on and clients connect to unless told otherwise.
+pub const DEFAULT_SOCKET_PATH: &str = "/run/hyper-connect.sock";
+
+/// Version of the request/response protocol spoken by this build.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Error text returned for requests this daemon doesn't know about.
+pub const UNSUPPORTED_REQUEST: &str = "unsupported request";
+
+/// Requests this daemon handles, as advertised in `Welcome`.
+const SUPPORTED_REQUESTS: &[&str] = &[
+    "Hello",
+    "GetStatus",
+    "GetHistory",
+    "GetConfig",
+    "GetDiagnostics",
+    "Scan",
+    "Connect",
+    "CancelConnect",
+    "SwitchBackend",
+    "SetPriority",
+    "SaveCredential",
+    "WaitForTerminal",
+    "RestartAp",
+    "Shutdown",
+];
+
+/// Upper bound on how long a single `WaitForTerminal` request may block.
+pub const MAX_WAIT_SECS: u64 = 600;
+
+/// A connection that sends nothing for this long is closed.
+const CLIENT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest request line accepted, newline included; a longer one is refused
+/// and the connection closed.
+const MAX_REQUEST_BYTES: u64 = 64 * 1024;
+
 /// IPC request from client
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum IpcRequest {
+    /// Optional handshake; answered with `Welcome`.
+    Hello {
+        client_version: String,
+        protocol: u32,
+        /// Shared secret from the token file, when the daemon requires one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+    },
     GetStatus,
+    /// Recent status transitions, oldest first
+    GetHistory,
+    /// Settings the daemon resolved at startup
+    GetConfig,
+    /// Wireless adapters found and whether their drivers are bound
+    GetDiagnostics,
     Scan,
     Connect {
         ssid: String,
         password: String,
         #[serde(default = "default_save")]
         save: bool,
+        /// Treat the network as hidden (not expected in scan results)
+        #[serde(default)]
+        hidden: bool,
+        /// Pin a specific access point ("aa:bb:cc:dd:ee:ff")
+        #[serde(default)]
+        bssid: Option<String>,
+        /// Client-certificate credentials for EAP-TLS networks
+        #[serde(default)]
+        eap_tls: Option<super::EapTls>,
+        /// Only verify the credentials; send `SaveCredential` once connected to keep them
+        #[serde(default)]
+        dry_run: bool,
+        /// Raw SSID of the network picked from the scan list, matched exactly
+        #[serde(default)]
+        ssid_bytes: Option<Vec<u8>>,
+        /// Security picked for a manually entered network, instead of
+        /// guessing from whether a password was given
+        #[serde(default)]
+        security: Option<super::ManualSecurity>,
+        /// Ignore `password` and use the one saved for `ssid`
+        #[serde(default)]
+        use_saved: bool,
     },
+    /// Abort the connect attempt in progress and bring the setup AP back
+    CancelConnect,
     SwitchBackend {
         backend: super::WifiBackend,
     },
+    /// Restart the setup AP, e.g. when it stopped showing up for clients
+    RestartAp,
+    /// Set the auto-connect priority of a saved network (higher is preferred)
+    SetPriority {
+        ssid: String,
+        priority: i32,
+    },
+    /// Save the credentials a dry-run connect to the network now joined
+    /// verified; the daemon still holds them, so no password is sent
+    SaveCredential {
+        ssid: String,
+        /// The dry-run attempt (`current_attempt_id`); the current one when absent
+        #[serde(default)]
+        attempt_id: Option<u64>,
+    },
+    /// Block until the status is `Connected` or `Failed` (or the timeout,
+    /// capped at [`MAX_WAIT_SECS`]) and answer with the final `Status`
+    WaitForTerminal {
+        timeout_secs: u64,
+    },
     Shutdown,
 }
 
@@ -29,24 +136,167 @@ fn default_save() -> bool {
     true // Default to saving credentials
 }
 
+/// Daemon settings once `auto` values have been picked, for clients to show
+/// the portal address before the AP is up.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResolvedConfig {
+    pub interface: String,
+    pub ap_ip: String,
+    pub port: u16,
+    /// Setup AP SSID
+    pub ssid: String,
+    pub portal_url: String,
+}
+
+impl From<&super::DaemonConfig> for ResolvedConfig {
+    fn from(config: &super::DaemonConfig) -> Self {
+        Self {
+            interface: config.interface.clone(),
+            ap_ip: config.ap_ip.clone(),
+            port: config.port,
+            ssid: config.ssid.clone(),
+            portal_url: config.portal_url(),
+        }
+    }
+}
+
 /// IPC response to client
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum IpcResponse {
-    Status(WifiStateSnapshot),
+    Welcome {
+        daemon_version: String,
+        protocol: u32,
+        supported_requests: Vec<String>,
+        /// Branding configured on the daemon, for clients to mirror
+        #[serde(default)]
+        portal_title: Option<String>,
+        #[serde(default)]
+        portal_subtitle: Option<String>,
+        /// Display threshold for weak networks, as configured on the daemon
+        #[serde(default)]
+        min_signal: Option<u8>,
+    },
+    Status(Box<WifiStateSnapshot>),
+    History(Vec<StateTransition>),
+    Config(ResolvedConfig),
+    Diagnostics(super::AdapterReport),
     Ok,
     Error(String),
 }
 
+/// Bind the IPC socket, replacing a stale one, with the given mode and group.
+pub fn bind_socket(path: &str, mode: u32, group: Option<&str>) -> Result<UnixListener> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let gid = group.map(resolve_group).transpose()?;
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path).context("Failed to bind IPC socket")?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .context("Failed to set IPC socket mode")?;
+    if let Some(gid) = gid {
+        std::os::unix::fs::chown(path, None, Some(gid))
+            .context("Failed to set IPC socket group")?;
+    }
+    Ok(listener)
+}
+
+/// File holding the shared secret for the socket at `socket_path`.
+pub fn token_path(socket_path: &str) -> String {
+    format!("{}.token", socket_path)
+}
+
+/// Generate a fresh token and write it next to the socket, readable by root
+/// and `group` only.
+pub fn write_token(socket_path: &str, group: Option<&str>) -> Result<String> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::fs::PermissionsExt;
+
+    let gid = group.map(resolve_group).transpose()?;
+    let path = token_path(socket_path);
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    let _ = std::fs::remove_file(&path);
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o640)
+        .open(&path)
+        .context("Failed to create IPC token file")?;
+    file.write_all(token.as_bytes())
+        .context("Failed to write IPC token file")?;
+    // The umask may have narrowed the creation mode.
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640))
+        .context("Failed to set IPC token file mode")?;
+    if let Some(gid) = gid {
+        std::os::unix::fs::chown(&path, None, Some(gid))
+            .context("Failed to set IPC token file group")?;
+    }
+    Ok(token)
+}
+
+/// Drop a token left behind by a previous run that required one.
+pub fn remove_token(socket_path: &str) {
+    let _ = std::fs::remove_file(token_path(socket_path));
+}
+
+/// Token for the socket at `socket_path`, if the daemon wrote one we can read.
+fn read_token(socket_path: &str) -> Option<String> {
+    let token = std::fs::read_to_string(token_path(socket_path)).ok()?;
+    let token = token.trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+/// Group id for a numeric gid or a name in /etc/group.
+fn resolve_group(group: &str) -> Result<u32> {
+    if let Ok(gid) = group.parse() {
+        return Ok(gid);
+    }
+    let groups = std::fs::read_to_string("/etc/group").context("Failed to read /etc/group")?;
+    find_gid(&groups, group).with_context(|| format!("Unknown group {}", group))
+}
+
+fn find_gid(groups: &str, name: &str) -> Option<u32> {
+    groups.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()? != name {
+            return None;
+        }
+        fields.nth(1)?.parse().ok()
+    })
+}
+
+/// Parse a socket mode given in octal (`660`, `0660` or `0o660`).
+pub fn parse_socket_mode(value: &str) -> std::result::Result<u32, String> {
+    let digits = value.strip_prefix("0o").unwrap_or(value);
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o777 => Ok(mode),
+        _ => Err(format!("{} is not an octal permission mode", value)),
+    }
+}
+
 /// Run the IPC server
 pub async fn run_ipc_server(listener: UnixListener, state: Arc<AppState>) -> Result<()> {
+    let slots = Arc::new(tokio::sync::Semaphore::new(state.config.ipc_max_clients));
     loop {
         match listener.accept().await {
-            Ok((stream, _)) => {
+            Ok((mut stream, _)) => {
+                // Over the cap: say why and hang up rather than queueing.
+                let Ok(permit) = slots.clone().try_acquire_owned() else {
+                    tracing::warn!(
+                        max = state.config.ipc_max_clients,
+                        "Refusing IPC client: too many connections"
+                    );
+                    let response = IpcResponse::Error("Too many IPC clients".to_string());
+                    let json = serde_json::to_string(&response)? + "\n";
+                    let _ = stream.write_all(json.as_bytes()).await;
+                    continue;
+                };
                 let state = state.clone();
                 tokio::spawn(async move {
                     if let Err(e) = handle_client(stream, state).await {
                         tracing::warn!(error = %e, "IPC client error");
                     }
+                    drop(permit);
                 });
             }
             Err(e) => {
@@ -60,108 +310,523 @@ async fn handle_client(stream: UnixStream, state: Arc<AppState>) -> Result<()> {
     let (reader, mut writer) = stream.into_split();
     let mut reader = BufReader::new(reader);
     let mut line = String::new();
+    let mut authenticated = state.ipc_token.is_none();
 
     loop {
         line.clear();
-        let n = reader.read_line(&mut line).await?;
+        let mut limited = (&mut reader).take(MAX_REQUEST_BYTES + 1);
+        let n = match tokio::time::timeout(CLIENT_IDLE_TIMEOUT, limited.read_line(&mut line)).await
+        {
+            Ok(read) => read?,
+            Err(_) => {
+                tracing::debug!("Closing idle IPC connection");
+                break;
+            }
+        };
         if n == 0 {
             break; // EOF
         }
+        if n as u64 > MAX_REQUEST_BYTES {
+            tracing::warn!("Closing IPC connection after an oversized request");
+            let response = IpcResponse::Error("Request too large".to_string());
+            let json = serde_json::to_string(&response)? + "\n";
+            writer.write_all(json.as_bytes()).await?;
+            break;
+        }
 
-        let request: IpcRequest = match serde_json::from_str(&line) {
-            Ok(req) => req,
-            Err(e) => {
-                let response = IpcResponse::Error(format!("Invalid request: {}", e));
-                let json = serde_json::to_string(&response)? + "\n";
-                writer.write_all(json.as_bytes()).await?;
-                continue;
+        let response = match parse_request(&line) {
+            Ok(request @ IpcRequest::Hello { .. }) if state.ipc_token.is_some() => {
+                if presents_token(&request, state.ipc_token.as_deref()) {
+                    authenticated = true;
+                    handle_request(&state, request).await
+                } else {
+                    tracing::warn!("IPC client presented an invalid token");
+                    IpcResponse::Error("Invalid IPC token".to_string())
+                }
             }
+            Ok(_) if !authenticated => {
+                IpcResponse::Error("Unauthorized: send Hello with the IPC token first".to_string())
+            }
+            Ok(IpcRequest::WaitForTerminal { timeout_secs }) => {
+                match wait_unless_closed(&mut reader, &state, timeout_secs).await {
+                    Some(response) => response,
+                    None => break, // client went away mid-wait
+                }
+            }
+            Ok(request) => handle_request(&state, request).await,
+            Err(message) => IpcResponse::Error(message),
         };
 
-        let response = match request {
-            IpcRequest::GetStatus => {
-                let wifi_state = state.wifi_state.read().await;
-                IpcResponse::Status(WifiStateSnapshot::from(&*wifi_state))
+        let json = serde_json::to_string(&response)? + "\n";
+        writer.write_all(json.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+/// Whether a `Hello` carries the token the daemon expects.
+fn presents_token(request: &IpcRequest, expected: Option<&str>) -> bool {
+    match (request, expected) {
+        (
+            IpcRequest::Hello {
+                token: Some(token), ..
+            },
+            Some(expected),
+        ) => tokens_match(token, expected),
+        (IpcRequest::Hello { token: None, .. }, None) => true,
+        _ => false,
+    }
+}
+
+/// Compare tokens in time independent of where they first differ, so a
+/// local client can't guess the secret byte by byte. Only the length leaks,
+/// and every generated token has the same one.
+fn tokens_match(presented: &str, expected: &str) -> bool {
+    let (presented, expected) = (presented.as_bytes(), expected.as_bytes());
+    presented.len() == expected.len()
+        && presented
+            .iter()
+            .zip(expected)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Run a `WaitForTerminal`, giving up early if the client hangs up.
+///
+/// Further requests pipelined behind the wait stay buffered for the next read.
+async fn wait_unless_closed<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    state: &AppState,
+    timeout_secs: u64,
+) -> Option<IpcResponse> {
+    let wait = settled_status(state, timeout_secs);
+    tokio::pin!(wait);
+
+    let closed = tokio::select! {
+        response = &mut wait => return Some(response),
+        closed = client_closed(reader) => closed,
+    };
+    if closed {
+        tracing::debug!("IPC client disconnected during WaitForTerminal");
+        return None;
+    }
+    Some(wait.await)
+}
+
+/// Resolves once the client has sent more data (`false`) or hung up (`true`).
+async fn client_closed<R: AsyncBufRead + Unpin>(reader: &mut R) -> bool {
+    matches!(reader.fill_buf().await, Ok([]) | Err(_))
+}
+
+/// Wait for the current connection attempt to settle and snapshot the result.
+async fn settled_status(state: &AppState, timeout_secs: u64) -> IpcResponse {
+    let deadline =
+        tokio::time::Instant::now() + Duration::from_secs(timeout_secs.min(MAX_WAIT_SECS));
+    // Subscribe before looking so an update between the check and the wait isn't lost.
+    let mut updates = state.state_tx.subscribe();
+
+    loop {
+        {
+            let wifi_state = state.wifi_state.read().await;
+            // A queued connect keeps the previous attempt's outcome from counting.
+            if wifi_state.status.is_terminal() && !state.connect_in_progress.load(Ordering::SeqCst)
+            {
+                return IpcResponse::Status(Box::new(WifiStateSnapshot::from(&*wifi_state)));
             }
-            IpcRequest::Scan => {
-                let _ = state.command_tx.send(ControlCommand::Scan).await;
-                IpcResponse::Ok
+        }
+        match tokio::time::timeout_at(deadline, updates.changed()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) | Err(_) => break,
+        }
+    }
+
+    let wifi_state = state.wifi_state.read().await;
+    IpcResponse::Status(Box::new(WifiStateSnapshot::from(&*wifi_state)))
+}
+
+/// Parse one request line, distinguishing unknown variants from garbage.
+fn parse_request(line: &str) -> std::result::Result<IpcRequest, String> {
+    serde_json::from_str(line).map_err(|e| match request_name(line) {
+        Some(name) if !SUPPORTED_REQUESTS.contains(&name.as_str()) => {
+            tracing::debug!(request = %name, "Unsupported IPC request");
+            UNSUPPORTED_REQUEST.to_string()
+        }
+        _ => format!("Invalid request: {}", e),
+    })
+}
+
+/// Variant name of an externally tagged request (`"Name"` or `{"Name": ...}`).
+fn request_name(line: &str) -> Option<String> {
+    match serde_json::from_str::<serde_json::Value>(line).ok()? {
+        serde_json::Value::String(name) => Some(name),
+        serde_json::Value::Object(map) if map.len() == 1 => map.keys().next().cloned(),
+        _ => None,
+    }
+}
+
+pub(super) async fn handle_request(state: &AppState, request: IpcRequest) -> IpcResponse {
+    match request {
+        IpcRequest::Hello {
+            client_version,
+            protocol,
+            ..
+        } => {
+            tracing::debug!(client_version = %client_version, protocol, "IPC client hello");
+            IpcResponse::Welcome {
+                daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+                protocol: PROTOCOL_VERSION,
+                supported_requests: SUPPORTED_REQUESTS.iter().map(|r| r.to_string()).collect(),
+                portal_title: Some(state.config.portal_title.clone()),
+                portal_subtitle: Some(state.config.portal_subtitle.clone()),
+                min_signal: Some(state.config.min_signal),
             }
-            IpcRequest::Connect {
-                ssid,
-                password,
-                save,
-            } => {
-                let _ = state
-                    .command_tx
-                    .send(ControlCommand::Connect {
-                        ssid,
-                        password,
-                        save,
-                    })
-                    .await;
-                IpcResponse::Ok
+        }
+        IpcRequest::GetStatus => {
+            let wifi_state = state.wifi_state.read().await;
+            IpcResponse::Status(Box::new(WifiStateSnapshot::from(&*wifi_state)))
+        }
+        IpcRequest::GetHistory => match state.history.lock() {
+            Ok(history) => IpcResponse::History(history.entries()),
+            Err(_) => IpcResponse::Error("History unavailable".to_string()),
+        },
+        IpcRequest::GetConfig => IpcResponse::Config(ResolvedConfig::from(&state.config)),
+        IpcRequest::GetDiagnostics => {
+            match tokio::task::spawn_blocking(super::AdapterReport::collect).await {
+                Ok(report) => IpcResponse::Diagnostics(report),
+                Err(_) => IpcResponse::Error("Diagnostics unavailable".to_string()),
             }
-            IpcRequest::SwitchBackend { backend } => {
-                let _ = state
-                    .command_tx
-                    .send(ControlCommand::SwitchBackend { backend })
-                    .await;
-                IpcResponse::Ok
+        }
+        IpcRequest::Scan => {
+            let _ = state.command_tx.send(ControlCommand::Scan).await;
+            IpcResponse::Ok
+        }
+        IpcRequest::Connect {
+            ssid,
+            password,
+            save,
+            hidden,
+            bssid,
+            eap_tls,
+            dry_run,
+            ssid_bytes,
+            security,
+            use_saved,
+        } => {
+            let bssid = match bssid.as_deref().map(super::parse_bssid) {
+                None => None,
+                Some(Some(bssid)) => Some(bssid),
+                Some(None) => return IpcResponse::Error("Invalid BSSID".to_string()),
+            };
+            if let Some(Err(e)) = eap_tls.as_ref().map(super::EapTls::validate) {
+                return IpcResponse::Error(format!("{:#}", e));
+            }
+            if ssid_bytes
+                .as_deref()
+                .is_some_and(|b| !super::valid_ssid_bytes(b))
+            {
+                return IpcResponse::Error("Invalid SSID bytes".to_string());
+            }
+            let password = if use_saved {
+                match super::saved_password(&state.config, &ssid) {
+                    Ok(password) => password,
+                    Err(e) => return IpcResponse::Error(format!("{:#}", e)),
+                }
+            } else {
+                password
+            };
+            if let Err(e) = super::check_password_given(
+                &*state.wifi_state.read().await,
+                &ssid,
+                &password,
+                eap_tls.is_some(),
+                security,
+            ) {
+                return IpcResponse::Error(format!("{:#}", e));
             }
-            IpcRequest::Shutdown => {
-                let _ = state.command_tx.send(ControlCommand::Shutdown).await;
+            if let Err(e) = super::check_connect_cooldown(&*state.wifi_state.read().await, &ssid) {
+                return IpcResponse::Error(format!("{:#}", e));
+            }
+            // Marked before queueing so a following WaitForTerminal waits for this attempt.
+            state.connect_in_progress.store(true, Ordering::SeqCst);
+            let sent = state
+                .command_tx
+                .send(ControlCommand::Connect {
+                    ssid,
+                    password,
+                    save: save && !dry_run,
+                    hidden,
+                    bssid,
+                    eap_tls,
+                    dry_run,
+                    ssid_bytes,
+                    security,
+                    attempt_id: state.allocate_attempt_id(),
+                })
+                .await;
+            if sent.is_err() {
+                state.connect_in_progress.store(false, Ordering::SeqCst);
+            }
+            IpcResponse::Ok
+        }
+        IpcRequest::SwitchBackend { backend } => {
+            let _ = state
+                .command_tx
+                .send(ControlCommand::SwitchBackend { backend })
+                .await;
+            IpcResponse::Ok
+        }
+        IpcRequest::CancelConnect => {
+            if state.cancel_connect() {
                 IpcResponse::Ok
+            } else {
+                IpcResponse::Error("No connection attempt in progress".to_string())
             }
-        };
+        }
+        IpcRequest::RestartAp => {
+            if state.connect_in_progress.load(Ordering::SeqCst) {
+                return IpcResponse::Error("A connection attempt is in progress".to_string());
+            }
+            if state.wifi_state.read().await.is_station_connected() {
+                return IpcResponse::Error(
+                    "Connected to a network; the setup AP is not in use".to_string(),
+                );
+            }
+            if state.ap_restart_in_progress.swap(true, Ordering::SeqCst) {
+                return IpcResponse::Error("An AP restart is already in progress".to_string());
+            }
+            if state
+                .command_tx
+                .send(ControlCommand::RestartAp)
+                .await
+                .is_err()
+            {
+                state.ap_restart_in_progress.store(false, Ordering::SeqCst);
+                return IpcResponse::Error("Controller is not running".to_string());
+            }
+            IpcResponse::Ok
+        }
+        IpcRequest::SetPriority { ssid, priority } => {
+            if !state.wifi_state.read().await.saved_ssids.contains(&ssid) {
+                return IpcResponse::Error(format!("No saved credentials for {}", ssid));
+            }
+            // The control loop is the only writer of the credentials store.
+            let _ = state
+                .command_tx
+                .send(ControlCommand::SetPriority { ssid, priority })
+                .await;
+            IpcResponse::Ok
+        }
+        IpcRequest::SaveCredential { ssid, attempt_id } => {
+            let attempt_id = {
+                let wifi_state = state.wifi_state.read().await;
+                if wifi_state.status != ConnectionStatus::Connected
+                    || wifi_state.connected_ssid.as_deref() != Some(ssid.as_str())
+                {
+                    return IpcResponse::Error(format!("Not connected to {}", ssid));
+                }
+                match attempt_id.or(wifi_state.current_attempt_id) {
+                    Some(attempt_id) => attempt_id,
+                    None => return IpcResponse::Error(format!("No verified attempt for {}", ssid)),
+                }
+            };
+            let _ = state
+                .command_tx
+                .send(ControlCommand::SaveCredential { ssid, attempt_id })
+                .await;
+            IpcResponse::Ok
+        }
+        IpcRequest::WaitForTerminal { timeout_secs } => settled_status(state, timeout_secs).await,
+        IpcRequest::Shutdown => {
+            let _ = state.command_tx.send(ControlCommand::Shutdown).await;
+            IpcResponse::Ok
+        }
+    }
+}
 
-        let json = serde_json::to_string(&response)? + "\n";
-        writer.write_all(json.as_bytes()).await?;
+/// What a daemon reported about itself during the handshake.
+#[derive(Debug, Clone)]
+pub struct DaemonInfo {
+    pub daemon_version: Option<String>,
+    pub protocol: u32,
+    pub supported_requests: Vec<String>,
+    pub portal_title: Option<String>,
+    pub portal_subtitle: Option<String>,
+    pub min_signal: Option<u8>,
+}
+
+impl DaemonInfo {
+    pub fn supports(&self, request: &str) -> bool {
+        self.supported_requests.iter().any(|r| r == request)
     }
+}
 
-    Ok(())
+/// Perform the `Hello` handshake (client side).
+///
+/// Daemons that predate the handshake reject `Hello` as an invalid request;
+/// they are reported as protocol 0 with the original request set.
+pub async fn handshake(socket_path: &str) -> Result<DaemonInfo> {
+    let request = IpcRequest::Hello {
+        client_version: env!("CARGO_PKG_VERSION").to_string(),
+        protocol: PROTOCOL_VERSION,
+        token: read_token(socket_path),
+    };
+
+    match round_trip(socket_path, &request).await? {
+        IpcResponse::Welcome {
+            daemon_version,
+            protocol,
+            supported_requests,
+            portal_title,
+            portal_subtitle,
+            min_signal,
+        } => Ok(DaemonInfo {
+            daemon_version: Some(daemon_version),
+            protocol,
+            supported_requests,
+            portal_title,
+            portal_subtitle,
+            min_signal,
+        }),
+        IpcResponse::Error(_) => Ok(DaemonInfo {
+            daemon_version: None,
+            protocol: 0,
+            supported_requests: ["GetStatus", "Scan", "Connect", "SwitchBackend", "Shutdown"]
+                .iter()
+                .map(|r| r.to_string())
+                .collect(),
+            portal_title: None,
+            portal_subtitle: None,
+            min_signal: None,
+        }),
+        _ => anyhow::bail!("Unexpected response"),
+    }
 }
 
-/// Get status from daemon (client side)
-pub async fn get_status(socket_path: &str) -> Result<WifiStateSnapshot> {
+/// Send a single request on a fresh connection and read the reply.
+///
+/// If a token file is readable, the connection is authenticated with a
+/// `Hello` first; its `Welcome` is discarded.
+async fn round_trip(socket_path: &str, request: &IpcRequest) -> Result<IpcResponse> {
     let stream = UnixStream::connect(socket_path).await?;
     let (reader, mut writer) = stream.into_split();
     let mut reader = BufReader::new(reader);
 
-    let request = IpcRequest::GetStatus;
-    let json = serde_json::to_string(&request)? + "\n";
+    let token = match request {
+        IpcRequest::Hello { .. } => None,
+        _ => read_token(socket_path),
+    };
+    if let Some(token) = token {
+        let hello = IpcRequest::Hello {
+            client_version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol: PROTOCOL_VERSION,
+            token: Some(token),
+        };
+        if let IpcResponse::Error(e) = exchange_line(&mut reader, &mut writer, &hello).await? {
+            anyhow::bail!("Daemon rejected IPC token: {}", e);
+        }
+    }
+
+    exchange_line(&mut reader, &mut writer, request).await
+}
+
+async fn exchange_line<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin>(
+    reader: &mut R,
+    writer: &mut W,
+    request: &IpcRequest,
+) -> Result<IpcResponse> {
+    let json = serde_json::to_string(request)? + "\n";
     writer.write_all(json.as_bytes()).await?;
 
     let mut line = String::new();
     reader.read_line(&mut line).await?;
 
-    let response: IpcResponse = serde_json::from_str(&line)?;
-    match response {
-        IpcResponse::Status(state) => Ok(state),
+    Ok(serde_json::from_str(&line)?)
+}
+
+/// Get status from daemon (client side)
+pub async fn get_status(socket_path: &str) -> Result<WifiStateSnapshot> {
+    match round_trip(socket_path, &IpcRequest::GetStatus).await? {
+        IpcResponse::Status(state) => Ok(*state),
         IpcResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
         _ => anyhow::bail!("Unexpected response"),
     }
 }
 
-/// Send connect command to daemon (client side)
-pub async fn send_connect(socket_path: &str, ssid: &str, password: &str, save: bool) -> Result<()> {
-    let stream = UnixStream::connect(socket_path).await?;
-    let (reader, mut writer) = stream.into_split();
-    let mut reader = BufReader::new(reader);
+/// Fetch recent status transitions, oldest first (client side)
+pub async fn get_history(socket_path: &str) -> Result<Vec<StateTransition>> {
+    match round_trip(socket_path, &IpcRequest::GetHistory).await? {
+        IpcResponse::History(entries) => Ok(entries),
+        IpcResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
+        _ => anyhow::bail!("Unexpected response"),
+    }
+}
 
-    let request = IpcRequest::Connect {
-        ssid: ssid.to_string(),
-        password: password.to_string(),
-        save,
+/// Fetch the daemon's resolved settings (client side)
+pub async fn get_config(socket_path: &str) -> Result<ResolvedConfig> {
+    match round_trip(socket_path, &IpcRequest::GetConfig).await? {
+        IpcResponse::Config(config) => Ok(config),
+        IpcResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
+        _ => anyhow::bail!("Unexpected response"),
+    }
+}
+
+/// Fetch the daemon's view of the wireless adapters (client side)
+pub async fn get_diagnostics(socket_path: &str) -> Result<super::AdapterReport> {
+    match round_trip(socket_path, &IpcRequest::GetDiagnostics).await? {
+        IpcResponse::Diagnostics(report) => Ok(report),
+        IpcResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
+        _ => anyhow::bail!("Unexpected response"),
+    }
+}
+
+/// Block until the daemon's connection attempt settles or `timeout` passes
+/// (client side). The returned snapshot may still be non-terminal on timeout.
+pub async fn wait_for_terminal(socket_path: &str, timeout: Duration) -> Result<WifiStateSnapshot> {
+    let request = IpcRequest::WaitForTerminal {
+        timeout_secs: timeout.as_secs().max(1),
     };
-    let json = serde_json::to_string(&request)? + "\n";
-    writer.write_all(json.as_bytes()).await?;
+    match round_trip(socket_path, &request).await? {
+        IpcResponse::Status(state) => Ok(*state),
+        IpcResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
+        _ => anyhow::bail!("Unexpected response"),
+    }
+}
 
-    let mut line = String::new();
-    reader.read_line(&mut line).await?;
+/// Parameters of a connect request (client side)
+#[derive(Debug, Clone)]
+pub struct ConnectParams {
+    pub ssid: String,
+    pub password: String,
+    pub save: bool,
+    pub hidden: bool,
+    pub bssid: Option<String>,
+    pub eap_tls: Option<super::EapTls>,
+    /// Verify only; the caller follows up with [`send_save_credential`]
+    pub dry_run: bool,
+    /// Exact bytes of a network chosen from the scan list
+    pub ssid_bytes: Option<Vec<u8>>,
+    /// Security of a manually entered network
+    pub security: Option<super::ManualSecurity>,
+    /// Connect with the daemon's saved password instead of `password`
+    pub use_saved: bool,
+}
 
-    let response: IpcResponse = serde_json::from_str(&line)?;
-    match response {
+/// Send connect command to daemon (client side)
+pub async fn send_connect(socket_path: &str, params: &ConnectParams) -> Result<()> {
+    let request = IpcRequest::Connect {
+        ssid: params.ssid.clone(),
+        password: params.password.clone(),
+        save: params.save,
+        hidden: params.hidden,
+        bssid: params.bssid.clone(),
+        eap_tls: params.eap_tls.clone(),
+        dry_run: params.dry_run,
+        ssid_bytes: params.ssid_bytes.clone(),
+        security: params.security,
+        use_saved: params.use_saved,
+    };
+
+    match round_trip(socket_path, &request).await? {
         IpcResponse::Ok => Ok(()),
         IpcResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
         _ => anyhow::bail!("Unexpected response"),
@@ -170,21 +835,558 @@ pub async fn send_connect(socket_path: &str, ssid: &str, password: &str, save: b
 
 /// Send backend switch command to daemon (client side)
 pub async fn send_switch_backend(socket_path: &str, backend: super::WifiBackend) -> Result<()> {
-    let stream = UnixStream::connect(socket_path).await?;
-    let (reader, mut writer) = stream.into_split();
-    let mut reader = BufReader::new(reader);
+    match round_trip(socket_path, &IpcRequest::SwitchBackend { backend }).await? {
+        IpcResponse::Ok => Ok(()),
+        IpcResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
+        _ => anyhow::bail!("Unexpected response"),
+    }
+}
 
-    let request = IpcRequest::SwitchBackend { backend };
-    let json = serde_json::to_string(&request)? + "\n";
-    writer.write_all(json.as_bytes()).await?;
+/// Ask the daemon to restart the setup AP (client side)
+pub async fn send_restart_ap(socket_path: &str) -> Result<()> {
+    match round_trip(socket_path, &IpcRequest::RestartAp).await? {
+        IpcResponse::Ok => Ok(()),
+        IpcResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
+        _ => anyhow::bail!("Unexpected response"),
+    }
+}
 
-    let mut line = String::new();
-    reader.read_line(&mut line).await?;
+/// Abort the daemon's running connect attempt (client side)
+pub async fn send_cancel_connect(socket_path: &str) -> Result<()> {
+    match round_trip(socket_path, &IpcRequest::CancelConnect).await? {
+        IpcResponse::Ok => Ok(()),
+        IpcResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
+        _ => anyhow::bail!("Unexpected response"),
+    }
+}
 
-    let response: IpcResponse = serde_json::from_str(&line)?;
-    match response {
+/// Keep credentials that a dry-run connect just verified (client side)
+pub async fn send_save_credential(
+    socket_path: &str,
+    ssid: &str,
+    attempt_id: Option<u64>,
+) -> Result<()> {
+    let request = IpcRequest::SaveCredential {
+        ssid: ssid.to_string(),
+        attempt_id,
+    };
+    match round_trip(socket_path, &request).await? {
         IpcResponse::Ok => Ok(()),
         IpcResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
         _ => anyhow::bail!("Unexpected response"),
     }
 }
+
+/// Set a saved network's auto-connect priority (client side)
+pub async fn send_set_priority(socket_path: &str, ssid: &str, priority: i32) -> Result<()> {
+    let request = IpcRequest::SetPriority {
+        ssid: ssid.to_string(),
+        priority,
+    };
+    match round_trip(socket_path, &request).await? {
+        IpcResponse::Ok => Ok(()),
+        IpcResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
+        _ => anyhow::bail!("Unexpected response"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{test_app_state, test_app_state_with};
+
+    /// Drive `handle_client` over a socket pair, returning one response per request line.
+    async fn exchange(lines: &[&str]) -> Vec<IpcResponse> {
+        exchange_with_token(None, lines).await
+    }
+
+    async fn exchange_with_token(token: Option<&str>, lines: &[&str]) -> Vec<IpcResponse> {
+        let (mut state, _command_rx) = test_app_state();
+        Arc::get_mut(&mut state).unwrap().ipc_token = token.map(str::to_string);
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = tokio::spawn(handle_client(server, state));
+
+        let (reader, mut writer) = client.into_split();
+        let mut reader = BufReader::new(reader);
+        let mut responses = Vec::new();
+        for line in lines {
+            writer
+                .write_all(format!("{}\n", line).as_bytes())
+                .await
+                .unwrap();
+            let mut reply = String::new();
+            reader.read_line(&mut reply).await.unwrap();
+            responses.push(serde_json::from_str(&reply).unwrap());
+        }
+
+        drop(writer);
+        server.await.unwrap().unwrap();
+        responses
+    }
+
+    #[tokio::test]
+    async fn test_get_status_without_handshake() {
+        let responses = exchange(&[r#""GetStatus""#]).await;
+        assert!(matches!(responses[0], IpcResponse::Status(_)));
+    }
+
+    #[tokio::test]
+    async fn test_hello_then_get_status() {
+        let responses = exchange(&[
+            r#"{"Hello":{"client_version":"0.0.1","protocol":1}}"#,
+            r#""GetStatus""#,
+        ])
+        .await;
+
+        match &responses[0] {
+            IpcResponse::Welcome {
+                protocol,
+                supported_requests,
+                ..
+            } => {
+                assert_eq!(*protocol, PROTOCOL_VERSION);
+                assert!(supported_requests.iter().any(|r| r == "GetStatus"));
+            }
+            other => panic!("expected Welcome, got {:?}", other),
+        }
+        assert!(matches!(responses[1], IpcResponse::Status(_)));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_request_keeps_connection() {
+        let responses = exchange(&[
+            r#"{"Forget":{"ssid":"Home"}}"#,
+            r#""Subscribe""#,
+            r#"not json"#,
+            r#""GetStatus""#,
+        ])
+        .await;
+
+        assert!(matches!(&responses[0], IpcResponse::Error(e) if e == UNSUPPORTED_REQUEST));
+        assert!(matches!(&responses[1], IpcResponse::Error(e) if e == UNSUPPORTED_REQUEST));
+        assert!(matches!(&responses[2], IpcResponse::Error(e) if e.starts_with("Invalid request")));
+        assert!(matches!(responses[3], IpcResponse::Status(_)));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_terminal_wakes_on_failure() {
+        let (state, _command_rx) = test_app_state();
+        state.connect_in_progress.store(true, Ordering::SeqCst);
+
+        let waiter = tokio::spawn({
+            let state = state.clone();
+            async move { settled_status(&state, 30).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        {
+            let mut wifi_state = state.wifi_state.write().await;
+            wifi_state.status = ConnectionStatus::Failed;
+            state.connect_in_progress.store(false, Ordering::SeqCst);
+            state.publish(&wifi_state);
+        }
+
+        let response = tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(response, IpcResponse::Status(s) if s.status == ConnectionStatus::Failed));
+    }
+
+    #[tokio::test]
+    async fn test_get_config_reports_portal_url() {
+        let (state, _command_rx) = test_app_state_with(|config| config.port = 8080);
+        let IpcResponse::Config(config) = handle_request(&state, IpcRequest::GetConfig).await
+        else {
+            panic!("expected a config response");
+        };
+        assert_eq!(config.interface, "wlan0");
+        assert_eq!(config.ap_ip, "192.168.42.1");
+        assert_eq!(config.ssid, "HyperRecovery");
+        assert_eq!(config.portal_url, "http://192.168.42.1:8080");
+    }
+
+    #[tokio::test]
+    async fn test_get_diagnostics_is_advertised_and_answered() {
+        let (state, _command_rx) = test_app_state();
+        assert!(SUPPORTED_REQUESTS.contains(&"GetDiagnostics"));
+        let response = handle_request(&state, IpcRequest::GetDiagnostics).await;
+        assert!(matches!(response, IpcResponse::Diagnostics(_)));
+    }
+
+    #[tokio::test]
+    async fn test_history_follows_published_transitions() {
+        let (state, _command_rx) = test_app_state();
+        {
+            let mut wifi_state = state.wifi_state.write().await;
+            for status in [
+                ConnectionStatus::AwaitingCredentials,
+                ConnectionStatus::Connecting,
+                ConnectionStatus::Connecting,
+            ] {
+                wifi_state.status = status;
+                state.publish(&wifi_state);
+            }
+            wifi_state.status = ConnectionStatus::Failed;
+            wifi_state.connect_error = Some(crate::controller::ConnectError::WrongPassword);
+            state.publish(&wifi_state);
+        }
+
+        let IpcResponse::History(entries) = handle_request(&state, IpcRequest::GetHistory).await
+        else {
+            panic!("expected a history response");
+        };
+        let steps: Vec<_> = entries
+            .iter()
+            .map(|e| (e.from.clone(), e.to.clone()))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                (
+                    ConnectionStatus::Initializing,
+                    ConnectionStatus::AwaitingCredentials
+                ),
+                (
+                    ConnectionStatus::AwaitingCredentials,
+                    ConnectionStatus::Connecting
+                ),
+                (ConnectionStatus::Connecting, ConnectionStatus::Failed),
+            ]
+        );
+        assert!(entries[2].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_terminal_ignores_stale_outcome_while_connecting() {
+        let (state, _command_rx) = test_app_state();
+        state.wifi_state.write().await.status = ConnectionStatus::Failed;
+        state.connect_in_progress.store(true, Ordering::SeqCst);
+
+        let response = settled_status(&state, 0).await;
+        assert!(matches!(response, IpcResponse::Status(s) if s.status == ConnectionStatus::Failed));
+        let waited =
+            tokio::time::timeout(Duration::from_millis(50), settled_status(&state, 30)).await;
+        assert!(
+            waited.is_err(),
+            "should still be waiting for the queued connect"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_terminal_ends_when_client_hangs_up() {
+        let (state, _command_rx) = test_app_state();
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = tokio::spawn(handle_client(server, state));
+
+        let (_reader, mut writer) = client.into_split();
+        writer
+            .write_all(b"{\"WaitForTerminal\":{\"timeout_secs\":600}}\n")
+            .await
+            .unwrap();
+        writer.shutdown().await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server task should stop once the client is gone")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_save_credential_requires_connection() {
+        let (state, mut command_rx) = test_app_state();
+        let save = || IpcRequest::SaveCredential {
+            ssid: "Home".to_string(),
+            attempt_id: None,
+        };
+
+        let response = handle_request(&state, save()).await;
+        assert!(matches!(response, IpcResponse::Error(e) if e.contains("Not connected")));
+        assert!(command_rx.try_recv().is_err());
+
+        {
+            let mut wifi_state = state.wifi_state.write().await;
+            wifi_state.status = ConnectionStatus::Connected;
+            wifi_state.connected_ssid = Some("Home".to_string());
+            wifi_state.current_attempt_id = Some(7);
+        }
+        assert!(matches!(
+            handle_request(&state, save()).await,
+            IpcResponse::Ok
+        ));
+        assert!(matches!(
+            command_rx.try_recv(),
+            Ok(ControlCommand::SaveCredential { ssid, attempt_id: 7 }) if ssid == "Home"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_set_priority_goes_through_control_loop() {
+        let (state, mut command_rx) = test_app_state();
+        let request = || IpcRequest::SetPriority {
+            ssid: "Home".to_string(),
+            priority: 5,
+        };
+
+        let response = handle_request(&state, request()).await;
+        assert!(matches!(response, IpcResponse::Error(e) if e.contains("No saved credentials")));
+        assert!(command_rx.try_recv().is_err());
+
+        state
+            .wifi_state
+            .write()
+            .await
+            .saved_ssids
+            .insert("Home".to_string());
+        assert!(matches!(
+            handle_request(&state, request()).await,
+            IpcResponse::Ok
+        ));
+        assert!(matches!(
+            command_rx.try_recv(),
+            Ok(ControlCommand::SetPriority { ssid, priority: 5 }) if ssid == "Home"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_restart_ap_rejects_concurrent_restart() {
+        let (state, mut command_rx) = test_app_state();
+
+        assert!(matches!(
+            handle_request(&state, IpcRequest::RestartAp).await,
+            IpcResponse::Ok
+        ));
+        assert!(matches!(
+            handle_request(&state, IpcRequest::RestartAp).await,
+            IpcResponse::Error(e) if e.contains("already in progress")
+        ));
+        assert!(matches!(
+            command_rx.try_recv(),
+            Ok(ControlCommand::RestartAp)
+        ));
+        assert!(command_rx.try_recv().is_err());
+
+        state.ap_restart_in_progress.store(false, Ordering::SeqCst);
+        state.connect_in_progress.store(true, Ordering::SeqCst);
+        assert!(matches!(
+            handle_request(&state, IpcRequest::RestartAp).await,
+            IpcResponse::Error(_)
+        ));
+        assert!(!state.ap_restart_in_progress.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_connect_wakes_only_a_listening_attempt() {
+        let (state, _command_rx) = test_app_state();
+        assert!(matches!(
+            handle_request(&state, IpcRequest::CancelConnect).await,
+            IpcResponse::Error(e) if e.contains("No connection attempt")
+        ));
+
+        state.connect_in_progress.store(true, Ordering::SeqCst);
+        let cancelled = state.connect_cancel.notified();
+        tokio::pin!(cancelled);
+        cancelled.as_mut().enable();
+        assert!(matches!(
+            handle_request(&state, IpcRequest::CancelConnect).await,
+            IpcResponse::Ok
+        ));
+        tokio::time::timeout(Duration::from_secs(1), cancelled)
+            .await
+            .expect("listening attempt was not cancelled");
+
+        // Nobody listening: the cancel must not linger for the next attempt.
+        let next = state.connect_cancel.notified();
+        assert!(tokio::time::timeout(Duration::from_millis(50), next)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_use_saved_without_credentials_is_rejected() {
+        let (state, mut command_rx) = test_app_state_with(|config| config.simulate = true);
+        let request = IpcRequest::Connect {
+            ssid: "Home".to_string(),
+            password: String::new(),
+            save: true,
+            hidden: false,
+            bssid: None,
+            eap_tls: None,
+            dry_run: false,
+            ssid_bytes: None,
+            security: None,
+            use_saved: true,
+        };
+        match handle_request(&state, request).await {
+            IpcResponse::Error(e) => assert_eq!(e, "No saved credentials for Home"),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert!(command_rx.try_recv().is_err());
+        assert!(!state.connect_in_progress.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_connect_does_not_save() {
+        let (state, mut command_rx) = test_app_state();
+        let request = IpcRequest::Connect {
+            ssid: "Home".to_string(),
+            password: "hunter22".to_string(),
+            save: true,
+            hidden: false,
+            bssid: None,
+            eap_tls: None,
+            dry_run: true,
+            ssid_bytes: None,
+            security: None,
+            use_saved: false,
+        };
+        assert!(matches!(
+            handle_request(&state, request).await,
+            IpcResponse::Ok
+        ));
+
+        match command_rx.try_recv() {
+            Ok(ControlCommand::Connect { save, dry_run, .. }) => {
+                assert!(!save);
+                assert!(dry_run);
+            }
+            _ => panic!("expected Connect command"),
+        }
+    }
+
+    #[test]
+    fn test_socket_mode_and_group_parsing() {
+        assert_eq!(parse_socket_mode("0660"), Ok(0o660));
+        assert_eq!(parse_socket_mode("600"), Ok(0o600));
+        assert_eq!(parse_socket_mode("0o770"), Ok(0o770));
+        assert!(parse_socket_mode("0680").is_err());
+        assert!(parse_socket_mode("1777").is_err());
+
+        let groups = "root:x:0:\nwheel:x:1:alice\nhyper:x:983:kiosk\n";
+        assert_eq!(find_gid(groups, "hyper"), Some(983));
+        assert_eq!(find_gid(groups, "hyp"), None);
+    }
+
+    #[test]
+    fn test_tokens_match_whole_token_only() {
+        let token = "0123456789abcdef0123456789abcdef";
+        assert!(tokens_match(token, token));
+        assert!(!tokens_match("0123456789abcdef0123456789abcdee", token));
+        assert!(!tokens_match("0123456789abcdef", token));
+        assert!(!tokens_match("", token));
+    }
+
+    #[tokio::test]
+    async fn test_token_required_before_commands() {
+        let responses = exchange_with_token(
+            Some("s3cret"),
+            &[
+                r#""Shutdown""#,
+                r#"{"Hello":{"client_version":"0.0.1","protocol":1,"token":"wrong"}}"#,
+                r#""GetStatus""#,
+                r#"{"Hello":{"client_version":"0.0.1","protocol":1,"token":"s3cret"}}"#,
+                r#""GetStatus""#,
+            ],
+        )
+        .await;
+
+        assert!(matches!(&responses[0], IpcResponse::Error(e) if e.starts_with("Unauthorized")));
+        assert!(matches!(&responses[1], IpcResponse::Error(e) if e == "Invalid IPC token"));
+        assert!(matches!(responses[2], IpcResponse::Error(_)));
+        assert!(matches!(responses[3], IpcResponse::Welcome { .. }));
+        assert!(matches!(responses[4], IpcResponse::Status(_)));
+    }
+
+    #[test]
+    fn test_write_token_is_group_readable_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("ipc.sock");
+        let socket = socket.to_str().unwrap();
+
+        let token = write_token(socket, None).unwrap();
+        assert_eq!(read_token(socket).as_deref(), Some(token.as_str()));
+        let mode = std::fs::metadata(token_path(socket))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o640);
+
+        // A restart issues a new token; turning the option off removes it.
+        assert_ne!(write_token(socket, None).unwrap(), token);
+        remove_token(socket);
+        assert_eq!(read_token(socket), None);
+    }
+
+    #[tokio::test]
+    async fn test_connections_over_the_cap_are_refused() {
+        let (state, _rx) = test_app_state_with(|config| config.ipc_max_clients = 2);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ipc.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = tokio::spawn(run_ipc_server(listener, state));
+
+        // Two idle clients hold both slots.
+        let held: Vec<_> = [
+            UnixStream::connect(&path).await.unwrap(),
+            UnixStream::connect(&path).await.unwrap(),
+        ]
+        .into();
+
+        let mut extra = BufReader::new(UnixStream::connect(&path).await.unwrap());
+        let mut reply = String::new();
+        extra.read_line(&mut reply).await.unwrap();
+        assert!(matches!(
+            serde_json::from_str(&reply).unwrap(),
+            IpcResponse::Error(e) if e == "Too many IPC clients"
+        ));
+        reply.clear();
+        assert_eq!(extra.read_line(&mut reply).await.unwrap(), 0);
+
+        // A slot frees up once a held client hangs up.
+        drop(held);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let status = get_status(path.to_str().unwrap()).await;
+        assert!(status.is_ok(), "{:?}", status.err());
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_oversized_request_is_refused() {
+        let (state, _rx) = test_app_state();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ipc.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = tokio::spawn(run_ipc_server(listener, state));
+
+        let (reader, mut writer) = UnixStream::connect(&path).await.unwrap().into_split();
+        let flood = vec![b'a'; MAX_REQUEST_BYTES as usize + 16];
+        writer.write_all(&flood).await.unwrap();
+
+        let mut reader = BufReader::new(reader);
+        let mut reply = String::new();
+        reader.read_line(&mut reply).await.unwrap();
+        assert!(matches!(
+            serde_json::from_str(&reply).unwrap(),
+            IpcResponse::Error(e) if e == "Request too large"
+        ));
+        reply.clear();
+        assert_eq!(reader.read_line(&mut reply).await.unwrap(), 0);
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_bind_socket_sets_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ipc.sock");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "stale").unwrap();
+
+        let _listener = bind_socket(path, 0o660, None).unwrap();
+        let mode = std::fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o660);
+    }
+}
@@ -0,0 +1,90 @@
+//! Signal and bitrate of the associated station link, read from `iw dev <iface> link`
+
+use tokio::process::Command;
+
+/// Snapshot of the current association's quality. Fields `iw` didn't report are `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkQuality {
+    /// Signal as a 0-100 percentage (same scale as scan results)
+    pub signal: Option<u8>,
+    /// Transmit bitrate in Mbit/s
+    pub rate: Option<u32>,
+}
+
+/// Read the link quality of `interface`, or `None` if it isn't associated.
+pub async fn read(interface: &str) -> Option<LinkQuality> {
+    let output = Command::new("iw")
+        .args(["dev", interface, "link"])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_iw_link(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_iw_link(output: &str) -> Option<LinkQuality> {
+    if !output.trim_start().starts_with("Connected to") {
+        return None;
+    }
+
+    let mut quality = LinkQuality::default();
+    for line in output.lines().map(str::trim) {
+        if let Some(value) = line.strip_prefix("signal:") {
+            quality.signal = first_number(value).map(dbm_to_percent);
+        } else if let Some(value) = line.strip_prefix("tx bitrate:") {
+            quality.rate = first_number(value).map(|mbps| mbps.round().max(0.0) as u32);
+        }
+    }
+    Some(quality)
+}
+
+fn first_number(value: &str) -> Option<f32> {
+    value.split_whitespace().next()?.parse().ok()
+}
+
+/// Map dBm onto NetworkManager's 0-100 strength scale (-100 dBm = 0, -50 dBm = 100).
+fn dbm_to_percent(dbm: f32) -> u8 {
+    (2.0 * (dbm + 100.0)).clamp(0.0, 100.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_iw_link() {
+        let output = "Connected to 00:11:22:33:44:55 (on wlan0)
+\tSSID: Home
+\tfreq: 2437
+\tRX: 123456 bytes (789 packets)
+\tTX: 23456 bytes (123 packets)
+\tsignal: -61 dBm
+\trx bitrate: 144.4 MBit/s MCS 15 short GI
+\ttx bitrate: 130.0 MBit/s MCS 15
+";
+        assert_eq!(
+            parse_iw_link(output),
+            Some(LinkQuality {
+                signal: Some(78),
+                rate: Some(130),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_iw_link_missing_fields() {
+        let output = "Connected to 00:11:22:33:44:55 (on wlan0)\n\tSSID: Home\n\tsignal: junk\n";
+        assert_eq!(parse_iw_link(output), Some(LinkQuality::default()));
+        assert_eq!(parse_iw_link("Not connected.\n"), None);
+        assert_eq!(parse_iw_link(""), None);
+    }
+
+    #[test]
+    fn test_dbm_to_percent() {
+        assert_eq!(dbm_to_percent(-30.0), 100);
+        assert_eq!(dbm_to_percent(-75.0), 50);
+        assert_eq!(dbm_to_percent(-110.0), 0);
+    }
+}
@@ -1,26 +1,345 @@
 //! WiFi Controller - Core logic for AP management and network connection
 
 mod ap_manager;
+mod audit;
+mod backend;
+mod connect_limit;
 pub mod credentials;
+mod dbus;
+mod diagnostics;
+pub mod history;
+mod hooks;
+mod instance_lock;
 pub mod ipc;
+mod link_quality;
+mod network_list;
 mod network_manager;
+mod nm_client;
+mod notify;
+mod simulate;
 pub mod state;
 
-pub use state::{ConnectionStatus, NetworkInfo, WifiBackend, WifiState, WifiStateSnapshot};
+pub use ap_manager::load_hostapd_extra;
+pub use diagnostics::{print_diagnostics, AdapterReport};
+pub use instance_lock::DEFAULT_PID_FILE;
+pub use network_manager::is_valid_probe_url;
+pub use simulate::load_demo_networks;
+pub use state::{
+    ap_clients_summary, format_bssid, parse_bssid, valid_ssid_bytes, ApClient, BackendSwitchStep,
+    ConnectError, ConnectStats, ConnectionStatus, EapTls, ManualSecurity, NetworkInfo,
+    OnboardingInfo, WifiBackend, WifiBand, WifiState, WifiStateSnapshot,
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::net::UnixListener;
 use tokio::signal;
 use tokio::sync::{mpsc, watch, RwLock};
 
+/// How long startup waits for NetworkManager to appear on the system bus.
+const NM_STARTUP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+/// Upper bound on the scan the boot fast path waits for.
+const FAST_RECONNECT_SCAN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+/// Portal heading used unless rebranded with `--portal-title`.
+pub const DEFAULT_PORTAL_TITLE: &str = "Hyper Recovery";
+/// Portal subheading used unless rebranded with `--portal-subtitle`.
+pub const DEFAULT_PORTAL_SUBTITLE: &str = "WiFi Setup Module";
+/// How often the spare station radio rescans while the AP is up.
+const BACKGROUND_RESCAN_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+/// How often connectivity is checked in persist mode once connected.
+const CONNECTIVITY_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+/// How often the associated link's signal is sampled in persist mode.
+const LINK_QUALITY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// How often the AP is checked for clients when an idle timeout is set.
+const AP_IDLE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+/// How often the devices on the setup AP are listed.
+const AP_CLIENTS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// How often persist mode checks that no other interface joined the AP subnet.
+const AP_SUBNET_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+/// How long a one-shot daemon waits for a client to save dry-run credentials.
+const SAVE_DECISION_WINDOW: std::time::Duration = std::time::Duration::from_secs(60);
+/// How long a one-shot daemon waits for a new link to reach the network before exiting.
+const POST_CONNECT_CONNECTIVITY_WAIT: std::time::Duration = std::time::Duration::from_secs(30);
+
 /// Daemon configuration
 pub struct DaemonConfig {
     pub interface: String,
+    pub sta_interface: String,
     pub ssid: String,
     pub ap_ip: String,
     pub port: u16,
     pub grace_period: u64,
+    /// Rejoin the last connected network before the grace period.
+    pub fast_reconnect: bool,
+    pub portal_hostname: String,
+    /// Stay resident after connecting and re-raise the AP if connectivity is lost.
+    pub persist: bool,
+    /// Persist mode: join upstream on the station radio and leave the AP up.
+    pub keep_ap_after_connect: bool,
+    /// Seconds connectivity may be lost in persist mode before the AP comes back.
+    pub link_loss_grace: u64,
+    /// Also auto-connect to networks NetworkManager already has saved profiles for.
+    pub use_nm_profiles: bool,
+    /// Seconds startup auto-connect may spend walking saved networks (0 = best only).
+    pub auto_connect_budget: u64,
+    /// Age (seconds) after which the portal treats scan results as stale and rescans.
+    pub scan_ttl: u64,
+    /// Seconds a scan waits for fresh results from the radio.
+    pub scan_timeout: u64,
+    /// Also serve the `org.hyper.Recovery.WifiSetup` D-Bus interface.
+    pub dbus: bool,
+    /// Addresses and lease time handed out by dnsmasq on the AP subnet.
+    pub dhcp: DhcpRange,
+    /// How dnsmasq answers AP clients' DNS queries.
+    pub ap_dns_mode: ApDnsMode,
+    /// Resolvers for `ApDnsMode::Passthrough`; empty means the system's.
+    pub ap_dns_upstreams: Vec<IpAddr>,
+    /// Append every connect outcome to the audit log.
+    pub audit: bool,
+    /// Band the setup AP runs on.
+    pub ap_band: ApBand,
+    /// WPA passphrase for the setup AP; open when unset.
+    pub ap_password: Option<String>,
+    /// Key management used when `ap_password` is set.
+    pub ap_security: ApSecurity,
+    /// Seconds without AP clients or portal requests before the AP is shut down (0 = never).
+    pub ap_idle_timeout: u64,
+    /// hostapd `max_num_sta`; hostapd's default when unset.
+    pub ap_max_clients: Option<u16>,
+    /// hostapd `beacon_int` in time units; hostapd's default when unset.
+    pub ap_beacon_interval: Option<u16>,
+    /// Lines from `--hostapd-extra`, appended verbatim to the hostapd config.
+    pub hostapd_extra: Option<String>,
+    /// Connect to open networks from the portal without an unencrypted-network warning.
+    pub allow_open_silently: bool,
+    /// Heading shown by the portal and TUI.
+    pub portal_title: String,
+    /// Line shown under the portal heading.
+    pub portal_subtitle: String,
+    /// Portal language; negotiated per request from Accept-Language when unset.
+    pub portal_lang: Option<crate::web::Lang>,
+    /// HTTP URL whose 2xx answer counts as connectivity when NM reports less than FULL
+    pub connectivity_probe: Option<String>,
+    /// Where captive checks and the portal send clients once connected
+    pub success_url: Option<String>,
+    /// Networks weaker than this (percent) are hidden from the portal and TUI lists
+    pub min_signal: u8,
+    /// IP families configured on station connections.
+    pub ip_family: IpFamily,
+    /// Rows the portal renders visible before "Show all" (0 = no cap)
+    pub portal_max_networks: usize,
+    /// Portal status poll interval (ms) while idle; see `PortalTiming`
+    pub portal_poll_ms: u64,
+    /// Scan results a dry run shows instead of the built-in list.
+    pub demo_networks: Option<Vec<NetworkInfo>>,
+    /// Also serve the portal over HTTPS with a self-signed certificate.
+    pub tls: bool,
+    /// Port of the HTTPS portal when `tls` is set.
+    pub tls_port: u16,
+    /// Shell command run once WiFi is connected
+    pub on_connected: Option<String>,
+    /// IPC socket clients (TUI, CLI) connect to.
+    pub socket_path: String,
+    /// Locked while the daemon runs to keep a second instance out.
+    pub pid_file: PathBuf,
+    /// Permission bits for the IPC socket.
+    pub socket_mode: u32,
+    /// Group (name or gid) given the IPC socket, for non-root clients.
+    pub socket_group: Option<String>,
+    /// Require clients to present the token written to `<socket>.token`.
+    pub ipc_token: bool,
+    /// IPC connections served at once; more are turned away.
+    pub ipc_max_clients: usize,
+    /// `--dry-run`: simulate the AP and scans instead of driving the radio.
+    pub simulate: bool,
+}
+
+/// Band requested for the setup AP.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApBand {
+    #[default]
+    Band2_4,
+    Band5,
+    /// 5GHz when the radio can host it on a non-DFS channel, 2.4GHz otherwise.
+    Auto,
+}
+
+impl std::str::FromStr for ApBand {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "2.4" => Ok(ApBand::Band2_4),
+            "5" => Ok(ApBand::Band5),
+            "auto" => Ok(ApBand::Auto),
+            _ => Err("expected \"2.4\", \"5\" or \"auto\"".to_string()),
+        }
+    }
+}
+
+/// Key management for a password-protected setup AP.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApSecurity {
+    #[default]
+    Wpa2,
+    Wpa3,
+    /// WPA3 for clients that can, WPA2 for the rest.
+    Wpa2Wpa3,
+}
+
+impl ApSecurity {
+    /// Whether hostapd needs SAE support for this mode.
+    pub fn needs_sae(self) -> bool {
+        self != ApSecurity::Wpa2
+    }
+}
+
+impl std::str::FromStr for ApSecurity {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "wpa2" => Ok(ApSecurity::Wpa2),
+            "wpa3" => Ok(ApSecurity::Wpa3),
+            "wpa2-wpa3" => Ok(ApSecurity::Wpa2Wpa3),
+            _ => Err("expected \"wpa2\", \"wpa3\" or \"wpa2-wpa3\"".to_string()),
+        }
+    }
+}
+
+/// IP families requested on station connections.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IpFamily {
+    /// Both families, each allowed to fail so a missing one doesn't stall activation.
+    #[default]
+    Dual,
+    V4,
+    V6,
+}
+
+impl std::str::FromStr for IpFamily {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "dual" => Ok(IpFamily::Dual),
+            "v4" => Ok(IpFamily::V4),
+            "v6" => Ok(IpFamily::V6),
+            _ => Err("expected \"dual\", \"v4\" or \"v6\"".to_string()),
+        }
+    }
+}
+
+/// DNS served to AP clients.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApDnsMode {
+    /// Every name resolves to the AP, so any page a client opens is the portal.
+    #[default]
+    Captive,
+    /// Queries are forwarded upstream, for when AP clients share our uplink.
+    Passthrough,
+}
+
+impl std::str::FromStr for ApDnsMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "captive" => Ok(ApDnsMode::Captive),
+            "passthrough" => Ok(ApDnsMode::Passthrough),
+            _ => Err("expected \"captive\" or \"passthrough\"".to_string()),
+        }
+    }
+}
+
+/// DHCP pool on the AP's /24, as host octets of the AP subnet.
+#[derive(Debug, Clone)]
+pub struct DhcpRange {
+    pub start_octet: u8,
+    pub end_octet: u8,
+    /// Lease time in dnsmasq syntax ("12h", "30m", "600", "infinite").
+    pub lease: String,
+}
+
+impl Default for DhcpRange {
+    fn default() -> Self {
+        Self {
+            start_octet: 10,
+            end_octet: 250,
+            lease: "12h".to_string(),
+        }
+    }
+}
+
+impl DhcpRange {
+    /// Octets must lie in 2-254 (clear of the network, AP and broadcast) with start < end.
+    pub fn validate(&self) -> Result<()> {
+        for octet in [self.start_octet, self.end_octet] {
+            if !(2..=254).contains(&octet) {
+                anyhow::bail!("DHCP range octet {} is outside 2-254", octet);
+            }
+        }
+        if self.start_octet >= self.end_octet {
+            anyhow::bail!(
+                "DHCP range start {} must be below end {}",
+                self.start_octet,
+                self.end_octet
+            );
+        }
+        if !is_valid_lease(&self.lease) {
+            anyhow::bail!("Invalid DHCP lease time: '{}'", self.lease);
+        }
+        Ok(())
+    }
+}
+
+/// dnsmasq lease syntax: seconds, optionally suffixed with s/m/h/d/w, or "infinite".
+pub fn is_valid_lease(lease: &str) -> bool {
+    if lease == "infinite" {
+        return true;
+    }
+    let digits = lease
+        .strip_suffix(['s', 'm', 'h', 'd', 'w'])
+        .unwrap_or(lease);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+impl DaemonConfig {
+    /// Spare station radio that can scan while the AP stays up, if one was resolved.
+    pub fn sta_interface(&self) -> Option<&str> {
+        (!self.sta_interface.is_empty()).then_some(self.sta_interface.as_str())
+    }
+
+    /// Interface that joins the upstream network. Normally the AP radio, which
+    /// gives up the AP to do so; the station radio when the AP is kept up.
+    pub fn station_interface(&self) -> &str {
+        if self.keep_ap_after_connect {
+            self.sta_interface().unwrap_or(&self.interface)
+        } else {
+            &self.interface
+        }
+    }
+
+    /// Interface used for scanning: the station radio if present, otherwise the AP radio.
+    pub fn scan_interface(&self) -> &str {
+        self.sta_interface().unwrap_or(&self.interface)
+    }
+
+    pub fn scan_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.scan_timeout)
+    }
+
+    /// Portal address on the setup AP, with the port unless it's the scheme's default.
+    pub fn portal_url(&self) -> String {
+        match (self.tls, self.port, self.tls_port) {
+            (false, 80, _) => format!("http://{}", self.ap_ip),
+            (false, port, _) => format!("http://{}:{}", self.ap_ip, port),
+            (true, _, 443) => format!("https://{}", self.ap_ip),
+            (true, _, port) => format!("https://{}:{}", self.ap_ip, port),
+        }
+    }
 }
 
 /// Shared application state
@@ -29,6 +348,106 @@ pub struct AppState {
     pub config: DaemonConfig,
     pub state_tx: watch::Sender<WifiState>,
     pub command_tx: mpsc::Sender<ControlCommand>,
+    pub connect_stats: ConnectStats,
+    /// Set while a connect is queued or running; cleared once it succeeds or fails.
+    pub connect_in_progress: AtomicBool,
+    /// When the portal last triggered a rescan, for debouncing `/api/scan`.
+    pub last_scan_request: std::sync::Mutex<Option<std::time::Instant>>,
+    /// Set while a queued rescan hasn't finished yet.
+    pub scan_in_progress: AtomicBool,
+    /// Set while a requested AP restart is queued or running.
+    pub ap_restart_in_progress: AtomicBool,
+    /// Last portal request or AP client association, for the AP idle timeout.
+    pub last_activity: std::sync::Mutex<std::time::Instant>,
+    pub hooks: hooks::HookRunner,
+    /// Recent status transitions, for `GetHistory`.
+    pub history: std::sync::Mutex<history::StateHistory>,
+    /// Wakes the connect attempt currently listening for a cancel.
+    pub connect_cancel: tokio::sync::Notify,
+    /// Source of connect attempt ids; the first attempt gets 1.
+    pub next_attempt_id: AtomicU64,
+    /// Shared secret IPC clients must present in `Hello`, when required.
+    pub ipc_token: Option<String>,
+}
+
+/// A connect attempt the user aborted
+#[derive(Debug, thiserror::Error)]
+#[error("Connection cancelled")]
+pub struct ConnectCancelled;
+
+impl AppState {
+    /// Publish a new state to subscribers, noting any status change in the history.
+    pub fn publish(&self, state: &WifiState) {
+        if let Ok(mut history) = self.history.lock() {
+            history.record(state, history::unix_now());
+        }
+        let _ = self.state_tx.send(state.clone());
+    }
+
+    /// Whether a running connect is about to tear the setup AP down, and with
+    /// it every portal client.
+    pub async fn ap_going_down(&self) -> bool {
+        let state = self.wifi_state.read().await;
+        state.status == ConnectionStatus::Connecting
+            && state.ap_running
+            && !self.config.keep_ap_after_connect
+    }
+
+    /// Abort the running connect attempt; false when there is none.
+    ///
+    /// Only an attempt already listening hears it, so a cancel racing a
+    /// finished connect changes nothing.
+    pub fn cancel_connect(&self) -> bool {
+        if !self.connect_in_progress.load(Ordering::SeqCst) {
+            return false;
+        }
+        self.connect_cancel.notify_waiters();
+        true
+    }
+
+    /// Append a finished connect attempt to the audit log under `--audit`.
+    ///
+    /// Every path that joins a network reports here, not only portal and IPC
+    /// requests.
+    pub async fn audit_attempt(
+        &self,
+        ssid: &str,
+        bssid: Option<String>,
+        started: std::time::Instant,
+        result: &Result<()>,
+    ) {
+        if !self.config.audit {
+            return;
+        }
+        let backend = self.wifi_state.read().await.wifi_backend;
+        audit::AuditLogger::default().record(&audit::ConnectAttempt::from_result(
+            ssid,
+            &self.config.interface,
+            bssid,
+            backend,
+            started.elapsed(),
+            result,
+        ));
+    }
+
+    /// Hand out the id for a newly queued connect attempt.
+    pub fn allocate_attempt_id(&self) -> u64 {
+        self.next_attempt_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Record activity on the setup AP, resetting its idle timer.
+    pub fn touch_activity(&self) {
+        if let Ok(mut last) = self.last_activity.lock() {
+            *last = std::time::Instant::now();
+        }
+    }
+
+    fn idle_for(&self) -> std::time::Duration {
+        self.last_activity
+            .lock()
+            .map(|last| last.elapsed())
+            .unwrap_or_default()
+    }
 }
 
 /// Commands that can be sent to the controller
@@ -39,21 +458,88 @@ pub enum ControlCommand {
         ssid: String,
         password: String,
         save: bool,
+        hidden: bool,
+        /// Pin the connection to this access point instead of the strongest one.
+        bssid: Option<[u8; 6]>,
+        /// Authenticate with a client certificate instead of a passphrase.
+        eap_tls: Option<EapTls>,
+        /// Only verify the credentials; a `SaveCredential` may follow.
+        dry_run: bool,
+        /// Exact SSID bytes of the network picked from the scan list.
+        ssid_bytes: Option<Vec<u8>>,
+        /// Security chosen for a manually entered network.
+        security: Option<ManualSecurity>,
+        /// Echoed in the snapshot as `current_attempt_id`.
+        attempt_id: u64,
+    },
+    /// Persist the credentials of the dry-run attempt `attempt_id`, which
+    /// the control loop still holds; nothing else can be saved this way.
+    SaveCredential {
+        ssid: String,
+        attempt_id: u64,
+    },
+    /// Change a saved network's auto-connect priority.
+    SetPriority {
+        ssid: String,
+        priority: i32,
+    },
+    /// Drop the saved credentials for `ssid`.
+    ForgetCredential {
+        ssid: String,
     },
     SwitchBackend {
         backend: WifiBackend,
     },
+    /// Cycle hostapd and dnsmasq with the current config.
+    RestartAp,
     Shutdown,
 }
 
 /// Run the daemon
 pub async fn run_daemon(config: DaemonConfig) -> Result<()> {
     let mut config = config;
-    config.interface = network_manager::resolve_wireless_interface(&config.interface)?;
+    config.dhcp.validate()?;
+    ap_manager::sanitize_ap_ssid(&config.ssid)?;
+    if let Some(password) = &config.ap_password {
+        ap_manager::sanitize_ap_password(password)?;
+    }
+    // Before touching the radio or the socket, which a running daemon owns.
+    let _instance = instance_lock::InstanceLock::acquire(&config.pid_file)?;
+    if config.simulate {
+        // No radio to look at and no subnet worth remembering.
+        if config.interface == "auto" {
+            config.interface = "wlan0".to_string();
+        }
+        config.sta_interface.clear();
+        if config.ap_ip.eq_ignore_ascii_case("auto") {
+            config.ap_ip = network_manager::DEFAULT_AP_IP.to_string();
+        }
+    } else {
+        let (interface, sta_interface) =
+            network_manager::resolve_wireless_interface(&config.interface, &config.sta_interface)?;
+        config.interface = interface;
+        config.sta_interface = sta_interface.unwrap_or_default();
+
+        // A soft-blocked radio scans nothing; a hard block is reported once
+        // the AP can't start.
+        network_manager::unblock_radio(&config.interface).await;
+        if let Some(sta) = config.sta_interface() {
+            network_manager::unblock_radio(sta).await;
+        }
+        // One radio can't host the AP and join another network at once.
+        if config.keep_ap_after_connect && config.sta_interface().is_none() {
+            anyhow::bail!(
+                "--keep-ap-after-connect needs a second wireless interface (--sta-interface) \
+                 to join the upstream network while {} hosts the AP",
+                config.interface
+            );
+        }
+    }
     config.ap_ip = network_manager::resolve_ap_ip(&config.ap_ip)?;
 
     tracing::info!(
         interface = %config.interface,
+        sta_interface = config.sta_interface().unwrap_or("none"),
         ssid = %config.ssid,
         ap_ip = %config.ap_ip,
         "Initializing WiFi controller"
@@ -61,223 +547,434 @@ pub async fn run_daemon(config: DaemonConfig) -> Result<()> {
 
     // Create state channels
     let (state_tx, state_rx) = watch::channel(WifiState::default());
+    tokio::spawn(notify::forward_status(state_rx.clone()));
     let (command_tx, mut command_rx) = mpsc::channel::<ControlCommand>(32);
 
+    let ipc_token = if config.ipc_token {
+        let token = ipc::write_token(&config.socket_path, config.socket_group.as_deref())?;
+        tracing::info!(
+            path = %ipc::token_path(&config.socket_path),
+            "IPC clients must present the token"
+        );
+        Some(token)
+    } else {
+        ipc::remove_token(&config.socket_path);
+        None
+    };
+
+    let hooks = hooks::HookRunner::new(config.on_connected.clone());
     let app_state = Arc::new(AppState {
         wifi_state: RwLock::new(WifiState::default()),
         config,
         state_tx,
         command_tx: command_tx.clone(),
+        connect_stats: ConnectStats::default(),
+        connect_in_progress: AtomicBool::new(false),
+        last_scan_request: std::sync::Mutex::new(None),
+        scan_in_progress: AtomicBool::new(false),
+        ap_restart_in_progress: AtomicBool::new(false),
+        last_activity: std::sync::Mutex::new(std::time::Instant::now()),
+        hooks,
+        history: std::sync::Mutex::default(),
+        connect_cancel: tokio::sync::Notify::new(),
+        next_attempt_id: AtomicU64::new(1),
+        ipc_token,
     });
 
-    // Record current NetworkManager WiFi backend (best effort).
-    if let Ok(backend) = network_manager::current_wifi_backend().await {
-        let mut state = app_state.wifi_state.write().await;
-        state.wifi_backend = Some(backend);
-        let _ = app_state.state_tx.send(state.clone());
-    }
-
-    // Check for existing connectivity
-    tracing::info!("Checking for existing network connectivity...");
-
-    let has_connectivity = network_manager::check_connectivity().await?;
-    if has_connectivity {
-        tracing::info!("Already connected to network, exiting");
-        return Ok(());
+    let mut online = if app_state.config.simulate {
+        tracing::info!("Dry run: leaving NetworkManager and the radio alone");
+        None
+    } else {
+        network_already_up(&app_state).await?
+    };
+    if online.is_none() {
+        online = auto_connect_at_startup(&app_state)
+            .await
+            .map(OnlineAtStartup::Joined);
     }
-
-    // Grace period - wait for Ethernet/existing WiFi
-    tracing::info!(
-        seconds = app_state.config.grace_period,
-        "Waiting grace period for network..."
-    );
-
-    let grace_result = tokio::time::timeout(
-        std::time::Duration::from_secs(app_state.config.grace_period),
-        network_manager::wait_for_connectivity(),
-    )
-    .await;
-
-    if grace_result.is_ok() {
-        tracing::info!("Network connected during grace period, exiting");
+    if online.is_some() && !app_state.config.persist {
+        app_state.hooks.finish().await;
         return Ok(());
     }
 
-    // No connectivity - scan and check for saved credentials
-    tracing::info!("No network connectivity, scanning for networks...");
+    // systemd stops the unit with SIGTERM; treat it like Ctrl-C so the radio
+    // is handed back either way.
+    let mut sigterm = signal::unix::signal(signal::unix::SignalKind::terminate())
+        .context("Failed to install SIGTERM handler")?;
 
-    // Initial WiFi scan (before starting AP)
-    {
+    if let Some(online) = &online {
+        // Persist mode: stay up and watch the link; the AP only comes back
+        // if it drops.
+        tracing::info!("Persist mode: monitoring connectivity");
         let mut state = app_state.wifi_state.write().await;
-        state.status = ConnectionStatus::Scanning;
-        let _ = app_state.state_tx.send(state.clone());
-    }
-
-    let networks = network_manager::scan_networks(&app_state.config.interface).await?;
-
-    // Load saved credentials and check for known networks
-    let creds_store = credentials::CredentialsStore::load().unwrap_or_default();
-
-    if let Some(known_network) = creds_store.best_known_network(&networks) {
-        if let Some(password) = creds_store.get_password(&known_network.ssid) {
-            tracing::info!(
-                ssid = %known_network.ssid,
-                signal = known_network.signal_strength,
-                "Found saved credentials for available network, attempting auto-connect"
-            );
+        state.status = ConnectionStatus::Connected;
+        if let OnlineAtStartup::Joined(ssid) = online {
+            state.connected_ssid = Some(ssid.clone());
+        }
+        // For the portal, should the link drop.
+        state.saved_ssids = credentials::CredentialsStore::load()
+            .unwrap_or_default()
+            .networks
+            .into_keys()
+            .collect();
+        app_state.publish(&state);
+    } else {
+        tracing::info!("Starting AP and portal");
 
-            // Try to connect with saved credentials (auto-connect always saves)
-            match network_manager::connect_to_network(
-                &app_state.config.interface,
-                &known_network.ssid,
-                password,
-                true,
-            )
-            .await
-            {
-                Ok(()) => {
-                    tracing::info!(ssid = %known_network.ssid, "Auto-connected using saved credentials");
-                    return Ok(());
-                }
-                Err(e) => {
-                    tracing::warn!(
-                        ssid = %known_network.ssid,
-                        error = %e,
-                        "Auto-connect failed, will start AP"
-                    );
-                }
+        // Start AP. If it can't come up, keep running so the TUI can still show
+        // why and connect through the station side.
+        match start_ap(&app_state.config).await {
+            Ok(()) => {
+                let mut state = app_state.wifi_state.write().await;
+                mark_ap_running(&mut state, &app_state.config);
+                app_state.publish(&state);
+            }
+            Err(e) => {
+                tracing::error!(error = %format!("{:#}", e), "Failed to start AP");
+                let mut state = app_state.wifi_state.write().await;
+                record_ap_failure(&mut state, &e);
+                app_state.publish(&state);
             }
         }
     }
 
-    // Update state with scanned networks
-    {
-        let mut state = app_state.wifi_state.write().await;
-        state.available_networks = networks;
-        state.status = ConnectionStatus::AwaitingCredentials;
-        state.last_scan = Some(std::time::Instant::now());
-        let _ = app_state.state_tx.send(state.clone());
-    }
-
-    tracing::info!("Starting AP and portal");
-
-    // Start AP
-    let _ap_handle = ap_manager::start_ap(
-        &app_state.config.interface,
-        &app_state.config.ssid,
-        &app_state.config.ap_ip,
-    )
-    .await?;
-
-    {
-        let mut state = app_state.wifi_state.write().await;
-        state.ap_running = true;
-        state.ap_ssid = Some(app_state.config.ssid.clone());
-        state.portal_url = Some(format!("http://{}", app_state.config.ap_ip));
-        let _ = app_state.state_tx.send(state.clone());
-    }
-
     // Start IPC server
-    let socket_path = "/run/hyper-connect.sock";
-    let _ = std::fs::remove_file(socket_path);
-    let listener = UnixListener::bind(socket_path)?;
-    tracing::info!(path = socket_path, "IPC server listening");
+    let config = &app_state.config;
+    let listener = match ipc::bind_socket(
+        &config.socket_path,
+        config.socket_mode,
+        config.socket_group.as_deref(),
+    ) {
+        Ok(listener) => listener,
+        Err(e) => {
+            restore_radio(config).await;
+            return Err(e);
+        }
+    };
+    tracing::info!(
+        path = %config.socket_path,
+        mode = %format!("{:o}", config.socket_mode),
+        group = config.socket_group.as_deref().unwrap_or("default"),
+        "IPC server listening"
+    );
 
     let ipc_state = app_state.clone();
     let ipc_handle = tokio::spawn(async move { ipc::run_ipc_server(listener, ipc_state).await });
 
+    // Optional D-Bus service; failing to claim the name shouldn't take the daemon down.
+    if app_state.config.dbus {
+        let dbus_state = app_state.clone();
+        let dbus_state_rx = state_rx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = dbus::serve(dbus_state, dbus_state_rx).await {
+                tracing::error!(error = %format!("{:#}", e), "D-Bus service failed");
+            }
+        });
+    }
+
     // Start web portal
+    let web_listener = match crate::web::bind(&app_state.config).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            ipc_handle.abort();
+            restore_radio(&app_state.config).await;
+            return Err(e);
+        }
+    };
     let web_state = app_state.clone();
     let web_state_rx = state_rx.clone();
     let web_handle =
-        tokio::spawn(async move { crate::web::run_server(web_state, web_state_rx).await });
+        tokio::spawn(
+            async move { crate::web::run_server(web_listener, web_state, web_state_rx).await },
+        );
+
+    // IPC and portal sockets are both bound; dependent units can start now.
+    notify::ready();
 
     // Main control loop
     let ctrl_state = app_state.clone();
+    let online_at_startup = online.is_some();
     let control_handle = tokio::spawn(async move {
+        // With a spare station radio the network list can be kept fresh without
+        // touching the AP.
+        let has_sta = ctrl_state.config.sta_interface().is_some();
+        let mut rescan_interval = tokio::time::interval(BACKGROUND_RESCAN_INTERVAL);
+        rescan_interval.tick().await;
+
+        // Persist mode: watch the link once connected instead of exiting.
+        let link_loss_grace = std::time::Duration::from_secs(ctrl_state.config.link_loss_grace);
+        let mut connectivity_interval = tokio::time::interval(CONNECTIVITY_CHECK_INTERVAL);
+        let mut link_quality_interval = tokio::time::interval(LINK_QUALITY_INTERVAL);
+        let mut monitoring = online_at_startup;
+        let mut verified: Option<VerifiedAttempt> = None;
+        let mut link_lost_since: Option<std::time::Instant> = None;
+
+        // Idle timeout: count from whenever the AP (re)appears.
+        let ap_idle_timeout = std::time::Duration::from_secs(ctrl_state.config.ap_idle_timeout);
+        let mut ap_idle_interval = tokio::time::interval(AP_IDLE_CHECK_INTERVAL);
+        let mut ap_was_running = false;
+        let mut ap_clients_interval = tokio::time::interval(AP_CLIENTS_INTERVAL);
+
+        // Persist mode: an uplink can come up in the AP subnet long after startup.
+        let watch_ap_subnet = ctrl_state.config.persist && !ctrl_state.config.simulate;
+        let mut ap_subnet_interval = tokio::time::interval(AP_SUBNET_CHECK_INTERVAL);
+        let mut ap_subnet_conflict: Option<String> = None;
+
         loop {
             tokio::select! {
                 Some(cmd) = command_rx.recv() => {
                     match cmd {
                         ControlCommand::Scan => {
                             tracing::info!("Rescan requested");
-                            // Would need to stop AP briefly for rescan
-                            // For now, just log
+                            match ctrl_state.config.sta_interface() {
+                                Some(sta) => refresh_networks(&ctrl_state, sta).await,
+                                None if ctrl_state.config.simulate => {
+                                    refresh_networks(&ctrl_state, &ctrl_state.config.interface).await
+                                }
+                                // Would need to stop AP briefly for rescan
+                                // For now, just log
+                                None => tracing::info!("No spare radio for scanning while the AP is up"),
+                            }
+                            ctrl_state.scan_in_progress.store(false, Ordering::SeqCst);
                         }
-                        ControlCommand::Connect { ssid, password, save } => {
-                            tracing::info!(ssid = %ssid, save = save, "Connection requested");
+                        ControlCommand::Connect { ssid, password, save, hidden, bssid, eap_tls, dry_run, ssid_bytes, security, attempt_id } => {
+                            tracing::info!(
+                                ssid = %ssid,
+                                attempt_id = attempt_id,
+                                save = save,
+                                dry_run = dry_run,
+                                hidden = hidden,
+                                bssid = bssid.as_ref().map(format_bssid).as_deref().unwrap_or("any"),
+                                "Connection requested"
+                            );
+                            ctrl_state.connect_stats.attempts.fetch_add(1, Ordering::Relaxed);
+                            ctrl_state.connect_in_progress.store(true, Ordering::SeqCst);
+                            monitoring = false;
+                            // A new attempt supersedes whatever was left unsaved.
+                            verified = None;
+                            let started = std::time::Instant::now();
 
-                            // Update state
-                            {
+                            // Update state. Already associated (persist mode): switch in
+                            // place and only raise the AP if the new network fails.
+                            let (ssid_bytes, audit_bssid, backend, switching, wpa3_only, security) = {
                                 let mut state = ctrl_state.wifi_state.write().await;
+                                let switching = state.is_station_connected();
                                 state.status = ConnectionStatus::Connecting;
                                 state.connecting_to = Some(ssid.clone());
+                                state.current_attempt_id = Some(attempt_id);
+                                state.connect_deadline = None;
+                                state.connected_signal = None;
+                                state.connected_rate = None;
                                 state.last_error = None;
-                                let _ = ctrl_state.state_tx.send(state.clone());
-                            }
+                                state.connect_error = None;
+                                ctrl_state.publish(&state);
+                                // NetworkManager picks the AP itself unless one is pinned.
+                                let audit_bssid = bssid
+                                    .as_ref()
+                                    .map(format_bssid)
+                                    .or_else(|| state.strongest_bssid_for(&ssid));
+                                let wpa3_only = state
+                                    .available_networks
+                                    .iter()
+                                    .any(|n| n.ssid == ssid && n.is_wpa3_only());
+                                // OWE needs its key-mgmt even though no password comes with it.
+                                let security = security.or_else(|| {
+                                    state
+                                        .available_networks
+                                        .iter()
+                                        .any(|n| n.ssid == ssid && n.is_enhanced_open())
+                                        .then_some(ManualSecurity::EnhancedOpen)
+                                });
+                                (ssid_bytes.unwrap_or_else(|| state.ssid_bytes_for(&ssid)), audit_bssid, state.wifi_backend, switching, wpa3_only, security)
+                            };
+
+                            // Listen from here on, so a cancel during AP teardown still counts.
+                            let cancelled = ctrl_state.connect_cancel.notified();
+                            tokio::pin!(cancelled);
+                            cancelled.as_mut().enable();
 
-                            // Give the portal a short window to render "connecting" before AP teardown.
-                            tokio::time::sleep(std::time::Duration::from_millis(1200)).await;
+                            let keep_ap = ctrl_state.config.keep_ap_after_connect;
+                            if switching {
+                                tracing::info!(ssid = %ssid, "Switching networks without the setup AP");
+                            } else if keep_ap {
+                                tracing::info!(ssid = %ssid, "Joining on the station radio; the setup AP stays up");
+                            } else {
+                                // Give the portal a short window to render "connecting" before AP teardown.
+                                tokio::time::sleep(std::time::Duration::from_millis(1200)).await;
 
-                            // Stop AP
-                            if let Err(e) = ap_manager::stop_ap().await {
-                                tracing::warn!(error = %e, "Failed to stop AP cleanly");
+                                // Stop AP
+                                if let Err(e) = stop_ap(&ctrl_state.config).await {
+                                    tracing::warn!(error = %e, "Failed to stop AP cleanly");
+                                }
                             }
 
-                            // Attempt connection
-                            match network_manager::connect_to_network(
-                                &ctrl_state.config.interface,
-                                &ssid,
-                                &password,
-                                save,
-                            ).await {
+                            // Attempt connection; a cancel drops the activation wait.
+                            let result = tokio::select! {
+                                result = connect_publishing_deadline(
+                                    &ctrl_state,
+                                    &ssid_bytes,
+                                    &password,
+                                    save,
+                                    hidden,
+                                    bssid,
+                                    eap_tls.as_ref(),
+                                    security,
+                                ) => result,
+                                () = &mut cancelled => Err(ConnectCancelled.into()),
+                            };
+                            ctrl_state.audit_attempt(&ssid, audit_bssid, started, &result).await;
+                            match result {
                                 Ok(()) => {
                                     tracing::info!("Successfully connected to WiFi");
 
-                                    // Save credentials if requested
-                                    if save {
-                                        let mut creds = credentials::CredentialsStore::load()
-                                            .unwrap_or_default();
-                                        creds.save_credential(&ssid, &password);
-                                        if let Err(e) = creds.save() {
-                                            tracing::warn!(error = %e, "Failed to save credentials");
-                                        } else {
-                                            tracing::info!(ssid = %ssid, "Saved WiFi credentials");
+                                    // Save credentials if requested. Certificate-based profiles are
+                                    // persisted by NetworkManager itself.
+                                    let saved = save && eap_tls.is_none() && save_credentials(&ctrl_state.config, &ssid, &password);
+                                    if dry_run {
+                                        tracing::info!(ssid = %ssid, "Credentials verified, left to the client to save");
+                                    }
+                                    // Held only until the client decides whether to keep it.
+                                    verified = dry_run.then(|| VerifiedAttempt {
+                                        ssid: ssid.clone(),
+                                        password,
+                                        attempt_id,
+                                    });
+
+                                    {
+                                        let mut state = ctrl_state.wifi_state.write().await;
+                                        state.status = ConnectionStatus::Connected;
+                                        state.connected_ssid = Some(ssid.clone());
+                                        state.connecting_to = None;
+                                        state.connect_deadline = None;
+                                        if !keep_ap {
+                                            state.ap_running = false;
                                         }
+                                        if saved {
+                                            state.saved_ssids.insert(ssid.clone());
+                                        }
+                                        state.recent_failures.clear(&ssid);
+                                        // Cleared before publishing so IPC waiters woken by
+                                        // this update see the attempt as finished.
+                                        ctrl_state.connect_in_progress.store(false, Ordering::SeqCst);
+                                        ctrl_state.publish(&state);
                                     }
+                                    refresh_link_quality(&ctrl_state).await;
+                                    ctrl_state.hooks.connected(&ssid, ctrl_state.config.station_interface()).await;
 
-                                    let mut state = ctrl_state.wifi_state.write().await;
-                                    state.status = ConnectionStatus::Connected;
-                                    state.connected_ssid = Some(ssid);
-                                    state.connecting_to = None;
-                                    state.ap_running = false;
-                                    let _ = ctrl_state.state_tx.send(state.clone());
+                                    if !ctrl_state.config.persist {
+                                        confirm_connectivity(&ctrl_state.config).await;
+                                        if let Some(verified) = verified.take() {
+                                            await_save_decision(&ctrl_state.config, &mut command_rx, verified).await;
+                                        }
+                                        break;
+                                    }
 
-                                    // Give time for DHCP, then exit
-                                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-                                    break;
+                                    // Hand the device back to NetworkManager so it can
+                                    // ride out brief drops on its own.
+                                    if !ctrl_state.config.simulate && !keep_ap {
+                                        let _ = ap_manager::restore_device_after_ap(&ctrl_state.config).await;
+                                    }
+                                    tracing::info!("Persist mode: monitoring connectivity");
+                                    monitoring = true;
+                                    link_lost_since = None;
+                                    connectivity_interval.reset();
                                 }
                                 Err(e) => {
-                                    tracing::error!(error = %e, "Failed to connect");
-
-                                    // Restart AP
-                                    if let Err(e) = ap_manager::start_ap(
-                                        &ctrl_state.config.interface,
-                                        &ctrl_state.config.ssid,
-                                        &ctrl_state.config.ap_ip,
-                                    ).await {
+                                    let cancelled = e.is::<ConnectCancelled>();
+                                    if cancelled {
+                                        tracing::info!(ssid = %ssid, "Connection cancelled");
+                                        // The activation is still running in NetworkManager.
+                                        if !ctrl_state.config.simulate {
+                                            if let Err(e) = network_manager::disconnect_station(ctrl_state.config.station_interface()).await {
+                                                tracing::warn!(error = %format!("{:#}", e), "Failed to stop the cancelled activation");
+                                            }
+                                        }
+                                    } else {
+                                        tracing::error!(error = %e, "Failed to connect");
+                                        ctrl_state.connect_stats.failures.fetch_add(1, Ordering::Relaxed);
+                                    }
+                                    if switching {
+                                        tracing::warn!("Network switch failed, raising the setup AP");
+                                    }
+
+                                    // Restart AP; after a failed switch the old link is gone too.
+                                    // NM must be off the device before the AP re-addresses it.
+                                    let ap_result = if keep_ap && ctrl_state.wifi_state.read().await.ap_running {
+                                        Ok(())
+                                    } else {
+                                        let released = if ctrl_state.config.simulate {
+                                            Ok(())
+                                        } else {
+                                            ap_manager::release_station(&ctrl_state.config.interface).await
+                                        };
+                                        if let Err(e) = released {
+                                            tracing::warn!(error = %format!("{:#}", e), "Device not cleanly released before AP restart");
+                                        }
+                                        tracing::info!("Restarting AP after unsuccessful connect");
+                                        start_ap(&ctrl_state.config).await
+                                    };
+                                    if let Err(e) = &ap_result {
                                         tracing::error!(error = %e, "Failed to restart AP");
                                     }
 
                                     let mut state = ctrl_state.wifi_state.write().await;
-                                    state.status = ConnectionStatus::Failed;
                                     state.connecting_to = None;
-                                    state.last_error = Some(e.to_string());
-                                    state.ap_running = true;
-                                    let _ = ctrl_state.state_tx.send(state.clone());
+                                    state.connect_deadline = None;
+                                    state.connected_ssid = None;
+                                    if cancelled {
+                                        state.status = ConnectionStatus::AwaitingCredentials;
+                                        state.last_error = None;
+                                        state.connect_error = None;
+                                    } else {
+                                        state.status = ConnectionStatus::Failed;
+                                        state.recent_failures.record(&ssid, std::time::Instant::now());
+                                        let connect_error = e
+                                            .downcast_ref::<ConnectError>()
+                                            .cloned()
+                                            .unwrap_or_else(|| ConnectError::Other(e.to_string()));
+                                        let hint = wpa3_only
+                                            .then(|| connect_error.wpa3_only_hint(backend))
+                                            .flatten();
+                                        state.last_error = Some(match hint {
+                                            Some(hint) => {
+                                                tracing::warn!(ssid = %ssid, "Connection to a WPA3-only network failed");
+                                                format!("{:#}. {}", e, hint)
+                                            }
+                                            None => format!("{:#}", e),
+                                        });
+                                        state.connect_error = Some(connect_error);
+                                    }
+                                    state.ap_running = ap_result.is_ok();
+                                    ctrl_state.connect_in_progress.store(false, Ordering::SeqCst);
+                                    ctrl_state.publish(&state);
+                                }
+                            }
+                        }
+                        ControlCommand::SaveCredential { ssid, attempt_id } => {
+                            match verified.take() {
+                                Some(attempt) if attempt.matches(&ssid, attempt_id) => {
+                                    if save_credentials(&ctrl_state.config, &attempt.ssid, &attempt.password) {
+                                        let mut state = ctrl_state.wifi_state.write().await;
+                                        state.saved_ssids.insert(ssid);
+                                        ctrl_state.publish(&state);
+                                    }
+                                }
+                                other => {
+                                    tracing::warn!(ssid = %ssid, attempt_id = attempt_id, "No verified attempt to save");
+                                    verified = other;
                                 }
                             }
                         }
+                        ControlCommand::SetPriority { ssid, priority } => {
+                            set_credential_priority(&ctrl_state.config, &ssid, priority);
+                        }
+                        ControlCommand::ForgetCredential { ssid } => {
+                            if forget_credentials(&ctrl_state.config, &ssid) {
+                                let mut state = ctrl_state.wifi_state.write().await;
+                                state.saved_ssids.remove(&ssid);
+                                ctrl_state.publish(&state);
+                            }
+                        }
                         ControlCommand::SwitchBackend { backend } => {
                             tracing::info!(backend = %backend.as_nm_value(), "WiFi backend switch requested");
 
@@ -285,65 +982,157 @@ pub async fn run_daemon(config: DaemonConfig) -> Result<()> {
                                 let mut state = ctrl_state.wifi_state.write().await;
                                 state.status = ConnectionStatus::SwitchingBackend;
                                 state.last_error = None;
-                                let _ = ctrl_state.state_tx.send(state.clone());
+                                state.connect_error = None;
+                                state.backend_switch_deadline = Some(
+                                    std::time::Instant::now()
+                                        + network_manager::BACKEND_SWITCH_TIMEOUT,
+                                );
+                                ctrl_state.publish(&state);
                             }
 
                             // Expect portal connectivity to drop; attempt a clean AP restart after switching.
-                            let _ = ap_manager::stop_ap().await;
+                            let _ = stop_ap(&ctrl_state.config).await;
 
-                            match network_manager::switch_wifi_backend(backend).await {
+                            let switched = switch_backend(&ctrl_state, backend).await;
+                            match switched {
                                 Ok(()) => {
                                     tracing::info!(backend = %backend.as_nm_value(), "WiFi backend switch completed");
 
-                                    let mut state = ctrl_state.wifi_state.write().await;
-                                    state.wifi_backend = Some(backend);
-                                    state.status = ConnectionStatus::AwaitingCredentials;
-                                    state.last_error = Some(format!(
-                                        "Switched WiFi backend to {}. Reconnect to the setup AP if needed and try again.",
-                                        backend.as_nm_value()
-                                    ));
-                                    let _ = ctrl_state.state_tx.send(state.clone());
-
-                                    let _ = ap_manager::start_ap(
-                                        &ctrl_state.config.interface,
-                                        &ctrl_state.config.ssid,
-                                        &ctrl_state.config.ap_ip,
-                                    )
-                                    .await;
+                                    {
+                                        let mut state = ctrl_state.wifi_state.write().await;
+                                        state.wifi_backend = Some(backend);
+                                        state.backend_switch_step = None;
+                                        state.backend_switch_deadline = None;
+                                        state.status = ConnectionStatus::AwaitingCredentials;
+                                        state.last_error = Some(format!(
+                                            "Switched WiFi backend to {}. Reconnect to the setup AP if needed and try again.",
+                                            backend.as_nm_value()
+                                        ));
+                                        ctrl_state.publish(&state);
+                                    }
+
+                                    let ap_result = start_ap(&ctrl_state.config).await;
 
                                     let mut state = ctrl_state.wifi_state.write().await;
-                                    state.ap_running = true;
-                                    state.ap_ssid = Some(ctrl_state.config.ssid.clone());
-                                    state.portal_url = Some(format!("http://{}", ctrl_state.config.ap_ip));
-                                    let _ = ctrl_state.state_tx.send(state.clone());
+                                    match ap_result {
+                                        Ok(()) => mark_ap_running(&mut state, &ctrl_state.config),
+                                        Err(e) => {
+                                            tracing::error!(error = %format!("{:#}", e), "Failed to restart AP after backend switch");
+                                            record_ap_failure(&mut state, &e);
+                                        }
+                                    }
+                                    ctrl_state.publish(&state);
                                 }
                                 Err(e) => {
                                     tracing::error!(error = %e, backend = %backend.as_nm_value(), "WiFi backend switch failed");
-                                    let _ = ap_manager::start_ap(
-                                        &ctrl_state.config.interface,
-                                        &ctrl_state.config.ssid,
-                                        &ctrl_state.config.ap_ip,
-                                    )
-                                    .await;
+                                    let ap_result = start_ap(&ctrl_state.config).await;
 
                                     let mut state = ctrl_state.wifi_state.write().await;
-                                    state.status = ConnectionStatus::Failed;
-                                    state.last_error = Some(format!("Backend switch failed: {}", e));
-                                    state.ap_running = true;
-                                    let _ = ctrl_state.state_tx.send(state.clone());
+                                    state.backend_switch_step = None;
+                                    state.backend_switch_deadline = None;
+                                    match ap_result {
+                                        Ok(()) => {
+                                            mark_ap_running(&mut state, &ctrl_state.config);
+                                            state.status = ConnectionStatus::Failed;
+                                            state.last_error =
+                                                Some(format!("Backend switch failed: {}", e));
+                                        }
+                                        Err(ap_err) => {
+                                            tracing::error!(error = %format!("{:#}", ap_err), "Failed to restart AP after backend switch");
+                                            record_ap_failure(&mut state, &ap_err);
+                                            state.last_error = Some(format!(
+                                                "Backend switch failed: {}; the AP did not come back: {:#}",
+                                                e, ap_err
+                                            ));
+                                        }
+                                    }
+                                    ctrl_state.publish(&state);
                                 }
                             }
                         }
+                        ControlCommand::RestartAp => {
+                            restart_ap(&ctrl_state).await;
+                        }
                         ControlCommand::Shutdown => {
                             tracing::info!("Shutdown requested");
                             break;
                         }
                     }
                 }
+                _ = rescan_interval.tick(), if has_sta => {
+                    if let Some(sta) = ctrl_state.config.sta_interface() {
+                        refresh_networks(&ctrl_state, sta).await;
+                    }
+                }
+                _ = connectivity_interval.tick(), if monitoring => {
+                    match check_connectivity(&ctrl_state.config).await {
+                        Ok(true) => link_lost_since = None,
+                        Ok(false) => {
+                            let since = *link_lost_since.get_or_insert_with(std::time::Instant::now);
+                            if since.elapsed() >= link_loss_grace {
+                                tracing::warn!(
+                                    seconds = link_loss_grace.as_secs(),
+                                    "Connectivity lost, restarting AP"
+                                );
+                                monitoring = false;
+                                link_lost_since = None;
+                                fall_back_to_ap(&ctrl_state).await;
+                            }
+                        }
+                        Err(e) => tracing::warn!(error = %e, "Connectivity check failed"),
+                    }
+                }
+                _ = link_quality_interval.tick(), if monitoring => {
+                    refresh_link_quality(&ctrl_state).await;
+                }
+                _ = ap_clients_interval.tick() => {
+                    refresh_ap_clients(&ctrl_state).await;
+                }
+                _ = ap_subnet_interval.tick(), if watch_ap_subnet => {
+                    let conflict = if ctrl_state.wifi_state.read().await.ap_running {
+                        network_manager::detect_ap_subnet_conflict(
+                            &ctrl_state.config.ap_ip,
+                            &ctrl_state.config.interface,
+                        )
+                    } else {
+                        None
+                    };
+                    if conflict.is_some() && conflict != ap_subnet_conflict {
+                        tracing::error!(
+                            ap_ip = %ctrl_state.config.ap_ip,
+                            interface = conflict.as_deref().unwrap_or_default(),
+                            "Another interface joined the setup AP subnet; the portal is likely unreachable. \
+                             Restart with a different --ap-ip (or --ap-ip auto)"
+                        );
+                    }
+                    ap_subnet_conflict = conflict;
+                }
+                _ = ap_idle_interval.tick(), if !ap_idle_timeout.is_zero() => {
+                    let ap_running = ctrl_state.wifi_state.read().await.ap_running;
+                    if ap_running && !ap_was_running {
+                        ctrl_state.touch_activity();
+                    }
+                    ap_was_running = ap_running;
+
+                    if ap_running {
+                        if !ctrl_state.config.simulate
+                            && ap_manager::has_associated_stations(&ctrl_state.config.interface).await
+                        {
+                            ctrl_state.touch_activity();
+                        } else if ctrl_state.idle_for() >= ap_idle_timeout {
+                            shut_down_idle_ap(&ctrl_state, ap_idle_timeout).await;
+                            ap_was_running = false;
+                        }
+                    }
+                }
                 _ = signal::ctrl_c() => {
                     tracing::info!("Received SIGINT, shutting down");
                     break;
                 }
+                _ = sigterm.recv() => {
+                    tracing::info!("Received SIGTERM, shutting down");
+                    break;
+                }
             }
         }
     });
@@ -353,40 +1142,1058 @@ pub async fn run_daemon(config: DaemonConfig) -> Result<()> {
 
     // Cleanup
     tracing::info!("Cleaning up...");
-    let _ = ap_manager::stop_ap().await;
-
-    // Restore NetworkManager management of the WiFi interface so it can maintain
-    // the station connection after the daemon exits.
-    let _ = ap_manager::restore_device_after_ap(&app_state.config.interface).await;
+    notify::stopping();
+    restore_radio(&app_state.config).await;
 
     ipc_handle.abort();
     web_handle.abort();
+    app_state.hooks.finish().await;
 
     Ok(())
 }
 
-/// Print current status (for CLI)
-pub async fn print_status(socket_path: &str) -> Result<()> {
-    match ipc::get_status(socket_path).await {
-        Ok(state) => {
-            println!("WiFi Setup Status");
-            println!("=================");
-            println!("Status: {:?}", state.status);
-            if let Some(ssid) = &state.connected_ssid {
-                println!("Connected to: {}", ssid);
-            }
-            if state.ap_running {
-                println!(
-                    "AP Running: {} ({})",
-                    state.ap_ssid.as_deref().unwrap_or("unknown"),
-                    state.portal_url.as_deref().unwrap_or("unknown")
-                );
+/// Scan and try the saved networks, then NetworkManager's own profiles.
+/// Returns the SSID joined; otherwise the scan is published for the portal.
+async fn auto_connect_at_startup(app_state: &AppState) -> Option<String> {
+    // No connectivity - scan and check for saved credentials
+    tracing::info!("No network connectivity, scanning for networks...");
+
+    // Initial WiFi scan (before starting AP)
+    {
+        let mut state = app_state.wifi_state.write().await;
+        state.status = ConnectionStatus::Scanning;
+        app_state.publish(&state);
+    }
+
+    let networks = if app_state.config.simulate {
+        simulate::scan_results(&app_state.config)
+    } else {
+        match network_manager::scan_networks(
+            app_state.config.scan_interface(),
+            app_state.config.scan_timeout(),
+        )
+        .await
+        {
+            Ok(networks) => networks,
+            Err(e) => {
+                // Still bring the portal up; it shows the failure and can rescan.
+                tracing::warn!(error = %format!("{:#}", e), "Initial scan failed");
+                app_state.wifi_state.write().await.fail_scan(&e);
+                Vec::new()
             }
-            println!("Available networks: {}", state.available_networks.len());
-            for net in &state.available_networks {
-                println!(
-                    "  - {} ({}%, {})",
-                    net.ssid,
+        }
+    };
+
+    // Load saved credentials and check for known networks
+    let creds_store = if app_state.config.simulate {
+        credentials::CredentialsStore::default()
+    } else {
+        credentials::CredentialsStore::load().unwrap_or_default()
+    };
+
+    // With a budget, fall through the saved networks in order; without one
+    // only the best is tried.
+    let budget = std::time::Duration::from_secs(app_state.config.auto_connect_budget);
+    let deadline = tokio::time::Instant::now() + budget;
+    let candidates: Vec<_> = if budget.is_zero() {
+        creds_store
+            .best_known_network(&networks)
+            .into_iter()
+            .collect()
+    } else {
+        creds_store.ranked_known_networks(&networks)
+    };
+    let total = candidates.len();
+    let mut auto_connected = None;
+    for (index, known_network) in candidates.into_iter().enumerate() {
+        let Some(password) = creds_store.get_password(&known_network.ssid) else {
+            continue;
+        };
+        if !budget.is_zero() && tokio::time::Instant::now() >= deadline {
+            tracing::warn!(
+                budget_secs = budget.as_secs(),
+                "Auto-connect budget exhausted, will start AP"
+            );
+            break;
+        }
+        tracing::info!(
+            ssid = %known_network.ssid,
+            signal = known_network.signal_strength,
+            attempt = index + 1,
+            of = total,
+            "Found saved credentials for available network, attempting auto-connect"
+        );
+
+        // Try to connect with saved credentials (auto-connect always saves)
+        app_state
+            .connect_stats
+            .attempts
+            .fetch_add(1, Ordering::Relaxed);
+        let started = std::time::Instant::now();
+        let attempt = network_manager::connect_to_network(
+            &app_state.config.interface,
+            backend::RadioRelease::for_config(&app_state.config),
+            known_network.raw_ssid(),
+            password,
+            true,
+            false,
+            None,
+            None,
+            app_state.config.ip_family,
+            app_state.config.scan_timeout(),
+        );
+        let result = if budget.is_zero() {
+            attempt.await
+        } else {
+            match tokio::time::timeout_at(deadline, attempt).await {
+                Ok(result) => result,
+                Err(_) => {
+                    // NetworkManager is still activating; stop it before the
+                    // fallback takes the radio.
+                    if let Err(e) =
+                        network_manager::disconnect_station(&app_state.config.interface).await
+                    {
+                        tracing::warn!(error = %format!("{:#}", e), "Failed to stop the timed-out activation");
+                    }
+                    Err(anyhow::anyhow!("auto-connect budget ran out"))
+                }
+            }
+        };
+        app_state
+            .audit_attempt(
+                &known_network.ssid,
+                known_bssid(known_network),
+                started,
+                &result,
+            )
+            .await;
+        match result {
+            Ok(()) => {
+                auto_connected = Some(known_network.ssid.clone());
+                break;
+            }
+            Err(e) => {
+                app_state
+                    .connect_stats
+                    .failures
+                    .fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(
+                    ssid = %known_network.ssid,
+                    error = %e,
+                    remaining = total - index - 1,
+                    "Auto-connect failed"
+                );
+            }
+        }
+    }
+    if let Some(ssid) = auto_connected {
+        tracing::info!(ssid = %ssid, "Auto-connected using saved credentials");
+        record_auto_connect(&ssid);
+        notify::status("Auto-connected using saved credentials");
+        notify::ready();
+        app_state
+            .hooks
+            .connected(&ssid, &app_state.config.interface)
+            .await;
+        return Some(ssid);
+    }
+
+    // Networks the system already trusts; NetworkManager keeps their secrets.
+    if app_state.config.use_nm_profiles && !app_state.config.simulate {
+        if let Some(ssid) = auto_connect_nm_profile(app_state, &networks).await {
+            notify::status("Auto-connected using a NetworkManager profile");
+            notify::ready();
+            app_state
+                .hooks
+                .connected(&ssid, &app_state.config.interface)
+                .await;
+            return Some(ssid);
+        }
+    }
+
+    // Update state with scanned networks
+    {
+        let mut state = app_state.wifi_state.write().await;
+        if state.last_scan_error.is_none() {
+            state.apply_scan(networks);
+        }
+        state.saved_ssids = creds_store.networks.keys().cloned().collect();
+        state.status = ConnectionStatus::AwaitingCredentials;
+        app_state.publish(&state);
+    }
+
+    None
+}
+
+/// Activate the best visible network NetworkManager has a saved profile for,
+/// returning its SSID once connected.
+async fn auto_connect_nm_profile(app_state: &AppState, networks: &[NetworkInfo]) -> Option<String> {
+    let profiles = match network_manager::saved_wifi_profiles().await {
+        Ok(profiles) => profiles,
+        Err(e) => {
+            tracing::warn!(error = %format!("{:#}", e), "Failed to read NetworkManager profiles");
+            return None;
+        }
+    };
+    let (network, profile) = network_manager::best_profile_match(&profiles, networks)?;
+    tracing::info!(
+        ssid = %network.ssid,
+        profile = %profile.id,
+        signal = network.signal_strength,
+        "Found a NetworkManager profile for available network, attempting auto-connect"
+    );
+
+    app_state
+        .connect_stats
+        .attempts
+        .fetch_add(1, Ordering::Relaxed);
+    let started = std::time::Instant::now();
+    let result = network_manager::activate_saved_profile(
+        &app_state.config.interface,
+        backend::RadioRelease::for_config(&app_state.config),
+        profile,
+    )
+    .await;
+    app_state
+        .audit_attempt(&network.ssid, known_bssid(network), started, &result)
+        .await;
+    match result {
+        Ok(()) => {
+            tracing::info!(ssid = %network.ssid, "Auto-connected using NetworkManager profile");
+            Some(network.ssid.clone())
+        }
+        Err(e) => {
+            app_state
+                .connect_stats
+                .failures
+                .fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(
+                ssid = %network.ssid,
+                error = %format!("{:#}", e),
+                "Profile auto-connect failed, will start AP"
+            );
+            None
+        }
+    }
+}
+
+/// Run a connect, publishing each activation deadline so clients can count down.
+#[allow(clippy::too_many_arguments)]
+async fn connect_publishing_deadline(
+    app_state: &AppState,
+    ssid: &[u8],
+    password: &str,
+    save: bool,
+    hidden: bool,
+    bssid: Option<[u8; 6]>,
+    eap_tls: Option<&EapTls>,
+    security: Option<ManualSecurity>,
+) -> Result<()> {
+    let (deadline_tx, mut deadline_rx) = mpsc::unbounded_channel();
+    let on_deadline = |deadline| {
+        let _ = deadline_tx.send(deadline);
+    };
+    let config = &app_state.config;
+    let connect = async {
+        if config.simulate {
+            simulate::connect(
+                &simulate::scan_results(config),
+                ssid,
+                password,
+                hidden,
+                on_deadline,
+            )
+            .await
+        } else {
+            network_manager::connect_to_network_with(
+                config.station_interface(),
+                backend::RadioRelease::for_config(config),
+                ssid,
+                password,
+                save,
+                hidden,
+                bssid,
+                eap_tls,
+                security,
+                config.ip_family,
+                config.scan_timeout(),
+                on_deadline,
+            )
+            .await
+        }
+    };
+    tokio::pin!(connect);
+
+    loop {
+        tokio::select! {
+            result = &mut connect => break result,
+            Some(deadline) = deadline_rx.recv() => {
+                let mut state = app_state.wifi_state.write().await;
+                state.connect_deadline = Some(deadline);
+                app_state.publish(&state);
+            }
+        }
+    }
+}
+
+/// How startup found the device online without the portal.
+enum OnlineAtStartup {
+    /// Connectivity was already there: wired, or WiFi NetworkManager joined itself.
+    Existing,
+    /// The daemon joined this WiFi network itself.
+    Joined(String),
+}
+
+/// Wait out the grace period for an existing connection (wired, or WiFi
+/// NetworkManager brings up itself), rejoining last boot's network first with
+/// `--fast-reconnect`. None if the device is still offline.
+async fn network_already_up(app_state: &AppState) -> Result<Option<OnlineAtStartup>> {
+    // Everything below talks to NetworkManager; fail clearly if it never shows up.
+    if let Err(e) = network_manager::wait_for_network_manager(NM_STARTUP_TIMEOUT).await {
+        notify::status("NetworkManager not available");
+        return Err(e);
+    }
+
+    // Record current NetworkManager WiFi backend (best effort).
+    if let Ok(backend) = network_manager::current_wifi_backend().await {
+        let mut state = app_state.wifi_state.write().await;
+        state.wifi_backend = Some(backend);
+        app_state.publish(&state);
+    }
+
+    // Check for existing connectivity
+    tracing::info!("Checking for existing network connectivity...");
+
+    let has_connectivity =
+        network_manager::check_connectivity(app_state.config.connectivity_probe.as_deref()).await?;
+    if has_connectivity {
+        tracing::info!("Already connected to network");
+        notify::status("Already connected to network");
+        notify::ready();
+        return Ok(Some(OnlineAtStartup::Existing));
+    }
+
+    // Same place as last boot: rejoin that network instead of sitting out the grace period.
+    if app_state.config.fast_reconnect {
+        if let Some(ssid) = reconnect_last_network(app_state).await {
+            tracing::info!(ssid = %ssid, "Reconnected to the last network");
+            notify::status("Reconnected to the last network");
+            notify::ready();
+            app_state
+                .hooks
+                .connected(&ssid, &app_state.config.interface)
+                .await;
+            return Ok(Some(OnlineAtStartup::Joined(ssid)));
+        }
+    }
+
+    // Grace period - wait for Ethernet/existing WiFi
+    tracing::info!(
+        seconds = app_state.config.grace_period,
+        "Waiting grace period for network..."
+    );
+
+    let grace_result = network_manager::wait_for_connectivity(
+        app_state.config.connectivity_probe.as_deref(),
+        std::time::Duration::from_secs(app_state.config.grace_period),
+    )
+    .await;
+
+    match grace_result {
+        Ok(network_manager::ConnectivityWait::Connected) => {
+            tracing::info!("Network connected during grace period");
+            notify::status("Network connected during grace period");
+            notify::ready();
+            return Ok(Some(OnlineAtStartup::Existing));
+        }
+        Ok(network_manager::ConnectivityWait::TimedOut) => {
+            tracing::info!("Grace period elapsed without connectivity");
+        }
+        Err(e) => {
+            tracing::warn!(error = %format!("{:#}", e), "Connectivity check failed during grace period");
+        }
+    }
+
+    Ok(None)
+}
+
+/// Boot fast path: connect straight to the last network joined, if its
+/// credentials are saved and a quick scan sees it. Anything missing or failing
+/// returns None and startup carries on as usual.
+async fn reconnect_last_network(app_state: &AppState) -> Option<String> {
+    let creds = credentials::CredentialsStore::load().unwrap_or_default();
+    let last = creds.last_connected()?;
+
+    let scan_timeout = app_state
+        .config
+        .scan_timeout()
+        .min(FAST_RECONNECT_SCAN_TIMEOUT);
+    let networks = match network_manager::scan_networks(
+        app_state.config.scan_interface(),
+        scan_timeout,
+    )
+    .await
+    {
+        Ok(networks) => networks,
+        Err(e) => {
+            tracing::warn!(error = %format!("{:#}", e), "Quick scan for the last network failed");
+            return None;
+        }
+    };
+    let Some(network) = networks.iter().find(|n| n.ssid == last.ssid) else {
+        tracing::info!(ssid = %last.ssid, "Last network not in range");
+        return None;
+    };
+
+    tracing::info!(
+        ssid = %last.ssid,
+        signal = network.signal_strength,
+        "Last network in range, reconnecting before the grace period"
+    );
+    app_state
+        .connect_stats
+        .attempts
+        .fetch_add(1, Ordering::Relaxed);
+    let started = std::time::Instant::now();
+    let result = network_manager::connect_to_network(
+        &app_state.config.interface,
+        backend::RadioRelease::for_config(&app_state.config),
+        network.raw_ssid(),
+        &last.password,
+        true,
+        false,
+        None,
+        None,
+        app_state.config.ip_family,
+        app_state.config.scan_timeout(),
+    )
+    .await;
+    app_state
+        .audit_attempt(&last.ssid, known_bssid(network), started, &result)
+        .await;
+    match result {
+        Ok(()) => {
+            record_auto_connect(&last.ssid);
+            Some(last.ssid.clone())
+        }
+        Err(e) => {
+            app_state
+                .connect_stats
+                .failures
+                .fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(ssid = %last.ssid, error = %e, "Reconnecting to the last network failed");
+            None
+        }
+    }
+}
+
+/// The scanned AP an unpinned startup connect most likely joined, for the audit log.
+fn known_bssid(network: &NetworkInfo) -> Option<String> {
+    Some(network.bssid.clone()).filter(|bssid| !bssid.is_empty())
+}
+
+/// Note a saved network joined without the portal, so the next boot's fast
+/// path knows where it was.
+fn record_auto_connect(ssid: &str) {
+    let mut creds = credentials::CredentialsStore::load().unwrap_or_default();
+    if creds.mark_connected(ssid) {
+        if let Err(e) = creds.save() {
+            tracing::warn!(error = %e, "Failed to record the connected network");
+        }
+    }
+}
+
+/// Rescan on the given interface and publish the results.
+///
+/// Networks are published as they are read so the list fills in progressively
+/// on busy bands; the completed scan then replaces the partial list.
+async fn refresh_networks(app_state: &AppState, interface: &str) {
+    if app_state.config.simulate {
+        let mut state = app_state.wifi_state.write().await;
+        state.apply_scan(simulate::scan_results(&app_state.config));
+        app_state.publish(&state);
+        return;
+    }
+
+    let (found_tx, mut found_rx) = mpsc::unbounded_channel();
+    let scan = network_manager::scan_networks_with(
+        interface,
+        app_state.config.scan_timeout(),
+        |network| {
+            let _ = found_tx.send(network.clone());
+        },
+    );
+    tokio::pin!(scan);
+
+    let result = loop {
+        tokio::select! {
+            result = &mut scan => break result,
+            Some(network) = found_rx.recv() => {
+                let mut state = app_state.wifi_state.write().await;
+                if state.add_partial_network(network) {
+                    app_state.publish(&state);
+                }
+            }
+        }
+    };
+
+    match result {
+        Ok(networks) => {
+            let mut state = app_state.wifi_state.write().await;
+            state.apply_scan(networks);
+            app_state.publish(&state);
+        }
+        Err(e) => {
+            tracing::warn!(interface = %interface, error = %e, "Background scan failed");
+            let mut state = app_state.wifi_state.write().await;
+            state.fail_scan(&e);
+            app_state.publish(&state);
+        }
+    }
+}
+
+/// Stop the AP and return the radio to NetworkManager client mode.
+///
+/// Used on every exit path, including before any AP was started.
+async fn restore_radio(config: &DaemonConfig) {
+    if config.simulate {
+        tracing::info!("Dry run: would stop the AP and hand the radio back to NetworkManager");
+        return;
+    }
+    if let Err(e) = ap_manager::stop_ap().await {
+        tracing::warn!(error = %e, "Failed to stop AP cleanly");
+    }
+    if let Err(e) = ap_manager::restore_device_after_ap(config).await {
+        tracing::warn!(error = %e, "Failed to restore WiFi device");
+    }
+}
+
+/// Start the setup AP, or only log it in a dry run.
+async fn start_ap(config: &DaemonConfig) -> Result<()> {
+    if config.simulate {
+        simulate::start_ap(config);
+        return Ok(());
+    }
+    ap_manager::start_ap(config).await
+}
+
+/// Stop the setup AP; a dry run has none to stop.
+async fn stop_ap(config: &DaemonConfig) -> Result<()> {
+    if config.simulate {
+        return Ok(());
+    }
+    ap_manager::stop_ap().await
+}
+
+/// Whether the joined network still reaches the outside; always in a dry run.
+async fn check_connectivity(config: &DaemonConfig) -> Result<bool> {
+    if config.simulate {
+        return Ok(true);
+    }
+    network_manager::check_connectivity(config.connectivity_probe.as_deref()).await
+}
+
+/// Wait for DHCP and routing on the joined network before a one-shot run exits.
+///
+/// Already associated, so running out of time only warrants a warning.
+async fn confirm_connectivity(config: &DaemonConfig) {
+    if config.simulate {
+        return;
+    }
+    match network_manager::wait_for_connectivity(
+        config.connectivity_probe.as_deref(),
+        POST_CONNECT_CONNECTIVITY_WAIT,
+    )
+    .await
+    {
+        Ok(network_manager::ConnectivityWait::Connected) => {
+            tracing::info!("Connectivity confirmed");
+        }
+        Ok(network_manager::ConnectivityWait::TimedOut) => {
+            tracing::warn!(
+                seconds = POST_CONNECT_CONNECTIVITY_WAIT.as_secs(),
+                "No connectivity after connecting; exiting anyway"
+            );
+        }
+        Err(e) => {
+            tracing::warn!(error = %format!("{:#}", e), "Connectivity check failed after connecting; exiting anyway");
+        }
+    }
+}
+
+/// Tear down an AP nobody has used for `idle` and go quiet until told otherwise.
+async fn shut_down_idle_ap(app_state: &AppState, idle: std::time::Duration) {
+    tracing::info!(
+        seconds = idle.as_secs(),
+        "No AP activity, shutting down the setup AP"
+    );
+    restore_radio(&app_state.config).await;
+
+    let mut state = app_state.wifi_state.write().await;
+    state.status = ConnectionStatus::Disconnected;
+    state.ap_running = false;
+    state.portal_url = None;
+    state.portal_hostname = None;
+    state.last_error = Some(format!(
+        "Setup AP stopped after {}s without activity",
+        idle.as_secs()
+    ));
+    app_state.publish(&state);
+}
+
+/// Run the steps of a backend switch, publishing each before it starts.
+async fn switch_backend(app_state: &AppState, backend: WifiBackend) -> Result<()> {
+    let deadline = app_state
+        .wifi_state
+        .read()
+        .await
+        .backend_switch_deadline
+        .unwrap_or_else(|| std::time::Instant::now() + network_manager::BACKEND_SWITCH_TIMEOUT);
+
+    for step in BackendSwitchStep::ALL {
+        {
+            let mut state = app_state.wifi_state.write().await;
+            state.backend_switch_step = Some(step);
+            app_state.publish(&state);
+        }
+        tracing::info!(step = step.describe(), "Backend switch step");
+        if app_state.config.simulate {
+            simulate::backend_switch_step(step).await;
+        } else {
+            network_manager::run_backend_switch_step(backend, step, deadline).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Tear the setup AP down and bring it back up, for an AP that stopped
+/// beaconing or lost its address while hostapd kept running.
+async fn restart_ap(app_state: &AppState) {
+    tracing::info!("AP restart requested");
+    {
+        let mut state = app_state.wifi_state.write().await;
+        state.ap_running = false;
+        state.ap_clients.clear();
+        app_state.publish(&state);
+    }
+
+    if let Err(e) = stop_ap(&app_state.config).await {
+        tracing::warn!(error = %e, "Failed to stop AP cleanly before restart");
+    }
+    let ap_result = start_ap(&app_state.config).await;
+
+    let mut state = app_state.wifi_state.write().await;
+    match ap_result {
+        Ok(()) => {
+            tracing::info!("AP restarted");
+            state.status = ConnectionStatus::AwaitingCredentials;
+            state.last_error = None;
+            mark_ap_running(&mut state, &app_state.config);
+            app_state.touch_activity();
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to restart AP");
+            record_ap_failure(&mut state, &e);
+        }
+    }
+    app_state.publish(&state);
+    app_state
+        .ap_restart_in_progress
+        .store(false, Ordering::SeqCst);
+}
+
+/// Return to setup mode after the link dropped in persist mode.
+async fn fall_back_to_ap(app_state: &AppState) {
+    {
+        let mut state = app_state.wifi_state.write().await;
+        state.status = ConnectionStatus::Scanning;
+        app_state.publish(&state);
+    }
+
+    // The AP isn't up yet, so the AP radio is free to scan too.
+    refresh_networks(app_state, app_state.config.scan_interface()).await;
+
+    // A kept AP never went down and only needs the state below.
+    let ap_result = if app_state.wifi_state.read().await.ap_running {
+        Ok(())
+    } else {
+        start_ap(&app_state.config).await
+    };
+
+    let mut state = app_state.wifi_state.write().await;
+    state.status = ConnectionStatus::AwaitingCredentials;
+    state.last_error = Some(format!(
+        "Lost connectivity to {}",
+        state.connected_ssid.as_deref().unwrap_or("network")
+    ));
+    state.connected_ssid = None;
+    state.connected_signal = None;
+    state.connected_rate = None;
+    match ap_result {
+        Ok(()) => {
+            mark_ap_running(&mut state, &app_state.config);
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to restart AP");
+            record_ap_failure(&mut state, &e);
+        }
+    }
+    app_state.publish(&state);
+}
+
+/// Remember a network's passphrase for auto-connect; returns whether it was stored.
+fn save_credentials(config: &DaemonConfig, ssid: &str, password: &str) -> bool {
+    if config.simulate {
+        tracing::info!(ssid = %ssid, "Dry run: would save WiFi credentials");
+        return false;
+    }
+    let mut creds = credentials::CredentialsStore::load().unwrap_or_default();
+    creds.save_credential(ssid, password);
+    if let Err(e) = creds.save() {
+        tracing::warn!(error = %e, "Failed to save credentials");
+        false
+    } else {
+        tracing::info!(ssid = %ssid, "Saved WiFi credentials");
+        true
+    }
+}
+
+fn set_credential_priority(config: &DaemonConfig, ssid: &str, priority: i32) {
+    if config.simulate {
+        tracing::info!(ssid = %ssid, priority, "Dry run: would update auto-connect priority");
+        return;
+    }
+    let mut creds = credentials::CredentialsStore::load().unwrap_or_default();
+    if !creds.set_priority(ssid, priority) {
+        tracing::warn!(ssid = %ssid, "No saved credentials to reprioritize");
+    } else if let Err(e) = creds.save() {
+        tracing::warn!(error = %e, "Failed to save credentials");
+    } else {
+        tracing::info!(ssid = %ssid, priority, "Updated auto-connect priority");
+    }
+}
+
+fn forget_credentials(config: &DaemonConfig, ssid: &str) -> bool {
+    if config.simulate {
+        tracing::info!(ssid = %ssid, "Dry run: would forget WiFi credentials");
+        return true;
+    }
+    let mut creds = credentials::CredentialsStore::load().unwrap_or_default();
+    if !creds.remove_credential(ssid) {
+        return false;
+    }
+    if let Err(e) = creds.save() {
+        tracing::warn!(error = %e, "Failed to save credentials");
+        false
+    } else {
+        tracing::info!(ssid = %ssid, "Forgot saved credentials");
+        true
+    }
+}
+
+/// Password stored for `ssid`, for a connect that reuses it instead of prompting.
+pub fn saved_password(config: &DaemonConfig, ssid: &str) -> Result<String> {
+    let creds = if config.simulate {
+        credentials::CredentialsStore::default()
+    } else {
+        credentials::CredentialsStore::load()?
+    };
+    creds
+        .get_password(ssid)
+        .map(str::to_string)
+        .with_context(|| format!("No saved credentials for {}", ssid))
+}
+
+/// Refuse a blank password for a network the last scan listed as secured;
+/// NetworkManager would otherwise try an open association and fail vaguely.
+/// An explicitly chosen `security` is checked on its own terms instead.
+pub fn check_password_given(
+    state: &WifiState,
+    ssid: &str,
+    password: &str,
+    eap_tls: bool,
+    security: Option<ManualSecurity>,
+) -> Result<()> {
+    if let Some(security) = security {
+        return security.check(password, eap_tls);
+    }
+    if password.is_empty() && !eap_tls && state.is_known_secured(ssid) {
+        anyhow::bail!("Password required for {}", ssid);
+    }
+    Ok(())
+}
+
+/// Refuse a connect to a network that failed too often just now, so repeated
+/// retries don't keep tearing down the AP.
+pub fn check_connect_cooldown(state: &WifiState, ssid: &str) -> Result<()> {
+    if let Some(remaining) = state
+        .recent_failures
+        .cooldown(ssid, std::time::Instant::now())
+    {
+        anyhow::bail!(
+            "Too many failed attempts for {}, wait {}s",
+            ssid,
+            state::whole_secs(remaining)
+        );
+    }
+    Ok(())
+}
+
+/// A dry-run connect that succeeded, kept until the client saves or drops it.
+struct VerifiedAttempt {
+    ssid: String,
+    password: String,
+    attempt_id: u64,
+}
+
+impl VerifiedAttempt {
+    fn matches(&self, ssid: &str, attempt_id: u64) -> bool {
+        self.ssid == ssid && self.attempt_id == attempt_id
+    }
+}
+
+/// Keep a one-shot daemon around while the client decides whether to keep
+/// dry-run credentials, saving them if asked.
+async fn await_save_decision(
+    config: &DaemonConfig,
+    command_rx: &mut mpsc::Receiver<ControlCommand>,
+    verified: VerifiedAttempt,
+) {
+    let deadline = tokio::time::Instant::now() + SAVE_DECISION_WINDOW;
+    loop {
+        match tokio::time::timeout_at(deadline, command_rx.recv()).await {
+            Ok(Some(ControlCommand::SaveCredential { ssid, attempt_id }))
+                if verified.matches(&ssid, attempt_id) =>
+            {
+                save_credentials(config, &verified.ssid, &verified.password);
+                return;
+            }
+            Ok(Some(ControlCommand::Shutdown)) | Ok(None) => return,
+            Ok(Some(_)) => tracing::debug!("Ignoring command while waiting to save credentials"),
+            Err(_) => {
+                tracing::info!("No save requested for the verified credentials");
+                return;
+            }
+        }
+    }
+}
+
+/// Publish the setup AP as up, with what clients need to join it.
+fn mark_ap_running(state: &mut WifiState, config: &DaemonConfig) {
+    state.ap_running = true;
+    state.ap_ssid = Some(config.ssid.clone());
+    state.ap_password = config.ap_password.clone();
+    state.portal_url = Some(config.portal_url());
+    state.portal_hostname = portal_hostname(config);
+}
+
+/// Publish why the AP couldn't be brought up.
+fn record_ap_failure(state: &mut WifiState, err: &anyhow::Error) {
+    state.ap_running = false;
+    state.status = match err.downcast_ref::<ap_manager::ApStartError>() {
+        Some(ap_manager::ApStartError::Unsupported { .. }) => ConnectionStatus::ApUnsupported,
+        Some(ap_manager::ApStartError::HardBlocked { .. }) => ConnectionStatus::RadioBlocked,
+        _ => ConnectionStatus::Failed,
+    };
+    state.last_error = Some(format!("{:#}", err));
+}
+
+/// Publish which devices are on the setup AP (none while it's down).
+async fn refresh_ap_clients(app_state: &AppState) {
+    let ap_running = app_state.wifi_state.read().await.ap_running;
+    let clients = if ap_running && !app_state.config.simulate {
+        ap_manager::list_ap_clients(&app_state.config.interface).await
+    } else {
+        Vec::new()
+    };
+
+    let mut state = app_state.wifi_state.write().await;
+    if state.ap_clients != clients {
+        state.ap_clients = clients;
+        app_state.publish(&state);
+    }
+}
+
+/// Sample the station link and publish its signal and bitrate.
+async fn refresh_link_quality(app_state: &AppState) {
+    if app_state.config.simulate {
+        return;
+    }
+    let quality = link_quality::read(app_state.config.station_interface())
+        .await
+        .unwrap_or_default();
+
+    let mut state = app_state.wifi_state.write().await;
+    if state.connected_signal != quality.signal || state.connected_rate != quality.rate {
+        state.connected_signal = quality.signal;
+        state.connected_rate = quality.rate;
+        app_state.publish(&state);
+    }
+}
+
+/// Friendly portal hostname served by the AP's DNS, if configured.
+fn portal_hostname(config: &DaemonConfig) -> Option<String> {
+    let hostname = config.portal_hostname.trim();
+    (!hostname.is_empty()).then(|| hostname.to_string())
+}
+
+/// Join instructions for the configured setup AP, whether or not it is up.
+pub fn onboarding_info(config: &DaemonConfig) -> OnboardingInfo {
+    OnboardingInfo::new(
+        &config.ssid,
+        config.ap_password.as_deref(),
+        &config.portal_url(),
+        portal_hostname(config).as_deref(),
+    )
+}
+
+/// Request a connection and wait for it to finish (for CLI)
+pub async fn run_connect(
+    socket_path: &str,
+    request: &ipc::ConnectParams,
+    timeout: std::time::Duration,
+) -> Result<()> {
+    let ssid = request.ssid.as_str();
+    let can_wait = ipc::handshake(socket_path)
+        .await
+        .is_ok_and(|daemon| daemon.supports("WaitForTerminal"));
+    ipc::send_connect(socket_path, request).await?;
+    println!("Connecting to {}...", ssid);
+
+    let deadline = std::time::Instant::now() + timeout;
+    if can_wait {
+        return wait_for_connect(socket_path, ssid, timeout, deadline).await;
+    }
+    // A Failed status from an earlier attempt, or Connected to the network being
+    // switched away from, is only trusted once the daemon has picked up this request.
+    let mut seen_connecting = false;
+
+    loop {
+        let state = ipc::get_status(socket_path)
+            .await
+            .context("Lost contact with the daemon while connecting")?;
+
+        match state.status {
+            ConnectionStatus::Connected
+                if seen_connecting || state.connected_ssid.as_deref() == Some(ssid) =>
+            {
+                println!(
+                    "Connected to {}",
+                    state.connected_ssid.as_deref().unwrap_or(ssid)
+                );
+                return Ok(());
+            }
+            ConnectionStatus::Connecting => seen_connecting = true,
+            ConnectionStatus::Failed if seen_connecting => {
+                anyhow::bail!(
+                    "Connection failed: {}",
+                    state.last_error.as_deref().unwrap_or("unknown error")
+                );
+            }
+            _ => {}
+        }
+
+        if std::time::Instant::now() >= deadline {
+            anyhow::bail!(
+                "Timed out after {}s waiting for connection to {}",
+                timeout.as_secs(),
+                ssid
+            );
+        }
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+}
+
+/// Block on the daemon until the connect settles; it only reports once the
+/// queued request has been picked up, so no stale outcome can leak through.
+async fn wait_for_connect(
+    socket_path: &str,
+    ssid: &str,
+    timeout: std::time::Duration,
+    deadline: std::time::Instant,
+) -> Result<()> {
+    loop {
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        let state = ipc::wait_for_terminal(socket_path, remaining)
+            .await
+            .context("Lost contact with the daemon while connecting")?;
+
+        match state.status {
+            ConnectionStatus::Connected => {
+                println!(
+                    "Connected to {}",
+                    state.connected_ssid.as_deref().unwrap_or(ssid)
+                );
+                return Ok(());
+            }
+            ConnectionStatus::Failed => anyhow::bail!(
+                "Connection failed: {}",
+                state.last_error.as_deref().unwrap_or("unknown error")
+            ),
+            // The daemon caps each wait, so long timeouts take several rounds.
+            _ if std::time::Instant::now() < deadline => {}
+            _ => anyhow::bail!(
+                "Timed out after {}s waiting for connection to {}",
+                timeout.as_secs(),
+                ssid
+            ),
+        }
+    }
+}
+
+/// Print current status (for CLI)
+pub async fn print_status(socket_path: &str, json: bool) -> Result<()> {
+    match ipc::get_status(socket_path).await {
+        Ok(state) if json => {
+            println!("{}", serde_json::to_string_pretty(&state)?);
+        }
+        Ok(state) => {
+            println!("WiFi Setup Status");
+            println!("=================");
+            if let Ok(daemon) = ipc::handshake(socket_path).await {
+                println!(
+                    "Daemon: {} (protocol {})",
+                    daemon.daemon_version.as_deref().unwrap_or("unknown"),
+                    daemon.protocol
+                );
+            }
+            println!("Status: {:?}", state.status);
+            if let Ok(config) = ipc::get_config(socket_path).await {
+                println!(
+                    "Setup AP: {} on {} ({})",
+                    config.ssid, config.interface, config.portal_url
+                );
+            }
+            if let Some(ssid) = &state.connected_ssid {
+                println!("Connected to: {}", ssid);
+            }
+            if state.ap_running {
+                println!(
+                    "AP Running: {} ({})",
+                    state.ap_ssid.as_deref().unwrap_or("unknown"),
+                    state.portal_url.as_deref().unwrap_or("unknown")
+                );
+                if let Some(hostname) = &state.portal_hostname {
+                    println!("Portal hostname: http://{}", hostname);
+                }
+                if let Some(qr) = state.onboarding.as_ref().and_then(|i| i.wifi_qr.as_ref()) {
+                    println!("Wi-Fi QR: {}", qr);
+                }
+                println!("AP clients: {}", ap_clients_summary(&state.ap_clients));
+                for client in &state.ap_clients {
+                    println!(
+                        "  - {} ({})",
+                        client.mac,
+                        client.ip.as_deref().unwrap_or("no lease yet")
+                    );
+                }
+            }
+            println!("Available networks: {}", state.available_networks.len());
+            for net in &state.available_networks {
+                println!(
+                    "  - {} ({}%, {})",
+                    net.ssid,
                     net.signal_strength,
                     if net.is_secured { "secured" } else { "open" }
                 );
@@ -399,3 +2206,88 @@ pub async fn print_status(socket_path: &str) -> Result<()> {
     }
     Ok(())
 }
+
+/// Minimal shared state for exercising handlers in tests.
+#[cfg(test)]
+pub(crate) fn test_app_state() -> (Arc<AppState>, mpsc::Receiver<ControlCommand>) {
+    test_app_state_with(|_| {})
+}
+
+/// Like [`test_app_state`], with a tweaked daemon config.
+#[cfg(test)]
+pub(crate) fn test_app_state_with(
+    configure: impl FnOnce(&mut DaemonConfig),
+) -> (Arc<AppState>, mpsc::Receiver<ControlCommand>) {
+    let (state_tx, _) = watch::channel(WifiState::default());
+    let (command_tx, command_rx) = mpsc::channel(32);
+    let mut config = DaemonConfig {
+        interface: "wlan0".to_string(),
+        sta_interface: String::new(),
+        ssid: "HyperRecovery".to_string(),
+        ap_ip: "192.168.42.1".to_string(),
+        port: 80,
+        grace_period: 10,
+        fast_reconnect: false,
+        portal_hostname: "hyper.setup".to_string(),
+        persist: false,
+        keep_ap_after_connect: false,
+        link_loss_grace: 30,
+        use_nm_profiles: false,
+        auto_connect_budget: 0,
+        scan_ttl: 60,
+        scan_timeout: 6,
+        dbus: false,
+        dhcp: DhcpRange::default(),
+        ap_dns_mode: ApDnsMode::default(),
+        ap_dns_upstreams: Vec::new(),
+        audit: false,
+        ap_band: ApBand::default(),
+        ap_password: None,
+        ap_security: ApSecurity::default(),
+        ap_idle_timeout: 0,
+        ap_max_clients: None,
+        ap_beacon_interval: None,
+        hostapd_extra: None,
+        success_url: None,
+        allow_open_silently: false,
+        portal_title: DEFAULT_PORTAL_TITLE.to_string(),
+        portal_subtitle: DEFAULT_PORTAL_SUBTITLE.to_string(),
+        portal_lang: None,
+        connectivity_probe: None,
+        min_signal: 0,
+        ip_family: IpFamily::default(),
+        portal_max_networks: 0,
+        portal_poll_ms: 2500,
+        demo_networks: None,
+        tls: false,
+        tls_port: 443,
+        on_connected: None,
+        socket_path: ipc::DEFAULT_SOCKET_PATH.to_string(),
+        pid_file: PathBuf::from(DEFAULT_PID_FILE),
+        socket_mode: 0o660,
+        socket_group: None,
+        ipc_token: false,
+        ipc_max_clients: 16,
+        simulate: false,
+    };
+    configure(&mut config);
+
+    let state = Arc::new(AppState {
+        wifi_state: RwLock::new(WifiState::default()),
+        config,
+        state_tx,
+        command_tx,
+        connect_stats: ConnectStats::default(),
+        connect_in_progress: AtomicBool::new(false),
+        last_scan_request: std::sync::Mutex::new(None),
+        scan_in_progress: AtomicBool::new(false),
+        ap_restart_in_progress: AtomicBool::new(false),
+        last_activity: std::sync::Mutex::new(std::time::Instant::now()),
+        hooks: hooks::HookRunner::new(None),
+        history: std::sync::Mutex::default(),
+        connect_cancel: tokio::sync::Notify::new(),
+        next_attempt_id: AtomicU64::new(1),
+        ipc_token: None,
+    });
+    (state, command_rx)
+}
@@ -0,0 +1,188 @@
+//! Smoothing of successive scan results for display
+//!
+//! Raw scans jitter by a few percent between runs, which makes the portal and
+//! TUI lists reorder constantly. Scans are merged into a tracked list where:
+//! - signal strength is an EWMA per SSID
+//! - neighbours only swap once the gap exceeds a hysteresis margin
+//! - networks missing from a scan linger for a few scans before being dropped
+
+use super::NetworkInfo;
+
+/// Weight of the newest sample in the signal EWMA.
+const SIGNAL_EWMA_ALPHA: f32 = 0.4;
+/// Smoothed signal gap (percentage points) required before two networks swap places.
+const REORDER_HYSTERESIS: f32 = 8.0;
+/// Consecutive scans a network may be missing from before it is dropped.
+const MAX_MISSED_SCANS: u32 = 3;
+
+#[derive(Debug, Clone)]
+struct TrackedNetwork {
+    info: NetworkInfo,
+    smoothed_signal: f32,
+    missed_scans: u32,
+}
+
+/// Display-ordered network list that absorbs scan-to-scan jitter.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkListTracker {
+    entries: Vec<TrackedNetwork>,
+}
+
+impl NetworkListTracker {
+    /// Merge a fresh scan and return the list to display.
+    ///
+    /// The scan is expected to hold one entry (the strongest BSSID) per SSID.
+    pub fn merge(&mut self, scan: Vec<NetworkInfo>) -> Vec<NetworkInfo> {
+        for entry in &mut self.entries {
+            entry.missed_scans += 1;
+        }
+
+        for network in scan {
+            let sample = network.signal_strength as f32;
+            match self
+                .entries
+                .iter_mut()
+                .find(|e| e.info.raw_ssid() == network.raw_ssid())
+            {
+                Some(entry) => {
+                    entry.smoothed_signal = SIGNAL_EWMA_ALPHA * sample
+                        + (1.0 - SIGNAL_EWMA_ALPHA) * entry.smoothed_signal;
+                    entry.info = network;
+                    entry.missed_scans = 0;
+                }
+                None => {
+                    let position = self
+                        .entries
+                        .iter()
+                        .position(|e| e.smoothed_signal < sample)
+                        .unwrap_or(self.entries.len());
+                    self.entries.insert(
+                        position,
+                        TrackedNetwork {
+                            info: network,
+                            smoothed_signal: sample,
+                            missed_scans: 0,
+                        },
+                    );
+                }
+            }
+        }
+
+        self.entries.retain(|e| e.missed_scans <= MAX_MISSED_SCANS);
+        self.reorder();
+
+        self.entries
+            .iter()
+            .map(|e| {
+                let mut info = e.info.clone();
+                info.signal_strength = e.smoothed_signal.round().clamp(0.0, 100.0) as u8;
+                info
+            })
+            .collect()
+    }
+
+    /// Bubble stronger networks up, but only past neighbours they clearly beat.
+    ///
+    /// Every swap moves a strictly stronger entry forward, so this terminates.
+    fn reorder(&mut self) {
+        let mut swapped = true;
+        while swapped {
+            swapped = false;
+            for i in 1..self.entries.len() {
+                let above = self.entries[i - 1].smoothed_signal;
+                let below = self.entries[i].smoothed_signal;
+                if below > above + REORDER_HYSTERESIS {
+                    self.entries.swap(i - 1, i);
+                    swapped = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ssids(list: &[NetworkInfo]) -> Vec<&str> {
+        list.iter().map(|n| n.ssid.as_str()).collect()
+    }
+
+    #[test]
+    fn test_jitter_does_not_reorder() {
+        let mut tracker = NetworkListTracker::default();
+        let list = tracker.merge(vec![
+            NetworkInfo::test_fixture("Home", 52),
+            NetworkInfo::test_fixture("Cafe", 50),
+        ]);
+        assert_eq!(ssids(&list), ["Home", "Cafe"]);
+
+        for (home, cafe) in [(48, 54), (51, 49), (47, 55), (50, 53)] {
+            let mut scan = vec![
+                NetworkInfo::test_fixture("Home", home),
+                NetworkInfo::test_fixture("Cafe", cafe),
+            ];
+            scan.sort_by_key(|n| std::cmp::Reverse(n.signal_strength));
+            let list = tracker.merge(scan);
+            assert_eq!(ssids(&list), ["Home", "Cafe"]);
+        }
+    }
+
+    #[test]
+    fn test_sustained_change_reorders() {
+        let mut tracker = NetworkListTracker::default();
+        tracker.merge(vec![
+            NetworkInfo::test_fixture("Home", 60),
+            NetworkInfo::test_fixture("Cafe", 40),
+        ]);
+
+        let mut list = Vec::new();
+        for _ in 0..5 {
+            list = tracker.merge(vec![
+                NetworkInfo::test_fixture("Cafe", 90),
+                NetworkInfo::test_fixture("Home", 30),
+            ]);
+        }
+        assert_eq!(ssids(&list), ["Cafe", "Home"]);
+    }
+
+    #[test]
+    fn test_signal_is_smoothed() {
+        let mut tracker = NetworkListTracker::default();
+        tracker.merge(vec![NetworkInfo::test_fixture("Home", 50)]);
+        let list = tracker.merge(vec![NetworkInfo::test_fixture("Home", 100)]);
+        assert_eq!(list[0].signal_strength, 70);
+    }
+
+    #[test]
+    fn test_new_networks_inserted_by_signal() {
+        let mut tracker = NetworkListTracker::default();
+        tracker.merge(vec![
+            NetworkInfo::test_fixture("Home", 80),
+            NetworkInfo::test_fixture("Cafe", 30),
+        ]);
+        let list = tracker.merge(vec![
+            NetworkInfo::test_fixture("Home", 80),
+            NetworkInfo::test_fixture("Office", 60),
+            NetworkInfo::test_fixture("Cafe", 30),
+        ]);
+        assert_eq!(ssids(&list), ["Home", "Office", "Cafe"]);
+    }
+
+    #[test]
+    fn test_missing_networks_age_out() {
+        let mut tracker = NetworkListTracker::default();
+        tracker.merge(vec![
+            NetworkInfo::test_fixture("Home", 80),
+            NetworkInfo::test_fixture("Cafe", 30),
+        ]);
+
+        for _ in 0..MAX_MISSED_SCANS {
+            let list = tracker.merge(vec![NetworkInfo::test_fixture("Home", 80)]);
+            assert_eq!(ssids(&list), ["Home", "Cafe"]);
+        }
+
+        let list = tracker.merge(vec![NetworkInfo::test_fixture("Home", 80)]);
+        assert_eq!(ssids(&list), ["Home"]);
+    }
+}
@@ -1,16 +1,24 @@
 //! NetworkManager D-Bus integration
 
-use super::{NetworkInfo, WifiBackend};
+use super::backend::{self, RadioRelease};
+use super::nm_client::{
+    AccessPoint, ConnectionSettings, NmClient, ZbusNm, NM_DEST, NM_DEVICE_IFACE, NM_IFACE, NM_PATH,
+};
+use super::state::{format_bssid, BackendSwitchStep, ENHANCED_OPEN_SECURITY, WPA3_ONLY_SECURITY};
+use super::{ConnectError, EapTls, IpFamily, ManualSecurity, NetworkInfo, WifiBackend};
 use anyhow::{Context, Result};
+use axum::http::{header, Request, StatusCode, Uri};
+use hyper_util::rt::TokioIo;
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::net::Ipv4Addr;
 use std::path::Path;
 use std::process::Command;
+use std::time::{Duration, Instant};
 use zbus::Connection;
 use zvariant::{OwnedObjectPath, Value};
 
-const DEFAULT_AP_IP: &str = "192.168.42.1";
+pub const DEFAULT_AP_IP: &str = "192.168.42.1";
 const AP_IP_CANDIDATES: [&str; 5] = [
     "192.168.42.1",
     "10.42.0.1",
@@ -18,16 +26,50 @@ const AP_IP_CANDIDATES: [&str; 5] = [
     "192.168.88.1",
     "10.123.0.1",
 ];
-const NM_DEST: &str = "org.freedesktop.NetworkManager";
-const NM_PATH: &str = "/org/freedesktop/NetworkManager";
-const NM_IFACE: &str = "org.freedesktop.NetworkManager";
-const NM_DEVICE_IFACE: &str = "org.freedesktop.NetworkManager.Device";
-const NM_WIFI_DEVICE_IFACE: &str = "org.freedesktop.NetworkManager.Device.Wireless";
-const NM_AP_IFACE: &str = "org.freedesktop.NetworkManager.AccessPoint";
+/// Last automatically selected AP address, reused while it stays conflict-free.
+const AP_SUBNET_PATH: &str = "/var/lib/hyper-connect/ap-subnet";
+const NM_SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
+const NM_SETTINGS_IFACE: &str = "org.freedesktop.NetworkManager.Settings";
+const NM_CONNECTION_IFACE: &str = "org.freedesktop.NetworkManager.Settings.Connection";
 const NM_DEVICE_TYPE_WIFI: u32 = 2;
+/// Pause between connectivity checks while waiting for a link
+const CONNECTIVITY_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// Upper bound on a single HTTP connectivity probe
+const PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+const NM_DEVICE_STATE_NEED_AUTH: u32 = 60;
+const NM_DEVICE_STATE_IP_CONFIG: u32 = 70;
 const NM_DEVICE_STATE_ACTIVATED: u32 = 100;
 const NM_DEVICE_STATE_FAILED: u32 = 120;
+const NM_DEVICE_STATE_REASON_IP_CONFIG_UNAVAILABLE: u32 = 5;
+const NM_DEVICE_STATE_REASON_NO_SECRETS: u32 = 7;
+const NM_DEVICE_STATE_REASON_SUPPLICANT_DISCONNECT: u32 = 8;
+const NM_DEVICE_STATE_REASON_SUPPLICANT_TIMEOUT: u32 = 11;
+const NM_DEVICE_STATE_REASON_DHCP_START_FAILED: u32 = 15;
+const NM_DEVICE_STATE_REASON_DHCP_ERROR: u32 = 16;
+const NM_DEVICE_STATE_REASON_DHCP_FAILED: u32 = 17;
+const NM_DEVICE_STATE_REASON_SSID_NOT_FOUND: u32 = 53;
+/// Activation errors that nmcli has been seen to get past on some NM versions.
+/// Anything else (permissions, unknown device) would fail the same way there.
+const NMCLI_FALLBACK_ERRORS: [&str; 6] = [
+    "org.freedesktop.NetworkManager.Failed",
+    "org.freedesktop.NetworkManager.ConnectionNotAvailable",
+    "org.freedesktop.NetworkManager.InvalidArguments",
+    "org.freedesktop.NetworkManager.Settings.Connection.InvalidProperty",
+    "org.freedesktop.NetworkManager.Settings.Connection.MissingProperty",
+    "org.freedesktop.NetworkManager.Settings.Connection.MissingSetting",
+];
+/// How long NEED_AUTH may last before the passphrase is taken to be wrong
+const NEED_AUTH_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+/// `nmcli --wait` bound for the fallback activation
+const NMCLI_ACTIVATION_TIMEOUT_SECS: u32 = 35;
+/// How long an activated profile gets to reach ACTIVATED before the attempt fails
+const ACTIVATION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(35);
 const NM_80211_AP_FLAGS_PRIVACY: u32 = 0x1;
+const NM_80211_AP_SEC_KEY_MGMT_PSK: u32 = 0x100;
+const NM_80211_AP_SEC_KEY_MGMT_802_1X: u32 = 0x200;
+const NM_80211_AP_SEC_KEY_MGMT_SAE: u32 = 0x400;
+const NM_80211_AP_SEC_KEY_MGMT_OWE: u32 = 0x800;
+const NM_80211_AP_SEC_KEY_MGMT_OWE_TM: u32 = 0x1000;
 
 /// Parse the active NetworkManager WiFi backend from `NetworkManager --print-config`.
 pub async fn current_wifi_backend() -> Result<WifiBackend> {
@@ -59,93 +101,206 @@ pub async fn current_wifi_backend() -> Result<WifiBackend> {
     anyhow::bail!("NetworkManager wifi.backend not found in printed config")
 }
 
-/// Switch NetworkManager WiFi backend (best-effort) by writing an override file and restarting services.
+/// Time a whole backend switch gets before the portal reports it failed.
+pub const BACKEND_SWITCH_TIMEOUT: Duration = Duration::from_secs(45);
+/// Exit code of the switch script when the target supplicant isn't installed.
+const SWITCH_EXIT_UNAVAILABLE: i32 = 2;
+/// Extra wait on `systemd-run` past the unit timeout before giving up on it.
+const SWITCH_RUN_GRACE: Duration = Duration::from_secs(5);
+
+/// Backend switch failures, worded for the portal.
+#[derive(Debug, thiserror::Error)]
+pub enum BackendSwitchError {
+    #[error("{service} is not available on this image, so NetworkManager can't use {backend}")]
+    ServiceUnavailable {
+        backend: &'static str,
+        service: &'static str,
+    },
+    #[error("timed out {}", step.describe())]
+    TimedOut { step: BackendSwitchStep },
+    #[error("failed {} ({status}){}", step.describe(), details(output))]
+    StepFailed {
+        step: BackendSwitchStep,
+        status: String,
+        output: String,
+    },
+}
+
+fn details(output: &str) -> String {
+    if output.is_empty() {
+        String::new()
+    } else {
+        format!(": {}", output)
+    }
+}
+
+/// Run one step of switching NetworkManager to `backend`.
+///
+/// Steps run as oneshot units outside hyper-connect's sandbox and share the
+/// time left until `deadline`.
 ///
 /// Notes:
-/// - This restarts NetworkManager and may interrupt connectivity.
+/// - Restarting NetworkManager may interrupt connectivity.
 /// - Intended for troubleshooting in a recovery environment.
-pub async fn switch_wifi_backend(backend: WifiBackend) -> Result<()> {
-    let backend_value = backend.as_nm_value();
-    let script = format!(
-        r#"set -e
-export PATH=/run/current-system/sw/bin:$PATH
-CONF_DIR=/etc/NetworkManager/conf.d
-CONF_FILE=$CONF_DIR/99-hyper-connect-backend.conf
-
-mkdir -p "$CONF_DIR"
-cat > "$CONF_FILE" <<'EOF'
-[device]
-wifi.backend={backend_value}
-EOF
-
-if [ "{backend_value}" = "iwd" ]; then
-  if ! systemctl cat iwd.service >/dev/null 2>&1; then
-    echo "iwd.service is not available on this image" >&2
-    exit 2
-  fi
-  systemctl stop wpa_supplicant.service || true
-  systemctl start iwd.service
-else
-  if ! systemctl cat wpa_supplicant.service >/dev/null 2>&1; then
-    echo "wpa_supplicant.service is not available on this image" >&2
-    exit 2
-  fi
-  systemctl stop iwd.service || true
-  systemctl start wpa_supplicant.service
-fi
-
-systemctl restart NetworkManager.service
-"#
-    );
+pub async fn run_backend_switch_step(
+    backend: WifiBackend,
+    step: BackendSwitchStep,
+    deadline: Instant,
+) -> Result<()> {
+    let budget = deadline.saturating_duration_since(Instant::now());
+    if budget.is_zero() {
+        return Err(BackendSwitchError::TimedOut { step }.into());
+    }
+    let script = backend_switch_script(backend, step);
+    let timeout_property = format!("TimeoutStartSec={}ms", budget.as_millis());
 
     // hyper-connect runs with a hardened unit; run the switch outside of its sandbox.
-    let output = tokio::process::Command::new("/run/current-system/sw/bin/systemd-run")
+    let run = tokio::process::Command::new("/run/current-system/sw/bin/systemd-run")
         .args([
             "--quiet",
             "--wait",
+            "--pipe",
             "--collect",
             "--unit",
             "hyper-connect-backend-switch",
             "--property",
             "Type=oneshot",
             "--property",
-            "TimeoutStartSec=45s",
+            &timeout_property,
             "--",
             "/run/current-system/sw/bin/bash",
             "-lc",
             &script,
         ])
-        .output()
-        .await
-        .context("Failed to execute backend switch via systemd-run")?;
+        .kill_on_drop(true)
+        .output();
+    let output = match tokio::time::timeout(budget + SWITCH_RUN_GRACE, run).await {
+        Ok(output) => output.context("Failed to execute backend switch via systemd-run")?,
+        Err(_) => return Err(BackendSwitchError::TimedOut { step }.into()),
+    };
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!("Backend switch failed: {}", stderr.trim());
+    if output.status.success() {
+        return Ok(());
     }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    tracing::warn!(
+        step = step.describe(),
+        status = %output.status,
+        stderr = %stderr.trim(),
+        "Backend switch step failed"
+    );
+    Err(switch_step_error(
+        backend,
+        step,
+        output.status.code(),
+        Instant::now() >= deadline,
+        &stderr,
+    )
+    .into())
+}
 
-    Ok(())
+/// Shell script for one backend switch step.
+///
+/// The first step checks the target service before touching any config, so an
+/// image without it keeps its working backend.
+fn backend_switch_script(backend: WifiBackend, step: BackendSwitchStep) -> String {
+    let old = match backend {
+        WifiBackend::Iwd => WifiBackend::WpaSupplicant,
+        WifiBackend::WpaSupplicant => WifiBackend::Iwd,
+    };
+    let body = match step {
+        BackendSwitchStep::StoppingOld => format!(
+            r#"if ! systemctl cat {new_service} >/dev/null 2>&1; then
+  echo "{new_service} is not available on this image" >&2
+  exit {unavailable}
+fi
+
+CONF_DIR=/etc/NetworkManager/conf.d
+mkdir -p "$CONF_DIR"
+cat > "$CONF_DIR/99-hyper-connect-backend.conf" <<'EOF'
+[device]
+wifi.backend={backend_value}
+EOF
+
+systemctl stop {old_service} || true
+"#,
+            new_service = backend.service(),
+            old_service = old.service(),
+            unavailable = SWITCH_EXIT_UNAVAILABLE,
+            backend_value = backend.as_nm_value(),
+        ),
+        BackendSwitchStep::StartingNew => format!("systemctl start {}\n", backend.service()),
+        BackendSwitchStep::RestartingNetworkManager => {
+            "systemctl restart NetworkManager.service\n".to_string()
+        }
+    };
+    format!(
+        "set -e\nexport PATH=/run/current-system/sw/bin:$PATH\n{}",
+        body
+    )
+}
+
+/// Map a failed step's exit to the error the portal shows.
+///
+/// How systemd-run reports a unit killed at its `TimeoutStartSec` varies, so
+/// running out of time is judged by the deadline instead.
+fn switch_step_error(
+    backend: WifiBackend,
+    step: BackendSwitchStep,
+    code: Option<i32>,
+    past_deadline: bool,
+    stderr: &str,
+) -> BackendSwitchError {
+    match code {
+        _ if past_deadline => BackendSwitchError::TimedOut { step },
+        Some(SWITCH_EXIT_UNAVAILABLE) if step == BackendSwitchStep::StoppingOld => {
+            BackendSwitchError::ServiceUnavailable {
+                backend: backend.as_nm_value(),
+                service: backend.service(),
+            }
+        }
+        _ => BackendSwitchError::StepFailed {
+            step,
+            status: code.map_or("killed by a signal".to_string(), |code| {
+                format!("exit code {}", code)
+            }),
+            output: stderr.lines().last().unwrap_or_default().trim().to_string(),
+        },
+    }
 }
 
 #[derive(Debug, Clone)]
-struct WirelessInterface {
-    name: String,
-    driver_bound: bool,
-    device_hint: String,
+pub(super) struct WirelessInterface {
+    pub(super) name: String,
+    pub(super) driver_bound: bool,
+    pub(super) device_hint: String,
 }
 
 /// Resolve and validate wireless interface selection.
 ///
+/// Returns the AP interface and, when a second usable radio exists, a station
+/// interface that can scan while the AP stays up.
+///
 /// Behavior:
 /// - `auto` picks a detected wireless interface
 /// - explicit interface is used if valid
 /// - if explicit interface is missing but exactly one wireless interface exists, fallback to it
-pub fn resolve_wireless_interface(configured: &str) -> Result<String> {
-    let configured = configured.trim();
+/// - the station interface is `auto` (any other viable radio), `none`, or an explicit name
+pub fn resolve_wireless_interface(
+    configured: &str,
+    sta_configured: &str,
+) -> Result<(String, Option<String>)> {
     let interfaces = list_wireless_interfaces();
+    let ap_interface = resolve_ap_interface(configured, &interfaces)?;
+    let sta_interface = resolve_sta_interface(sta_configured, &ap_interface, &interfaces)?;
+    Ok((ap_interface, sta_interface))
+}
+
+fn resolve_ap_interface(configured: &str, interfaces: &[WirelessInterface]) -> Result<String> {
+    let configured = configured.trim();
 
     if configured.eq_ignore_ascii_case("auto") || configured.is_empty() {
-        return choose_auto_interface(&interfaces);
+        return choose_auto_interface(interfaces);
     }
 
     if let Some(iface) = interfaces.iter().find(|iface| iface.name == configured) {
@@ -223,7 +378,55 @@ pub fn resolve_wireless_interface(configured: &str) -> Result<String> {
     );
 }
 
+fn resolve_sta_interface(
+    configured: &str,
+    ap_interface: &str,
+    interfaces: &[WirelessInterface],
+) -> Result<Option<String>> {
+    let configured = configured.trim();
+
+    if configured.is_empty() || configured.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+
+    if configured.eq_ignore_ascii_case("auto") {
+        let mut candidates = interfaces
+            .iter()
+            .filter(|iface| {
+                iface.driver_bound && !iface.name.starts_with("p2p-") && iface.name != ap_interface
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let sta = candidates.first().map(|iface| iface.name.clone());
+        if let Some(name) = &sta {
+            tracing::info!(interface = %name, "Using second wireless interface for scanning");
+        }
+        return Ok(sta);
+    }
+
+    if configured == ap_interface {
+        anyhow::bail!(
+            "Station interface '{}' is already used for the AP. Pick a different radio or use --sta-interface none.",
+            configured
+        );
+    }
+
+    match interfaces.iter().find(|iface| iface.name == configured) {
+        Some(iface) if iface.driver_bound => Ok(Some(iface.name.clone())),
+        Some(iface) => anyhow::bail!(
+            "Wireless card detected for station interface '{}' (device: {}) but no kernel driver is bound.",
+            iface.name,
+            iface.device_hint
+        ),
+        None => anyhow::bail!("Station interface '{}' was not found", configured),
+    }
+}
+
 /// Resolve AP IP address, using conflict-aware selection when `auto` is requested.
+///
+/// The automatically chosen address is remembered across boots so the portal URL
+/// stays stable while it remains conflict-free.
 pub fn resolve_ap_ip(configured: &str) -> Result<String> {
     let configured = configured.trim();
     if !configured.eq_ignore_ascii_case("auto") {
@@ -233,23 +436,58 @@ pub fn resolve_ap_ip(configured: &str) -> Result<String> {
         return Ok(ip.to_string());
     }
 
+    let remembered = load_remembered_ap_ip(AP_SUBNET_PATH);
     let occupied = occupied_ipv4_prefixes();
-    for candidate in AP_IP_CANDIDATES {
-        let Ok(ip) = candidate.parse::<Ipv4Addr>() else {
-            continue;
-        };
-        let [a, b, c, _] = ip.octets();
-        if !occupied.contains(&(a, b, c)) {
-            tracing::info!(ap_ip = %candidate, "Selected AP subnet automatically");
-            return Ok(candidate.to_string());
+    let Some(ip) = choose_ap_ip(&occupied, remembered) else {
+        tracing::warn!(
+            fallback = DEFAULT_AP_IP,
+            "All preferred AP subnets overlap with existing addresses; falling back to default"
+        );
+        return Ok(DEFAULT_AP_IP.to_string());
+    };
+
+    if Some(ip) == remembered {
+        tracing::info!(ap_ip = %ip, "Reusing remembered AP subnet");
+    } else {
+        tracing::info!(ap_ip = %ip, "Selected AP subnet automatically");
+        if let Err(e) = remember_ap_ip(AP_SUBNET_PATH, ip) {
+            tracing::warn!(error = %e, "Failed to persist AP subnet");
         }
     }
 
-    tracing::warn!(
-        fallback = DEFAULT_AP_IP,
-        "All preferred AP subnets overlap with existing addresses; falling back to default"
-    );
-    Ok(DEFAULT_AP_IP.to_string())
+    Ok(ip.to_string())
+}
+
+/// Pick a conflict-free AP address, preferring the one remembered from a previous boot.
+fn choose_ap_ip(
+    occupied: &HashSet<(u8, u8, u8)>,
+    remembered: Option<Ipv4Addr>,
+) -> Option<Ipv4Addr> {
+    let is_free = |ip: &Ipv4Addr| {
+        let [a, b, c, _] = ip.octets();
+        !occupied.contains(&(a, b, c))
+    };
+
+    if let Some(ip) = remembered.filter(is_free) {
+        return Some(ip);
+    }
+
+    AP_IP_CANDIDATES
+        .iter()
+        .filter_map(|candidate| candidate.parse::<Ipv4Addr>().ok())
+        .find(is_free)
+}
+
+fn load_remembered_ap_ip<P: AsRef<Path>>(path: P) -> Option<Ipv4Addr> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn remember_ap_ip<P: AsRef<Path>>(path: P, ip: Ipv4Addr) -> Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context("Failed to create state directory")?;
+    }
+    fs::write(path, format!("{}\n", ip)).context("Failed to write AP subnet file")
 }
 
 fn choose_auto_interface(interfaces: &[WirelessInterface]) -> Result<String> {
@@ -289,7 +527,7 @@ fn choose_auto_interface(interfaces: &[WirelessInterface]) -> Result<String> {
     anyhow::bail!("No usable wireless interfaces detected")
 }
 
-fn list_wireless_interfaces() -> Vec<WirelessInterface> {
+pub(super) fn list_wireless_interfaces() -> Vec<WirelessInterface> {
     let mut interfaces = Vec::new();
     let Ok(entries) = fs::read_dir("/sys/class/net") else {
         return interfaces;
@@ -318,22 +556,146 @@ fn list_wireless_interfaces() -> Vec<WirelessInterface> {
     interfaces
 }
 
+/// One radio from `rfkill list`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RfkillEntry {
+    index: u32,
+    /// Device name, e.g. `phy0`
+    name: String,
+    /// Radio type, e.g. `Wireless LAN`
+    kind: String,
+    soft_blocked: bool,
+    hard_blocked: bool,
+}
+
+/// What stands between a radio and use after lifting any soft block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RadioBlock {
+    None,
+    /// Only the hardware switch or key can turn it back on.
+    Hard,
+}
+
+/// Lift an rfkill soft block on the interface's radio and report a hard block.
+///
+/// Without `rfkill` or a matching entry the radio is assumed usable.
+pub async fn unblock_radio(interface: &str) -> RadioBlock {
+    let Some(entry) = rfkill_entry(interface).await else {
+        return RadioBlock::None;
+    };
+    let entry = if entry.soft_blocked && !entry.hard_blocked {
+        tracing::warn!(interface = %interface, radio = %entry.name, "WiFi radio is soft-blocked, unblocking");
+        let unblocked = tokio::process::Command::new("rfkill")
+            .args(["unblock", &entry.index.to_string()])
+            .output()
+            .await;
+        if let Err(e) = unblocked {
+            tracing::warn!(error = %e, "Failed to run rfkill unblock");
+        }
+        rfkill_entry(interface).await.unwrap_or(entry)
+    } else {
+        entry
+    };
+
+    if entry.hard_blocked {
+        tracing::error!(interface = %interface, radio = %entry.name, "WiFi radio is hard-blocked by a hardware switch");
+        RadioBlock::Hard
+    } else {
+        if entry.soft_blocked {
+            tracing::warn!(interface = %interface, "WiFi radio is still soft-blocked");
+        }
+        RadioBlock::None
+    }
+}
+
+async fn rfkill_entry(interface: &str) -> Option<RfkillEntry> {
+    let output = tokio::process::Command::new("rfkill")
+        .arg("list")
+        .output()
+        .await
+        .ok()?;
+    let entries = parse_rfkill_list(&String::from_utf8_lossy(&output.stdout));
+    let phy = fs::read_to_string(format!("/sys/class/net/{}/phy80211/name", interface)).ok();
+    select_rfkill_entry(entries, phy.as_deref().map(str::trim))
+}
+
+/// Parse `rfkill list` output:
+///
+/// ```text
+/// 0: phy0: Wireless LAN
+///         Soft blocked: yes
+///         Hard blocked: no
+/// ```
+fn parse_rfkill_list(output: &str) -> Vec<RfkillEntry> {
+    let mut entries: Vec<RfkillEntry> = Vec::new();
+    for line in output.lines() {
+        if let Some((key, value)) = line.trim().split_once(": ") {
+            let blocked = value.trim() == "yes";
+            match (key, entries.last_mut()) {
+                ("Soft blocked", Some(entry)) => entry.soft_blocked = blocked,
+                ("Hard blocked", Some(entry)) => entry.hard_blocked = blocked,
+                _ => {
+                    let mut fields = line.splitn(3, ": ");
+                    let (Some(index), Some(name), Some(kind)) =
+                        (fields.next(), fields.next(), fields.next())
+                    else {
+                        continue;
+                    };
+                    let Ok(index) = index.trim().parse() else {
+                        continue;
+                    };
+                    entries.push(RfkillEntry {
+                        index,
+                        name: name.to_string(),
+                        kind: kind.trim().to_string(),
+                        soft_blocked: false,
+                        hard_blocked: false,
+                    });
+                }
+            }
+        }
+    }
+    entries
+}
+
+/// The entry for `phy`, or the first WiFi radio when the phy is unknown.
+fn select_rfkill_entry(entries: Vec<RfkillEntry>, phy: Option<&str>) -> Option<RfkillEntry> {
+    match phy {
+        Some(phy) => entries.into_iter().find(|e| e.name == phy),
+        None => entries.into_iter().find(|e| e.kind == "Wireless LAN"),
+    }
+}
+
 fn occupied_ipv4_prefixes() -> HashSet<(u8, u8, u8)> {
-    let mut prefixes = HashSet::new();
+    interface_ipv4_prefixes()
+        .into_iter()
+        .map(|(_, prefix)| prefix)
+        .collect()
+}
 
+/// Every interface's IPv4 /24 prefixes, as listed by `ip -4 -o addr show`.
+fn interface_ipv4_prefixes() -> Vec<(String, (u8, u8, u8))> {
     let Ok(output) = Command::new("ip")
         .args(["-4", "-o", "addr", "show"])
         .output()
     else {
-        return prefixes;
+        return Vec::new();
     };
 
     if !output.status.success() {
-        return prefixes;
+        return Vec::new();
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    for line in stdout.lines() {
+    parse_interface_ipv4_prefixes(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_interface_ipv4_prefixes(output: &str) -> Vec<(String, (u8, u8, u8))> {
+    let mut prefixes = Vec::new();
+    for line in output.lines() {
+        // "3: eth0    inet 10.0.0.5/24 brd ..."
+        let Some(interface) = line.split_whitespace().nth(1) else {
+            continue;
+        };
         for token in line.split_whitespace() {
             if !token.contains('/') {
                 continue;
@@ -345,7 +707,7 @@ fn occupied_ipv4_prefixes() -> HashSet<(u8, u8, u8)> {
 
             if let Ok(ip) = address.parse::<Ipv4Addr>() {
                 let [a, b, c, _] = ip.octets();
-                prefixes.insert((a, b, c));
+                prefixes.push((interface.to_string(), (a, b, c)));
                 break;
             }
         }
@@ -354,7 +716,28 @@ fn occupied_ipv4_prefixes() -> HashSet<(u8, u8, u8)> {
     prefixes
 }
 
-fn detect_unbound_pci_wifi_devices() -> Vec<String> {
+/// The first interface other than the AP's own that sits in the AP's /24.
+fn ap_subnet_conflict(
+    ap_ip: Ipv4Addr,
+    ap_interface: &str,
+    prefixes: &[(String, (u8, u8, u8))],
+) -> Option<String> {
+    let [a, b, c, _] = ap_ip.octets();
+    prefixes
+        .iter()
+        .find(|(interface, prefix)| interface != ap_interface && *prefix == (a, b, c))
+        .map(|(interface, _)| interface.clone())
+}
+
+/// Interface that came up in the AP subnet after startup, if any.
+///
+/// Routes to the portal go out the wrong interface once this happens.
+pub fn detect_ap_subnet_conflict(ap_ip: &str, ap_interface: &str) -> Option<String> {
+    let ap_ip = ap_ip.parse().ok()?;
+    ap_subnet_conflict(ap_ip, ap_interface, &interface_ipv4_prefixes())
+}
+
+pub(super) fn detect_unbound_pci_wifi_devices() -> Vec<String> {
     let mut devices = Vec::new();
     let pci_root = Path::new("/sys/bus/pci/devices");
 
@@ -393,141 +776,388 @@ fn detect_unbound_pci_wifi_devices() -> Vec<String> {
     devices
 }
 
-/// Check if we have network connectivity via NetworkManager
-pub async fn check_connectivity() -> Result<bool> {
-    let connection = Connection::system().await?;
+/// Open the system bus, with an actionable error when it isn't reachable.
+async fn system_bus() -> Result<Connection> {
+    Connection::system()
+        .await
+        .context("Failed to connect to the system D-Bus (is dbus running?)")
+}
+
+/// Whether NetworkManager currently owns its bus name.
+pub async fn network_manager_running() -> Result<bool> {
+    let connection = system_bus().await?;
+    let dbus = zbus::fdo::DBusProxy::new(&connection).await?;
+    Ok(dbus
+        .name_has_owner(zbus::names::BusName::try_from(NM_DEST)?)
+        .await?)
+}
+
+/// Wait until NetworkManager owns its bus name, giving up after `timeout`.
+///
+/// On boot the daemon can race NetworkManager's startup, so a short wait avoids
+/// failing on the first probe; a missing NetworkManager still ends in a clear error.
+pub async fn wait_for_network_manager(timeout: std::time::Duration) -> Result<()> {
+    let connection = system_bus().await?;
+    let dbus = zbus::fdo::DBusProxy::new(&connection).await?;
+    let name = zbus::names::BusName::try_from(NM_DEST)?;
+    let deadline = tokio::time::Instant::now() + timeout;
+
+    let mut warned = false;
+    while !dbus.name_has_owner(name.clone()).await? {
+        if tokio::time::Instant::now() >= deadline {
+            anyhow::bail!(
+                "NetworkManager not available on the system bus after {}s; \
+                 make sure NetworkManager.service is enabled and running",
+                timeout.as_secs()
+            );
+        }
+        if !warned {
+            tracing::warn!("NetworkManager not available yet, waiting for it to start");
+            warned = true;
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+
+    Ok(())
+}
+
+/// Check if we have network connectivity.
+///
+/// NetworkManager's own check wins when it reports FULL; otherwise the
+/// optional HTTP probe gets the final say, since NM's check endpoint may be
+/// unreachable on air-gapped or custom networks.
+pub async fn check_connectivity(probe_url: Option<&str>) -> Result<bool> {
+    let connection = system_bus().await?;
 
     let proxy = zbus::Proxy::new(&connection, NM_DEST, NM_PATH, NM_IFACE).await?;
 
     // NM_CONNECTIVITY_FULL = 4
     let connectivity: u32 = proxy.get_property("Connectivity").await?;
 
-    Ok(connectivity == 4)
+    if connectivity == 4 {
+        return Ok(true);
+    }
+    match probe_url {
+        Some(url) => probe_http_connectivity(url).await,
+        None => Ok(false),
+    }
+}
+
+/// Outcome of waiting for connectivity until a deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectivityWait {
+    Connected,
+    TimedOut,
 }
 
-/// Wait for network connectivity
-pub async fn wait_for_connectivity() -> Result<()> {
-    let connection = Connection::system().await?;
+/// Wait for network connectivity, giving up once `timeout` has elapsed.
+pub async fn wait_for_connectivity(
+    probe_url: Option<&str>,
+    timeout: std::time::Duration,
+) -> Result<ConnectivityWait> {
+    poll_connectivity(
+        || check_connectivity(probe_url),
+        tokio::time::Instant::now() + timeout,
+        CONNECTIVITY_POLL_INTERVAL,
+    )
+    .await
+}
 
+/// Run `check` every `interval` until it reports connectivity or `deadline` passes.
+///
+/// Each check is itself cut off at the deadline, so a stalled D-Bus call or
+/// probe can't hold the loop past it.
+async fn poll_connectivity<F, Fut>(
+    mut check: F,
+    deadline: tokio::time::Instant,
+    interval: std::time::Duration,
+) -> Result<ConnectivityWait>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<bool>>,
+{
     loop {
-        let proxy = zbus::Proxy::new(&connection, NM_DEST, NM_PATH, NM_IFACE).await?;
-
-        let connectivity: u32 = proxy.get_property("Connectivity").await?;
+        match tokio::time::timeout_at(deadline, check()).await {
+            Ok(Ok(true)) => return Ok(ConnectivityWait::Connected),
+            Ok(Ok(false)) => {}
+            Ok(Err(e)) => return Err(e),
+            Err(_) => return Ok(ConnectivityWait::TimedOut),
+        }
 
-        if connectivity == 4 {
-            return Ok(());
+        if tokio::time::Instant::now() + interval >= deadline {
+            return Ok(ConnectivityWait::TimedOut);
         }
+        tokio::time::sleep(interval).await;
+    }
+}
 
-        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+/// Whether `url` is usable as a connectivity probe (plain `http://` with a host).
+pub fn is_valid_probe_url(url: &str) -> bool {
+    url.parse::<Uri>()
+        .map(|uri| uri.scheme_str() == Some("http") && uri.host().is_some())
+        .unwrap_or(false)
+}
+
+/// GET `url` and report whether it answered with a 2xx status.
+///
+/// Unreachable hosts, timeouts and non-2xx answers all count as "not
+/// connected"; only a malformed URL is an error.
+pub async fn probe_http_connectivity(url: &str) -> Result<bool> {
+    let uri: Uri = url.parse().context("Invalid connectivity probe URL")?;
+    if !is_valid_probe_url(url) {
+        anyhow::bail!("Connectivity probe URL must be http://<host>[:port]/...");
     }
+
+    match tokio::time::timeout(PROBE_TIMEOUT, http_get_status(&uri)).await {
+        Ok(Ok(status)) => {
+            tracing::debug!(url = %url, status = %status, "Connectivity probe answered");
+            Ok(status.is_success())
+        }
+        Ok(Err(e)) => {
+            tracing::debug!(url = %url, error = %format!("{:#}", e), "Connectivity probe failed");
+            Ok(false)
+        }
+        Err(_) => {
+            tracing::debug!(url = %url, "Connectivity probe timed out");
+            Ok(false)
+        }
+    }
+}
+
+async fn http_get_status(uri: &Uri) -> Result<StatusCode> {
+    let host = uri.host().context("Probe URL has no host")?;
+    let port = uri.port_u16().unwrap_or(80);
+    let stream = tokio::net::TcpStream::connect((host, port))
+        .await
+        .context("Failed to connect to probe host")?;
+
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .context("HTTP handshake failed")?;
+    tokio::spawn(async move {
+        let _ = conn.await;
+    });
+
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let authority = uri.authority().map(|a| a.as_str()).unwrap_or(host);
+    let request = Request::get(path)
+        .header(header::HOST, authority)
+        .header(header::USER_AGENT, "hyper-connect")
+        .body(String::new())?;
+    let response = sender
+        .send_request(request)
+        .await
+        .context("Probe request failed")?;
+    Ok(response.status())
 }
 
 /// Scan for available WiFi networks
-pub async fn scan_networks(interface: &str) -> Result<Vec<NetworkInfo>> {
+pub async fn scan_networks(
+    interface: &str,
+    scan_timeout: std::time::Duration,
+) -> Result<Vec<NetworkInfo>> {
+    scan_networks_with(interface, scan_timeout, |_| {}).await
+}
+
+/// Scan for WiFi networks, reporting each network as its access point is read.
+///
+/// `on_found` sees an SSID the first time it appears and again whenever a
+/// stronger BSSID for it turns up; the returned list is the final de-duplicated,
+/// strongest-first result.
+pub async fn scan_networks_with(
+    interface: &str,
+    scan_timeout: std::time::Duration,
+    mut on_found: impl FnMut(&NetworkInfo),
+) -> Result<Vec<NetworkInfo>> {
     tracing::info!(interface = %interface, "Scanning for WiFi networks");
 
-    let connection = Connection::system().await?;
+    let connection = system_bus().await?;
     let device_path = get_wifi_device_path(&connection, interface).await?;
+    let client = ZbusNm::new(connection);
 
-    request_scan_and_wait(&connection, &device_path).await;
+    request_scan_and_wait(&client, &device_path, scan_timeout).await;
 
-    let ap_paths = get_access_points(&connection, &device_path).await?;
-    let mut by_ssid = HashMap::<String, NetworkInfo>::new();
+    let ap_paths = client.access_points(&device_path).await?;
+    let mut by_ssid = HashMap::<Vec<u8>, NetworkInfo>::new();
 
     for ap_path in ap_paths {
-        let Some(network) = read_access_point(&connection, &ap_path).await? else {
+        let Some(network) = network_from_access_point(client.access_point(&ap_path).await?) else {
             continue;
         };
 
-        by_ssid
-            .entry(network.ssid.clone())
-            .and_modify(|existing| {
-                if network.signal_strength > existing.signal_strength {
-                    *existing = network.clone();
-                }
-            })
-            .or_insert(network);
+        let key = network.ssid_bytes.clone();
+        if merge_access_point(&mut by_ssid, network) {
+            on_found(&by_ssid[&key]);
+        }
     }
 
     let mut networks: Vec<_> = by_ssid.into_values().collect();
-    networks.sort_by(|a, b| b.signal_strength.cmp(&a.signal_strength));
+    networks.sort_by_key(|n| std::cmp::Reverse(n.signal_strength));
 
     tracing::info!(count = networks.len(), "Found WiFi networks");
     Ok(networks)
 }
 
-/// Connect to a WiFi network
-pub async fn connect_to_network(interface: &str, ssid: &str, password: &str, save: bool) -> Result<()> {
-    tracing::info!(interface = %interface, ssid = %ssid, save = save, "Connecting to WiFi network");
-
-    // During AP mode we stop the WiFi backend and mark the device unmanaged to allow
-    // hostapd to take exclusive control. Ensure the backend is restarted before asking
-    // NetworkManager to activate a station connection.
-    let backend = current_wifi_backend().await.unwrap_or(WifiBackend::Iwd);
-    let service_name = match backend {
-        WifiBackend::Iwd => "iwd.service",
-        WifiBackend::WpaSupplicant => "wpa_supplicant.service",
-    };
-    let _ = tokio::process::Command::new("systemctl")
-        .args(["start", service_name])
-        .output()
-        .await;
+/// Fold one access point into its SSID's entry, keeping the strongest BSSID and
+/// counting the others. Returns whether the entry is new or got a stronger BSSID.
+fn merge_access_point(by_ssid: &mut HashMap<Vec<u8>, NetworkInfo>, network: NetworkInfo) -> bool {
+    match by_ssid.get_mut(&network.ssid_bytes) {
+        Some(existing) => {
+            let bss_count = existing.bss_count + network.bss_count;
+            let stronger = network.signal_strength > existing.signal_strength;
+            if stronger {
+                *existing = network;
+            }
+            existing.bss_count = bss_count;
+            stronger
+        }
+        None => {
+            by_ssid.insert(network.ssid_bytes.clone(), network);
+            true
+        }
+    }
+}
 
-    let connection = Connection::system().await?;
-    let device_path = get_wifi_device_path(&connection, interface).await?;
-    let device_proxy =
-        zbus::Proxy::new(&connection, NM_DEST, device_path.as_str(), NM_DEVICE_IFACE).await?;
+/// Connect to a WiFi network
+#[allow(clippy::too_many_arguments)]
+pub async fn connect_to_network(
+    interface: &str,
+    release: RadioRelease,
+    ssid: &[u8],
+    password: &str,
+    save: bool,
+    hidden: bool,
+    bssid: Option<[u8; 6]>,
+    eap_tls: Option<&EapTls>,
+    ip_family: IpFamily,
+    scan_timeout: std::time::Duration,
+) -> Result<()> {
+    connect_to_network_with(
+        interface,
+        release,
+        ssid,
+        password,
+        save,
+        hidden,
+        bssid,
+        eap_tls,
+        None,
+        ip_family,
+        scan_timeout,
+        |_| {},
+    )
+    .await
+}
 
-    let _ = device_proxy.set_property("Managed", &true).await;
-    let _ = device_proxy.set_property("Autoconnect", &true).await;
-    let _ = device_proxy.call::<_, _, ()>("Disconnect", &()).await;
+/// Connect to a WiFi network, reporting when each activation wait will give up.
+///
+/// `on_deadline` is called as every attempt starts waiting on NetworkManager;
+/// past that instant the attempt fails with a timeout error.
+#[allow(clippy::too_many_arguments)]
+pub async fn connect_to_network_with(
+    interface: &str,
+    release: RadioRelease,
+    ssid: &[u8],
+    password: &str,
+    save: bool,
+    hidden: bool,
+    bssid: Option<[u8; 6]>,
+    eap_tls: Option<&EapTls>,
+    security: Option<ManualSecurity>,
+    ip_family: IpFamily,
+    scan_timeout: std::time::Duration,
+    on_deadline: impl FnMut(std::time::Instant),
+) -> Result<()> {
+    let ssid_bytes = ssid;
+    let ssid = String::from_utf8_lossy(ssid_bytes);
+    tracing::info!(interface = %interface, ssid = %ssid, save = save, "Connecting to WiFi network");
 
-    // Clear AP addressing leftovers before returning interface to client mode.
-    let _ = tokio::process::Command::new("ip")
-        .args(["addr", "flush", "dev", interface])
-        .output()
-        .await;
-    let _ = tokio::process::Command::new("ip")
-        .args(["link", "set", interface, "up"])
-        .output()
-        .await;
+    let connection = system_bus().await?;
+    let device_path = prepare_station(&connection, interface, release).await?;
+
+    connect_on_device(
+        &ZbusNm::new(connection),
+        &device_path,
+        interface,
+        ssid_bytes,
+        password,
+        save,
+        hidden,
+        bssid,
+        eap_tls,
+        security,
+        ip_family,
+        scan_timeout,
+        on_deadline,
+    )
+    .await
+}
 
+/// The attempt/retry loop of [`connect_to_network_with`], once the device is
+/// back in station mode.
+#[allow(clippy::too_many_arguments)]
+async fn connect_on_device<C: NmClient>(
+    client: &C,
+    device_path: &OwnedObjectPath,
+    interface: &str,
+    ssid_bytes: &[u8],
+    password: &str,
+    save: bool,
+    hidden: bool,
+    bssid: Option<[u8; 6]>,
+    eap_tls: Option<&EapTls>,
+    security: Option<ManualSecurity>,
+    ip_family: IpFamily,
+    scan_timeout: std::time::Duration,
+    mut on_deadline: impl FnMut(std::time::Instant),
+) -> Result<()> {
+    let ssid = String::from_utf8_lossy(ssid_bytes);
     let max_attempts = 3;
-    let mut last_error = String::new();
+    let mut last_error = ConnectError::Other("No connection attempt was made".to_string());
 
     for attempt in 1..=max_attempts {
         tracing::info!(attempt, max_attempts, ssid = %ssid, "Activating WiFi connection via D-Bus");
-        request_scan_and_wait(&connection, &device_path).await;
+        request_scan_and_wait(client, device_path, scan_timeout).await;
 
-        let specific_ap = match find_best_ap_for_ssid(&connection, &device_path, ssid).await? {
-            Some(path) => path,
+        let best_ap =
+            find_best_ap_for_ssid(client, device_path, ssid_bytes, bssid.as_ref()).await?;
+        let (specific_ap, ssid_missing) = match best_ap {
+            Some(path) => (path, false),
             None => {
                 let any = OwnedObjectPath::try_from("/")
                     .context("Failed to create root object path for activation")?;
-                last_error = format!("SSID '{}' not found in current scan results", ssid);
                 tracing::warn!(attempt, max_attempts, ssid = %ssid, "SSID not in scan list, trying hidden profile activation");
-                any
+                (any, true)
             }
         };
 
-        let settings = build_connection_settings(ssid, password, specific_ap.as_str() == "/");
-        let nm_proxy = zbus::Proxy::new(&connection, NM_DEST, NM_PATH, NM_IFACE).await?;
-        let activate_result =
-            activate_connection(&nm_proxy, &settings, device_path.clone(), specific_ap, save).await;
+        let settings = build_connection_settings(
+            ssid_bytes,
+            password,
+            hidden || ssid_missing,
+            bssid.as_ref(),
+            eap_tls,
+            security,
+            ip_family,
+        );
+        let activate_result = client
+            .add_and_activate(&settings, device_path, &specific_ap, save)
+            .await;
 
         match activate_result {
             Ok(()) => {
-                wait_for_device_activation(
-                    &connection,
-                    &device_path,
-                    std::time::Duration::from_secs(35),
-                )
-                .await?;
+                let deadline = std::time::Instant::now() + ACTIVATION_TIMEOUT;
+                on_deadline(deadline);
+                wait_for_device_activation(client, device_path, deadline).await?;
                 tracing::info!("Successfully connected to WiFi network");
                 return Ok(());
             }
             Err(e) => {
-                last_error = e;
+                last_error = if ssid_missing {
+                    ConnectError::SsidNotFound
+                } else {
+                    ConnectError::Other(e.to_string())
+                };
                 tracing::warn!(
                     attempt,
                     max_attempts,
@@ -535,6 +1165,45 @@ pub async fn connect_to_network(interface: &str, ssid: &str, password: &str, sav
                     error = %last_error,
                     "WiFi connection attempt failed"
                 );
+
+                // Second chance within the same attempt; EAP-TLS and non-UTF-8
+                // SSIDs stay D-Bus only.
+                let utf8_ssid = std::str::from_utf8(ssid_bytes).ok();
+                if let (Some(utf8_ssid), None, false) = (utf8_ssid, eap_tls, ssid_missing) {
+                    if nmcli_fallback_applies(&e) {
+                        tracing::warn!(attempt, max_attempts, ssid = %ssid, "Falling back to nmcli after D-Bus activation failure");
+                        on_deadline(
+                            std::time::Instant::now()
+                                + std::time::Duration::from_secs(
+                                    NMCLI_ACTIVATION_TIMEOUT_SECS.into(),
+                                ),
+                        );
+                        match connect_via_nmcli(
+                            interface,
+                            utf8_ssid,
+                            password,
+                            security,
+                            save,
+                            hidden,
+                            bssid.as_ref(),
+                            ip_family,
+                        )
+                        .await
+                        {
+                            Ok(()) => {
+                                let deadline = std::time::Instant::now() + ACTIVATION_TIMEOUT;
+                                on_deadline(deadline);
+                                wait_for_device_activation(client, device_path, deadline).await?;
+                                tracing::info!("Successfully connected to WiFi network via nmcli");
+                                return Ok(());
+                            }
+                            Err(e) => {
+                                tracing::warn!(attempt, max_attempts, ssid = %ssid, error = %e, "nmcli fallback failed");
+                                last_error = e;
+                            }
+                        }
+                    }
+                }
             }
         }
 
@@ -543,23 +1212,204 @@ pub async fn connect_to_network(interface: &str, ssid: &str, password: &str, sav
         }
     }
 
-    anyhow::bail!(
-        "Connection failed after {} attempts: {}",
-        max_attempts,
-        last_error
-    );
+    Err(anyhow::Error::new(last_error)
+        .context(format!("Connection failed after {} attempts", max_attempts)))
 }
 
-/// Create a WiFi connection profile via D-Bus
-#[allow(dead_code)]
-pub async fn create_wifi_connection_dbus(ssid: &str, password: &str) -> Result<String> {
-    let connection = Connection::system().await?;
+/// Hand the radio back to NetworkManager in client mode and return its device path.
+async fn prepare_station(
+    connection: &Connection,
+    interface: &str,
+    release: RadioRelease,
+) -> Result<OwnedObjectPath> {
+    // During AP mode the WiFi backend lets go of the AP radio and the device is
+    // unmanaged so hostapd has exclusive control. Give it back to the backend
+    // before asking NetworkManager to activate a station connection.
+    if let Err(e) = backend::reclaim_for_station(&backend::active().await, release, interface).await
+    {
+        tracing::warn!(error = %e, "Failed to start WiFi backend");
+    }
 
-    let proxy = zbus::Proxy::new(
-        &connection,
-        "org.freedesktop.NetworkManager",
-        "/org/freedesktop/NetworkManager/Settings",
-        "org.freedesktop.NetworkManager.Settings",
+    let device_path = get_wifi_device_path(connection, interface).await?;
+    let device_proxy =
+        zbus::Proxy::new(connection, NM_DEST, device_path.as_str(), NM_DEVICE_IFACE).await?;
+
+    let _ = device_proxy.set_property("Managed", &true).await;
+    let _ = device_proxy.set_property("Autoconnect", &true).await;
+    let _ = device_proxy.call::<_, _, ()>("Disconnect", &()).await;
+
+    // Clear AP addressing leftovers before returning interface to client mode.
+    let _ = tokio::process::Command::new("ip")
+        .args(["addr", "flush", "dev", interface])
+        .output()
+        .await;
+    let _ = tokio::process::Command::new("ip")
+        .args(["link", "set", interface, "up"])
+        .output()
+        .await;
+
+    Ok(device_path)
+}
+
+/// A WiFi connection profile NetworkManager already has saved
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedProfile {
+    /// D-Bus object path of the profile
+    pub path: String,
+    /// Profile name (`connection.id`)
+    pub id: String,
+    pub ssid: Vec<u8>,
+    /// `connection.autoconnect-priority`
+    pub priority: i32,
+}
+
+type NmSettings = HashMap<String, HashMap<String, zvariant::OwnedValue>>;
+
+/// Station-mode WiFi profiles NetworkManager would auto-connect.
+pub async fn saved_wifi_profiles() -> Result<Vec<SavedProfile>> {
+    let connection = system_bus().await?;
+    let settings_proxy =
+        zbus::Proxy::new(&connection, NM_DEST, NM_SETTINGS_PATH, NM_SETTINGS_IFACE).await?;
+    let paths: Vec<OwnedObjectPath> = settings_proxy
+        .call("ListConnections", &())
+        .await
+        .context("Failed to list NetworkManager connections")?;
+
+    let mut profiles = Vec::new();
+    for path in paths {
+        let proxy =
+            zbus::Proxy::new(&connection, NM_DEST, path.as_str(), NM_CONNECTION_IFACE).await?;
+        let settings: NmSettings = match proxy.call("GetSettings", &()).await {
+            Ok(settings) => settings,
+            Err(e) => {
+                tracing::debug!(path = %path.as_str(), error = %e, "Skipping unreadable connection");
+                continue;
+            }
+        };
+        profiles.extend(wifi_profile_from_settings(path.as_str(), &settings));
+    }
+    Ok(profiles)
+}
+
+/// The saved profile described by `settings`, if it is an auto-connecting
+/// infrastructure WiFi profile.
+fn wifi_profile_from_settings(path: &str, settings: &NmSettings) -> Option<SavedProfile> {
+    let get = |group: &str, key: &str| settings.get(group)?.get(key).map(|v| &**v);
+
+    if !matches!(get("connection", "type"), Some(Value::Str(t)) if t.as_str() == "802-11-wireless")
+    {
+        return None;
+    }
+    if matches!(get("connection", "autoconnect"), Some(Value::Bool(false))) {
+        return None;
+    }
+    if matches!(get("802-11-wireless", "mode"), Some(Value::Str(m)) if m.as_str() != "infrastructure")
+    {
+        return None;
+    }
+    let ssid = match get("802-11-wireless", "ssid")? {
+        Value::Array(bytes) => bytes
+            .iter()
+            .map(|b| match b {
+                Value::U8(b) => Some(*b),
+                _ => None,
+            })
+            .collect::<Option<Vec<u8>>>()?,
+        _ => return None,
+    };
+    let id = match get("connection", "id") {
+        Some(Value::Str(id)) => id.to_string(),
+        _ => String::from_utf8_lossy(&ssid).into_owned(),
+    };
+    let priority = match get("connection", "autoconnect-priority") {
+        Some(Value::I32(priority)) => *priority,
+        _ => 0,
+    };
+    Some(SavedProfile {
+        path: path.to_string(),
+        id,
+        ssid,
+        priority,
+    })
+}
+
+/// Visible network with a saved profile to try first: highest profile
+/// priority, then strongest signal.
+pub fn best_profile_match<'a>(
+    profiles: &'a [SavedProfile],
+    available: &'a [NetworkInfo],
+) -> Option<(&'a NetworkInfo, &'a SavedProfile)> {
+    available
+        .iter()
+        .filter_map(|network| {
+            profiles
+                .iter()
+                .filter(|p| p.ssid == network.raw_ssid())
+                .max_by_key(|p| p.priority)
+                .map(|profile| (network, profile))
+        })
+        .max_by(|(a, pa), (b, pb)| {
+            pa.priority
+                .cmp(&pb.priority)
+                .then(a.signal_strength.cmp(&b.signal_strength))
+        })
+}
+
+/// Bring up a saved profile as NetworkManager has it; its secrets never pass through us.
+pub async fn activate_saved_profile(
+    interface: &str,
+    release: RadioRelease,
+    profile: &SavedProfile,
+) -> Result<()> {
+    tracing::info!(interface = %interface, profile = %profile.id, "Activating saved NetworkManager profile");
+    let connection = system_bus().await?;
+    let device_path = prepare_station(&connection, interface, release).await?;
+
+    let nm_proxy = zbus::Proxy::new(&connection, NM_DEST, NM_PATH, NM_IFACE).await?;
+    let profile_path =
+        OwnedObjectPath::try_from(profile.path.as_str()).context("Invalid profile object path")?;
+    let any = OwnedObjectPath::try_from("/")
+        .context("Failed to create root object path for activation")?;
+    let _active: OwnedObjectPath = nm_proxy
+        .call("ActivateConnection", &(&profile_path, &device_path, &any))
+        .await
+        .context("Failed to activate saved profile")?;
+
+    wait_for_device_activation(
+        &ZbusNm::new(connection),
+        &device_path,
+        std::time::Instant::now() + ACTIVATION_TIMEOUT,
+    )
+    .await
+}
+
+/// Abort whatever NetworkManager is activating or holding on `interface`.
+///
+/// Dropping a connect future only stops us waiting; NetworkManager keeps
+/// associating until the device is told to disconnect.
+pub async fn disconnect_station(interface: &str) -> Result<()> {
+    let connection = system_bus().await?;
+    let device_path = get_wifi_device_path(&connection, interface).await?;
+    let device_proxy =
+        zbus::Proxy::new(&connection, NM_DEST, device_path.as_str(), NM_DEVICE_IFACE).await?;
+    device_proxy
+        .call::<_, _, ()>("Disconnect", &())
+        .await
+        .with_context(|| format!("Failed to disconnect {}", interface))?;
+    tracing::info!(interface = %interface, "Disconnected station device");
+    Ok(())
+}
+
+/// Create a WiFi connection profile via D-Bus
+#[allow(dead_code)]
+pub async fn create_wifi_connection_dbus(ssid: &str, password: &str) -> Result<String> {
+    let connection = system_bus().await?;
+
+    let proxy = zbus::Proxy::new(
+        &connection,
+        "org.freedesktop.NetworkManager",
+        "/org/freedesktop/NetworkManager/Settings",
+        "org.freedesktop.NetworkManager.Settings",
     )
     .await?;
 
@@ -597,43 +1447,91 @@ pub async fn create_wifi_connection_dbus(ssid: &str, password: &str) -> Result<S
     Ok(path.to_string())
 }
 
-async fn wait_for_device_activation(
-    connection: &Connection,
+async fn wait_for_device_activation<C: NmClient>(
+    client: &C,
     device_path: &OwnedObjectPath,
-    timeout: std::time::Duration,
+    deadline: std::time::Instant,
 ) -> Result<()> {
-    let deadline = std::time::Instant::now() + timeout;
-    let device_proxy =
-        zbus::Proxy::new(connection, NM_DEST, device_path.as_str(), NM_DEVICE_IFACE).await?;
+    let mut need_auth = NeedAuthTracker::default();
 
     loop {
-        let state: u32 = device_proxy.get_property("State").await?;
+        let state = client.device_state(device_path).await?;
         if state == NM_DEVICE_STATE_ACTIVATED {
             return Ok(());
         }
+        if need_auth.observe(state, std::time::Instant::now()) {
+            tracing::warn!("Device is stuck asking for secrets, treating the passphrase as wrong");
+            return Err(ConnectError::WrongPassword.into());
+        }
         if state == NM_DEVICE_STATE_FAILED {
-            let reason: (u32, u32) = device_proxy
-                .get_property("StateReason")
+            let reason = client
+                .device_state_reason(device_path)
                 .await
                 .unwrap_or((state, 0));
-            anyhow::bail!(
-                "Device activation failed: state={} reason={}",
-                reason.0,
-                reason.1
+            tracing::warn!(
+                state = reason.0,
+                reason = reason.1,
+                "Device activation failed"
             );
+            return Err(connect_error_from_reason(reason.0, reason.1).into());
         }
 
         if std::time::Instant::now() >= deadline {
-            anyhow::bail!(
-                "Connection timed out waiting for device activation (state={})",
-                state
-            );
+            tracing::warn!(state, "Connection timed out waiting for device activation");
+            let error = if state >= NM_DEVICE_STATE_IP_CONFIG {
+                ConnectError::DhcpFailed
+            } else {
+                ConnectError::AuthTimeout
+            };
+            return Err(error.into());
         }
 
         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
     }
 }
 
+/// Tells a rejected passphrase apart from the brief NEED_AUTH seen during
+/// normal key negotiation: only one that lasts or comes back counts.
+#[derive(Debug, Default)]
+struct NeedAuthTracker {
+    since: Option<std::time::Instant>,
+    entered: u32,
+}
+
+impl NeedAuthTracker {
+    /// Record a polled device state; true once NEED_AUTH looks terminal.
+    fn observe(&mut self, state: u32, now: std::time::Instant) -> bool {
+        if state != NM_DEVICE_STATE_NEED_AUTH {
+            self.since = None;
+            return false;
+        }
+        let since = *self.since.get_or_insert_with(|| {
+            self.entered += 1;
+            now
+        });
+        self.entered > 1 || now.duration_since(since) >= NEED_AUTH_GRACE
+    }
+}
+
+/// Map a NetworkManager `StateReason` pair to a connect error classification.
+fn connect_error_from_reason(state: u32, reason: u32) -> ConnectError {
+    match reason {
+        NM_DEVICE_STATE_REASON_NO_SECRETS | NM_DEVICE_STATE_REASON_SUPPLICANT_DISCONNECT => {
+            ConnectError::WrongPassword
+        }
+        NM_DEVICE_STATE_REASON_SUPPLICANT_TIMEOUT => ConnectError::AuthTimeout,
+        NM_DEVICE_STATE_REASON_IP_CONFIG_UNAVAILABLE
+        | NM_DEVICE_STATE_REASON_DHCP_START_FAILED
+        | NM_DEVICE_STATE_REASON_DHCP_ERROR
+        | NM_DEVICE_STATE_REASON_DHCP_FAILED => ConnectError::DhcpFailed,
+        NM_DEVICE_STATE_REASON_SSID_NOT_FOUND => ConnectError::SsidNotFound,
+        _ => ConnectError::Other(format!(
+            "Device activation failed: state={} reason={}",
+            state, reason
+        )),
+    }
+}
+
 async fn get_wifi_device_path(connection: &Connection, interface: &str) -> Result<OwnedObjectPath> {
     let nm_proxy = zbus::Proxy::new(connection, NM_DEST, NM_PATH, NM_IFACE).await?;
     let device_path: OwnedObjectPath = nm_proxy
@@ -660,107 +1558,97 @@ async fn get_wifi_device_path(connection: &Connection, interface: &str) -> Resul
     Ok(device_path)
 }
 
-async fn request_scan_and_wait(connection: &Connection, device_path: &OwnedObjectPath) {
-    let Ok(wifi_proxy) = zbus::Proxy::new(
-        connection,
-        NM_DEST,
-        device_path.as_str(),
-        NM_WIFI_DEVICE_IFACE,
-    )
-    .await
-    else {
-        return;
-    };
-
-    let last_scan_before: i64 = wifi_proxy.get_property("LastScan").await.unwrap_or(-1);
+/// Request a scan and wait up to `timeout` for `LastScan` to advance, asking
+/// once more if the radio seems to have dropped the first request.
+async fn request_scan_and_wait<C: NmClient>(
+    client: &C,
+    device_path: &OwnedObjectPath,
+    timeout: std::time::Duration,
+) {
+    let last_scan_before = client.last_scan(device_path).await.unwrap_or(-1);
 
-    let options = HashMap::<&str, Value>::new();
-    let _ = wifi_proxy
-        .call::<_, _, ()>("RequestScan", &(options,))
-        .await;
+    let request_scan = || async {
+        let _ = client.request_scan(device_path).await;
+    };
+    request_scan().await;
 
-    let scan_deadline = std::time::Instant::now() + std::time::Duration::from_secs(6);
-    while std::time::Instant::now() < scan_deadline {
-        let last_scan_now: i64 = wifi_proxy.get_property("LastScan").await.unwrap_or(-1);
+    let started = std::time::Instant::now();
+    let mut reissued = false;
+    while started.elapsed() < timeout {
+        let last_scan_now = client.last_scan(device_path).await.unwrap_or(-1);
         if last_scan_now > last_scan_before {
             break;
         }
+        if should_reissue_scan(started.elapsed(), timeout, reissued) {
+            tracing::debug!(device = %device_path.as_str(), "LastScan stalled, re-issuing RequestScan");
+            request_scan().await;
+            reissued = true;
+        }
         tokio::time::sleep(std::time::Duration::from_millis(400)).await;
     }
 }
 
-async fn get_access_points(
-    connection: &Connection,
-    device_path: &OwnedObjectPath,
-) -> Result<Vec<OwnedObjectPath>> {
-    let wifi_proxy = zbus::Proxy::new(
-        connection,
-        NM_DEST,
-        device_path.as_str(),
-        NM_WIFI_DEVICE_IFACE,
-    )
-    .await?;
-
-    let ap_paths: Vec<OwnedObjectPath> = wifi_proxy.call("GetAllAccessPoints", &()).await?;
-    Ok(ap_paths)
+/// Whether a scan whose `LastScan` hasn't moved should be requested again:
+/// once, from halfway through the wait.
+fn should_reissue_scan(
+    elapsed: std::time::Duration,
+    timeout: std::time::Duration,
+    reissued: bool,
+) -> bool {
+    !reissued && elapsed >= timeout / 2
 }
 
-async fn read_access_point(
-    connection: &Connection,
-    ap_path: &OwnedObjectPath,
-) -> Result<Option<NetworkInfo>> {
-    let ap_proxy = zbus::Proxy::new(connection, NM_DEST, ap_path.as_str(), NM_AP_IFACE).await?;
-
-    let ssid_raw: Vec<u8> = ap_proxy.get_property("Ssid").await?;
-    if ssid_raw.is_empty() {
-        return Ok(None);
-    }
-
-    let ssid = String::from_utf8_lossy(&ssid_raw).to_string();
-    if ssid.is_empty() {
-        return Ok(None);
+/// The scan entry for an access point, or `None` for a hidden one.
+fn network_from_access_point(ap: AccessPoint) -> Option<NetworkInfo> {
+    if ap.ssid.is_empty() {
+        return None;
     }
 
-    let bssid: String = ap_proxy.get_property("HwAddress").await.unwrap_or_default();
-    let signal_strength: u8 = ap_proxy.get_property("Strength").await.unwrap_or(0);
-    let frequency: u32 = ap_proxy.get_property("Frequency").await.unwrap_or(0);
-    let flags: u32 = ap_proxy.get_property("Flags").await.unwrap_or(0);
-    let wpa_flags: u32 = ap_proxy.get_property("WpaFlags").await.unwrap_or(0);
-    let rsn_flags: u32 = ap_proxy.get_property("RsnFlags").await.unwrap_or(0);
-    let is_secured = (flags & NM_80211_AP_FLAGS_PRIVACY) != 0 || wpa_flags != 0 || rsn_flags != 0;
-
-    Ok(Some(NetworkInfo {
-        ssid,
-        bssid,
-        signal_strength,
-        frequency,
-        channel: frequency_to_channel(frequency),
+    let security_type = classify_security(ap.flags, ap.wpa_flags, ap.rsn_flags);
+    // OWE encrypts the link without a password, so it isn't prompted for one.
+    let is_secured = security_type != ENHANCED_OPEN_SECURITY
+        && ((ap.flags & NM_80211_AP_FLAGS_PRIVACY) != 0 || ap.wpa_flags != 0 || ap.rsn_flags != 0);
+
+    Some(NetworkInfo {
+        ssid: String::from_utf8_lossy(&ap.ssid).to_string(),
+        ssid_bytes: ap.ssid,
+        bssid: ap.hw_address,
+        signal_strength: ap.strength,
+        frequency: ap.frequency,
+        channel: frequency_to_channel(ap.frequency),
         is_secured,
-        security_type: classify_security(flags, wpa_flags, rsn_flags),
-    }))
+        security_type,
+        bss_count: 1,
+    })
 }
 
-async fn find_best_ap_for_ssid(
-    connection: &Connection,
+/// Strongest AP broadcasting `ssid`, or the one with the pinned `bssid` if given.
+async fn find_best_ap_for_ssid<C: NmClient>(
+    client: &C,
     device_path: &OwnedObjectPath,
-    ssid: &str,
+    ssid: &[u8],
+    bssid: Option<&[u8; 6]>,
 ) -> Result<Option<OwnedObjectPath>> {
-    let ap_paths = get_access_points(connection, device_path).await?;
+    let ap_paths = client.access_points(device_path).await?;
     let mut best: Option<(OwnedObjectPath, u8)> = None;
 
     for ap_path in ap_paths {
-        let ap_proxy = zbus::Proxy::new(connection, NM_DEST, ap_path.as_str(), NM_AP_IFACE).await?;
-        let ssid_raw: Vec<u8> = ap_proxy.get_property("Ssid").await.unwrap_or_default();
-        if ssid_raw.is_empty() {
+        let Ok(ap) = client.access_point(&ap_path).await else {
+            continue;
+        };
+        // Compare raw bytes: the lossy display string can't round-trip non-UTF-8 SSIDs.
+        if ap.ssid.is_empty() || ap.ssid != ssid {
             continue;
         }
 
-        let candidate_ssid = String::from_utf8_lossy(&ssid_raw).to_string();
-        if candidate_ssid != ssid {
+        if let Some(bssid) = bssid {
+            if super::parse_bssid(&ap.hw_address).as_ref() == Some(bssid) {
+                return Ok(Some(ap_path));
+            }
             continue;
         }
 
-        let strength: u8 = ap_proxy.get_property("Strength").await.unwrap_or(0);
+        let strength = ap.strength;
         match &best {
             Some((_, best_strength)) if *best_strength >= strength => {}
             _ => best = Some((ap_path, strength)),
@@ -771,28 +1659,51 @@ async fn find_best_ap_for_ssid(
 }
 
 fn build_connection_settings<'a>(
-    ssid: &'a str,
+    ssid: &'a [u8],
     password: &'a str,
     hidden: bool,
-) -> HashMap<&'static str, HashMap<&'static str, Value<'a>>> {
+    bssid: Option<&[u8; 6]>,
+    eap_tls: Option<&EapTls>,
+    security: Option<ManualSecurity>,
+    ip_family: IpFamily,
+) -> ConnectionSettings<'a> {
     let mut conn_settings = HashMap::new();
     conn_settings.insert("type", Value::from("802-11-wireless"));
-    conn_settings.insert("id", Value::from(ssid));
+    conn_settings.insert(
+        "id",
+        Value::from(String::from_utf8_lossy(ssid).into_owned()),
+    );
     conn_settings.insert("uuid", Value::from(uuid::Uuid::new_v4().to_string()));
     conn_settings.insert("autoconnect", Value::from(false));
 
     let mut wifi_settings = HashMap::new();
-    wifi_settings.insert("ssid", Value::from(ssid.as_bytes().to_vec()));
+    wifi_settings.insert("ssid", Value::from(ssid.to_vec()));
     wifi_settings.insert("mode", Value::from("infrastructure"));
     if hidden {
         wifi_settings.insert("hidden", Value::from(true));
     }
+    if let Some(bssid) = bssid {
+        wifi_settings.insert("bssid", Value::from(bssid.to_vec()));
+    }
 
+    // A family that is configured but absent (DHCPv4 on an IPv6-only network)
+    // holds up activation unless it may fail; a lone family is required.
+    let (ipv4_method, ipv6_method, may_fail) = match ip_family {
+        IpFamily::Dual => ("auto", "auto", true),
+        IpFamily::V4 => ("auto", "ignore", false),
+        IpFamily::V6 => ("disabled", "auto", false),
+    };
     let mut ipv4_settings = HashMap::new();
-    ipv4_settings.insert("method", Value::from("auto"));
+    ipv4_settings.insert("method", Value::from(ipv4_method));
+    if ipv4_method == "auto" {
+        ipv4_settings.insert("may-fail", Value::from(may_fail));
+    }
 
     let mut ipv6_settings = HashMap::new();
-    ipv6_settings.insert("method", Value::from("auto"));
+    ipv6_settings.insert("method", Value::from(ipv6_method));
+    if ipv6_method == "auto" {
+        ipv6_settings.insert("may-fail", Value::from(may_fail));
+    }
 
     let mut settings = HashMap::new();
     settings.insert("connection", conn_settings);
@@ -800,66 +1711,249 @@ fn build_connection_settings<'a>(
     settings.insert("ipv4", ipv4_settings);
     settings.insert("ipv6", ipv6_settings);
 
-    if !password.is_empty() {
+    if let Some(eap) = eap_tls {
         let mut security_settings = HashMap::new();
-        security_settings.insert("key-mgmt", Value::from("wpa-psk"));
-        security_settings.insert("psk", Value::from(password));
+        security_settings.insert("key-mgmt", Value::from("wpa-eap"));
+        settings.insert("802-11-wireless-security", security_settings);
+
+        let mut eap_settings = HashMap::new();
+        eap_settings.insert("eap", Value::from(vec!["tls".to_string()]));
+        if let Some(identity) = &eap.identity {
+            eap_settings.insert("identity", Value::from(identity.clone()));
+        }
+        if let Some(ca_cert) = &eap.ca_cert {
+            eap_settings.insert("ca-cert", Value::from(nm_cert_path(ca_cert)));
+        }
+        eap_settings.insert("client-cert", Value::from(nm_cert_path(&eap.client_cert)));
+        eap_settings.insert("private-key", Value::from(nm_cert_path(&eap.private_key)));
+        if let Some(key_password) = &eap.private_key_password {
+            eap_settings.insert("private-key-password", Value::from(key_password.clone()));
+        }
+        settings.insert("802-1x", eap_settings);
+    } else if let Some(key_mgmt) = psk_key_mgmt(password, security) {
+        let mut security_settings = HashMap::new();
+        security_settings.insert("key-mgmt", Value::from(key_mgmt));
+        if !password.is_empty() {
+            security_settings.insert("psk", Value::from(password));
+        }
         settings.insert("802-11-wireless-security", security_settings);
     }
 
     settings
 }
 
-async fn activate_connection<'a>(
-    nm_proxy: &zbus::Proxy<'_>,
-    settings: &HashMap<&'static str, HashMap<&'static str, Value<'a>>>,
-    device_path: OwnedObjectPath,
-    specific_ap: OwnedObjectPath,
+/// `key-mgmt` for a password-based profile: the security picked for a manual
+/// entry wins, otherwise any password means WPA-PSK and none means open.
+fn psk_key_mgmt(password: &str, security: Option<ManualSecurity>) -> Option<&'static str> {
+    match security {
+        Some(security) => security.key_mgmt(),
+        None => (!password.is_empty()).then_some("wpa-psk"),
+    }
+}
+
+/// Encode a certificate path the way NM's 802-1x settings expect:
+/// a NUL-terminated `file://` URI as a byte array.
+fn nm_cert_path(path: &Path) -> Vec<u8> {
+    let mut value = b"file://".to_vec();
+    value.extend_from_slice(path.as_os_str().as_encoded_bytes());
+    value.push(0);
+    value
+}
+
+fn nmcli_fallback_applies(error: &zbus::Error) -> bool {
+    match error {
+        zbus::Error::MethodError(name, _, _) => NMCLI_FALLBACK_ERRORS.contains(&name.as_str()),
+        _ => false,
+    }
+}
+
+/// Activate the network through nmcli instead of `AddAndActivateConnection2`.
+///
+/// The profile is added first (in memory unless `save`), then brought up with
+/// the PSK fed through `passwd-file` on stdin so it never appears in argv.
+/// A profile that fails to come up is deleted again.
+#[allow(clippy::too_many_arguments)]
+async fn connect_via_nmcli(
+    interface: &str,
+    ssid: &str,
+    password: &str,
+    security: Option<ManualSecurity>,
     save: bool,
-) -> std::result::Result<(), String> {
-    let mut options: HashMap<&str, Value> = HashMap::new();
-    // Use NetworkManager's built-in credential storage when save=true
-    let persist_mode = if save { "disk" } else { "volatile" };
-    options.insert("persist", Value::from(persist_mode));
-
-    let v2_result: std::result::Result<
-        (
-            OwnedObjectPath,
-            OwnedObjectPath,
-            HashMap<String, zvariant::OwnedValue>,
-        ),
-        zbus::Error,
-    > = nm_proxy
-        .call(
-            "AddAndActivateConnection2",
-            &(settings, &device_path, &specific_ap, options),
-        )
+    hidden: bool,
+    bssid: Option<&[u8; 6]>,
+    ip_family: IpFamily,
+) -> std::result::Result<(), ConnectError> {
+    use tokio::io::AsyncWriteExt;
+
+    let uuid = uuid::Uuid::new_v4().to_string();
+    let add_args = nmcli_add_args(
+        &uuid,
+        interface,
+        ssid,
+        psk_key_mgmt(password, security),
+        save,
+        hidden,
+        bssid,
+        ip_family,
+    );
+    let add = tokio::process::Command::new("nmcli")
+        .args(&add_args)
+        .output()
+        .await
+        .map_err(|e| ConnectError::Other(format!("Failed to run nmcli: {}", e)))?;
+    if !add.status.success() {
+        return Err(ConnectError::Other(format!(
+            "nmcli connection add failed: {}",
+            String::from_utf8_lossy(&add.stderr).trim()
+        )));
+    }
+
+    let timeout = NMCLI_ACTIVATION_TIMEOUT_SECS.to_string();
+    let mut up_args = vec![
+        "--wait",
+        timeout.as_str(),
+        "connection",
+        "up",
+        "uuid",
+        uuid.as_str(),
+        "ifname",
+        interface,
+    ];
+    if !password.is_empty() {
+        up_args.extend(["passwd-file", "/dev/stdin"]);
+    }
+    let up = async {
+        let mut child = tokio::process::Command::new("nmcli")
+            .args(&up_args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            if !password.is_empty() {
+                let line = format!("802-11-wireless-security.psk:{}\n", password);
+                stdin.write_all(line.as_bytes()).await?;
+            }
+        }
+        child.wait_with_output().await
+    }
+    .await;
+
+    let error = match up {
+        Ok(output) if output.status.success() => return Ok(()),
+        Ok(output) => connect_error_from_nmcli(&String::from_utf8_lossy(&output.stderr)),
+        Err(e) => ConnectError::Other(format!("Failed to run nmcli: {}", e)),
+    };
+    let _ = tokio::process::Command::new("nmcli")
+        .args(["connection", "delete", "uuid", uuid.as_str()])
+        .output()
         .await;
+    Err(error)
+}
 
-    match v2_result {
-        Ok(_) => Ok(()),
-        Err(zbus::Error::MethodError(name, _, _))
-            if name.as_str() == "org.freedesktop.DBus.Error.UnknownMethod" =>
-        {
-            let legacy: std::result::Result<(OwnedObjectPath, OwnedObjectPath), zbus::Error> =
-                nm_proxy
-                    .call(
-                        "AddAndActivateConnection",
-                        &(settings, &device_path, &specific_ap),
-                    )
-                    .await;
-            legacy.map(|_| ()).map_err(|e| e.to_string())
+/// Arguments for the `nmcli connection add` that mirrors [`build_connection_settings`].
+#[allow(clippy::too_many_arguments)]
+fn nmcli_add_args(
+    uuid: &str,
+    interface: &str,
+    ssid: &str,
+    key_mgmt: Option<&str>,
+    save: bool,
+    hidden: bool,
+    bssid: Option<&[u8; 6]>,
+    ip_family: IpFamily,
+) -> Vec<String> {
+    let mut args: Vec<String> = [
+        "connection",
+        "add",
+        "type",
+        "wifi",
+        "ifname",
+        interface,
+        "con-name",
+        ssid,
+        "ssid",
+        ssid,
+        "connection.uuid",
+        uuid,
+        "connection.autoconnect",
+        "no",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+
+    let mut set = |key: &str, value: &str| {
+        args.push(key.to_string());
+        args.push(value.to_string());
+    };
+    if hidden {
+        set("802-11-wireless.hidden", "yes");
+    }
+    if let Some(bssid) = bssid {
+        set("802-11-wireless.bssid", &format_bssid(bssid));
+    }
+    if let Some(key_mgmt) = key_mgmt {
+        set("802-11-wireless-security.key-mgmt", key_mgmt);
+    }
+    match ip_family {
+        IpFamily::Dual => {
+            set("ipv4.may-fail", "yes");
+            set("ipv6.may-fail", "yes");
+        }
+        IpFamily::V4 => {
+            set("ipv4.may-fail", "no");
+            set("ipv6.method", "ignore");
+        }
+        IpFamily::V6 => {
+            set("ipv4.method", "disabled");
+            set("ipv6.may-fail", "no");
         }
-        Err(e) => Err(e.to_string()),
+    }
+    set("save", if save { "yes" } else { "no" });
+    args
+}
+
+/// Classify a failed `nmcli connection up` from its error output.
+fn connect_error_from_nmcli(stderr: &str) -> ConnectError {
+    let lower = stderr.to_ascii_lowercase();
+    if lower.contains("secrets were required") || lower.contains("no secrets") {
+        ConnectError::WrongPassword
+    } else if lower.contains("no network with ssid") || lower.contains("ssid not found") {
+        ConnectError::SsidNotFound
+    } else if lower.contains("ip configuration could not be reserved") {
+        ConnectError::DhcpFailed
+    } else if lower.contains("timeout") {
+        ConnectError::AuthTimeout
+    } else {
+        ConnectError::Other(format!("nmcli activation failed: {}", stderr.trim()))
     }
 }
 
 fn classify_security(flags: u32, wpa_flags: u32, rsn_flags: u32) -> String {
+    // 802.1X with no passphrase mode on offer wants EAP credentials, not a password.
+    let key_mgmt = wpa_flags | rsn_flags;
+    let passphrase = NM_80211_AP_SEC_KEY_MGMT_PSK | NM_80211_AP_SEC_KEY_MGMT_SAE;
+    if key_mgmt & NM_80211_AP_SEC_KEY_MGMT_802_1X != 0 && key_mgmt & passphrase == 0 {
+        return "Enterprise".to_string();
+    }
+    // OWE, or the open half of an OWE transition pair; either way no password.
+    let owe = NM_80211_AP_SEC_KEY_MGMT_OWE | NM_80211_AP_SEC_KEY_MGMT_OWE_TM;
+    if rsn_flags & owe != 0 && key_mgmt & passphrase == 0 {
+        return ENHANCED_OPEN_SECURITY.to_string();
+    }
     if rsn_flags != 0 && wpa_flags != 0 {
         return "WPA/WPA2".to_string();
     }
     if rsn_flags != 0 {
-        return "WPA2/WPA3".to_string();
+        let psk = rsn_flags & NM_80211_AP_SEC_KEY_MGMT_PSK != 0;
+        let sae = rsn_flags & NM_80211_AP_SEC_KEY_MGMT_SAE != 0;
+        return match (psk, sae) {
+            (false, true) => WPA3_ONLY_SECURITY,
+            (true, false) => "WPA2",
+            _ => "WPA2/WPA3",
+        }
+        .to_string();
     }
     if wpa_flags != 0 {
         return "WPA".to_string();
@@ -875,6 +1969,934 @@ fn frequency_to_channel(freq: u32) -> u8 {
         2412..=2472 => ((freq - 2407) / 5) as u8,
         2484 => 14,
         5000..=5900 => ((freq - 5000) / 5) as u8,
+        5955..=7115 => ((freq - 5950) / 5) as u8,
         _ => 0,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{parse_bssid, WifiState};
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// In-memory NetworkManager: a fixed scan list and device states handed
+    /// out one per poll, the last repeating.
+    #[derive(Default)]
+    struct MockNm {
+        access_points: Vec<AccessPoint>,
+        states: Mutex<VecDeque<u32>>,
+        reason: (u32, u32),
+        last_scan: Mutex<i64>,
+        activations: Mutex<Vec<String>>,
+    }
+
+    impl MockNm {
+        fn with_states(access_points: Vec<AccessPoint>, states: &[u32]) -> Self {
+            Self {
+                access_points,
+                states: Mutex::new(states.iter().copied().collect()),
+                ..Default::default()
+            }
+        }
+
+        fn activations(&self) -> Vec<String> {
+            self.activations.lock().unwrap().clone()
+        }
+    }
+
+    fn ap_path(index: usize) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(format!(
+            "/org/freedesktop/NetworkManager/AccessPoint/{}",
+            index
+        ))
+        .unwrap()
+    }
+
+    fn device_path() -> OwnedObjectPath {
+        OwnedObjectPath::try_from("/org/freedesktop/NetworkManager/Devices/3").unwrap()
+    }
+
+    fn ap(ssid: &str, hw_address: &str, strength: u8) -> AccessPoint {
+        AccessPoint {
+            ssid: ssid.as_bytes().to_vec(),
+            hw_address: hw_address.to_string(),
+            strength,
+            frequency: 2437,
+            rsn_flags: NM_80211_AP_SEC_KEY_MGMT_PSK,
+            ..Default::default()
+        }
+    }
+
+    impl NmClient for MockNm {
+        async fn request_scan(&self, _device: &OwnedObjectPath) -> Result<()> {
+            *self.last_scan.lock().unwrap() += 1;
+            Ok(())
+        }
+
+        async fn last_scan(&self, _device: &OwnedObjectPath) -> Result<i64> {
+            Ok(*self.last_scan.lock().unwrap())
+        }
+
+        async fn access_points(&self, _device: &OwnedObjectPath) -> Result<Vec<OwnedObjectPath>> {
+            Ok((0..self.access_points.len()).map(ap_path).collect())
+        }
+
+        async fn access_point(&self, ap: &OwnedObjectPath) -> Result<AccessPoint> {
+            let index = (0..self.access_points.len())
+                .find(|i| ap_path(*i) == *ap)
+                .context("no such access point")?;
+            Ok(self.access_points[index].clone())
+        }
+
+        async fn device_state(&self, _device: &OwnedObjectPath) -> Result<u32> {
+            let mut states = self.states.lock().unwrap();
+            let state = *states.front().context("no device state scripted")?;
+            if states.len() > 1 {
+                states.pop_front();
+            }
+            Ok(state)
+        }
+
+        async fn device_state_reason(&self, _device: &OwnedObjectPath) -> Result<(u32, u32)> {
+            Ok(self.reason)
+        }
+
+        async fn add_and_activate(
+            &self,
+            _settings: &ConnectionSettings<'_>,
+            _device: &OwnedObjectPath,
+            specific_ap: &OwnedObjectPath,
+            _save: bool,
+        ) -> std::result::Result<(), zbus::Error> {
+            self.activations
+                .lock()
+                .unwrap()
+                .push(specific_ap.as_str().to_string());
+            Ok(())
+        }
+    }
+
+    async fn connect_with_mock(nm: &MockNm, password: &str) -> Result<()> {
+        connect_on_device(
+            nm,
+            &device_path(),
+            "wlan0",
+            b"Home",
+            password,
+            false,
+            false,
+            None,
+            None,
+            None,
+            IpFamily::Dual,
+            std::time::Duration::from_secs(1),
+            |_| {},
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_find_best_ap_prefers_strongest_or_pinned_bssid() {
+        let nm = MockNm::with_states(
+            vec![
+                ap("Home", "AA:BB:CC:00:00:01", 40),
+                ap("Other", "AA:BB:CC:00:00:02", 90),
+                ap("Home", "AA:BB:CC:00:00:03", 75),
+                ap("", "AA:BB:CC:00:00:04", 99),
+            ],
+            &[],
+        );
+
+        let best = find_best_ap_for_ssid(&nm, &device_path(), b"Home", None).await;
+        assert_eq!(best.unwrap(), Some(ap_path(2)));
+
+        let pinned = parse_bssid("AA:BB:CC:00:00:01").unwrap();
+        let best = find_best_ap_for_ssid(&nm, &device_path(), b"Home", Some(&pinned)).await;
+        assert_eq!(best.unwrap(), Some(ap_path(0)));
+
+        let best = find_best_ap_for_ssid(&nm, &device_path(), b"Missing", None).await;
+        assert_eq!(best.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_activation_wait_follows_device_state() {
+        // Activated after two polls in IP configuration.
+        let nm = MockNm::with_states(
+            vec![],
+            &[
+                NM_DEVICE_STATE_IP_CONFIG,
+                NM_DEVICE_STATE_IP_CONFIG,
+                NM_DEVICE_STATE_ACTIVATED,
+            ],
+        );
+        let deadline = std::time::Instant::now() + ACTIVATION_TIMEOUT;
+        assert!(wait_for_device_activation(&nm, &device_path(), deadline)
+            .await
+            .is_ok());
+
+        let nm = MockNm {
+            reason: (NM_DEVICE_STATE_FAILED, NM_DEVICE_STATE_REASON_NO_SECRETS),
+            ..MockNm::with_states(vec![], &[NM_DEVICE_STATE_FAILED])
+        };
+        let error = wait_for_device_activation(&nm, &device_path(), deadline)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ConnectError>(),
+            Some(&ConnectError::WrongPassword)
+        );
+
+        // Stuck in IP configuration past the deadline reads as a DHCP failure.
+        let nm = MockNm::with_states(vec![], &[NM_DEVICE_STATE_IP_CONFIG]);
+        let error = wait_for_device_activation(&nm, &device_path(), std::time::Instant::now())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ConnectError>(),
+            Some(&ConnectError::DhcpFailed)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_activates_strongest_ap() {
+        let nm = MockNm::with_states(
+            vec![
+                ap("Home", "AA:BB:CC:00:00:01", 40),
+                ap("Home", "AA:BB:CC:00:00:02", 80),
+            ],
+            &[NM_DEVICE_STATE_ACTIVATED],
+        );
+
+        connect_with_mock(&nm, "secret123").await.unwrap();
+        assert_eq!(nm.activations(), [ap_path(1).as_str()]);
+        assert_eq!(*nm.last_scan.lock().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_connect_reports_wrong_password() {
+        let nm = MockNm {
+            reason: (NM_DEVICE_STATE_FAILED, NM_DEVICE_STATE_REASON_NO_SECRETS),
+            ..MockNm::with_states(
+                vec![ap("Home", "AA:BB:CC:00:00:01", 60)],
+                &[NM_DEVICE_STATE_FAILED],
+            )
+        };
+
+        let error = connect_with_mock(&nm, "wrong-pass").await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ConnectError>(),
+            Some(&ConnectError::WrongPassword)
+        );
+        // A rejected passphrase isn't retried.
+        assert_eq!(nm.activations().len(), 1);
+    }
+
+    #[test]
+    fn test_backend_switch_errors_by_exit() {
+        let error = |step, code, past_deadline, stderr| {
+            switch_step_error(WifiBackend::Iwd, step, code, past_deadline, stderr).to_string()
+        };
+        assert_eq!(
+            error(BackendSwitchStep::StoppingOld, Some(2), false, ""),
+            "iwd.service is not available on this image, so NetworkManager can't use iwd"
+        );
+        assert_eq!(
+            error(
+                BackendSwitchStep::RestartingNetworkManager,
+                Some(1),
+                true,
+                ""
+            ),
+            "timed out restarting NetworkManager"
+        );
+        assert_eq!(
+            error(
+                BackendSwitchStep::StartingNew,
+                Some(1),
+                false,
+                "Job for iwd.service failed.\nSee the journal.\n"
+            ),
+            "failed starting the new WiFi service (exit code 1): See the journal."
+        );
+        assert_eq!(
+            error(BackendSwitchStep::StartingNew, None, false, ""),
+            "failed starting the new WiFi service (killed by a signal)"
+        );
+
+        // Only the first step checks the service; a later 2 is an ordinary failure.
+        assert!(error(BackendSwitchStep::StartingNew, Some(2), false, "").contains("exit code 2"));
+        assert!(
+            backend_switch_script(WifiBackend::Iwd, BackendSwitchStep::StoppingOld)
+                .contains("systemctl stop wpa_supplicant.service")
+        );
+    }
+
+    fn iface(name: &str, driver_bound: bool) -> WirelessInterface {
+        WirelessInterface {
+            name: name.to_string(),
+            driver_bound,
+            device_hint: "0000:00:14.3".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_sta_interface_single_radio() {
+        let interfaces = [iface("wlan0", true), iface("p2p-dev-wlan0", true)];
+        let sta = resolve_sta_interface("auto", "wlan0", &interfaces).unwrap();
+        assert_eq!(sta, None);
+    }
+
+    #[test]
+    fn test_sta_interface_auto_picks_other_radio() {
+        let interfaces = [
+            iface("wlan1", true),
+            iface("wlan0", true),
+            iface("wlan2", false),
+        ];
+        let sta = resolve_sta_interface("auto", "wlan0", &interfaces).unwrap();
+        assert_eq!(sta.as_deref(), Some("wlan1"));
+
+        let sta = resolve_sta_interface("none", "wlan0", &interfaces).unwrap();
+        assert_eq!(sta, None);
+    }
+
+    #[test]
+    fn test_sta_interface_explicit() {
+        let interfaces = [
+            iface("wlan0", true),
+            iface("wlan1", true),
+            iface("wlan2", false),
+        ];
+        let sta = resolve_sta_interface("wlan1", "wlan0", &interfaces).unwrap();
+        assert_eq!(sta.as_deref(), Some("wlan1"));
+
+        assert!(resolve_sta_interface("wlan0", "wlan0", &interfaces).is_err());
+        assert!(resolve_sta_interface("wlan2", "wlan0", &interfaces).is_err());
+        assert!(resolve_sta_interface("wlan9", "wlan0", &interfaces).is_err());
+    }
+
+    #[test]
+    fn test_non_utf8_ssid_uses_raw_bytes() {
+        let raw = b"Caf\xe9 Guest".to_vec();
+        let state = WifiState {
+            available_networks: vec![NetworkInfo {
+                ssid: String::from_utf8_lossy(&raw).to_string(),
+                ssid_bytes: raw.clone(),
+                bssid: "00:11:22:33:44:55".to_string(),
+                signal_strength: 70,
+                frequency: 2437,
+                channel: 6,
+                is_secured: true,
+                security_type: "WPA2/WPA3".to_string(),
+                bss_count: 1,
+            }],
+            ..Default::default()
+        };
+
+        // The portal only ever sees the lossy display string.
+        let display = "Caf\u{fffd} Guest";
+        let ssid = state.ssid_bytes_for(display);
+        assert_eq!(ssid, raw);
+
+        let settings =
+            build_connection_settings(&ssid, "hunter22", false, None, None, None, IpFamily::Dual);
+        assert_eq!(
+            settings["802-11-wireless"]["ssid"],
+            Value::from(raw.clone())
+        );
+        assert!(!settings["802-11-wireless"].contains_key("bssid"));
+        assert_eq!(settings["connection"]["id"], Value::from(display));
+
+        // Unknown SSIDs (manual entry) fall back to their UTF-8 encoding.
+        assert_eq!(state.ssid_bytes_for("Hidden"), b"Hidden".to_vec());
+    }
+
+    #[test]
+    fn test_pinned_bssid_in_settings() {
+        let bssid = [0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc];
+        let settings = build_connection_settings(
+            b"Mesh",
+            "hunter22",
+            false,
+            Some(&bssid),
+            None,
+            None,
+            IpFamily::Dual,
+        );
+        assert_eq!(
+            settings["802-11-wireless"]["bssid"],
+            Value::from(bssid.to_vec())
+        );
+    }
+
+    #[test]
+    fn test_manual_security_sets_key_mgmt() {
+        let key_mgmt = |password, security| {
+            let settings = build_connection_settings(
+                b"Hidden",
+                password,
+                true,
+                None,
+                None,
+                security,
+                IpFamily::Dual,
+            );
+            settings.get("802-11-wireless-security").map(|s| {
+                let key_mgmt = <&str>::try_from(&s["key-mgmt"]).unwrap().to_string();
+                (key_mgmt, s.contains_key("psk"))
+            })
+        };
+        let secured = |key_mgmt: &str, psk| Some((key_mgmt.to_string(), psk));
+
+        // Without a choice the password decides, as before.
+        assert_eq!(key_mgmt("", None), None);
+        assert_eq!(key_mgmt("hunter22", None), secured("wpa-psk", true));
+        // A secured choice keeps key-mgmt even with a blank password.
+        assert_eq!(
+            key_mgmt("", Some(ManualSecurity::Wpa2)),
+            secured("wpa-psk", false)
+        );
+        assert_eq!(
+            key_mgmt("hunter22", Some(ManualSecurity::Wpa3)),
+            secured("sae", true)
+        );
+        assert_eq!(key_mgmt("", Some(ManualSecurity::Open)), None);
+        assert_eq!(
+            key_mgmt("", Some(ManualSecurity::EnhancedOpen)),
+            secured("owe", false)
+        );
+    }
+
+    #[test]
+    fn test_eap_tls_settings() {
+        let eap = EapTls {
+            identity: Some("device-42".to_string()),
+            ca_cert: Some("/etc/certs/ca.pem".into()),
+            client_cert: "/etc/certs/client.pem".into(),
+            private_key: "/etc/certs/client.key".into(),
+            private_key_password: Some("secret".to_string()),
+        };
+        let settings =
+            build_connection_settings(b"Corp", "", false, None, Some(&eap), None, IpFamily::Dual);
+
+        assert_eq!(
+            settings["802-11-wireless-security"]["key-mgmt"],
+            Value::from("wpa-eap")
+        );
+        assert!(!settings["802-11-wireless-security"].contains_key("psk"));
+
+        let dot1x = &settings["802-1x"];
+        assert_eq!(dot1x["eap"], Value::from(vec!["tls".to_string()]));
+        assert_eq!(dot1x["identity"], Value::from("device-42"));
+        assert_eq!(
+            dot1x["ca-cert"],
+            Value::from(b"file:///etc/certs/ca.pem\0".to_vec())
+        );
+        assert_eq!(
+            dot1x["client-cert"],
+            Value::from(b"file:///etc/certs/client.pem\0".to_vec())
+        );
+        assert_eq!(
+            dot1x["private-key"],
+            Value::from(b"file:///etc/certs/client.key\0".to_vec())
+        );
+        assert_eq!(dot1x["private-key-password"], Value::from("secret"));
+    }
+
+    #[test]
+    fn test_choose_ap_ip_prefers_remembered_subnet() {
+        let remembered: Ipv4Addr = "172.20.42.1".parse().unwrap();
+        let occupied = HashSet::new();
+        assert_eq!(choose_ap_ip(&occupied, Some(remembered)), Some(remembered));
+    }
+
+    #[test]
+    fn test_choose_ap_ip_skips_conflicting_remembered_subnet() {
+        let remembered: Ipv4Addr = "192.168.42.1".parse().unwrap();
+        let occupied = HashSet::from([(192, 168, 42)]);
+        assert_eq!(
+            choose_ap_ip(&occupied, Some(remembered)),
+            Some("10.42.0.1".parse().unwrap())
+        );
+
+        let occupied = AP_IP_CANDIDATES
+            .iter()
+            .map(|c| {
+                let [a, b, c, _] = c.parse::<Ipv4Addr>().unwrap().octets();
+                (a, b, c)
+            })
+            .collect();
+        assert_eq!(choose_ap_ip(&occupied, None), None);
+    }
+
+    #[test]
+    fn test_ap_subnet_conflict_ignores_the_ap_interface() {
+        let prefixes = parse_interface_ipv4_prefixes(
+            "1: lo    inet 127.0.0.1/8 scope host lo\\       valid_lft forever\n\
+             3: wlan0    inet 192.168.42.1/24 scope global wlan0\\       valid_lft forever\n",
+        );
+        let ap_ip: Ipv4Addr = "192.168.42.1".parse().unwrap();
+        assert_eq!(ap_subnet_conflict(ap_ip, "wlan0", &prefixes), None);
+
+        let mut prefixes = prefixes;
+        prefixes.extend(parse_interface_ipv4_prefixes(
+            "2: eth0    inet 192.168.42.17/24 brd 192.168.42.255 scope global dynamic eth0\n",
+        ));
+        assert_eq!(
+            ap_subnet_conflict(ap_ip, "wlan0", &prefixes),
+            Some("eth0".to_string())
+        );
+        assert_eq!(
+            ap_subnet_conflict("10.42.0.1".parse().unwrap(), "wlan0", &prefixes),
+            None
+        );
+    }
+
+    #[test]
+    fn test_remembered_ap_ip_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/ap-subnet");
+        assert_eq!(load_remembered_ap_ip(&path), None);
+
+        let ip: Ipv4Addr = "10.123.0.1".parse().unwrap();
+        remember_ap_ip(&path, ip).unwrap();
+        assert_eq!(load_remembered_ap_ip(&path), Some(ip));
+    }
+
+    #[test]
+    fn test_connect_error_from_reason() {
+        let table = [
+            (7, ConnectError::WrongPassword),
+            (8, ConnectError::WrongPassword),
+            (11, ConnectError::AuthTimeout),
+            (5, ConnectError::DhcpFailed),
+            (15, ConnectError::DhcpFailed),
+            (16, ConnectError::DhcpFailed),
+            (17, ConnectError::DhcpFailed),
+            (53, ConnectError::SsidNotFound),
+        ];
+
+        for (reason, expected) in table {
+            assert_eq!(
+                connect_error_from_reason(NM_DEVICE_STATE_FAILED, reason),
+                expected,
+                "reason {}",
+                reason
+            );
+        }
+
+        assert!(matches!(
+            connect_error_from_reason(NM_DEVICE_STATE_FAILED, 1),
+            ConnectError::Other(_)
+        ));
+    }
+
+    /// Serve a single HTTP request with `status_line` and return the probe URL.
+    async fn one_shot_server(status_line: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await;
+            let response = format!("HTTP/1.1 {}\r\ncontent-length: 0\r\n\r\n", status_line);
+            let _ = socket.write_all(response.as_bytes()).await;
+        });
+        format!("http://{}/generate_204", addr)
+    }
+
+    #[tokio::test]
+    async fn test_probe_http_connectivity() {
+        let url = one_shot_server("204 No Content").await;
+        assert!(probe_http_connectivity(&url).await.unwrap());
+
+        let url = one_shot_server("503 Service Unavailable").await;
+        assert!(!probe_http_connectivity(&url).await.unwrap());
+
+        // Nothing listening: not connected rather than an error
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+        assert!(!probe_http_connectivity(&url).await.unwrap());
+
+        assert!(probe_http_connectivity("https://example.com/")
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_is_valid_probe_url() {
+        assert!(is_valid_probe_url(
+            "http://updates.example.com/generate_204"
+        ));
+        assert!(is_valid_probe_url("http://10.0.0.1:8080"));
+        assert!(!is_valid_probe_url("https://updates.example.com/"));
+        assert!(!is_valid_probe_url("/generate_204"));
+        assert!(!is_valid_probe_url("not a url"));
+    }
+
+    #[test]
+    fn test_merge_access_point_counts_bssids() {
+        let ap = |bssid: &str, signal| NetworkInfo {
+            ssid: "Mesh".to_string(),
+            ssid_bytes: b"Mesh".to_vec(),
+            bssid: bssid.to_string(),
+            signal_strength: signal,
+            frequency: 2437,
+            channel: 6,
+            is_secured: true,
+            security_type: "WPA2".to_string(),
+            bss_count: 1,
+        };
+        let mut by_ssid = HashMap::new();
+
+        assert!(merge_access_point(
+            &mut by_ssid,
+            ap("00:00:00:00:00:01", 50)
+        ));
+        assert!(!merge_access_point(
+            &mut by_ssid,
+            ap("00:00:00:00:00:02", 40)
+        ));
+        assert!(merge_access_point(
+            &mut by_ssid,
+            ap("00:00:00:00:00:03", 70)
+        ));
+
+        let mesh = &by_ssid[b"Mesh".as_slice()];
+        assert_eq!(mesh.bss_count, 3);
+        assert_eq!(mesh.bssid, "00:00:00:00:00:03");
+        assert_eq!(mesh.signal_strength, 70);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_connectivity_times_out() {
+        let interval = std::time::Duration::from_millis(10);
+        let window = std::time::Duration::from_millis(100);
+
+        // NM stuck below FULL: every check says "not yet".
+        let mut checks = 0;
+        let started = tokio::time::Instant::now();
+        let result = poll_connectivity(
+            || {
+                checks += 1;
+                async { Ok(false) }
+            },
+            started + window,
+            interval,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result, ConnectivityWait::TimedOut);
+        assert!(checks > 1);
+        assert!(started.elapsed() < window * 3);
+
+        // A check that hangs is cut off at the deadline too.
+        let started = tokio::time::Instant::now();
+        let result = poll_connectivity(
+            std::future::pending::<Result<bool>>,
+            started + window,
+            interval,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result, ConnectivityWait::TimedOut);
+        assert!(started.elapsed() < window * 3);
+
+        let mut remaining = 2;
+        let result = poll_connectivity(
+            || {
+                remaining -= 1;
+                let connected = remaining == 0;
+                async move { Ok(connected) }
+            },
+            tokio::time::Instant::now() + window,
+            interval,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result, ConnectivityWait::Connected);
+    }
+
+    #[test]
+    fn classify_security_distinguishes_wpa3_only() {
+        let psk = NM_80211_AP_SEC_KEY_MGMT_PSK | 0x8 | 0x80; // + CCMP pair/group
+        let sae = NM_80211_AP_SEC_KEY_MGMT_SAE | 0x8 | 0x80;
+        let privacy = NM_80211_AP_FLAGS_PRIVACY;
+
+        assert_eq!(classify_security(privacy, 0, sae), "WPA3");
+        assert_eq!(classify_security(privacy, 0, psk | sae), "WPA2/WPA3");
+        assert_eq!(classify_security(privacy, 0, psk), "WPA2");
+        assert_eq!(classify_security(privacy, 0, 0x200 | 0x8), "Enterprise");
+        assert_eq!(classify_security(privacy, 0x200, 0x200), "Enterprise");
+        assert_eq!(classify_security(privacy, 0, psk | 0x200), "WPA2");
+        assert_eq!(classify_security(privacy, psk, psk), "WPA/WPA2");
+        assert_eq!(classify_security(privacy, 0, 0), "WEP/Protected");
+        assert_eq!(classify_security(0, 0, 0), "Open");
+    }
+
+    #[test]
+    fn classify_security_detects_enhanced_open() {
+        let owe = NM_80211_AP_SEC_KEY_MGMT_OWE | 0x8 | 0x80;
+        let privacy = NM_80211_AP_FLAGS_PRIVACY;
+
+        assert_eq!(classify_security(privacy, 0, owe), "Enhanced Open");
+        // The open BSS of a transition pair only advertises OWE_TM.
+        assert_eq!(
+            classify_security(0, 0, NM_80211_AP_SEC_KEY_MGMT_OWE_TM),
+            "Enhanced Open"
+        );
+        // A passphrase on offer still means a password prompt.
+        let sae = NM_80211_AP_SEC_KEY_MGMT_SAE | 0x8 | 0x80;
+        assert_eq!(classify_security(privacy, 0, owe | sae), "WPA3");
+    }
+
+    #[test]
+    fn test_ip_family_settings() {
+        let ip = |family| {
+            let mut settings =
+                build_connection_settings(b"Home", "", false, None, None, None, family);
+            (
+                settings.remove("ipv4").unwrap(),
+                settings.remove("ipv6").unwrap(),
+            )
+        };
+
+        let (v4, v6) = ip(IpFamily::Dual);
+        assert_eq!(v4["method"], Value::from("auto"));
+        assert_eq!(v4["may-fail"], Value::from(true));
+        assert_eq!(v6["method"], Value::from("auto"));
+        assert_eq!(v6["may-fail"], Value::from(true));
+
+        let (v4, v6) = ip(IpFamily::V4);
+        assert_eq!(v4["method"], Value::from("auto"));
+        assert_eq!(v4["may-fail"], Value::from(false));
+        assert_eq!(v6["method"], Value::from("ignore"));
+        assert!(!v6.contains_key("may-fail"));
+
+        let (v4, v6) = ip(IpFamily::V6);
+        assert_eq!(v4["method"], Value::from("disabled"));
+        assert!(!v4.contains_key("may-fail"));
+        assert_eq!(v6["method"], Value::from("auto"));
+        assert_eq!(v6["may-fail"], Value::from(false));
+    }
+
+    #[test]
+    fn test_nmcli_add_args_mirror_dbus_settings() {
+        let bssid = [0xaa, 0xbb, 0xcc, 0x00, 0x11, 0x22];
+        let args = nmcli_add_args(
+            "u-1",
+            "wlan0",
+            "Cafe",
+            Some("wpa-psk"),
+            false,
+            true,
+            Some(&bssid),
+            IpFamily::V4,
+        );
+        let pairs: Vec<_> = args[2..]
+            .chunks(2)
+            .map(|c| (c[0].as_str(), c[1].as_str()))
+            .collect();
+        for expected in [
+            ("ifname", "wlan0"),
+            ("ssid", "Cafe"),
+            ("connection.uuid", "u-1"),
+            ("802-11-wireless.hidden", "yes"),
+            ("802-11-wireless.bssid", "AA:BB:CC:00:11:22"),
+            ("802-11-wireless-security.key-mgmt", "wpa-psk"),
+            ("ipv6.method", "ignore"),
+            ("save", "no"),
+        ] {
+            assert!(pairs.contains(&expected), "missing {:?}", expected);
+        }
+        assert!(!args.iter().any(|a| a.contains("psk:")));
+
+        let open = nmcli_add_args(
+            "u-2",
+            "wlan0",
+            "Open",
+            None,
+            true,
+            false,
+            None,
+            IpFamily::Dual,
+        );
+        assert!(!open.iter().any(|a| a.starts_with("802-11-wireless")));
+        assert_eq!(open[open.len() - 2..], ["save", "yes"]);
+    }
+
+    #[test]
+    fn test_nmcli_fallback_only_for_activation_errors() {
+        assert!(NMCLI_FALLBACK_ERRORS.contains(&"org.freedesktop.NetworkManager.Failed"));
+        assert!(!nmcli_fallback_applies(&zbus::Error::InvalidReply));
+        assert!(matches!(
+            connect_error_from_nmcli(
+                "Error: Connection activation failed: Secrets were required, but not provided."
+            ),
+            ConnectError::WrongPassword
+        ));
+        assert!(matches!(
+            connect_error_from_nmcli("Error: Timeout expired (35 seconds)"),
+            ConnectError::AuthTimeout
+        ));
+        assert!(matches!(
+            connect_error_from_nmcli("Error: something else"),
+            ConnectError::Other(_)
+        ));
+    }
+
+    #[test]
+    fn test_stalled_scan_reissued_once_after_half_window() {
+        let timeout = std::time::Duration::from_secs(6);
+        let at = std::time::Duration::from_millis;
+        assert!(!should_reissue_scan(at(2800), timeout, false));
+        assert!(should_reissue_scan(at(3000), timeout, false));
+        assert!(should_reissue_scan(at(5600), timeout, false));
+        assert!(!should_reissue_scan(at(3400), timeout, true));
+    }
+
+    #[test]
+    fn test_need_auth_terminal_only_when_it_lasts_or_recurs() {
+        let start = std::time::Instant::now();
+        let at = |secs| start + std::time::Duration::from_secs(secs);
+
+        // Brief NEED_AUTH during negotiation, then on to IP config.
+        let mut tracker = NeedAuthTracker::default();
+        assert!(!tracker.observe(NM_DEVICE_STATE_NEED_AUTH, at(0)));
+        assert!(!tracker.observe(NM_DEVICE_STATE_NEED_AUTH, at(2)));
+        assert!(!tracker.observe(NM_DEVICE_STATE_IP_CONFIG, at(3)));
+
+        // Stuck asking for secrets.
+        let mut tracker = NeedAuthTracker::default();
+        assert!(!tracker.observe(NM_DEVICE_STATE_NEED_AUTH, at(0)));
+        assert!(tracker.observe(NM_DEVICE_STATE_NEED_AUTH, at(5)));
+
+        // Rejected, retried by the supplicant, asked again.
+        let mut tracker = NeedAuthTracker::default();
+        assert!(!tracker.observe(NM_DEVICE_STATE_NEED_AUTH, at(0)));
+        assert!(!tracker.observe(50, at(1)));
+        assert!(tracker.observe(NM_DEVICE_STATE_NEED_AUTH, at(2)));
+    }
+
+    #[test]
+    fn test_saved_profiles_match_visible_networks() {
+        let profile_settings = |kind: &str, ssid: &[u8], extra: &[(&str, &str, Value)]| {
+            let mut settings = NmSettings::new();
+            let mut set = |group: &str, key: &str, value: Value| {
+                settings
+                    .entry(group.to_string())
+                    .or_default()
+                    .insert(key.to_string(), value.try_into().unwrap());
+            };
+            set("connection", "type", Value::from(kind));
+            set("connection", "id", Value::from("profile"));
+            set("802-11-wireless", "ssid", Value::from(ssid.to_vec()));
+            for (group, key, value) in extra {
+                set(group, key, value.try_clone().unwrap());
+            }
+            settings
+        };
+
+        let home = wifi_profile_from_settings(
+            "/p/1",
+            &profile_settings(
+                "802-11-wireless",
+                b"Home",
+                &[("connection", "autoconnect-priority", Value::from(5i32))],
+            ),
+        )
+        .unwrap();
+        assert_eq!(home.ssid, b"Home");
+        assert_eq!(home.priority, 5);
+        let office = wifi_profile_from_settings(
+            "/p/2",
+            &profile_settings("802-11-wireless", b"Office", &[]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            wifi_profile_from_settings("/p/3", &profile_settings("802-3-ethernet", b"", &[])),
+            None
+        );
+        let hotspot = [("802-11-wireless", "mode", Value::from("ap"))];
+        assert_eq!(
+            wifi_profile_from_settings(
+                "/p/4",
+                &profile_settings("802-11-wireless", b"Hot", &hotspot)
+            ),
+            None
+        );
+        let manual = [("connection", "autoconnect", Value::from(false))];
+        assert_eq!(
+            wifi_profile_from_settings(
+                "/p/5",
+                &profile_settings("802-11-wireless", b"Lab", &manual)
+            ),
+            None
+        );
+
+        let network = |ssid: &str, signal| NetworkInfo {
+            ssid: ssid.to_string(),
+            ssid_bytes: ssid.as_bytes().to_vec(),
+            bssid: String::new(),
+            signal_strength: signal,
+            frequency: 2412,
+            channel: 1,
+            is_secured: true,
+            security_type: "WPA2".to_string(),
+            bss_count: 1,
+        };
+        let profiles = [home, office];
+        // Profile priority beats a stronger signal.
+        let available = [
+            network("Office", 90),
+            network("Home", 40),
+            network("Cafe", 99),
+        ];
+        let (picked, profile) = best_profile_match(&profiles, &available).unwrap();
+        assert_eq!(
+            (picked.ssid.as_str(), profile.path.as_str()),
+            ("Home", "/p/1")
+        );
+        assert!(best_profile_match(&profiles, &[network("Cafe", 99)]).is_none());
+    }
+
+    #[test]
+    fn test_parse_rfkill_list() {
+        let output = "\
+0: hci0: Bluetooth
+	Soft blocked: yes
+	Hard blocked: no
+1: phy0: Wireless LAN
+	Soft blocked: yes
+	Hard blocked: no
+2: phy1: Wireless LAN
+	Soft blocked: no
+	Hard blocked: yes
+";
+        let entries = parse_rfkill_list(output);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[1],
+            RfkillEntry {
+                index: 1,
+                name: "phy0".to_string(),
+                kind: "Wireless LAN".to_string(),
+                soft_blocked: true,
+                hard_blocked: false,
+            }
+        );
+        assert!(entries[2].hard_blocked && !entries[2].soft_blocked);
+
+        let pick = |phy| select_rfkill_entry(entries.clone(), phy).map(|e| e.index);
+        assert_eq!(pick(Some("phy1")), Some(2));
+        assert_eq!(pick(None), Some(1));
+        assert_eq!(pick(Some("phy7")), None);
+        assert!(parse_rfkill_list("").is_empty());
+    }
+}
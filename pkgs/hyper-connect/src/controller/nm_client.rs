@@ -0,0 +1,172 @@
+//! NetworkManager D-Bus calls behind a trait
+//!
+//! Scanning, AP selection and the connect/activation loops reach
+//! NetworkManager only through [`NmClient`], so their logic can be driven by
+//! scripted device states in tests. [`ZbusNm`] is the real implementation on
+//! the system bus.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use zbus::Connection;
+use zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+pub(super) const NM_DEST: &str = "org.freedesktop.NetworkManager";
+pub(super) const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+pub(super) const NM_IFACE: &str = "org.freedesktop.NetworkManager";
+pub(super) const NM_DEVICE_IFACE: &str = "org.freedesktop.NetworkManager.Device";
+const NM_WIFI_DEVICE_IFACE: &str = "org.freedesktop.NetworkManager.Device.Wireless";
+const NM_AP_IFACE: &str = "org.freedesktop.NetworkManager.AccessPoint";
+
+/// Connection settings in the shape `AddAndActivateConnection` takes.
+pub type ConnectionSettings<'a> = HashMap<&'static str, HashMap<&'static str, Value<'a>>>;
+
+/// Properties of one scanned access point, as NetworkManager reports them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccessPoint {
+    pub ssid: Vec<u8>,
+    pub hw_address: String,
+    pub strength: u8,
+    pub frequency: u32,
+    pub flags: u32,
+    pub wpa_flags: u32,
+    pub rsn_flags: u32,
+}
+
+/// The NetworkManager operations the connect and scan logic relies on.
+pub trait NmClient {
+    /// Ask the wireless device for a fresh scan.
+    async fn request_scan(&self, device: &OwnedObjectPath) -> Result<()>;
+    /// The device's `LastScan` stamp, which advances when a scan completes.
+    async fn last_scan(&self, device: &OwnedObjectPath) -> Result<i64>;
+    /// Object paths of every access point the device currently sees.
+    async fn access_points(&self, device: &OwnedObjectPath) -> Result<Vec<OwnedObjectPath>>;
+    async fn access_point(&self, ap: &OwnedObjectPath) -> Result<AccessPoint>;
+    /// The device's `State` (an `NMDeviceState` value).
+    async fn device_state(&self, device: &OwnedObjectPath) -> Result<u32>;
+    /// The device's `StateReason` as a (state, reason) pair.
+    async fn device_state_reason(&self, device: &OwnedObjectPath) -> Result<(u32, u32)>;
+    /// Add a profile and activate it on `device`, kept on disk only if `save`.
+    ///
+    /// Returns the raw D-Bus error so callers can tell which failures are
+    /// worth retrying through nmcli.
+    async fn add_and_activate(
+        &self,
+        settings: &ConnectionSettings<'_>,
+        device: &OwnedObjectPath,
+        specific_ap: &OwnedObjectPath,
+        save: bool,
+    ) -> std::result::Result<(), zbus::Error>;
+}
+
+/// [`NmClient`] over a system bus connection.
+pub struct ZbusNm {
+    connection: Connection,
+}
+
+impl ZbusNm {
+    pub fn new(connection: Connection) -> Self {
+        Self { connection }
+    }
+
+    async fn proxy(&self, path: &str, interface: &'static str) -> zbus::Result<zbus::Proxy<'_>> {
+        zbus::Proxy::new(&self.connection, NM_DEST, path.to_owned(), interface).await
+    }
+}
+
+impl NmClient for ZbusNm {
+    async fn request_scan(&self, device: &OwnedObjectPath) -> Result<()> {
+        let options = HashMap::<&str, Value>::new();
+        self.proxy(device.as_str(), NM_WIFI_DEVICE_IFACE)
+            .await?
+            .call::<_, _, ()>("RequestScan", &(options,))
+            .await?;
+        Ok(())
+    }
+
+    async fn last_scan(&self, device: &OwnedObjectPath) -> Result<i64> {
+        Ok(self
+            .proxy(device.as_str(), NM_WIFI_DEVICE_IFACE)
+            .await?
+            .get_property("LastScan")
+            .await?)
+    }
+
+    async fn access_points(&self, device: &OwnedObjectPath) -> Result<Vec<OwnedObjectPath>> {
+        Ok(self
+            .proxy(device.as_str(), NM_WIFI_DEVICE_IFACE)
+            .await?
+            .call("GetAllAccessPoints", &())
+            .await?)
+    }
+
+    async fn access_point(&self, ap: &OwnedObjectPath) -> Result<AccessPoint> {
+        let proxy = self.proxy(ap.as_str(), NM_AP_IFACE).await?;
+        Ok(AccessPoint {
+            ssid: proxy.get_property("Ssid").await?,
+            hw_address: proxy.get_property("HwAddress").await.unwrap_or_default(),
+            strength: proxy.get_property("Strength").await.unwrap_or(0),
+            frequency: proxy.get_property("Frequency").await.unwrap_or(0),
+            flags: proxy.get_property("Flags").await.unwrap_or(0),
+            wpa_flags: proxy.get_property("WpaFlags").await.unwrap_or(0),
+            rsn_flags: proxy.get_property("RsnFlags").await.unwrap_or(0),
+        })
+    }
+
+    async fn device_state(&self, device: &OwnedObjectPath) -> Result<u32> {
+        Ok(self
+            .proxy(device.as_str(), NM_DEVICE_IFACE)
+            .await?
+            .get_property("State")
+            .await?)
+    }
+
+    async fn device_state_reason(&self, device: &OwnedObjectPath) -> Result<(u32, u32)> {
+        Ok(self
+            .proxy(device.as_str(), NM_DEVICE_IFACE)
+            .await?
+            .get_property("StateReason")
+            .await?)
+    }
+
+    async fn add_and_activate(
+        &self,
+        settings: &ConnectionSettings<'_>,
+        device: &OwnedObjectPath,
+        specific_ap: &OwnedObjectPath,
+        save: bool,
+    ) -> std::result::Result<(), zbus::Error> {
+        let nm_proxy = self.proxy(NM_PATH, NM_IFACE).await?;
+        let mut options: HashMap<&str, Value> = HashMap::new();
+        // Use NetworkManager's built-in credential storage when save=true
+        let persist_mode = if save { "disk" } else { "volatile" };
+        options.insert("persist", Value::from(persist_mode));
+
+        let v2_result: std::result::Result<
+            (
+                OwnedObjectPath,
+                OwnedObjectPath,
+                HashMap<String, OwnedValue>,
+            ),
+            zbus::Error,
+        > = nm_proxy
+            .call(
+                "AddAndActivateConnection2",
+                &(settings, device, specific_ap, options),
+            )
+            .await;
+
+        match v2_result {
+            Ok(_) => Ok(()),
+            Err(zbus::Error::MethodError(name, _, _))
+                if name.as_str() == "org.freedesktop.DBus.Error.UnknownMethod" =>
+            {
+                let legacy: std::result::Result<(OwnedObjectPath, OwnedObjectPath), zbus::Error> =
+                    nm_proxy
+                        .call("AddAndActivateConnection", &(settings, device, specific_ap))
+                        .await;
+                legacy.map(|_| ())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
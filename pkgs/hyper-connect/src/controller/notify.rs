@@ -0,0 +1,73 @@
+//! systemd service notifications (sd_notify)
+//!
+//! All calls are best-effort and silently do nothing when the daemon is not
+//! running under systemd or the `systemd` feature is disabled.
+
+use super::{ConnectionStatus, WifiState};
+use tokio::sync::watch;
+
+/// Tell systemd the daemon has finished starting up.
+pub fn ready() {
+    #[cfg(feature = "systemd")]
+    send(&[sd_notify::NotifyState::Ready]);
+}
+
+/// Tell systemd the daemon is shutting down.
+pub fn stopping() {
+    #[cfg(feature = "systemd")]
+    send(&[sd_notify::NotifyState::Stopping]);
+}
+
+/// Update the free-form status line shown by `systemctl status`.
+pub fn status(text: &str) {
+    #[cfg(feature = "systemd")]
+    send(&[sd_notify::NotifyState::Status(text)]);
+
+    #[cfg(not(feature = "systemd"))]
+    let _ = text;
+}
+
+#[cfg(feature = "systemd")]
+fn send(states: &[sd_notify::NotifyState]) {
+    if let Err(e) = sd_notify::notify(false, states) {
+        tracing::debug!(error = %e, "sd_notify failed");
+    }
+}
+
+/// Mirror state changes into the systemd status line until the channel closes.
+pub async fn forward_status(mut state_rx: watch::Receiver<WifiState>) {
+    while state_rx.changed().await.is_ok() {
+        let line = status_line(&state_rx.borrow_and_update());
+        status(&line);
+    }
+}
+
+fn status_line(state: &WifiState) -> String {
+    match state.status {
+        ConnectionStatus::Initializing => "Initializing".to_string(),
+        ConnectionStatus::SwitchingBackend => match state.backend_switch_step {
+            Some(step) => format!("Switching WiFi backend: {}", step.describe()),
+            None => "Switching WiFi backend".to_string(),
+        },
+        ConnectionStatus::Scanning => "Scanning for networks".to_string(),
+        ConnectionStatus::AwaitingCredentials if state.ap_running => {
+            "AP running, awaiting credentials".to_string()
+        }
+        ConnectionStatus::AwaitingCredentials => "Awaiting credentials".to_string(),
+        ConnectionStatus::Connecting => format!(
+            "Connecting to {}",
+            state.connecting_to.as_deref().unwrap_or("network")
+        ),
+        ConnectionStatus::Connected => format!(
+            "Connected to {}",
+            state.connected_ssid.as_deref().unwrap_or("network")
+        ),
+        ConnectionStatus::Failed => format!(
+            "Connection failed: {}",
+            state.last_error.as_deref().unwrap_or("unknown error")
+        ),
+        ConnectionStatus::ApUnsupported => "Adapter cannot host a setup AP".to_string(),
+        ConnectionStatus::RadioBlocked => "WiFi disabled by hardware switch".to_string(),
+        ConnectionStatus::Disconnected => "Disconnected".to_string(),
+    }
+}
@@ -0,0 +1,194 @@
+//! `daemon --dry-run`: the setup flow without touching the radio
+//!
+//! NetworkManager is never contacted and hostapd, dnsmasq, ip and systemctl
+//! are never spawned; the controller logs what it would have done instead.
+//! Scans return [`fake_networks`] (or the `--demo-networks` file) and connects
+//! follow [`connect_outcome`], so the portal, IPC and TUI can be exercised end
+//! to end on a workstation.
+
+use super::state::{BackendSwitchStep, ENHANCED_OPEN_SECURITY};
+use super::{ConnectError, DaemonConfig, NetworkInfo};
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Password that joins any simulated secured network
+pub const GOOD_PASSWORD: &str = "good";
+
+/// How long a simulated connect takes, so the portal's progress states show.
+const CONNECT_DELAY: Duration = Duration::from_secs(6);
+/// How long each simulated backend switch step takes.
+const BACKEND_STEP_DELAY: Duration = Duration::from_secs(2);
+
+/// Fixed scan results covering the cases the portal and TUI render differently.
+pub fn fake_networks() -> Vec<NetworkInfo> {
+    let network = |ssid: &str, signal, frequency, channel, security: &str| NetworkInfo {
+        ssid: ssid.to_string(),
+        ssid_bytes: ssid.as_bytes().to_vec(),
+        bssid: format!("02:00:00:00:00:{:02x}", channel),
+        signal_strength: signal,
+        frequency,
+        channel,
+        is_secured: !matches!(security, "Open" | ENHANCED_OPEN_SECURITY),
+        security_type: security.to_string(),
+        bss_count: 1,
+    };
+    vec![
+        network("Home", 88, 5180, 36, "WPA2/WPA3"),
+        network("Home", 71, 2437, 6, "WPA2"),
+        network("Cafe Guest", 64, 2412, 1, "Open"),
+        network("Library", 58, 5240, 48, ENHANCED_OPEN_SECURITY),
+        network("Office", 52, 5745, 149, "WPA3"),
+        network("Lab ", 40, 2462, 11, "WPA2"),
+        network("Neighbor", 12, 2472, 13, "WPA/WPA2"),
+    ]
+}
+
+/// What a simulated scan finds: the demo list if one was loaded.
+pub fn scan_results(config: &DaemonConfig) -> Vec<NetworkInfo> {
+    config.demo_networks.clone().unwrap_or_else(fake_networks)
+}
+
+/// Load a curated scan list for demos and screenshots.
+///
+/// The file is a JSON array of [`NetworkInfo`]; `ssid_bytes` and `bss_count`
+/// may be left out.
+pub fn load_demo_networks(path: &Path) -> Result<Vec<NetworkInfo>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut networks: Vec<NetworkInfo> = serde_json::from_str(&contents)
+        .with_context(|| format!("{} is not a list of networks", path.display()))?;
+    for (index, network) in networks.iter_mut().enumerate() {
+        validate_demo_network(network)
+            .with_context(|| format!("{}: network {}", path.display(), index + 1))?;
+    }
+    Ok(networks)
+}
+
+fn validate_demo_network(network: &mut NetworkInfo) -> Result<()> {
+    if network.ssid_bytes.is_empty() {
+        network.ssid_bytes = network.ssid.as_bytes().to_vec();
+    }
+    if network.ssid_bytes.is_empty() || network.ssid_bytes.len() > 32 {
+        anyhow::bail!("ssid must be 1-32 bytes");
+    }
+    if network.signal_strength > 100 {
+        anyhow::bail!("signal_strength {} is above 100", network.signal_strength);
+    }
+    if network.bss_count == 0 {
+        anyhow::bail!("bss_count must be at least 1");
+    }
+    let passwordless = network.security_type == "Open" || network.is_enhanced_open();
+    if network.is_secured == passwordless {
+        anyhow::bail!(
+            "is_secured contradicts security_type \"{}\"",
+            network.security_type
+        );
+    }
+    Ok(())
+}
+
+/// Log the AP a real run would have started.
+pub fn start_ap(config: &DaemonConfig) {
+    tracing::info!(
+        interface = %config.interface,
+        ssid = %config.ssid,
+        ap_ip = %config.ap_ip,
+        security = ?config.ap_password.as_ref().map(|_| config.ap_security),
+        "Dry run: would start hostapd and dnsmasq"
+    );
+}
+
+/// Log a backend switch step and pretend it took a while.
+pub async fn backend_switch_step(step: BackendSwitchStep) {
+    tracing::info!("Dry run: would be {}", step.describe());
+    tokio::time::sleep(BACKEND_STEP_DELAY).await;
+}
+
+/// Simulated connect: open networks and [`GOOD_PASSWORD`] succeed.
+pub async fn connect(
+    networks: &[NetworkInfo],
+    ssid: &[u8],
+    password: &str,
+    hidden: bool,
+    mut on_deadline: impl FnMut(Instant),
+) -> Result<()> {
+    on_deadline(Instant::now() + CONNECT_DELAY);
+    tokio::time::sleep(CONNECT_DELAY).await;
+    connect_outcome(networks, ssid, password, hidden).map_err(Into::into)
+}
+
+fn connect_outcome(
+    networks: &[NetworkInfo],
+    ssid: &[u8],
+    password: &str,
+    hidden: bool,
+) -> Result<(), ConnectError> {
+    let network = networks.iter().find(|n| n.raw_ssid() == ssid);
+    match network {
+        None if !hidden => Err(ConnectError::SsidNotFound),
+        Some(network) if !network.is_secured => Ok(()),
+        _ if password == GOOD_PASSWORD => Ok(()),
+        _ => Err(ConnectError::WrongPassword),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_demo_networks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("networks.json");
+        std::fs::write(
+            &path,
+            r#"[
+                {"ssid":"Museum","bssid":"02:00:00:00:01:01","signal_strength":77,
+                 "frequency":5180,"channel":36,"is_secured":true,"security_type":"WPA3"},
+                {"ssid":"Lobby","bssid":"02:00:00:00:01:02","signal_strength":40,
+                 "frequency":2412,"channel":1,"is_secured":false,"security_type":"Open"}
+            ]"#,
+        )
+        .unwrap();
+        let networks = load_demo_networks(&path).unwrap();
+        assert_eq!(networks.len(), 2);
+        assert_eq!(networks[0].raw_ssid(), b"Museum");
+        assert_eq!(networks[1].bss_count, 1);
+        assert_eq!(connect_outcome(&networks, b"Lobby", "", false), Ok(()));
+
+        std::fs::write(
+            &path,
+            r#"[{"ssid":"Loud","bssid":"","signal_strength":140,"frequency":2412,
+                 "channel":1,"is_secured":false,"security_type":"Open"}]"#,
+        )
+        .unwrap();
+        let err = format!("{:#}", load_demo_networks(&path).unwrap_err());
+        assert!(
+            err.contains("network 1: signal_strength 140 is above 100"),
+            "{err}"
+        );
+
+        std::fs::write(&path, r#"[{"ssid":"Partial"}]"#).unwrap();
+        let err = format!("{:#}", load_demo_networks(&path).unwrap_err());
+        assert!(err.contains("missing field `bssid`"), "{err}");
+    }
+
+    #[test]
+    fn test_connect_outcome() {
+        let networks = fake_networks();
+        assert_eq!(connect_outcome(&networks, b"Home", "good", false), Ok(()));
+        assert_eq!(
+            connect_outcome(&networks, b"Home", "hunter22", false),
+            Err(ConnectError::WrongPassword)
+        );
+        assert_eq!(connect_outcome(&networks, b"Cafe Guest", "", false), Ok(()));
+        assert_eq!(connect_outcome(&networks, b"Library", "", false), Ok(()));
+        assert_eq!(
+            connect_outcome(&networks, b"Lab", "good", false),
+            Err(ConnectError::SsidNotFound)
+        );
+        assert_eq!(connect_outcome(&networks, b"Lab ", "good", false), Ok(()));
+        assert_eq!(connect_outcome(&networks, b"Attic", "good", true), Ok(()));
+    }
+}
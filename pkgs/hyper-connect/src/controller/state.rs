@@ -1,9 +1,18 @@
 //! Shared state types for WiFi controller
 
+use super::connect_limit::FailureWindow;
+use super::network_list::NetworkListTracker;
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant};
 
 /// Current connection status
+///
+/// Serialized as the bare variant name (`"AwaitingCredentials"`), which the
+/// portal JS compares against; there is deliberately no `rename_all`.
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub enum ConnectionStatus {
     #[default]
@@ -14,10 +23,37 @@ pub enum ConnectionStatus {
     Connecting,
     Connected,
     Failed,
+    /// The radio can't run in AP mode, so no setup portal can be offered.
+    ApUnsupported,
+    /// The radio is switched off by a hardware rfkill switch.
+    RadioBlocked,
     Disconnected,
 }
 
+impl ConnectionStatus {
+    /// Every status, in lifecycle order (used for exhaustive metrics labels).
+    pub const ALL: [ConnectionStatus; 10] = [
+        ConnectionStatus::Initializing,
+        ConnectionStatus::SwitchingBackend,
+        ConnectionStatus::Scanning,
+        ConnectionStatus::AwaitingCredentials,
+        ConnectionStatus::Connecting,
+        ConnectionStatus::Connected,
+        ConnectionStatus::Failed,
+        ConnectionStatus::ApUnsupported,
+        ConnectionStatus::RadioBlocked,
+        ConnectionStatus::Disconnected,
+    ];
+
+    /// Whether a connection attempt has settled one way or the other.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, ConnectionStatus::Connected | ConnectionStatus::Failed)
+    }
+}
+
 /// WiFi backend used by NetworkManager.
+///
+/// `snake_case` on the wire so the values read like NM's own backend names.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum WifiBackend {
@@ -32,18 +68,363 @@ impl WifiBackend {
             WifiBackend::WpaSupplicant => "wpa_supplicant",
         }
     }
+
+    /// systemd unit of the supplicant behind this backend.
+    pub fn service(self) -> &'static str {
+        match self {
+            WifiBackend::Iwd => "iwd.service",
+            WifiBackend::WpaSupplicant => "wpa_supplicant.service",
+        }
+    }
+}
+
+/// Stage of a running WiFi backend switch, in the order they run.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendSwitchStep {
+    /// Writing the NetworkManager config and stopping the old supplicant
+    StoppingOld,
+    StartingNew,
+    RestartingNetworkManager,
+}
+
+impl BackendSwitchStep {
+    pub const ALL: [BackendSwitchStep; 3] = [
+        BackendSwitchStep::StoppingOld,
+        BackendSwitchStep::StartingNew,
+        BackendSwitchStep::RestartingNetworkManager,
+    ];
+
+    /// What the step is doing, for logs and error messages.
+    pub fn describe(self) -> &'static str {
+        match self {
+            BackendSwitchStep::StoppingOld => "stopping the old WiFi service",
+            BackendSwitchStep::StartingNew => "starting the new WiFi service",
+            BackendSwitchStep::RestartingNetworkManager => "restarting NetworkManager",
+        }
+    }
+}
+
+/// `security_type` of an AP that only offers SAE, with no WPA2-PSK fallback.
+pub const WPA3_ONLY_SECURITY: &str = "WPA3";
+
+/// `security_type` of an OWE AP: encrypted, but joined without a password.
+pub const ENHANCED_OPEN_SECURITY: &str = "Enhanced Open";
+
+/// Classified reason a connection attempt failed.
+///
+/// Unit variants serialize as their name (`"WrongPassword"`), `Other` as
+/// `{"Other": "..."}`; the portal maps the names to messages.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, thiserror::Error)]
+pub enum ConnectError {
+    #[error("Incorrect password or missing secrets")]
+    WrongPassword,
+    #[error("Network not found in range")]
+    SsidNotFound,
+    #[error("Timed out while authenticating with the access point")]
+    AuthTimeout,
+    #[error("Failed to obtain an IP address via DHCP")]
+    DhcpFailed,
+    #[error("{0}")]
+    Other(String),
+}
+
+impl ConnectError {
+    /// Stable variant name for logs (matches the serialized tag).
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConnectError::WrongPassword => "WrongPassword",
+            ConnectError::SsidNotFound => "SsidNotFound",
+            ConnectError::AuthTimeout => "AuthTimeout",
+            ConnectError::DhcpFailed => "DhcpFailed",
+            ConnectError::Other(_) => "Other",
+        }
+    }
+
+    /// Advice for a failed join to a WPA3-only network, where older drivers and
+    /// supplicants fail SAE in ways that look like a bad password or timeout.
+    pub fn wpa3_only_hint(&self, backend: Option<WifiBackend>) -> Option<String> {
+        match self {
+            ConnectError::SsidNotFound | ConnectError::DhcpFailed => None,
+            _ => {
+                let mut hint = "This router is WPA3-only, which this adapter may not support. \
+                                Enable WPA2/WPA3 transitional mode on the router"
+                    .to_string();
+                if backend != Some(WifiBackend::Iwd) {
+                    hint.push_str(" or switch the WiFi backend to iwd");
+                }
+                hint.push('.');
+                Some(hint)
+            }
+        }
+    }
+}
+
+/// WiFi frequency band
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WifiBand {
+    #[serde(rename = "2.4GHz")]
+    Band2_4,
+    #[serde(rename = "5GHz")]
+    Band5,
+    #[serde(rename = "6GHz")]
+    Band6,
+}
+
+impl WifiBand {
+    pub const ALL: [WifiBand; 3] = [WifiBand::Band2_4, WifiBand::Band5, WifiBand::Band6];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WifiBand::Band2_4 => "2.4GHz",
+            WifiBand::Band5 => "5GHz",
+            WifiBand::Band6 => "6GHz",
+        }
+    }
+}
+
+/// Classify a channel center frequency (MHz) into its band.
+pub fn band_of(frequency: u32) -> Option<WifiBand> {
+    match frequency {
+        2400..=2500 => Some(WifiBand::Band2_4),
+        4900..=5924 => Some(WifiBand::Band5),
+        5925..=7125 => Some(WifiBand::Band6),
+        _ => None,
+    }
+}
+
+/// Parse a BSSID written as six colon-separated hex octets.
+pub fn parse_bssid(bssid: &str) -> Option<[u8; 6]> {
+    let mut octets = [0u8; 6];
+    let mut parts = bssid.trim().split(':');
+    for octet in &mut octets {
+        let part = parts.next()?;
+        if part.len() != 2 || !part.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        *octet = u8::from_str_radix(part, 16).ok()?;
+    }
+    parts.next().is_none().then_some(octets)
+}
+
+/// Format a BSSID the way NetworkManager reports `HwAddress`.
+pub fn format_bssid(octets: &[u8; 6]) -> String {
+    octets
+        .iter()
+        .map(|o| format!("{:02X}", o))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Client-certificate (EAP-TLS) credentials for WPA-Enterprise networks.
+///
+/// Paths are read by the daemon, so they must be absolute and readable by it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EapTls {
+    /// EAP identity; many servers match it against the certificate subject
+    #[serde(default)]
+    pub identity: Option<String>,
+    #[serde(default)]
+    pub ca_cert: Option<PathBuf>,
+    pub client_cert: PathBuf,
+    pub private_key: PathBuf,
+    #[serde(default)]
+    pub private_key_password: Option<String>,
+}
+
+impl EapTls {
+    /// Check that every referenced file can be opened by this process.
+    pub fn validate(&self) -> Result<()> {
+        let files = [
+            ("CA certificate", self.ca_cert.as_ref()),
+            ("client certificate", Some(&self.client_cert)),
+            ("private key", Some(&self.private_key)),
+        ];
+        for (what, path) in files {
+            let Some(path) = path else { continue };
+            std::fs::File::open(path)
+                .with_context(|| format!("Cannot read {} {}", what, path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Security the user picked for a network entered by hand.
+///
+/// A hidden network never shows up in a scan, so without this the profile's
+/// `key-mgmt` is guessed from whether a password was typed.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ManualSecurity {
+    Open,
+    Wpa2,
+    Wpa3,
+    Enterprise,
+    #[serde(rename = "owe")]
+    EnhancedOpen,
+}
+
+impl ManualSecurity {
+    pub fn label(self) -> &'static str {
+        match self {
+            ManualSecurity::Open => "Open",
+            ManualSecurity::Wpa2 => "WPA2",
+            ManualSecurity::Wpa3 => "WPA3",
+            ManualSecurity::Enterprise => "Enterprise",
+            ManualSecurity::EnhancedOpen => ENHANCED_OPEN_SECURITY,
+        }
+    }
+
+    /// NetworkManager `key-mgmt` for the profile; none for an open network.
+    pub fn key_mgmt(self) -> Option<&'static str> {
+        match self {
+            ManualSecurity::Open => None,
+            ManualSecurity::Wpa2 => Some("wpa-psk"),
+            ManualSecurity::Wpa3 => Some("sae"),
+            ManualSecurity::Enterprise => Some("wpa-eap"),
+            ManualSecurity::EnhancedOpen => Some("owe"),
+        }
+    }
+
+    /// Reject credentials that can't go with this security type. A blank
+    /// password for WPA2/WPA3 is let through on purpose.
+    pub fn check(self, password: &str, eap_tls: bool) -> Result<()> {
+        match self {
+            ManualSecurity::Open | ManualSecurity::EnhancedOpen
+                if !password.is_empty() || eap_tls =>
+            {
+                anyhow::bail!("{} networks take no password", self.label())
+            }
+            ManualSecurity::Wpa2 | ManualSecurity::Wpa3 if eap_tls => {
+                anyhow::bail!(
+                    "{} networks use a password, not a certificate",
+                    self.label()
+                )
+            }
+            ManualSecurity::Enterprise if !eap_tls => {
+                anyhow::bail!("Enterprise networks need a client certificate")
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 /// Information about a discovered WiFi network
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkInfo {
+    /// Display form of the SSID (lossy if the raw bytes aren't valid UTF-8)
     pub ssid: String,
+    /// Raw SSID bytes as broadcast by the access point
+    #[serde(default)]
+    pub ssid_bytes: Vec<u8>,
     pub bssid: String,
     pub signal_strength: u8, // 0-100
     pub frequency: u32,      // MHz
     pub channel: u8,
     pub is_secured: bool,
     pub security_type: String, // "WPA2", "WPA3", "WEP", "Open"
+    /// Access points merged into this entry; `bssid` is the strongest of them
+    #[serde(default = "default_bss_count")]
+    pub bss_count: usize,
+}
+
+fn default_bss_count() -> usize {
+    1
+}
+
+/// A device associated with the setup AP
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApClient {
+    pub mac: String,
+    /// Address handed out by dnsmasq, once the device has asked for one
+    pub ip: Option<String>,
+    /// Unix time the DHCP lease runs out (`None` for infinite leases)
+    pub lease_expiry: Option<u64>,
+}
+
+/// "N devices connected" line for status displays.
+pub fn ap_clients_summary(clients: &[ApClient]) -> String {
+    match clients.len() {
+        0 => "No devices connected".to_string(),
+        1 => "1 device connected".to_string(),
+        n => format!("{} devices connected", n),
+    }
+}
+
+/// Everything needed to get a user onto the setup AP and portal, for labels
+/// and secondary displays.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OnboardingInfo {
+    pub ap_ssid: String,
+    /// None while the setup AP is open, and wherever the passphrase is withheld.
+    pub ap_password: Option<String>,
+    /// Whether joining the AP takes a passphrase.
+    #[serde(default)]
+    pub ap_secured: bool,
+    pub portal_url: String,
+    pub portal_hostname: Option<String>,
+    /// `WIFI:` payload phones scan to join the AP; None when it would carry a
+    /// withheld passphrase.
+    pub wifi_qr: Option<String>,
+}
+
+impl OnboardingInfo {
+    pub fn new(
+        ap_ssid: &str,
+        ap_password: Option<&str>,
+        portal_url: &str,
+        portal_hostname: Option<&str>,
+    ) -> Self {
+        Self {
+            ap_ssid: ap_ssid.to_string(),
+            ap_password: ap_password.map(str::to_string),
+            ap_secured: ap_password.is_some(),
+            portal_url: portal_url.to_string(),
+            portal_hostname: portal_hostname.map(str::to_string),
+            wifi_qr: Some(wifi_qr_payload(ap_ssid, ap_password)),
+        }
+    }
+
+    /// The same instructions without the passphrase, for anyone not already
+    /// on the AP: snapshots reach IPC clients, D-Bus listeners and the
+    /// upstream LAN.
+    pub fn redacted(mut self) -> Self {
+        if self.ap_password.take().is_some() {
+            self.wifi_qr = None;
+        }
+        self
+    }
+}
+
+/// Whether client-supplied raw SSID bytes are a possible 802.11 SSID.
+pub fn valid_ssid_bytes(bytes: &[u8]) -> bool {
+    (1..=32).contains(&bytes.len())
+}
+
+/// Wi-Fi join QR payload (`WIFI:T:..;S:..;P:..;;`) with special characters escaped.
+pub fn wifi_qr_payload(ssid: &str, password: Option<&str>) -> String {
+    fn escape(value: &str) -> String {
+        let mut out = String::with_capacity(value.len());
+        for c in value.chars() {
+            if matches!(c, '\\' | ';' | ',' | ':' | '"') {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        out
+    }
+
+    match password {
+        Some(password) => format!("WIFI:T:WPA;S:{};P:{};;", escape(ssid), escape(password)),
+        None => format!("WIFI:T:nopass;S:{};;", escape(ssid)),
+    }
+}
+
+/// Connection counters exposed via the metrics endpoint
+#[derive(Debug, Default)]
+pub struct ConnectStats {
+    pub attempts: AtomicU64,
+    pub failures: AtomicU64,
 }
 
 /// Complete WiFi state
@@ -51,29 +432,238 @@ pub struct NetworkInfo {
 pub struct WifiState {
     pub status: ConnectionStatus,
     pub available_networks: Vec<NetworkInfo>,
+    pub network_tracker: NetworkListTracker,
     pub connected_ssid: Option<String>,
+    /// Live signal (0-100) of the current association
+    pub connected_signal: Option<u8>,
+    /// Live transmit bitrate (Mbit/s) of the current association
+    pub connected_rate: Option<u32>,
     pub connecting_to: Option<String>,
+    /// Id of the latest connect attempt, so clients can drop stale updates
+    pub current_attempt_id: Option<u64>,
+    /// When the running connect attempt gives up waiting on NetworkManager
+    pub connect_deadline: Option<Instant>,
     pub ap_running: bool,
     pub ap_ssid: Option<String>,
+    /// Passphrase of the setup AP, when it isn't open
+    pub ap_password: Option<String>,
+    /// Devices currently on the setup AP
+    pub ap_clients: Vec<ApClient>,
     pub portal_url: Option<String>,
+    pub portal_hostname: Option<String>,
     pub last_error: Option<String>,
+    pub connect_error: Option<ConnectError>,
     pub wifi_backend: Option<WifiBackend>,
+    /// Step of the running backend switch
+    pub backend_switch_step: Option<BackendSwitchStep>,
+    /// When the running backend switch gives up
+    pub backend_switch_deadline: Option<Instant>,
     pub last_scan: Option<Instant>,
+    /// Why the last scan failed; cleared by the next one that completes
+    pub last_scan_error: Option<String>,
+    /// SSIDs with credentials in the store; the passwords stay on disk
+    pub saved_ssids: HashSet<String>,
+    /// Recent connect failures, for the retry cooldown
+    pub recent_failures: FailureWindow,
+}
+
+#[cfg(test)]
+impl NetworkInfo {
+    /// A secured 2.4 GHz network for tests; only the SSID and signal vary.
+    pub(crate) fn test_fixture(ssid: &str, signal: u8) -> Self {
+        Self {
+            ssid: ssid.to_string(),
+            ssid_bytes: ssid.as_bytes().to_vec(),
+            bssid: "00:11:22:33:44:55".to_string(),
+            signal_strength: signal,
+            frequency: 2437,
+            channel: 6,
+            is_secured: true,
+            security_type: "WPA2".to_string(),
+            bss_count: 1,
+        }
+    }
+}
+
+impl NetworkInfo {
+    pub fn band(&self) -> Option<WifiBand> {
+        band_of(self.frequency)
+    }
+
+    /// Whether the AP only accepts WPA3 (SAE).
+    pub fn is_wpa3_only(&self) -> bool {
+        self.security_type == WPA3_ONLY_SECURITY
+    }
+
+    /// Whether the AP uses OWE, so the link is encrypted without a password.
+    pub fn is_enhanced_open(&self) -> bool {
+        self.security_type == ENHANCED_OPEN_SECURITY
+    }
+
+    /// Whether the network is strong enough to list under a `min_signal` display threshold.
+    pub fn meets_min_signal(&self, min_signal: u8) -> bool {
+        self.signal_strength >= min_signal
+    }
+
+    /// Raw SSID bytes, falling back to the display string when they weren't recorded.
+    pub fn raw_ssid(&self) -> &[u8] {
+        if self.ssid_bytes.is_empty() {
+            self.ssid.as_bytes()
+        } else {
+            &self.ssid_bytes
+        }
+    }
+}
+
+impl WifiState {
+    /// Join instructions for the running setup AP.
+    pub fn onboarding_info(&self) -> Option<OnboardingInfo> {
+        if !self.ap_running {
+            return None;
+        }
+        Some(OnboardingInfo::new(
+            self.ap_ssid.as_deref()?,
+            self.ap_password.as_deref(),
+            self.portal_url.as_deref()?,
+            self.portal_hostname.as_deref(),
+        ))
+    }
+
+    /// Merge fresh scan results into the smoothed, display-ordered network list.
+    pub fn apply_scan(&mut self, networks: Vec<NetworkInfo>) {
+        self.available_networks = self.network_tracker.merge(networks);
+        self.last_scan = Some(Instant::now());
+        self.last_scan_error = None;
+    }
+
+    /// Record a failed scan, so an empty list can be told apart from an
+    /// empty neighbourhood. The networks already listed stay.
+    pub fn fail_scan(&mut self, error: &anyhow::Error) {
+        self.last_scan_error = Some(format!("{:#}", error));
+    }
+
+    /// Whether a connect request can move straight to another network, i.e. we
+    /// are associated in station mode with no setup AP to tear down.
+    pub fn is_station_connected(&self) -> bool {
+        self.status == ConnectionStatus::Connected && !self.ap_running
+    }
+
+    /// Show a network from a scan that is still running.
+    ///
+    /// Only SSIDs not yet listed are added; `apply_scan` settles signal and order
+    /// once the scan completes. Returns whether the list changed.
+    pub fn add_partial_network(&mut self, network: NetworkInfo) -> bool {
+        if self
+            .available_networks
+            .iter()
+            .any(|n| n.raw_ssid() == network.raw_ssid())
+        {
+            return false;
+        }
+        self.available_networks.push(network);
+        true
+    }
+
+    /// Listed SSIDs that have a saved password, in list order.
+    pub fn saved_networks(&self) -> Vec<String> {
+        let mut saved: Vec<String> = Vec::new();
+        for network in &self.available_networks {
+            if self.saved_ssids.contains(&network.ssid) && !saved.contains(&network.ssid) {
+                saved.push(network.ssid.clone());
+            }
+        }
+        saved
+    }
+
+    /// Whether the last scan listed `ssid` as needing a password or key.
+    pub fn is_known_secured(&self, ssid: &str) -> bool {
+        self.available_networks
+            .iter()
+            .any(|n| n.ssid == ssid && n.is_secured)
+    }
+
+    /// BSSID of the strongest scanned access point for `ssid`, if any.
+    pub fn strongest_bssid_for(&self, ssid: &str) -> Option<String> {
+        self.available_networks
+            .iter()
+            .filter(|n| n.ssid == ssid && !n.bssid.is_empty())
+            .max_by_key(|n| n.signal_strength)
+            .map(|n| n.bssid.clone())
+    }
+
+    /// Resolve a typed SSID to the raw bytes to connect with.
+    ///
+    /// An exact byte match wins, so "Cafe" never lands on "Cafe " or "cafe";
+    /// the lossy display name is only consulted for non-UTF-8 SSIDs.
+    pub fn ssid_bytes_for(&self, ssid: &str) -> Vec<u8> {
+        let networks = &self.available_networks;
+        networks
+            .iter()
+            .find(|n| n.raw_ssid() == ssid.as_bytes())
+            .or_else(|| {
+                networks
+                    .iter()
+                    .find(|n| n.ssid == ssid && std::str::from_utf8(n.raw_ssid()).is_err())
+            })
+            .map(|n| n.raw_ssid().to_vec())
+            .unwrap_or_else(|| ssid.as_bytes().to_vec())
+    }
 }
 
 /// Serializable version of WifiState (for IPC/web)
+///
+/// The field names are a wire contract with the portal JS, IPC clients and
+/// `status --json`; `test_snapshot_json_schema` pins them. Add fields with
+/// `#[serde(default)]` so older peers still parse.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WifiStateSnapshot {
     pub status: ConnectionStatus,
     pub available_networks: Vec<NetworkInfo>,
     pub connected_ssid: Option<String>,
+    pub connected_signal: Option<u8>,
+    pub connected_rate: Option<u32>,
     pub connecting_to: Option<String>,
+    /// Id of the latest connect attempt, as returned by `/api/connect`
+    #[serde(default)]
+    pub current_attempt_id: Option<u64>,
+    /// Seconds until the running connect attempt times out
+    #[serde(default)]
+    pub connect_deadline_secs: Option<u64>,
     pub ap_running: bool,
     pub ap_ssid: Option<String>,
+    #[serde(default)]
+    pub ap_clients: Vec<ApClient>,
     pub portal_url: Option<String>,
+    pub portal_hostname: Option<String>,
     pub last_error: Option<String>,
+    pub connect_error: Option<ConnectError>,
     pub wifi_backend: Option<WifiBackend>,
+    /// Step of the running backend switch
+    #[serde(default)]
+    pub backend_switch_step: Option<BackendSwitchStep>,
+    /// Seconds until the running backend switch times out
+    #[serde(default)]
+    pub backend_switch_deadline_secs: Option<u64>,
     pub last_scan_secs_ago: Option<u64>,
+    /// Why the last scan failed, if it did
+    #[serde(default)]
+    pub last_scan_error: Option<String>,
+    /// Join instructions while the setup AP is up.
+    #[serde(default)]
+    pub onboarding: Option<OnboardingInfo>,
+    /// Listed SSIDs that can connect with a saved password
+    #[serde(default)]
+    pub saved_networks: Vec<String>,
+    /// Seconds until each SSID that failed repeatedly may be tried again
+    #[serde(default)]
+    pub connect_cooldowns: BTreeMap<String, u64>,
+}
+
+impl WifiStateSnapshot {
+    /// Whether `ssid` can be joined without asking for its password again.
+    pub fn has_saved(&self, ssid: &str) -> bool {
+        self.saved_networks.iter().any(|s| s == ssid)
+    }
 }
 
 impl From<&WifiState> for WifiStateSnapshot {
@@ -82,13 +672,431 @@ impl From<&WifiState> for WifiStateSnapshot {
             status: state.status.clone(),
             available_networks: state.available_networks.clone(),
             connected_ssid: state.connected_ssid.clone(),
+            connected_signal: state.connected_signal,
+            connected_rate: state.connected_rate,
             connecting_to: state.connecting_to.clone(),
+            current_attempt_id: state.current_attempt_id,
+            connect_deadline_secs: state
+                .connect_deadline
+                .map(|d| d.saturating_duration_since(Instant::now()).as_secs()),
             ap_running: state.ap_running,
             ap_ssid: state.ap_ssid.clone(),
+            ap_clients: state.ap_clients.clone(),
             portal_url: state.portal_url.clone(),
+            portal_hostname: state.portal_hostname.clone(),
             last_error: state.last_error.clone(),
+            connect_error: state.connect_error.clone(),
             wifi_backend: state.wifi_backend,
+            backend_switch_step: state.backend_switch_step,
+            backend_switch_deadline_secs: state
+                .backend_switch_deadline
+                .map(|d| d.saturating_duration_since(Instant::now()).as_secs()),
             last_scan_secs_ago: state.last_scan.map(|t| t.elapsed().as_secs()),
+            last_scan_error: state.last_scan_error.clone(),
+            onboarding: state.onboarding_info().map(OnboardingInfo::redacted),
+            saved_networks: state.saved_networks(),
+            connect_cooldowns: state
+                .recent_failures
+                .cooldowns(Instant::now())
+                .into_iter()
+                .map(|(ssid, remaining)| (ssid, whole_secs(remaining)))
+                .collect(),
         }
     }
 }
+
+/// Seconds left, rounded up so a cooldown never reads as 0s while it holds.
+pub(super) fn whole_secs(remaining: Duration) -> u64 {
+    remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_band_of() {
+        assert_eq!(band_of(2412), Some(WifiBand::Band2_4));
+        assert_eq!(band_of(2484), Some(WifiBand::Band2_4));
+        assert_eq!(band_of(5180), Some(WifiBand::Band5));
+        assert_eq!(band_of(5825), Some(WifiBand::Band5));
+        assert_eq!(band_of(5955), Some(WifiBand::Band6));
+        assert_eq!(band_of(0), None);
+    }
+
+    #[test]
+    fn test_partial_scan_then_final_pass() {
+        let network = |ssid: &str, signal| NetworkInfo {
+            ssid: ssid.to_string(),
+            ssid_bytes: ssid.as_bytes().to_vec(),
+            bssid: String::new(),
+            signal_strength: signal,
+            frequency: 2437,
+            channel: 6,
+            is_secured: true,
+            security_type: "WPA2".to_string(),
+            bss_count: 1,
+        };
+
+        let mut state = WifiState::default();
+        assert!(state.add_partial_network(network("Weak", 20)));
+        assert!(state.add_partial_network(network("Strong", 90)));
+        assert!(!state.add_partial_network(network("Weak", 40)));
+        assert_eq!(state.available_networks.len(), 2);
+
+        state.apply_scan(vec![network("Strong", 90), network("Weak", 40)]);
+        let ssids: Vec<_> = state
+            .available_networks
+            .iter()
+            .map(|n| n.ssid.as_str())
+            .collect();
+        assert_eq!(ssids, ["Strong", "Weak"]);
+        assert!(state.last_scan.is_some());
+
+        // A failed rescan keeps the list; the next good one clears the error.
+        state.fail_scan(&anyhow::anyhow!("scan timed out"));
+        assert_eq!(state.last_scan_error.as_deref(), Some("scan timed out"));
+        assert_eq!(state.available_networks.len(), 2);
+        state.apply_scan(Vec::new());
+        assert_eq!(state.last_scan_error, None);
+    }
+
+    #[test]
+    fn test_saved_networks_only_lists_visible_ssids() {
+        let network = |ssid: &str, frequency| NetworkInfo {
+            ssid: ssid.to_string(),
+            ssid_bytes: ssid.as_bytes().to_vec(),
+            bssid: String::new(),
+            signal_strength: 60,
+            frequency,
+            channel: 6,
+            is_secured: true,
+            security_type: "WPA2".to_string(),
+            bss_count: 1,
+        };
+        let state = WifiState {
+            available_networks: vec![
+                network("Cafe", 2437),
+                network("Home", 5180),
+                network("Home", 2437),
+            ],
+            saved_ssids: ["Home", "Attic"].map(String::from).into(),
+            ..Default::default()
+        };
+
+        let snapshot = WifiStateSnapshot::from(&state);
+        assert_eq!(snapshot.saved_networks, ["Home"]);
+        assert!(snapshot.has_saved("Home"));
+        assert!(!snapshot.has_saved("Attic"));
+        assert!(!snapshot.has_saved("Cafe"));
+    }
+
+    #[test]
+    fn test_parse_bssid() {
+        let octets = [0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc];
+        assert_eq!(parse_bssid("00:11:22:aa:bb:cc"), Some(octets));
+        assert_eq!(parse_bssid("00:11:22:AA:BB:CC"), Some(octets));
+        assert_eq!(format_bssid(&octets), "00:11:22:AA:BB:CC");
+
+        assert_eq!(parse_bssid(""), None);
+        assert_eq!(parse_bssid("00:11:22:aa:bb"), None);
+        assert_eq!(parse_bssid("00:11:22:aa:bb:cc:dd"), None);
+        assert_eq!(parse_bssid("00-11-22-aa-bb-cc"), None);
+        assert_eq!(parse_bssid("0:11:22:aa:bb:cc"), None);
+        assert_eq!(parse_bssid("00:11:22:aa:bb:zz"), None);
+        assert_eq!(parse_bssid("+0:11:22:aa:bb:cc"), None);
+    }
+
+    #[test]
+    fn test_eap_tls_validate() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert = dir.path().join("client.pem");
+        let key = dir.path().join("client.key");
+        std::fs::write(&cert, "cert").unwrap();
+        std::fs::write(&key, "key").unwrap();
+
+        let mut eap = EapTls {
+            identity: None,
+            ca_cert: None,
+            client_cert: cert,
+            private_key: key,
+            private_key_password: None,
+        };
+        assert!(eap.validate().is_ok());
+
+        eap.ca_cert = Some(dir.path().join("missing-ca.pem"));
+        let err = eap.validate().unwrap_err();
+        assert!(err.to_string().contains("CA certificate"));
+    }
+
+    #[test]
+    fn test_is_station_connected() {
+        let mut state = WifiState {
+            status: ConnectionStatus::Connected,
+            ..Default::default()
+        };
+        assert!(state.is_station_connected());
+
+        state.ap_running = true;
+        assert!(!state.is_station_connected());
+
+        state.ap_running = false;
+        state.status = ConnectionStatus::AwaitingCredentials;
+        assert!(!state.is_station_connected());
+    }
+
+    #[test]
+    fn test_meets_min_signal_boundaries() {
+        let network = |signal| NetworkInfo {
+            ssid: "Net".to_string(),
+            ssid_bytes: Vec::new(),
+            bssid: String::new(),
+            signal_strength: signal,
+            frequency: 2412,
+            channel: 1,
+            is_secured: true,
+            security_type: "WPA2".to_string(),
+            bss_count: 1,
+        };
+
+        assert!(network(0).meets_min_signal(0));
+        assert!(network(19).meets_min_signal(0));
+        assert!(!network(19).meets_min_signal(20));
+        assert!(network(20).meets_min_signal(20));
+        assert!(network(100).meets_min_signal(100));
+        assert!(!network(99).meets_min_signal(100));
+    }
+
+    #[test]
+    fn wpa3_only_hint_suggests_iwd_unless_already_used() {
+        let hint = ConnectError::WrongPassword
+            .wpa3_only_hint(Some(WifiBackend::WpaSupplicant))
+            .unwrap();
+        assert!(hint.contains("transitional mode"));
+        assert!(hint.contains("iwd"));
+
+        let hint = ConnectError::AuthTimeout
+            .wpa3_only_hint(Some(WifiBackend::Iwd))
+            .unwrap();
+        assert!(!hint.contains("iwd"));
+
+        assert_eq!(ConnectError::SsidNotFound.wpa3_only_hint(None), None);
+        assert_eq!(ConnectError::DhcpFailed.wpa3_only_hint(None), None);
+    }
+
+    #[test]
+    fn test_wifi_qr_payload_escapes() {
+        assert_eq!(
+            wifi_qr_payload("HyperRecovery", None),
+            "WIFI:T:nopass;S:HyperRecovery;;"
+        );
+        assert_eq!(
+            wifi_qr_payload("a;b,c", Some("p:w\\\"")),
+            "WIFI:T:WPA;S:a\\;b\\,c;P:p\\:w\\\\\\\";;"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_withholds_ap_password() {
+        let state = WifiState {
+            ap_running: true,
+            ap_ssid: Some("HyperRecovery".to_string()),
+            ap_password: Some("setup-pass".to_string()),
+            portal_url: Some("http://192.168.42.1".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            state.onboarding_info().unwrap().ap_password.as_deref(),
+            Some("setup-pass")
+        );
+
+        let snapshot = WifiStateSnapshot::from(&state);
+        let onboarding = snapshot.onboarding.as_ref().unwrap();
+        assert!(onboarding.ap_secured);
+        assert_eq!(onboarding.ap_password, None);
+        assert_eq!(onboarding.wifi_qr, None);
+        assert!(!serde_json::to_string(&snapshot)
+            .unwrap()
+            .contains("setup-pass"));
+    }
+
+    #[test]
+    fn test_snapshot_counts_down_to_connect_deadline() {
+        let mut state = WifiState {
+            status: ConnectionStatus::Connecting,
+            connect_deadline: Some(Instant::now() + std::time::Duration::from_secs(30)),
+            ..Default::default()
+        };
+        let secs = WifiStateSnapshot::from(&state).connect_deadline_secs;
+        assert!(matches!(secs, Some(29 | 30)), "{secs:?}");
+
+        // A deadline already passed reads as zero rather than going negative.
+        state.connect_deadline = Some(Instant::now());
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert_eq!(
+            WifiStateSnapshot::from(&state).connect_deadline_secs,
+            Some(0)
+        );
+
+        state.connect_deadline = None;
+        assert_eq!(WifiStateSnapshot::from(&state).connect_deadline_secs, None);
+    }
+
+    #[test]
+    fn test_snapshot_json_schema() {
+        let snapshot = WifiStateSnapshot {
+            status: ConnectionStatus::Connected,
+            available_networks: vec![NetworkInfo {
+                ssid: "Home".to_string(),
+                ssid_bytes: b"Home".to_vec(),
+                bssid: "AA:BB:CC:00:11:22".to_string(),
+                signal_strength: 70,
+                frequency: 2412,
+                channel: 1,
+                is_secured: true,
+                security_type: "WPA2".to_string(),
+                bss_count: 2,
+            }],
+            connected_ssid: Some("Home".to_string()),
+            connected_signal: Some(70),
+            connected_rate: Some(144),
+            connecting_to: Some("Home".to_string()),
+            current_attempt_id: Some(4),
+            connect_deadline_secs: Some(28),
+            ap_running: true,
+            ap_ssid: Some("HyperRecovery".to_string()),
+            ap_clients: vec![ApClient {
+                mac: "AA:BB:CC:00:11:33".to_string(),
+                ip: Some("192.168.42.10".to_string()),
+                lease_expiry: Some(1_700_000_000),
+            }],
+            portal_url: Some("http://192.168.42.1".to_string()),
+            portal_hostname: Some("hyper.setup".to_string()),
+            last_error: Some("boom".to_string()),
+            connect_error: Some(ConnectError::WrongPassword),
+            wifi_backend: Some(WifiBackend::WpaSupplicant),
+            backend_switch_step: Some(BackendSwitchStep::RestartingNetworkManager),
+            backend_switch_deadline_secs: Some(12),
+            last_scan_secs_ago: Some(3),
+            last_scan_error: None,
+            onboarding: Some(OnboardingInfo::new(
+                "HyperRecovery",
+                None,
+                "http://192.168.42.1",
+                None,
+            )),
+            saved_networks: vec!["Home".to_string()],
+            connect_cooldowns: BTreeMap::from([("Office".to_string(), 40)]),
+        };
+
+        let json = serde_json::to_value(&snapshot).unwrap();
+        let mut keys: Vec<_> = json.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                "ap_clients",
+                "ap_running",
+                "ap_ssid",
+                "available_networks",
+                "backend_switch_deadline_secs",
+                "backend_switch_step",
+                "connect_cooldowns",
+                "connect_deadline_secs",
+                "connect_error",
+                "connected_rate",
+                "connected_signal",
+                "connected_ssid",
+                "connecting_to",
+                "current_attempt_id",
+                "last_error",
+                "last_scan_error",
+                "last_scan_secs_ago",
+                "onboarding",
+                "portal_hostname",
+                "portal_url",
+                "saved_networks",
+                "status",
+                "wifi_backend",
+            ]
+        );
+        assert_eq!(json["status"], "Connected");
+        assert_eq!(json["connect_error"], "WrongPassword");
+        assert_eq!(json["wifi_backend"], "wpa_supplicant");
+        assert_eq!(json["backend_switch_step"], "restarting_network_manager");
+        assert_eq!(json["available_networks"][0]["signal_strength"], 70);
+        assert_eq!(json["ap_clients"][0]["ip"], "192.168.42.10");
+
+        let parsed: WifiStateSnapshot = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), json);
+    }
+
+    #[test]
+    fn test_status_and_error_wire_names() {
+        let names: Vec<_> = ConnectionStatus::ALL
+            .iter()
+            .map(|s| serde_json::to_value(s).unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Initializing",
+                "SwitchingBackend",
+                "Scanning",
+                "AwaitingCredentials",
+                "Connecting",
+                "Connected",
+                "Failed",
+                "ApUnsupported",
+                "RadioBlocked",
+                "Disconnected",
+            ]
+        );
+        for error in [
+            ConnectError::WrongPassword,
+            ConnectError::SsidNotFound,
+            ConnectError::AuthTimeout,
+            ConnectError::DhcpFailed,
+        ] {
+            assert_eq!(serde_json::to_value(&error).unwrap(), error.code());
+        }
+        assert_eq!(
+            serde_json::to_value(ConnectError::Other("x".to_string())).unwrap(),
+            serde_json::json!({ "Other": "x" })
+        );
+        assert_eq!(serde_json::to_value(WifiBackend::Iwd).unwrap(), "iwd");
+    }
+
+    #[test]
+    fn test_ssid_bytes_for_matches_exact_bytes() {
+        let network = |raw: &[u8]| NetworkInfo {
+            ssid: String::from_utf8_lossy(raw).into_owned(),
+            ssid_bytes: raw.to_vec(),
+            bssid: String::new(),
+            signal_strength: 50,
+            frequency: 2412,
+            channel: 1,
+            is_secured: true,
+            security_type: "WPA2".to_string(),
+            bss_count: 1,
+        };
+        let state = WifiState {
+            available_networks: vec![
+                network(b"Cafe "),
+                network(b"cafe"),
+                network(b"Cafe"),
+                network(b"Caf\xe9"),
+            ],
+            ..Default::default()
+        };
+
+        // Trailing space and case each select their own network.
+        assert_eq!(state.ssid_bytes_for("Cafe "), b"Cafe ");
+        assert_eq!(state.ssid_bytes_for("Cafe"), b"Cafe");
+        assert_eq!(state.ssid_bytes_for("cafe"), b"cafe");
+        // Unknown names pass through as typed (hidden networks).
+        assert_eq!(state.ssid_bytes_for("CAFE"), b"CAFE");
+        // A non-UTF-8 SSID is still reachable by its display name.
+        assert_eq!(state.ssid_bytes_for("Caf\u{FFFD}"), b"Caf\xe9");
+    }
+}
@@ -0,0 +1,123 @@
+//! Daemon log file
+//!
+//! With `--log-file`, tracing output is also written to disk so a failed
+//! setup can be investigated after a reboot on images without a persistent
+//! journal. Lines may name SSIDs, so the file is private to root. It is
+//! rotated to `<name>.1` once it reaches a size cap.
+
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// Size at which the log is rotated
+pub const MAX_LOG_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Size-capped log file with a single `.1` generation
+pub struct RotatingLogFile {
+    path: PathBuf,
+    max_bytes: u64,
+    file: File,
+    written: u64,
+}
+
+impl RotatingLogFile {
+    pub fn open<P: AsRef<Path>>(path: P, max_bytes: u64) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Failed to create log directory")?;
+        }
+        let file = open_private(&path)
+            .with_context(|| format!("Failed to open log file {}", path.display()))?;
+        let written = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            path,
+            max_bytes,
+            file,
+            written,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        rotate(&self.path)?;
+        self.file = open_private(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingLogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Move `path` aside to `<path>.1`, replacing the previous generation.
+pub fn rotate(path: &Path) -> io::Result<()> {
+    let mut rotated = path.to_path_buf().into_os_string();
+    rotated.push(".1");
+    fs::rename(path, rotated)
+}
+
+/// Open `path` for appending, readable by its owner only.
+///
+/// `mode` only applies when the file is created, so a file left behind with
+/// looser permissions is tightened as well.
+pub fn open_private(path: &Path) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_rotates_at_cap_with_private_files() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("logs/daemon.log");
+        let mut log = RotatingLogFile::open(&path, 16).unwrap();
+
+        log.write_all(b"first line\n").unwrap();
+        log.write_all(b"second line\n").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second line\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("logs/daemon.log.1")).unwrap(),
+            "first line\n"
+        );
+        for file in [path.clone(), dir.path().join("logs/daemon.log.1")] {
+            let mode = fs::metadata(file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_existing_file_is_made_private() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("daemon.log");
+        fs::write(&path, "old\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        RotatingLogFile::open(&path, MAX_LOG_BYTES).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
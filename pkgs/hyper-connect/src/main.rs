@@ -1,15 +1,27 @@
 //! Hyper Connect - WiFi configuration daemon with TUI and captive portal
 //!
-//! This binary provides three modes:
+//! Subcommands:
 //! - `daemon`: Runs the WiFi controller, AP, and web portal
 //! - `tui`: Connects to the daemon and provides a terminal UI
 //! - `status`: Quick status check (for scripts)
+//! - `connect`: Headless connect through the daemon (for scripts)
+//! - `priority`: Prefer a saved network when auto-connecting
+//! - `diagnostics`: Report adapters, backend and required tools (no daemon needed)
+//! - `export-creds`: Write the saved networks to a bundle for another machine
+//! - `import-creds`: Merge such a bundle into the saved networks
 
 mod controller;
+mod log_file;
 mod tui;
 mod web;
 
+use anyhow::Context;
 use clap::{Parser, Subcommand};
+use controller::credentials::CredentialsStore;
+use controller::ipc;
+use std::io::Read;
+use std::net::IpAddr;
+use std::path::PathBuf;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 #[derive(Parser)]
@@ -21,6 +33,8 @@ struct Cli {
 }
 
 #[derive(Subcommand)]
+// Parsed once at startup; boxing the daemon's flags would buy nothing.
+#[allow(clippy::large_enum_variant)]
 enum Commands {
     /// Run the WiFi setup daemon (controller + web portal)
     Daemon {
@@ -28,6 +42,10 @@ enum Commands {
         #[arg(long, default_value = "auto")]
         interface: String,
 
+        /// Second WiFi interface used for scanning while the AP is up ("auto", "none", or a name)
+        #[arg(long, default_value = "auto")]
+        sta_interface: String,
+
         /// AP SSID
         #[arg(long, default_value = "HyperRecovery")]
         ssid: String,
@@ -43,59 +61,659 @@ enum Commands {
         /// Grace period before starting AP (seconds)
         #[arg(long, default_value = "10")]
         grace_period: u64,
+
+        /// Before the grace period, rejoin the last connected network if it is
+        /// saved and shows up in a quick scan
+        #[arg(long)]
+        fast_reconnect: bool,
+
+        /// Hostname that resolves to the portal on the AP network (empty to disable)
+        #[arg(long, default_value = "hyper.setup")]
+        portal_hostname: String,
+
+        /// Keep running after connecting and restart the AP if connectivity is lost
+        #[arg(long)]
+        persist: bool,
+
+        /// In --persist mode, leave the setup AP up after connecting; needs a
+        /// second radio (--sta-interface) to join the upstream network
+        #[arg(long, requires = "persist")]
+        keep_ap_after_connect: bool,
+
+        /// Seconds connectivity may be lost in --persist mode before restarting the AP
+        #[arg(long, default_value = "30")]
+        link_loss_grace: u64,
+
+        /// Also auto-connect to networks NetworkManager already has saved WiFi profiles for
+        #[arg(long)]
+        use_nm_profiles: bool,
+
+        /// Seconds to spend trying every available saved network, in priority
+        /// order, before raising the AP (0 = only try the best one)
+        #[arg(long, default_value = "0")]
+        auto_connect_budget: u64,
+
+        /// Seconds before the portal treats the network list as stale and rescans
+        #[arg(long, default_value = "60")]
+        scan_ttl: u64,
+
+        /// Seconds a scan waits for fresh results; slow radios may need more
+        #[arg(long, default_value = "6", value_parser = clap::value_parser!(u64).range(1..))]
+        scan_timeout: u64,
+
+        /// Also expose the org.hyper.Recovery.WifiSetup service on the system bus
+        #[arg(long)]
+        dbus: bool,
+
+        /// Last octet of the first address handed out on the AP subnet
+        #[arg(long, default_value = "10", value_parser = clap::value_parser!(u8).range(2..=254))]
+        dhcp_start_octet: u8,
+
+        /// Last octet of the final address handed out on the AP subnet
+        #[arg(long, default_value = "250", value_parser = clap::value_parser!(u8).range(2..=254))]
+        dhcp_end_octet: u8,
+
+        /// DHCP lease time in dnsmasq syntax (e.g. "12h", "10m", "infinite")
+        #[arg(long, default_value = "12h", value_parser = parse_lease_arg)]
+        dhcp_lease: String,
+
+        /// DNS for AP clients: "captive" answers every name with the portal,
+        /// "passthrough" forwards queries to real resolvers
+        #[arg(long, default_value = "captive")]
+        ap_dns_mode: controller::ApDnsMode,
+
+        /// Resolver AP clients' queries go to in passthrough mode (repeatable);
+        /// without one dnsmasq uses the system's resolv.conf
+        #[arg(long, value_parser = parse_dns_upstream_arg)]
+        ap_dns_upstream: Vec<IpAddr>,
+
+        /// Don't record connect attempts in /var/log/hyper-connect/attempts.jsonl
+        #[arg(long = "no-audit", action = clap::ArgAction::SetFalse)]
+        audit: bool,
+
+        /// Band for the setup AP ("2.4", "5", or "auto" to prefer 5GHz when supported)
+        #[arg(long, default_value = "2.4")]
+        ap_band: controller::ApBand,
+
+        /// Protect the setup AP with this WPA passphrase (8-63 characters) instead of leaving it open
+        /// (prefer --ap-password-file to keep it out of process args)
+        #[arg(long, conflicts_with = "ap_password_file")]
+        ap_password: Option<String>,
+
+        /// Read the setup AP passphrase from a file ("-" for stdin)
+        #[arg(long)]
+        ap_password_file: Option<PathBuf>,
+
+        /// Setup AP security with a passphrase: "wpa2", "wpa3" or "wpa2-wpa3" (transitional);
+        /// WPA3 modes fall back to WPA2 when hostapd lacks SAE support
+        #[arg(long)]
+        ap_security: Option<controller::ApSecurity>,
+
+        /// Shut the AP down after this many seconds without clients or portal requests (0 = never)
+        #[arg(long, default_value = "0")]
+        ap_idle_timeout: u64,
+
+        /// Most devices that may join the setup AP at once (1-2007)
+        #[arg(long, value_parser = clap::value_parser!(u16).range(1..=2007))]
+        ap_max_clients: Option<u16>,
+
+        /// AP beacon interval in time units of 1.024ms (15-65535)
+        #[arg(long, value_parser = clap::value_parser!(u16).range(15..))]
+        ap_beacon_interval: Option<u16>,
+
+        /// Append the lines of this file verbatim to the generated hostapd config,
+        /// for adapters that need extra directives (ht_capab, wmm_enabled, ...)
+        #[arg(long)]
+        hostapd_extra: Option<PathBuf>,
+
+        /// Don't ask the portal user to confirm before joining an unencrypted network
+        #[arg(long)]
+        allow_open_silently: bool,
+
+        /// Heading shown by the portal and TUI
+        #[arg(long, default_value = controller::DEFAULT_PORTAL_TITLE)]
+        portal_title: String,
+
+        /// Line shown under the portal heading
+        #[arg(long, default_value = controller::DEFAULT_PORTAL_SUBTITLE)]
+        portal_subtitle: String,
+
+        /// Portal language ("en" or "de"); follows the browser's Accept-Language when unset
+        #[arg(long)]
+        lang: Option<web::Lang>,
+
+        /// HTTP URL probed when NetworkManager reports limited connectivity; a 2xx answer counts as online
+        #[arg(long, value_parser = parse_probe_url_arg)]
+        connectivity_probe: Option<String>,
+
+        /// Once connected, send captive-portal checks and the portal to this http(s) URL
+        /// instead of answering them as online
+        #[arg(long, value_parser = parse_success_url_arg)]
+        success_url: Option<String>,
+
+        /// Hide networks weaker than this signal percentage from the portal and TUI lists
+        #[arg(long, default_value = "0", value_parser = clap::value_parser!(u8).range(0..=100))]
+        min_signal: u8,
+
+        /// IP families to configure on the joined network ("dual", "v4" or "v6")
+        #[arg(long, default_value = "dual")]
+        ip_family: controller::IpFamily,
+
+        /// Networks the portal lists before a "Show all" control (0 = no limit)
+        #[arg(long, default_value = "20")]
+        portal_max_networks: usize,
+
+        /// Portal status poll interval in milliseconds while idle; the faster
+        /// connecting polls and the page reload scale with it
+        #[arg(long, default_value = "2500", value_parser = clap::value_parser!(u64).range(500..=60000))]
+        portal_poll_ms: u64,
+
+        /// Also serve the portal over HTTPS with a self-signed certificate generated
+        /// at startup; plain HTTP keeps answering captive-portal checks and redirects
+        #[arg(long)]
+        tls: bool,
+
+        /// HTTPS port used with --tls
+        #[arg(long, default_value = "443")]
+        tls_port: u16,
+
+        /// Also write the log to this file (rotated at 10 MiB, readable by root only)
+        #[arg(long)]
+        log_file: Option<PathBuf>,
+
+        /// Shell command to run once WiFi is connected; gets HYPER_SSID, HYPER_IFACE and HYPER_IP
+        #[arg(long)]
+        on_connected: Option<String>,
+
+        /// Unix socket path clients connect to
+        #[arg(long, default_value = ipc::DEFAULT_SOCKET_PATH)]
+        socket: String,
+
+        /// Pid file locked for the daemon's lifetime, so a second instance refuses to start
+        #[arg(long, default_value = controller::DEFAULT_PID_FILE)]
+        pid_file: PathBuf,
+
+        /// Octal permission mode of the IPC socket
+        #[arg(long, default_value = "0660", value_parser = ipc::parse_socket_mode)]
+        socket_mode: u32,
+
+        /// Group (name or gid) owning the IPC socket, so its members can use the TUI
+        #[arg(long)]
+        socket_group: Option<String>,
+
+        /// Write a random token next to the socket and refuse IPC clients
+        /// that don't present it
+        #[arg(long)]
+        ipc_token: bool,
+
+        /// IPC connections served at once; further clients are refused
+        #[arg(long, default_value = "16", value_parser = clap::value_parser!(u16).range(1..))]
+        ipc_max_clients: u16,
+
+        /// Simulate the AP and a fixed scan list without touching the radio or
+        /// NetworkManager; password "good" joins any secured network. Implies --no-audit
+        #[arg(long)]
+        dry_run: bool,
+
+        /// With --dry-run, scan results from this JSON list of networks instead of
+        /// the built-in ones, for demos and screenshots
+        #[arg(long, requires = "dry_run")]
+        demo_networks: Option<PathBuf>,
     },
 
     /// Run the TUI client (connects to daemon)
     Tui {
         /// Unix socket path for daemon communication
-        #[arg(long, default_value = "/run/hyper-connect.sock")]
+        #[arg(long, default_value = ipc::DEFAULT_SOCKET_PATH)]
         socket: String,
+
+        /// Don't ask for confirmation before joining an unencrypted network
+        #[arg(long)]
+        allow_open_silently: bool,
+
+        /// Dim the screen after this many seconds without input (off by default)
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        idle_dim_secs: Option<u64>,
+
+        /// Redraw and input poll interval in milliseconds; raise it on slow hardware
+        #[arg(long, default_value = "250", value_parser = clap::value_parser!(u64).range(50..=5000))]
+        tui_tick_ms: u64,
     },
 
     /// Check current status
     Status {
+        /// Print the daemon's status, including AP join instructions, as JSON
+        #[arg(long)]
+        json: bool,
+
         /// Unix socket path for daemon communication
-        #[arg(long, default_value = "/run/hyper-connect.sock")]
+        #[arg(long, default_value = ipc::DEFAULT_SOCKET_PATH)]
         socket: String,
     },
+
+    /// Connect to a network through the daemon and wait for the result
+    Connect {
+        /// Network SSID
+        ssid: String,
+
+        /// Network password, or the private key password with --client-cert
+        /// (prefer --password-file to keep it out of process args)
+        #[arg(long, conflicts_with = "password_file")]
+        password: Option<String>,
+
+        /// Read the password from a file ("-" for stdin)
+        #[arg(long)]
+        password_file: Option<PathBuf>,
+
+        /// Don't save the credentials for auto-connect
+        #[arg(long = "no-save", action = clap::ArgAction::SetFalse)]
+        save: bool,
+
+        /// The network is hidden (doesn't broadcast its SSID)
+        #[arg(long)]
+        hidden: bool,
+
+        /// Connect to this access point only (six colon-separated hex octets)
+        #[arg(long, value_parser = parse_bssid_arg)]
+        bssid: Option<String>,
+
+        /// Client certificate for EAP-TLS networks
+        #[arg(long, requires = "private_key")]
+        client_cert: Option<PathBuf>,
+
+        /// Private key for --client-cert
+        #[arg(long, requires = "client_cert")]
+        private_key: Option<PathBuf>,
+
+        /// CA certificate used to verify the EAP-TLS server
+        #[arg(long, requires = "client_cert")]
+        ca_cert: Option<PathBuf>,
+
+        /// EAP identity for EAP-TLS networks
+        #[arg(long, requires = "client_cert")]
+        identity: Option<String>,
+
+        /// Seconds to wait for the connection to succeed or fail
+        #[arg(long, default_value = "120")]
+        timeout: u64,
+
+        /// Unix socket path for daemon communication
+        #[arg(long, default_value = ipc::DEFAULT_SOCKET_PATH)]
+        socket: String,
+    },
+
+    /// Report wireless adapters, backend and required tools for troubleshooting
+    Diagnostics {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Set the auto-connect priority of a saved network
+    Priority {
+        /// Network SSID (must have saved credentials)
+        ssid: String,
+
+        /// Higher values are preferred over stronger signals; 0 is the default
+        #[arg(allow_hyphen_values = true)]
+        priority: i32,
+
+        /// Unix socket path for daemon communication
+        #[arg(long, default_value = ipc::DEFAULT_SOCKET_PATH)]
+        socket: String,
+    },
+
+    /// Write the saved networks to a bundle another machine can import
+    ExportCreds {
+        /// Bundle to write (created readable by root only)
+        path: PathBuf,
+
+        /// Leave passwords out, keeping only which networks are known and how they rank
+        #[arg(long)]
+        redact: bool,
+    },
+
+    /// Merge a bundle from export-creds into the saved networks
+    ImportCreds {
+        /// Bundle to read
+        path: PathBuf,
+
+        /// Let the bundle's entry replace a network saved on both sides; by
+        /// default the one with more successful connections wins
+        #[arg(long)]
+        overwrite: bool,
+    },
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
+    let cli = Cli::parse();
+
+    // Initialize tracing; the file writer's guard flushes it on exit.
+    let (file_layer, _log_guard) = match &cli.command {
+        Commands::Daemon {
+            log_file: Some(path),
+            ..
+        } => {
+            let file = log_file::RotatingLogFile::open(path, log_file::MAX_LOG_BYTES)?;
+            let (writer, guard) = tracing_appender::non_blocking(file);
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer);
+            (Some(layer), Some(guard))
+        }
+        _ => (None, None),
+    };
     tracing_subscriber::registry()
         .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
         .with(tracing_subscriber::fmt::layer())
+        .with(file_layer)
         .init();
 
-    let cli = Cli::parse();
-
     match cli.command {
         Commands::Daemon {
             interface,
+            sta_interface,
             ssid,
             ap_ip,
             port,
             grace_period,
+            fast_reconnect,
+            portal_hostname,
+            persist,
+            keep_ap_after_connect,
+            link_loss_grace,
+            use_nm_profiles,
+            auto_connect_budget,
+            scan_ttl,
+            scan_timeout,
+            dbus,
+            dhcp_start_octet,
+            dhcp_end_octet,
+            dhcp_lease,
+            ap_dns_mode,
+            ap_dns_upstream,
+            audit,
+            ap_band,
+            ap_password,
+            ap_password_file,
+            ap_security,
+            ap_idle_timeout,
+            ap_max_clients,
+            ap_beacon_interval,
+            hostapd_extra,
+            allow_open_silently,
+            portal_title,
+            portal_subtitle,
+            lang,
+            connectivity_probe,
+            success_url,
+            min_signal,
+            ip_family,
+            portal_max_networks,
+            portal_poll_ms,
+            tls,
+            tls_port,
+            log_file: _,
+            on_connected,
+            socket,
+            pid_file,
+            socket_mode,
+            socket_group,
+            ipc_token,
+            ipc_max_clients,
+            dry_run,
+            demo_networks,
         } => {
+            let ap_password = match (ap_password, ap_password_file) {
+                (Some(password), _) => Some(password),
+                (None, Some(path)) => Some(read_password_file(&path)?),
+                (None, None) => None,
+            };
+            let demo_networks = demo_networks
+                .as_deref()
+                .map(controller::load_demo_networks)
+                .transpose()?;
+            let hostapd_extra = hostapd_extra
+                .as_deref()
+                .map(controller::load_hostapd_extra)
+                .transpose()?;
+            if !ap_dns_upstream.is_empty() && ap_dns_mode != controller::ApDnsMode::Passthrough {
+                anyhow::bail!("--ap-dns-upstream needs --ap-dns-mode passthrough");
+            }
+            if ap_security.is_some() && ap_password.is_none() {
+                anyhow::bail!("--ap-security needs --ap-password or --ap-password-file");
+            }
+
             tracing::info!("Starting Hyper Connect daemon");
             controller::run_daemon(controller::DaemonConfig {
                 interface,
+                sta_interface,
                 ssid,
                 ap_ip,
                 port,
                 grace_period,
+                fast_reconnect,
+                portal_hostname,
+                persist,
+                keep_ap_after_connect,
+                link_loss_grace,
+                use_nm_profiles,
+                auto_connect_budget,
+                scan_ttl,
+                scan_timeout,
+                dbus,
+                dhcp: controller::DhcpRange {
+                    start_octet: dhcp_start_octet,
+                    end_octet: dhcp_end_octet,
+                    lease: dhcp_lease,
+                },
+                ap_dns_mode,
+                ap_dns_upstreams: ap_dns_upstream,
+                audit: audit && !dry_run,
+                ap_band,
+                ap_password,
+                ap_security: ap_security.unwrap_or_default(),
+                ap_idle_timeout,
+                ap_max_clients,
+                ap_beacon_interval,
+                hostapd_extra,
+                allow_open_silently,
+                portal_title,
+                portal_subtitle,
+                portal_lang: lang,
+                connectivity_probe,
+                success_url,
+                min_signal,
+                ip_family,
+                portal_max_networks,
+                portal_poll_ms,
+                demo_networks,
+                tls,
+                tls_port,
+                on_connected,
+                socket_path: socket,
+                pid_file,
+                socket_mode,
+                socket_group,
+                ipc_token,
+                ipc_max_clients: ipc_max_clients.into(),
+                simulate: dry_run,
             })
             .await?;
         }
-        Commands::Tui { socket } => {
+        Commands::Tui {
+            socket,
+            allow_open_silently,
+            idle_dim_secs,
+            tui_tick_ms,
+        } => {
             tracing::info!("Starting TUI client");
-            tui::run_tui(&socket).await?;
+            let idle_dim = idle_dim_secs.map(std::time::Duration::from_secs);
+            let tick_rate = std::time::Duration::from_millis(tui_tick_ms);
+            tui::run_tui(&socket, !allow_open_silently, idle_dim, tick_rate).await?;
         }
-        Commands::Status { socket } => {
-            controller::print_status(&socket).await?;
+        Commands::Status { json, socket } => {
+            controller::print_status(&socket, json).await?;
+        }
+        Commands::Connect {
+            ssid,
+            password,
+            password_file,
+            save,
+            hidden,
+            bssid,
+            client_cert,
+            private_key,
+            ca_cert,
+            identity,
+            timeout,
+            socket,
+        } => {
+            let password = match (password, password_file) {
+                (Some(password), _) => password,
+                (None, Some(path)) => read_password_file(&path)?,
+                (None, None) => String::new(),
+            };
+
+            // The daemon resolves paths from its own working directory.
+            let absolute = |path: PathBuf| {
+                std::path::absolute(&path)
+                    .with_context(|| format!("Invalid path {}", path.display()))
+            };
+            let (password, eap_tls) = match (client_cert, private_key) {
+                (Some(client_cert), Some(private_key)) => {
+                    let eap_tls = controller::EapTls {
+                        identity,
+                        ca_cert: ca_cert.map(absolute).transpose()?,
+                        client_cert: absolute(client_cert)?,
+                        private_key: absolute(private_key)?,
+                        private_key_password: (!password.is_empty()).then_some(password),
+                    };
+                    (String::new(), Some(eap_tls))
+                }
+                _ => (password, None),
+            };
+
+            let request = ipc::ConnectParams {
+                ssid,
+                password,
+                save,
+                hidden,
+                bssid,
+                eap_tls,
+                dry_run: false,
+                ssid_bytes: None,
+                security: None,
+                use_saved: false,
+            };
+            controller::run_connect(&socket, &request, std::time::Duration::from_secs(timeout))
+                .await?;
+        }
+        Commands::Diagnostics { json } => {
+            controller::print_diagnostics(json).await?;
+        }
+        Commands::Priority {
+            ssid,
+            priority,
+            socket,
+        } => {
+            ipc::send_set_priority(&socket, &ssid, priority).await?;
+            println!("Set priority of {} to {}", ssid, priority);
+        }
+        Commands::ExportCreds { path, redact } => {
+            let mut store = CredentialsStore::load()?;
+            if redact {
+                store = store.redacted();
+            }
+            store.save_to(&path)?;
+            println!(
+                "Exported {} networks to {}",
+                store.networks.len(),
+                path.display()
+            );
+        }
+        Commands::ImportCreds { path, overwrite } => {
+            // load_from treats a missing file as an empty store.
+            if !path.exists() {
+                anyhow::bail!("{} does not exist", path.display());
+            }
+            let bundle = CredentialsStore::load_from(&path)?;
+            let mut store = CredentialsStore::load()?;
+            let summary = store.merge(bundle, overwrite);
+            store.save()?;
+            println!(
+                "Imported {}: {} added, {} updated, {} kept, {} skipped without a password",
+                path.display(),
+                summary.added,
+                summary.updated,
+                summary.kept,
+                summary.skipped
+            );
         }
     }
 
     Ok(())
 }
+
+fn parse_bssid_arg(value: &str) -> Result<String, String> {
+    controller::parse_bssid(value)
+        .map(|_| value.to_string())
+        .ok_or_else(|| {
+            "expected six colon-separated hex octets, e.g. 00:11:22:aa:bb:cc".to_string()
+        })
+}
+
+fn parse_lease_arg(value: &str) -> Result<String, String> {
+    if controller::is_valid_lease(value) {
+        Ok(value.to_string())
+    } else {
+        Err("expected seconds with an optional s/m/h/d/w suffix, or \"infinite\"".to_string())
+    }
+}
+
+fn parse_probe_url_arg(value: &str) -> Result<String, String> {
+    if controller::is_valid_probe_url(value) {
+        Ok(value.to_string())
+    } else {
+        Err("expected an http:// URL with a host".to_string())
+    }
+}
+
+fn parse_dns_upstream_arg(value: &str) -> Result<IpAddr, String> {
+    let ip: IpAddr = value
+        .parse()
+        .map_err(|_| "expected an IPv4 or IPv6 address".to_string())?;
+    let broadcast = ip == IpAddr::from(std::net::Ipv4Addr::BROADCAST);
+    if ip.is_unspecified() || ip.is_multicast() || broadcast {
+        return Err("expected the unicast address of a resolver".to_string());
+    }
+    Ok(ip)
+}
+
+fn parse_success_url_arg(value: &str) -> Result<String, String> {
+    if web::is_valid_success_url(value) {
+        Ok(value.to_string())
+    } else {
+        Err("expected an http:// or https:// URL with a host".to_string())
+    }
+}
+
+/// Read a password from a file, or stdin when the path is "-".
+///
+/// Only the trailing line ending is stripped; passphrases may contain spaces.
+fn read_password_file(path: &std::path::Path) -> anyhow::Result<String> {
+    let mut content = String::new();
+    if path.as_os_str() == "-" {
+        std::io::stdin()
+            .read_to_string(&mut content)
+            .context("Failed to read password from stdin")?;
+    } else {
+        content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read password file {}", path.display()))?;
+    }
+
+    let trimmed = content.strip_suffix('\n').unwrap_or(&content);
+    let trimmed = trimmed.strip_suffix('\r').unwrap_or(trimmed);
+    Ok(trimmed.to_string())
+}
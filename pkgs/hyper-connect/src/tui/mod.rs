@@ -3,77 +3,381 @@
 mod ui;
 mod widgets;
 
-use crate::controller::{ipc, ConnectionStatus, WifiBackend, WifiStateSnapshot};
+use crate::controller::{
+    history::StateTransition, ipc, ConnectionStatus, ManualSecurity, NetworkInfo, WifiBackend,
+    WifiBand, WifiStateSnapshot,
+};
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
+use std::collections::{HashMap, VecDeque};
 use std::io;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+/// Percentage points `+`/`-` move the signal threshold by.
+const MIN_SIGNAL_STEP: i16 = 5;
+
+/// Signal samples kept per SSID for the details sparkline.
+const SIGNAL_HISTORY_LEN: usize = 24;
 
 /// TUI application state
 pub struct App {
     socket_path: String,
+    daemon: Option<ipc::DaemonInfo>,
     state: Option<WifiStateSnapshot>,
+    /// Recent status transitions, oldest first.
+    history: Vec<StateTransition>,
+    /// Daemon settings, for the portal address before the AP reports one.
+    config: Option<ipc::ResolvedConfig>,
+    /// Adapter problems the daemon sees, shown while no networks are listed.
+    adapter_problems: Vec<String>,
+    /// Recent signal strengths per SSID, oldest first, one sample per refresh.
+    signal_history: HashMap<String, VecDeque<u8>>,
     selected_network: usize,
+    band_filter: Option<WifiBand>,
+    /// Networks below this signal percentage are left out of the list.
+    min_signal: u8,
+    /// (SSID, BSSID) the next connect to that SSID is pinned to.
+    locked_ap: Option<(String, String)>,
     input_mode: InputMode,
+    /// Ask before joining an unencrypted network.
+    confirm_open: bool,
+    manual_ssid: String,
+    /// Security picked for the manual entry; `None` guesses from the password.
+    manual_security: Option<ManualSecurity>,
     password_input: String,
     password_visible: bool,
     error_message: Option<String>,
+    /// Credentials from a test connect, kept only until the user decides on saving them.
+    pending_save: Option<PendingSave>,
+    /// Dim the screen after this long without input.
+    idle_dim: Option<Duration>,
+    last_input: Instant,
     should_quit: bool,
 }
 
+/// A dry-run connect awaiting its outcome and then the save prompt.
+struct PendingSave {
+    ssid: String,
+    /// The daemon's id for this attempt, once it shows up in the status.
+    attempt_id: Option<u64>,
+    /// The daemon has picked the attempt up, so a failure belongs to it.
+    started: bool,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 enum InputMode {
     Normal,
     Password,
     ManualSsid,
+    /// Waiting for the user to accept joining an unencrypted network.
+    ConfirmOpen,
+    /// A test connect succeeded; asking whether to keep the credentials.
+    ConfirmSave,
 }
 
 impl App {
-    fn new(socket_path: String) -> Self {
+    fn new(socket_path: String, confirm_open: bool, idle_dim: Option<Duration>) -> Self {
         Self {
             socket_path,
+            daemon: None,
             state: None,
+            history: Vec::new(),
+            config: None,
+            adapter_problems: Vec::new(),
+            signal_history: HashMap::new(),
             selected_network: 0,
+            band_filter: None,
+            min_signal: 0,
+            locked_ap: None,
             input_mode: InputMode::Normal,
+            confirm_open,
+            manual_ssid: String::new(),
+            manual_security: None,
             password_input: String::new(),
             password_visible: false,
             error_message: None,
+            pending_save: None,
+            idle_dim,
+            last_input: Instant::now(),
             should_quit: false,
         }
     }
 
+    /// Idle long enough that the screen is dimmed to spare an unattended display.
+    fn is_dimmed(&self) -> bool {
+        self.idle_dim
+            .is_some_and(|idle| self.last_input.elapsed() >= idle)
+    }
+
     async fn refresh_state(&mut self) {
         match ipc::get_status(&self.socket_path).await {
             Ok(state) => {
+                self.record_signals(&state.available_networks);
                 self.state = Some(state);
                 self.error_message = None;
+                self.track_pending_save();
             }
             Err(e) => {
                 self.error_message = Some(format!("Failed to connect to daemon: {}", e));
+                return;
+            }
+        }
+        if self
+            .daemon
+            .as_ref()
+            .is_some_and(|d| d.supports("GetHistory"))
+        {
+            if let Ok(history) = ipc::get_history(&self.socket_path).await {
+                self.history = history;
+            }
+        }
+    }
+
+    /// Add this refresh's signal for each listed SSID; SSIDs that dropped out
+    /// of the scan lose their history.
+    fn record_signals(&mut self, networks: &[NetworkInfo]) {
+        let mut strongest: HashMap<&str, u8> = HashMap::new();
+        for network in networks {
+            let signal = strongest.entry(&network.ssid).or_default();
+            *signal = (*signal).max(network.signal_strength);
+        }
+
+        self.signal_history
+            .retain(|ssid, _| strongest.contains_key(ssid.as_str()));
+        for (ssid, signal) in strongest {
+            let samples = self.signal_history.entry(ssid.to_string()).or_default();
+            if samples.len() == SIGNAL_HISTORY_LEN {
+                samples.pop_front();
+            }
+            samples.push_back(signal);
+        }
+    }
+
+    fn signal_samples(&self, ssid: &str) -> Vec<u8> {
+        self.signal_history
+            .get(ssid)
+            .map(|samples| samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Follow a test connect: prompt to save once it's connected, forget the
+    /// password if it fails.
+    fn track_pending_save(&mut self) {
+        let (Some(pending), Some(state)) = (self.pending_save.as_mut(), self.state.as_ref()) else {
+            return;
+        };
+        match state.status {
+            ConnectionStatus::Connecting => {
+                pending.started = true;
+                pending.attempt_id = state.current_attempt_id;
             }
+            ConnectionStatus::Connected
+                if state.connected_ssid.as_deref() == Some(pending.ssid.as_str()) =>
+            {
+                self.input_mode = InputMode::ConfirmSave;
+            }
+            ConnectionStatus::Failed if pending.started => self.pending_save = None,
+            _ => {}
+        }
+    }
+
+    /// Answer the save prompt; the daemon drops the password if we don't save.
+    async fn finish_pending_save(&mut self, save: bool) {
+        self.input_mode = InputMode::Normal;
+        let Some(pending) = self.pending_save.take() else {
+            return;
+        };
+        if save {
+            if let Err(e) =
+                ipc::send_save_credential(&self.socket_path, &pending.ssid, pending.attempt_id)
+                    .await
+            {
+                self.error_message = Some(format!("Saving credentials failed: {}", e));
+            }
+        }
+    }
+
+    /// Networks passing the band and signal filters, in display order.
+    fn visible_networks(&self) -> Vec<&NetworkInfo> {
+        self.state
+            .as_ref()
+            .map(|s| {
+                s.available_networks
+                    .iter()
+                    .filter(|n| self.band_filter.is_none() || n.band() == self.band_filter)
+                    .filter(|n| n.meets_min_signal(self.min_signal))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn selected(&self) -> Option<&NetworkInfo> {
+        self.visible_networks().get(self.selected_network).copied()
+    }
+
+    /// Whether the daemon holds a password for the selected network.
+    fn selected_has_saved(&self) -> bool {
+        match (&self.state, self.selected()) {
+            (Some(state), Some(network)) => state.has_saved(&network.ssid),
+            _ => false,
         }
     }
 
     fn selected_ssid(&self) -> Option<String> {
-        self.state.as_ref().and_then(|s| {
-            s.available_networks
-                .get(self.selected_network)
-                .map(|n| n.ssid.clone())
-        })
+        self.selected().map(|n| n.ssid.clone())
+    }
+
+    /// Cycle all bands -> 2.4GHz -> 5GHz -> 6GHz -> all bands.
+    fn cycle_band_filter(&mut self) {
+        self.band_filter = match self.band_filter {
+            None => Some(WifiBand::ALL[0]),
+            Some(band) => WifiBand::ALL
+                .iter()
+                .position(|b| *b == band)
+                .and_then(|i| WifiBand::ALL.get(i + 1))
+                .copied(),
+        };
+        self.selected_network = 0;
+    }
+
+    /// Raise or lower the signal threshold by `step` percentage points, within 0-100.
+    fn adjust_min_signal(&mut self, step: i16) {
+        self.min_signal = (self.min_signal as i16 + step).clamp(0, 100) as u8;
+        self.selected_network = 0;
+    }
+
+    /// Pin the selected network's access point, or release the pin if it's already set.
+    fn toggle_lock(&mut self) {
+        let Some(network) = self.selected() else {
+            return;
+        };
+        let target = (network.ssid.clone(), network.bssid.clone());
+        if self.locked_ap.as_ref() == Some(&target) {
+            self.locked_ap = None;
+        } else {
+            self.locked_ap = Some(target);
+        }
+    }
+
+    /// BSSID the connection to `ssid` is pinned to, if any.
+    fn locked_bssid_for(&self, ssid: &str) -> Option<&str> {
+        self.locked_ap
+            .as_ref()
+            .filter(|(locked_ssid, _)| locked_ssid == ssid)
+            .map(|(_, bssid)| bssid.as_str())
+    }
+
+    /// SSID the password prompt applies to: the manually entered one, if any.
+    fn target_ssid(&self) -> Option<String> {
+        if self.manual_ssid.is_empty() {
+            self.selected_ssid()
+        } else {
+            Some(self.manual_ssid.clone())
+        }
+    }
+
+    /// Step the manual entry's security through Auto, Open, WPA2, WPA3 and
+    /// Enhanced Open.
+    /// Enterprise needs a certificate, which the TUI can't supply.
+    fn cycle_manual_security(&mut self, forward: bool) {
+        let choices = [
+            None,
+            Some(ManualSecurity::Open),
+            Some(ManualSecurity::Wpa2),
+            Some(ManualSecurity::Wpa3),
+            Some(ManualSecurity::EnhancedOpen),
+        ];
+        let current = choices
+            .iter()
+            .position(|c| *c == self.manual_security)
+            .unwrap_or(0);
+        let next = if forward {
+            (current + 1) % choices.len()
+        } else {
+            (current + choices.len() - 1) % choices.len()
+        };
+        self.manual_security = choices[next];
     }
 
-    async fn connect_to_selected(&mut self) {
-        if let Some(ssid) = self.selected_ssid() {
-            // TUI always saves credentials by default
-            match ipc::send_connect(&self.socket_path, &ssid, &self.password_input, true).await {
+    fn cancel_input(&mut self) {
+        self.input_mode = InputMode::Normal;
+        self.manual_ssid.clear();
+        self.manual_security = None;
+        self.password_input.clear();
+    }
+
+    /// Connect without a password, after a confirmation step if required.
+    /// Enhanced Open is still encrypted, so it skips the warning.
+    async fn connect_open(&mut self) {
+        self.password_input.clear();
+        let enhanced_open = if self.manual_ssid.is_empty() {
+            self.selected().is_some_and(|n| n.is_enhanced_open())
+        } else {
+            self.manual_security == Some(ManualSecurity::EnhancedOpen)
+        };
+        if self.confirm_open && !enhanced_open {
+            self.input_mode = InputMode::ConfirmOpen;
+        } else {
+            self.connect_to_selected(false).await;
+        }
+    }
+
+    /// Connect to the target network; a `dry_run` only saves the credentials
+    /// if the user confirms once connected.
+    async fn connect_to_selected(&mut self, dry_run: bool) {
+        if dry_run
+            && !self
+                .daemon
+                .as_ref()
+                .is_some_and(|d| d.supports("SaveCredential"))
+        {
+            self.error_message = Some("Daemon cannot test credentials without saving".to_string());
+            return;
+        }
+        if let Some(ssid) = self.target_ssid() {
+            // Manually entered networks are usually hidden ones.
+            let hidden = !self.manual_ssid.is_empty();
+            let bssid = if hidden {
+                None
+            } else {
+                self.locked_bssid_for(&ssid).map(str::to_string)
+            };
+            let ssid_bytes = if hidden {
+                None
+            } else {
+                self.selected().map(|n| n.raw_ssid().to_vec())
+            };
+            // Submitting nothing for a saved network reuses its password.
+            let use_saved =
+                !hidden && !dry_run && self.password_input.is_empty() && self.selected_has_saved();
+            let params = ipc::ConnectParams {
+                ssid,
+                password: self.password_input.clone(),
+                // Saved by default unless the user is only testing the password
+                save: !dry_run,
+                hidden,
+                bssid,
+                eap_tls: None,
+                dry_run,
+                ssid_bytes,
+                security: if hidden { self.manual_security } else { None },
+                use_saved,
+            };
+            match ipc::send_connect(&self.socket_path, &params).await {
                 Ok(()) => {
-                    self.input_mode = InputMode::Normal;
-                    self.password_input.clear();
+                    self.pending_save = dry_run.then_some(PendingSave {
+                        ssid: params.ssid,
+                        attempt_id: None,
+                        started: false,
+                    });
+                    self.cancel_input();
                 }
                 Err(e) => {
                     self.error_message = Some(format!("Connection failed: {}", e));
@@ -83,6 +387,13 @@ impl App {
     }
 
     async fn switch_backend(&mut self) {
+        if let Some(daemon) = &self.daemon {
+            if !daemon.supports("SwitchBackend") {
+                self.error_message = Some("Daemon does not support backend switching".to_string());
+                return;
+            }
+        }
+
         // Toggle between iwd and wpa_supplicant
         let current_backend = self
             .state
@@ -104,10 +415,50 @@ impl App {
             }
         }
     }
+
+    /// Whether the daemon is in the middle of a connect attempt.
+    fn is_connecting(&self) -> bool {
+        self.state
+            .as_ref()
+            .is_some_and(|s| s.status == ConnectionStatus::Connecting)
+    }
+
+    async fn cancel_connect(&mut self) {
+        if let Some(daemon) = &self.daemon {
+            if !daemon.supports("CancelConnect") {
+                self.error_message =
+                    Some("Daemon does not support cancelling a connection".to_string());
+                return;
+            }
+        }
+
+        match ipc::send_cancel_connect(&self.socket_path).await {
+            Ok(()) => self.refresh_state().await,
+            Err(e) => self.error_message = Some(format!("Cancel failed: {}", e)),
+        }
+    }
+
+    async fn restart_ap(&mut self) {
+        if let Some(daemon) = &self.daemon {
+            if !daemon.supports("RestartAp") {
+                self.error_message = Some("Daemon does not support restarting the AP".to_string());
+                return;
+            }
+        }
+
+        if let Err(e) = ipc::send_restart_ap(&self.socket_path).await {
+            self.error_message = Some(format!("AP restart failed: {}", e));
+        }
+    }
 }
 
 /// Run the TUI
-pub async fn run_tui(socket_path: &str) -> Result<()> {
+pub async fn run_tui(
+    socket_path: &str,
+    confirm_open: bool,
+    idle_dim: Option<Duration>,
+    tick_rate: Duration,
+) -> Result<()> {
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -116,13 +467,28 @@ pub async fn run_tui(socket_path: &str) -> Result<()> {
     let mut terminal = Terminal::new(backend)?;
 
     // Create app
-    let mut app = App::new(socket_path.to_string());
+    let mut app = App::new(socket_path.to_string(), confirm_open, idle_dim);
 
     // Initial state fetch
+    app.daemon = ipc::handshake(socket_path).await.ok();
+    app.min_signal = app.daemon.as_ref().and_then(|d| d.min_signal).unwrap_or(0);
+    if app.daemon.as_ref().is_some_and(|d| d.supports("GetConfig")) {
+        app.config = ipc::get_config(socket_path).await.ok();
+    }
+    if app
+        .daemon
+        .as_ref()
+        .is_some_and(|d| d.supports("GetDiagnostics"))
+    {
+        app.adapter_problems = ipc::get_diagnostics(socket_path)
+            .await
+            .map(|report| report.problems())
+            .unwrap_or_default();
+    }
     app.refresh_state().await;
 
     // Main loop
-    let result = run_app(&mut terminal, &mut app).await;
+    let result = run_app(&mut terminal, &mut app, tick_rate).await;
 
     // Restore terminal
     disable_raw_mode()?;
@@ -139,8 +505,8 @@ pub async fn run_tui(socket_path: &str) -> Result<()> {
 async fn run_app(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     app: &mut App,
+    tick_rate: Duration,
 ) -> Result<()> {
-    let tick_rate = Duration::from_millis(250);
     let mut last_tick = std::time::Instant::now();
 
     loop {
@@ -150,43 +516,56 @@ async fn run_app(
         // Handle input with timeout
         let timeout = tick_rate.saturating_sub(last_tick.elapsed());
         if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
+            let event = event::read()?;
+            let woke = matches!(event, Event::Key(_) | Event::Mouse(_)) && {
+                let dimmed = app.is_dimmed();
+                app.last_input = Instant::now();
+                dimmed
+            };
+            // The input that wakes a dimmed screen isn't acted on.
+            if let (Event::Key(key), false) = (event, woke) {
                 if key.kind == KeyEventKind::Press {
                     match app.input_mode {
                         InputMode::Normal => match key.code {
+                            KeyCode::Esc if app.is_connecting() => {
+                                app.cancel_connect().await;
+                            }
                             KeyCode::Char('q') | KeyCode::Esc => {
                                 app.should_quit = true;
                             }
-                            KeyCode::Up | KeyCode::Char('k') => {
-                                if app.selected_network > 0 {
-                                    app.selected_network -= 1;
-                                }
+                            KeyCode::Up | KeyCode::Char('k') if app.selected_network > 0 => {
+                                app.selected_network -= 1;
                             }
-                            KeyCode::Down | KeyCode::Char('j') => {
-                                if let Some(state) = &app.state {
-                                    if app.selected_network
-                                        < state.available_networks.len().saturating_sub(1)
-                                    {
-                                        app.selected_network += 1;
-                                    }
-                                }
+                            KeyCode::Down | KeyCode::Char('j')
+                                if app.selected_network + 1 < app.visible_networks().len() =>
+                            {
+                                app.selected_network += 1;
                             }
                             KeyCode::Enter => {
-                                if let Some(state) = &app.state {
-                                    if let Some(network) =
-                                        state.available_networks.get(app.selected_network)
-                                    {
-                                        if network.is_secured {
-                                            app.input_mode = InputMode::Password;
-                                        } else {
-                                            // Connect to open network
-                                            app.password_input.clear();
-                                            app.connect_to_selected().await;
-                                        }
+                                if let Some(is_secured) = app.selected().map(|n| n.is_secured) {
+                                    if is_secured {
+                                        app.input_mode = InputMode::Password;
+                                    } else {
+                                        app.connect_open().await;
                                     }
                                 }
                             }
+                            KeyCode::Char('f') => {
+                                app.cycle_band_filter();
+                            }
+                            KeyCode::Char('l') => {
+                                app.toggle_lock();
+                            }
+                            KeyCode::Char('+') | KeyCode::Char('=') => {
+                                app.adjust_min_signal(MIN_SIGNAL_STEP);
+                            }
+                            KeyCode::Char('-') => {
+                                app.adjust_min_signal(-MIN_SIGNAL_STEP);
+                            }
                             KeyCode::Char('m') => {
+                                app.manual_ssid.clear();
+                                app.manual_security = None;
+                                app.password_input.clear();
                                 app.input_mode = InputMode::ManualSsid;
                             }
                             KeyCode::Char('r') => {
@@ -195,15 +574,37 @@ async fn run_app(
                             KeyCode::Char('b') => {
                                 app.switch_backend().await;
                             }
+                            KeyCode::Char('a') => {
+                                app.restart_ap().await;
+                            }
                             _ => {}
                         },
                         InputMode::Password => match key.code {
                             KeyCode::Esc => {
-                                app.input_mode = InputMode::Normal;
-                                app.password_input.clear();
+                                app.cancel_input();
+                            }
+                            // A manual entry without a password is an open network,
+                            // unless a secured type was picked for it.
+                            KeyCode::Enter
+                                if app.password_input.is_empty()
+                                    && !app.manual_ssid.is_empty()
+                                    && app.manual_security.is_none_or(|s| {
+                                        matches!(
+                                            s,
+                                            ManualSecurity::Open | ManualSecurity::EnhancedOpen
+                                        )
+                                    }) =>
+                            {
+                                app.connect_open().await;
+                            }
+                            KeyCode::Left | KeyCode::Right if !app.manual_ssid.is_empty() => {
+                                app.cycle_manual_security(key.code == KeyCode::Right);
                             }
                             KeyCode::Enter => {
-                                app.connect_to_selected().await;
+                                app.connect_to_selected(false).await;
+                            }
+                            KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.connect_to_selected(true).await;
                             }
                             KeyCode::Backspace => {
                                 app.password_input.pop();
@@ -218,18 +619,38 @@ async fn run_app(
                         },
                         InputMode::ManualSsid => match key.code {
                             KeyCode::Esc => {
-                                app.input_mode = InputMode::Normal;
+                                app.cancel_input();
+                            }
+                            KeyCode::Enter if !app.manual_ssid.is_empty() => {
                                 app.password_input.clear();
+                                app.input_mode = InputMode::Password;
                             }
-                            KeyCode::Enter => {
-                                // TODO: Handle manual SSID entry
-                                app.input_mode = InputMode::Normal;
+                            KeyCode::Left | KeyCode::Right => {
+                                app.cycle_manual_security(key.code == KeyCode::Right);
                             }
                             KeyCode::Backspace => {
-                                app.password_input.pop();
+                                app.manual_ssid.pop();
                             }
                             KeyCode::Char(c) => {
-                                app.password_input.push(c);
+                                app.manual_ssid.push(c);
+                            }
+                            _ => {}
+                        },
+                        InputMode::ConfirmOpen => match key.code {
+                            KeyCode::Enter | KeyCode::Char('y') => {
+                                app.connect_to_selected(false).await;
+                            }
+                            KeyCode::Esc | KeyCode::Char('n') => {
+                                app.cancel_input();
+                            }
+                            _ => {}
+                        },
+                        InputMode::ConfirmSave => match key.code {
+                            KeyCode::Enter | KeyCode::Char('y') => {
+                                app.finish_pending_save(true).await;
+                            }
+                            KeyCode::Esc | KeyCode::Char('n') => {
+                                app.finish_pending_save(false).await;
                             }
                             _ => {}
                         },
@@ -250,7 +671,11 @@ async fn run_app(
         }
 
         if let Some(state) = &app.state {
-            if state.status == ConnectionStatus::Connected {
+            // Stay open while a kept AP is still serving setup.
+            if state.status == ConnectionStatus::Connected
+                && !state.ap_running
+                && app.input_mode != InputMode::ConfirmSave
+            {
                 // Show success briefly then exit
                 terminal.draw(|f| ui::draw(f, app))?;
                 tokio::time::sleep(Duration::from_secs(3)).await;
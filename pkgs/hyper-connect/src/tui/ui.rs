@@ -1,7 +1,11 @@
 //! TUI rendering
 
 use super::{App, InputMode};
-use crate::controller::ConnectionStatus;
+use crate::controller::{
+    ap_clients_summary,
+    history::{unix_now, StateTransition},
+    ConnectionStatus, ManualSecurity, NetworkInfo,
+};
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
@@ -48,10 +52,38 @@ pub fn draw(f: &mut Frame, app: &App) {
     draw_content(f, chunks[1], app);
     draw_footer(f, chunks[2], app);
 
-    // Draw modal if in password mode
-    if app.input_mode == InputMode::Password {
-        draw_password_modal(f, app);
+    // Draw modal for text input
+    match app.input_mode {
+        InputMode::ManualSsid => draw_manual_modal(f, app),
+        InputMode::Password if !app.manual_ssid.is_empty() => draw_manual_modal(f, app),
+        InputMode::Password => draw_password_modal(f, app),
+        InputMode::ConfirmOpen => draw_open_network_modal(f, app),
+        InputMode::ConfirmSave => draw_save_modal(f, app),
+        InputMode::Normal => {}
+    }
+
+    if app.is_dimmed() {
+        draw_idle_dim(f);
+    }
+}
+
+/// Mute everything already drawn and ask for a key press to wake up.
+fn draw_idle_dim(f: &mut Frame) {
+    let muted = Style::default().fg(Color::DarkGray).bg(Color::Black);
+    for cell in f.buffer_mut().content.iter_mut() {
+        cell.set_style(muted);
+        cell.modifier = Modifier::empty();
     }
+
+    let area = centered_box(f.area(), 24, 3);
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new("Press any key")
+            .alignment(ratatui::layout::Alignment::Center)
+            .style(muted)
+            .block(Block::default().borders(Borders::ALL).border_style(muted)),
+        area,
+    );
 }
 
 fn draw_header(f: &mut Frame, area: Rect, app: &App) {
@@ -61,16 +93,21 @@ fn draw_header(f: &mut Frame, area: Rect, app: &App) {
         Some(ConnectionStatus::SwitchingBackend) => ("SWITCHING BACKEND...", WARNING),
         Some(ConnectionStatus::Scanning) => ("SCANNING...", ACCENT),
         Some(ConnectionStatus::Failed) => ("FAILED", ERROR),
+        Some(ConnectionStatus::ApUnsupported) => ("AP UNSUPPORTED", ERROR),
+        Some(ConnectionStatus::RadioBlocked) => ("WIFI SWITCHED OFF", ERROR),
         Some(ConnectionStatus::AwaitingCredentials) => ("AWAITING CREDENTIALS", PRIMARY),
         _ => ("INITIALIZING", Color::Gray),
     };
 
     let backend_text = if let Some(state) = &app.state {
         if let Some(backend) = state.wifi_backend {
-            format!(" | {}", match backend {
-                crate::controller::WifiBackend::Iwd => "IWD",
-                crate::controller::WifiBackend::WpaSupplicant => "WPA_SUPPLICANT",
-            })
+            format!(
+                " | {}",
+                match backend {
+                    crate::controller::WifiBackend::Iwd => "IWD",
+                    crate::controller::WifiBackend::WpaSupplicant => "WPA_SUPPLICANT",
+                }
+            )
         } else {
             String::new()
         }
@@ -78,17 +115,35 @@ fn draw_header(f: &mut Frame, area: Rect, app: &App) {
         String::new()
     };
 
+    // Mirror the daemon's branding; older daemons don't report any.
+    let daemon = app.daemon.as_ref();
+    let title = daemon
+        .and_then(|d| d.portal_title.as_deref())
+        .unwrap_or("Hyper Recovery")
+        .to_uppercase();
+    let subtitle = daemon
+        .and_then(|d| d.portal_subtitle.as_deref())
+        .unwrap_or("WiFi Setup")
+        .to_uppercase();
+
     let header = Paragraph::new(Line::from(vec![
         Span::styled(
-            "HYPER RECOVERY",
+            title,
             Style::default().fg(ACCENT).add_modifier(Modifier::BOLD),
         ),
         Span::raw("  ::  "),
-        Span::styled("WIFI SETUP", Style::default().fg(PRIMARY)),
+        Span::styled(subtitle, Style::default().fg(PRIMARY)),
         Span::styled(&backend_text, Style::default().fg(Color::DarkGray)),
         Span::raw("                        "),
         Span::styled(
-            format!("[ {} ]", status_text.0),
+            match app
+                .state
+                .as_ref()
+                .and_then(|s| s.connect_deadline_secs.or(s.backend_switch_deadline_secs))
+            {
+                Some(secs) => format!("[ {} {}s ]", status_text.0, secs),
+                None => format!("[ {} ]", status_text.0),
+            },
             Style::default().fg(status_text.1),
         ),
     ]))
@@ -114,16 +169,28 @@ fn draw_content(f: &mut Frame, area: Rect, app: &App) {
     draw_details_panel(f, chunks[1], app);
 }
 
+/// SSID, noting how many access points share it.
+fn network_label(network: &NetworkInfo) -> String {
+    if network.bss_count > 1 {
+        format!("{} ({} APs)", network.ssid, network.bss_count)
+    } else {
+        network.ssid.clone()
+    }
+}
+
 fn draw_network_list(f: &mut Frame, area: Rect, app: &App) {
-    let networks: Vec<ListItem> = app
-        .state
-        .as_ref()
-        .map(|s| &s.available_networks)
-        .unwrap_or(&vec![])
-        .iter()
+    let mut networks: Vec<ListItem> = app
+        .visible_networks()
+        .into_iter()
         .enumerate()
         .map(|(i, network)| {
-            let lock = if network.is_secured { "🔒" } else { "🔓" };
+            let lock = if network.is_secured {
+                "🔒"
+            } else if network.is_enhanced_open() {
+                "🔐"
+            } else {
+                "🔓"
+            };
             let signal_bar = signal_to_bar(network.signal_strength);
             let selected = i == app.selected_network;
 
@@ -134,11 +201,15 @@ fn draw_network_list(f: &mut Frame, area: Rect, app: &App) {
             };
 
             let prefix = if selected { "> " } else { "  " };
+            let saved = app
+                .state
+                .as_ref()
+                .is_some_and(|s| s.has_saved(&network.ssid));
 
             ListItem::new(Line::from(vec![
                 Span::styled(prefix, style),
                 Span::styled(lock, style),
-                Span::styled(format!(" {:<20}", network.ssid), style),
+                Span::styled(format!(" {:<20}", network_label(network)), style),
                 Span::styled(
                     format!(" [{}] {:>3}%", signal_bar, network.signal_strength),
                     Style::default().fg(signal_color(network.signal_strength)),
@@ -147,13 +218,39 @@ fn draw_network_list(f: &mut Frame, area: Rect, app: &App) {
                     format!("  CH{}", network.channel),
                     Style::default().fg(Color::DarkGray),
                 ),
+                Span::styled(
+                    if saved { "  SAVED" } else { "" },
+                    Style::default().fg(SUCCESS),
+                ),
             ]))
         })
         .collect();
+    if networks.is_empty() {
+        if let Some(reason) = app.state.as_ref().and_then(|s| s.last_scan_error.as_ref()) {
+            networks.push(ListItem::new(Line::from(Span::styled(
+                format!("  Scan failed: {}", reason),
+                Style::default().fg(ERROR),
+            ))));
+        }
+        networks.extend(app.adapter_problems.iter().map(|problem| {
+            ListItem::new(Line::from(Span::styled(
+                format!("  {}", problem),
+                Style::default().fg(ERROR),
+            )))
+        }));
+    }
+
+    let mut title = match app.band_filter {
+        Some(band) => format!(" SELECT NETWORK [{}] ", band.label()),
+        None => " SELECT NETWORK ".to_string(),
+    };
+    if app.min_signal > 0 {
+        title.push_str(&format!("[≥{}%] ", app.min_signal));
+    }
 
     let list = List::new(networks).block(
         Block::default()
-            .title(" SELECT NETWORK ")
+            .title(title)
             .borders(Borders::ALL)
             .border_style(Style::default().fg(PRIMARY)),
     );
@@ -167,29 +264,52 @@ fn draw_details_panel(f: &mut Frame, area: Rect, app: &App) {
         .constraints([
             Constraint::Length(8), // Network details
             Constraint::Min(6),    // QR code / portal info
+            Constraint::Length(7), // Recent transitions
         ])
         .split(area);
 
     // Network details
-    let details = if let Some(state) = &app.state {
-        if let Some(network) = state.available_networks.get(app.selected_network) {
+    let details = if app.state.is_some() {
+        if let Some(network) = app.selected() {
             vec![
                 Line::from(vec![
                     Span::styled("SSID: ", Style::default().fg(Color::DarkGray)),
                     Span::styled(&network.ssid, Style::default().fg(Color::White)),
                 ]),
-                Line::from(vec![
-                    Span::styled("BSSID: ", Style::default().fg(Color::DarkGray)),
-                    Span::styled(&network.bssid, Style::default().fg(Color::White)),
-                ]),
+                match app.locked_bssid_for(&network.ssid) {
+                    Some(locked) => Line::from(vec![
+                        Span::styled("BSSID: ", Style::default().fg(Color::DarkGray)),
+                        Span::styled(locked, Style::default().fg(ACCENT)),
+                        Span::styled(" (locked)", Style::default().fg(Color::DarkGray)),
+                    ]),
+                    None => Line::from(vec![
+                        Span::styled("BSSID: ", Style::default().fg(Color::DarkGray)),
+                        Span::styled(&network.bssid, Style::default().fg(Color::White)),
+                    ]),
+                },
                 Line::from(vec![
                     Span::styled("Security: ", Style::default().fg(Color::DarkGray)),
                     Span::styled(&network.security_type, Style::default().fg(Color::White)),
                 ]),
+                Line::from(vec![
+                    Span::styled("Signal: ", Style::default().fg(Color::DarkGray)),
+                    Span::styled(
+                        signal_sparkline(&app.signal_samples(&network.ssid)),
+                        Style::default().fg(signal_color(network.signal_strength)),
+                    ),
+                    Span::styled(
+                        format!(" {}%", network.signal_strength),
+                        Style::default().fg(Color::White),
+                    ),
+                ]),
                 Line::from(vec![
                     Span::styled("Channel: ", Style::default().fg(Color::DarkGray)),
                     Span::styled(
-                        format!("{}", network.channel),
+                        format!(
+                            "{} ({})",
+                            network.channel,
+                            network.band().map(|b| b.label()).unwrap_or("unknown band")
+                        ),
                         Style::default().fg(Color::White),
                     ),
                 ]),
@@ -213,13 +333,24 @@ fn draw_details_panel(f: &mut Frame, area: Rect, app: &App) {
     // Portal info
     let portal_info = if let Some(state) = &app.state {
         if state.ap_running {
-            let url = state.portal_url.as_deref().unwrap_or("http://192.168.42.1");
-            vec![
+            let url = state
+                .portal_url
+                .as_deref()
+                .or(app.config.as_ref().map(|c| c.portal_url.as_str()))
+                .unwrap_or("unknown");
+            let mut lines = vec![
                 Line::from(Span::styled(
                     "CAPTIVE PORTAL ACTIVE",
                     Style::default().fg(SUCCESS),
                 )),
-                Line::from(""),
+                // A kept AP: the uplink is up too.
+                match (&state.status, &state.connected_ssid) {
+                    (ConnectionStatus::Connected, Some(ssid)) => Line::from(vec![
+                        Span::styled("Uplink: ", Style::default().fg(Color::DarkGray)),
+                        Span::styled(ssid.as_str(), Style::default().fg(SUCCESS)),
+                    ]),
+                    _ => Line::from(""),
+                },
                 Line::from(vec![
                     Span::styled("Connect to: ", Style::default().fg(Color::DarkGray)),
                     Span::styled(
@@ -232,7 +363,41 @@ fn draw_details_panel(f: &mut Frame, area: Rect, app: &App) {
                     Span::styled("Then visit: ", Style::default().fg(Color::DarkGray)),
                     Span::styled(url, Style::default().fg(PRIMARY)),
                 ]),
-            ]
+            ];
+            if let Some(hostname) = &state.portal_hostname {
+                lines.push(Line::from(vec![
+                    Span::styled("        or: ", Style::default().fg(Color::DarkGray)),
+                    Span::styled(format!("http://{}", hostname), Style::default().fg(PRIMARY)),
+                ]));
+            }
+            if let Some(info) = &state.onboarding {
+                lines.push(Line::from(vec![
+                    Span::styled("Password: ", Style::default().fg(Color::DarkGray)),
+                    Span::styled(
+                        if info.ap_secured {
+                            "set (shown to AP clients at /info)"
+                        } else {
+                            "none (open)"
+                        },
+                        Style::default().fg(Color::White),
+                    ),
+                ]));
+                if let Some(qr) = &info.wifi_qr {
+                    lines.push(Line::from(vec![
+                        Span::styled("Wi-Fi QR: ", Style::default().fg(Color::DarkGray)),
+                        Span::styled(qr, Style::default().fg(Color::White)),
+                    ]));
+                }
+            }
+            lines.push(Line::from(""));
+            lines.push(Line::from(vec![
+                Span::styled("AP clients: ", Style::default().fg(Color::DarkGray)),
+                Span::styled(
+                    ap_clients_summary(&state.ap_clients),
+                    Style::default().fg(Color::White),
+                ),
+            ]));
+            lines
         } else if state.status == ConnectionStatus::Connected {
             vec![
                 Line::from(Span::styled(
@@ -247,6 +412,26 @@ fn draw_details_panel(f: &mut Frame, area: Rect, app: &App) {
                         Style::default().fg(Color::White),
                     ),
                 ]),
+                match state.connected_signal {
+                    Some(signal) => Line::from(vec![
+                        Span::styled("Signal: ", Style::default().fg(Color::DarkGray)),
+                        Span::styled(
+                            format!("[{}] {}%", signal_to_bar(signal), signal),
+                            Style::default().fg(signal_color(signal)),
+                        ),
+                    ]),
+                    None => Line::from(""),
+                },
+                match state.connected_rate {
+                    Some(rate) => Line::from(vec![
+                        Span::styled("Rate: ", Style::default().fg(Color::DarkGray)),
+                        Span::styled(
+                            format!("{} Mbit/s", rate),
+                            Style::default().fg(Color::White),
+                        ),
+                    ]),
+                    None => Line::from(""),
+                },
             ]
         } else {
             vec![Line::from("Waiting...")]
@@ -265,15 +450,77 @@ fn draw_details_panel(f: &mut Frame, area: Rect, app: &App) {
         .wrap(Wrap { trim: true });
 
     f.render_widget(portal_widget, chunks[1]);
+
+    draw_history(f, chunks[2], app);
+}
+
+/// Most recent status transitions, newest first.
+fn draw_history(f: &mut Frame, area: Rect, app: &App) {
+    let now = unix_now();
+    let rows = area.height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = if app.history.is_empty() {
+        vec![Line::from(Span::styled(
+            "No transitions yet",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.history
+            .iter()
+            .rev()
+            .take(rows)
+            .map(|entry| {
+                let color = match entry.to {
+                    ConnectionStatus::Failed => ERROR,
+                    ConnectionStatus::Connected => SUCCESS,
+                    _ => Color::White,
+                };
+                Line::from(Span::styled(
+                    describe_transition(entry, now),
+                    Style::default().fg(color),
+                ))
+            })
+            .collect()
+    };
+
+    let history_widget = Paragraph::new(lines).block(
+        Block::default()
+            .title(" HISTORY ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(PRIMARY)),
+    );
+
+    f.render_widget(history_widget, area);
+}
+
+/// "Connecting→Failed: Incorrect password, 12s ago"
+fn describe_transition(entry: &StateTransition, now: u64) -> String {
+    let mut text = format!("{:?}→{:?}", entry.from, entry.to);
+    if let Some(error) = &entry.error {
+        text.push_str(&format!(": {}", error));
+    }
+    let age = entry.age_secs(now);
+    if age < 60 {
+        text.push_str(&format!(", {}s ago", age));
+    } else {
+        text.push_str(&format!(", {}m ago", age / 60));
+    }
+    text
 }
 
 fn draw_footer(f: &mut Frame, area: Rect, app: &App) {
     let help_text = match app.input_mode {
+        InputMode::Normal if app.is_connecting() => {
+            "[Esc] Cancel Connect   [R] Refresh   [Q] Quit"
+        }
         InputMode::Normal => {
-            "[↑/↓] Select   [Enter] Connect   [B] Switch Backend   [R] Refresh   [Q] Quit"
+            "[↑/↓] Select   [Enter] Connect   [M] Manual   [F] Band   [+/-] Min Signal   [L] Lock AP   [B] Switch Backend   [A] Restart AP   [R] Refresh   [Q] Quit"
+        }
+        InputMode::Password => {
+            "[Enter] Submit   [Ctrl+T] Test Without Saving   [Tab] Show/Hide   [Esc] Cancel"
         }
-        InputMode::Password => "[Enter] Submit   [Tab] Show/Hide   [Esc] Cancel",
-        InputMode::ManualSsid => "[Enter] Submit   [Esc] Cancel",
+        InputMode::ManualSsid => "[Enter] Next   [←/→] Security   [Esc] Cancel",
+        InputMode::ConfirmOpen => "[Y/Enter] Connect Anyway   [N/Esc] Cancel",
+        InputMode::ConfirmSave => "[Y/Enter] Save   [N/Esc] Don't Save",
     };
 
     let footer = Paragraph::new(help_text)
@@ -317,7 +564,11 @@ fn draw_password_modal(f: &mut Frame, app: &App) {
         ]),
         Line::from(""),
         Line::from(Span::styled(
-            "[Enter] Confirm    [Tab] Show/Hide    [Esc] Cancel",
+            if app.selected_has_saved() {
+                "[Enter] Confirm (empty: saved password)    [Tab] Show/Hide    [Esc] Cancel"
+            } else {
+                "[Enter] Confirm    [Tab] Show/Hide    [Esc] Cancel"
+            },
             Style::default().fg(Color::DarkGray),
         )),
     ];
@@ -333,6 +584,179 @@ fn draw_password_modal(f: &mut Frame, app: &App) {
     f.render_widget(modal, area);
 }
 
+fn draw_open_network_modal(f: &mut Frame, app: &App) {
+    let area = centered_rect(50, 30, f.area());
+
+    // Clear the area
+    f.render_widget(Clear, area);
+
+    let ssid = app.target_ssid().unwrap_or_default();
+
+    let content = vec![
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("NETWORK: ", Style::default().fg(Color::DarkGray)),
+            Span::styled(&ssid, Style::default().fg(ACCENT)),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled(
+            "This network is unencrypted.",
+            Style::default().fg(WARNING),
+        )),
+        Line::from(Span::styled(
+            "Anyone nearby can see the traffic you send over it.",
+            Style::default().fg(Color::White),
+        )),
+        Line::from(""),
+        Line::from(Span::styled(
+            "[Y/Enter] Connect anyway    [N/Esc] Cancel",
+            Style::default().fg(Color::DarkGray),
+        )),
+    ];
+
+    let modal = Paragraph::new(content).wrap(Wrap { trim: true }).block(
+        Block::default()
+            .title(" UNENCRYPTED NETWORK ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(WARNING))
+            .style(Style::default().bg(BG_DARK)),
+    );
+
+    f.render_widget(modal, area);
+}
+
+fn draw_save_modal(f: &mut Frame, app: &App) {
+    let area = centered_rect(50, 30, f.area());
+
+    // Clear the area
+    f.render_widget(Clear, area);
+
+    let ssid = app
+        .pending_save
+        .as_ref()
+        .map(|p| p.ssid.as_str())
+        .unwrap_or_default();
+
+    let content = vec![
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("NETWORK: ", Style::default().fg(Color::DarkGray)),
+            Span::styled(ssid, Style::default().fg(ACCENT)),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled(
+            "The password works.",
+            Style::default().fg(SUCCESS),
+        )),
+        Line::from(Span::styled(
+            "Save it so this network is joined automatically next time?",
+            Style::default().fg(Color::White),
+        )),
+        Line::from(""),
+        Line::from(Span::styled(
+            "[Y/Enter] Save    [N/Esc] Don't save",
+            Style::default().fg(Color::DarkGray),
+        )),
+    ];
+
+    let modal = Paragraph::new(content).wrap(Wrap { trim: true }).block(
+        Block::default()
+            .title(" SAVE CREDENTIALS ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(SUCCESS))
+            .style(Style::default().bg(BG_DARK)),
+    );
+
+    f.render_widget(modal, area);
+}
+
+fn draw_manual_modal(f: &mut Frame, app: &App) {
+    let area = centered_rect(50, 50, f.area());
+
+    // Clear the area
+    f.render_widget(Clear, area);
+
+    let editing_ssid = app.input_mode == InputMode::ManualSsid;
+    let cursor = |active: bool| if active { "_" } else { "" };
+    let field_style = |active: bool| {
+        if active {
+            Style::default().fg(Color::White)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        }
+    };
+
+    let password_display = if app.password_visible {
+        app.password_input.clone()
+    } else {
+        "*".repeat(app.password_input.len())
+    };
+
+    let hint = if editing_ssid {
+        "[Enter] Next    [←/→] Security    [Esc] Cancel"
+    } else {
+        "[Enter] Connect    [←/→] Security    [Tab] Show/Hide    [Esc] Cancel"
+    };
+    let (security, password_label) = match app.manual_security {
+        None => ("Auto", "PASSWORD (leave empty for open network)"),
+        Some(ManualSecurity::Open) => ("Open", "PASSWORD (none for an open network)"),
+        Some(ManualSecurity::EnhancedOpen) => (
+            ManualSecurity::EnhancedOpen.label(),
+            "PASSWORD (none for enhanced open)",
+        ),
+        Some(security) => (security.label(), "PASSWORD"),
+    };
+
+    let content = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            "NETWORK NAME (SSID)",
+            Style::default().fg(Color::DarkGray),
+        )),
+        Line::from(vec![
+            Span::styled("> ", Style::default().fg(PRIMARY)),
+            Span::styled(
+                format!("{}{}", app.manual_ssid, cursor(editing_ssid)),
+                field_style(editing_ssid),
+            ),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled(
+            "SECURITY",
+            Style::default().fg(Color::DarkGray),
+        )),
+        Line::from(vec![
+            Span::styled("< ", Style::default().fg(PRIMARY)),
+            Span::styled(security, Style::default().fg(Color::White)),
+            Span::styled(" >", Style::default().fg(PRIMARY)),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled(
+            password_label,
+            Style::default().fg(Color::DarkGray),
+        )),
+        Line::from(vec![
+            Span::styled("> ", Style::default().fg(PRIMARY)),
+            Span::styled(
+                format!("{}{}", password_display, cursor(!editing_ssid)),
+                field_style(!editing_ssid),
+            ),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled(hint, Style::default().fg(Color::DarkGray))),
+    ];
+
+    let modal = Paragraph::new(content).block(
+        Block::default()
+            .title(" MANUAL NETWORK ENTRY ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(WARNING))
+            .style(Style::default().bg(BG_DARK)),
+    );
+
+    f.render_widget(modal, area);
+}
+
 fn signal_to_bar(signal: u8) -> String {
     let bars = (signal as f32 / 25.0).ceil() as usize;
     let filled = "█".repeat(bars.min(4));
@@ -340,6 +764,16 @@ fn signal_to_bar(signal: u8) -> String {
     format!("{}{}", filled, empty)
 }
 
+/// One block character per sample, scaled over the full 0-100 range so a
+/// trend reads the same whatever the current strength.
+fn signal_sparkline(samples: &[u8]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    samples
+        .iter()
+        .map(|&signal| BLOCKS[(signal.min(100) as usize * (BLOCKS.len() - 1) + 50) / 100])
+        .collect()
+}
+
 fn signal_color(signal: u8) -> Color {
     match signal {
         80..=100 => SUCCESS,
@@ -392,3 +826,17 @@ fn inset(area: Rect, padding: u16) -> Rect {
         height: area.height.saturating_sub(double).max(1),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_sparkline_scales_and_clamps() {
+        assert_eq!(signal_sparkline(&[]), "");
+        assert_eq!(signal_sparkline(&[42]), "▄");
+        assert_eq!(signal_sparkline(&[0, 50, 100]), "▁▅█");
+        // Out-of-range readings draw as a full bar instead of panicking.
+        assert_eq!(signal_sparkline(&[101, 255]), "██");
+    }
+}
@@ -1,17 +1,51 @@
 //! Static asset serving
 
 use axum::{
-    http::{header, StatusCode},
-    response::IntoResponse,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
 };
 
-/// Serve the CSS stylesheet
-pub async fn serve_css() -> impl IntoResponse {
+/// How long browsers may reuse the stylesheet before revalidating it.
+const CSS_MAX_AGE_SECS: u32 = 86_400;
+
+/// Serve the CSS stylesheet, answering a matching `If-None-Match` with 304
+pub async fn serve_css(headers: HeaderMap) -> Response {
+    let etag = css_etag();
+    let cache_control = format!("public, max-age={}", CSS_MAX_AGE_SECS);
+    let fresh = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+
+    if fresh {
+        return (
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::CACHE_CONTROL, cache_control)],
+        )
+            .into_response();
+    }
     (
         StatusCode::OK,
-        [(header::CONTENT_TYPE, "text/css")],
+        [
+            (header::CONTENT_TYPE, "text/css; charset=utf-8".to_string()),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, cache_control),
+        ],
         CSS_CONTENT,
     )
+        .into_response()
+}
+
+/// Strong validator for the stylesheet, which only changes between builds.
+fn css_etag() -> String {
+    format!("\"{:016x}\"", fnv1a(CSS_CONTENT.as_bytes()))
+}
+
+/// 64-bit FNV-1a; stable across builds, unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
 }
 
 const CSS_CONTENT: &str = r##"
@@ -105,6 +139,12 @@ textarea {
   font-size: 0.92rem;
 }
 
+.portal-hint {
+  margin: 6px 0 0;
+  color: hsl(var(--muted-foreground));
+  font-size: 0.8rem;
+}
+
 .portal-content {
   padding: 18px 20px 20px;
   display: grid;
@@ -168,6 +208,20 @@ textarea {
   font-weight: 600;
 }
 
+.portal-band-filter {
+  border: 1px solid hsl(var(--border));
+  border-radius: calc(var(--radius) - 6px);
+  padding: 10px 12px;
+  min-height: 40px;
+  background: hsla(var(--card), 0.9);
+  color: hsl(var(--foreground));
+  font-weight: 600;
+}
+
+.network-row.hidden {
+  display: none;
+}
+
 .portal-action-btn:hover {
   background: hsla(var(--accent), 0.8);
 }
@@ -261,6 +315,36 @@ textarea {
   color: hsl(var(--muted-foreground));
 }
 
+.scan-age {
+  margin: 0;
+  font-size: 0.8rem;
+  color: hsl(var(--muted-foreground));
+}
+
+.scan-age.stale {
+  color: hsl(var(--destructive));
+  font-weight: 600;
+}
+
+.weak-note {
+  margin: 0;
+  display: flex;
+  align-items: center;
+  gap: 8px;
+  font-size: 0.8rem;
+  color: hsl(var(--muted-foreground));
+}
+
+.weak-toggle {
+  border: none;
+  background: none;
+  padding: 0;
+  color: hsl(var(--primary));
+  font: inherit;
+  text-decoration: underline;
+  cursor: pointer;
+}
+
 .empty-state {
   margin: 0;
   border: 1px dashed hsl(var(--border));
@@ -272,6 +356,12 @@ textarea {
   text-align: center;
 }
 
+.empty-state.adapter-problem {
+  margin-top: 8px;
+  border-color: hsl(var(--destructive));
+  color: hsl(var(--destructive));
+}
+
 .modal {
   position: fixed;
   inset: 0;
@@ -314,6 +404,16 @@ textarea {
   padding: 0 16px 16px;
 }
 
+.modal-warning {
+  margin: 0 0 14px;
+  padding: 10px 12px;
+  border: 1px solid hsl(42 92% 48%);
+  border-radius: 8px;
+  background: hsla(42 92% 48%, 0.12);
+  font-size: 0.86rem;
+  line-height: 1.45;
+}
+
 .portal-form {
   display: grid;
   gap: 10px;
@@ -421,6 +521,10 @@ textarea {
   color: hsl(var(--foreground));
 }
 
+.plain-btn.hidden {
+  display: none;
+}
+
 .plain-btn:hover,
 .toggle-btn:hover {
   filter: brightness(1.03);
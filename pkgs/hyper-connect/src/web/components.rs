@@ -1,6 +1,10 @@
 //! Leptos + shadcn component rendering for the captive portal.
 
-use crate::controller::{ConnectionStatus, NetworkInfo, WifiStateSnapshot};
+use super::i18n::{fill, Lang, Messages};
+use crate::controller::{
+    ApClient, BackendSwitchStep, ConnectError, ConnectionStatus, DaemonConfig, NetworkInfo,
+    WifiBand, WifiStateSnapshot,
+};
 use leptos::prelude::*;
 use leptos_shadcn_alert::{Alert, AlertDescription, AlertTitle, AlertVariant};
 use leptos_shadcn_badge::{Badge, BadgeVariant};
@@ -11,13 +15,26 @@ use leptos_shadcn_input::Input;
 const PORTAL_BEHAVIOR_JS: &str = r#"
 (function () {
   var selectedSsid = '';
+  var selectedSsidBytes = null;
   var pollTimer = null;
   var connectInProgress = false;
+  var currentAttemptId = null;
+  var TIMING = __PORTAL_TIMING__;
+  var TEXT = __PORTAL_TEXT__;
+  var KEEP_AP = __KEEP_AP__;
+  var SUCCESS_URL = __SUCCESS_URL__;
 
   function byId(id) {
     return document.getElementById(id);
   }
 
+  // Same single-pass {name} substitution as the server's catalog.
+  function fill(template, args) {
+    return template.replace(/\{(\w+)\}/g, function (match, name) {
+      return Object.prototype.hasOwnProperty.call(args, name) ? String(args[name]) : match;
+    });
+  }
+
   function showModal(id) {
     byId(id).classList.remove('hidden');
   }
@@ -31,31 +48,85 @@ const PORTAL_BEHAVIOR_JS: &str = r#"
     if (status === 'Connecting') return 'connecting';
     if (status === 'SwitchingBackend') return 'connecting';
     if (status === 'Failed') return 'failed';
+    if (status === 'ApUnsupported') return 'failed';
+    if (status === 'RadioBlocked') return 'failed';
     return 'waiting';
   }
 
+  function failureTitleForSnapshot(data) {
+    if (data.connect_error === 'WrongPassword') return TEXT.status_wrong_password;
+    if (data.connect_error === 'SsidNotFound') return TEXT.status_ssid_not_found;
+    if (data.connect_error === 'AuthTimeout') return TEXT.status_auth_timeout;
+    if (data.connect_error === 'DhcpFailed') return TEXT.status_dhcp_failed;
+    return TEXT.status_failed;
+  }
+
   function statusTextForSnapshot(data) {
-    if (!data || !data.status) return 'Preparing WiFi setup';
-    if (data.status === 'Connected') return 'Connected to ' + (data.connected_ssid || 'network');
-    if (data.status === 'Connecting') return 'Connecting to ' + (data.connecting_to || 'network') + '...';
-    if (data.status === 'SwitchingBackend') return 'Switching WiFi backend...';
-    if (data.status === 'Failed') return 'Connection failed';
-    if (data.status === 'Scanning') return 'Scanning for nearby networks';
-    if (data.status === 'AwaitingCredentials') return 'Select a network to connect';
-    if (data.status === 'Disconnected') return 'Disconnected from WiFi';
-    return 'Preparing WiFi setup';
+    if (!data || !data.status) return TEXT.status_preparing;
+    if (data.status === 'Connected') return fill(TEXT.status_connected, { ssid: data.connected_ssid || TEXT.some_network });
+    if (data.status === 'Connecting') {
+      var remaining = data.connect_deadline_secs != null ? fill(TEXT.status_remaining, { secs: data.connect_deadline_secs }) : '';
+      return fill(TEXT.status_connecting, { ssid: data.connecting_to || TEXT.some_network, remaining: remaining });
+    }
+    if (data.status === 'SwitchingBackend') {
+      var left = data.backend_switch_deadline_secs != null ? fill(TEXT.status_remaining, { secs: data.backend_switch_deadline_secs }) : '';
+      return fill(TEXT.status_switching_backend, { remaining: left });
+    }
+    if (data.status === 'Failed') return failureTitleForSnapshot(data);
+    if (data.status === 'ApUnsupported') return TEXT.status_ap_unsupported;
+    if (data.status === 'RadioBlocked') return TEXT.status_radio_blocked;
+    if (data.status === 'Scanning') return TEXT.status_scanning;
+    if (data.status === 'AwaitingCredentials') return TEXT.status_awaiting_credentials;
+    if (data.status === 'Disconnected') return TEXT.status_disconnected;
+    return TEXT.status_preparing;
+  }
+
+  function linkQualityText(data) {
+    var parts = [];
+    if (data.connected_signal != null) parts.push(fill(TEXT.quality_signal, { signal: data.connected_signal }));
+    if (data.connected_rate != null) parts.push(fill(TEXT.quality_rate, { rate: data.connected_rate }));
+    return parts.join(', ');
+  }
+
+  var BACKEND_STEPS = {
+    stopping_old: 'backend_step_stopping_old',
+    starting_new: 'backend_step_starting_new',
+    restarting_network_manager: 'backend_step_restarting_nm'
+  };
+
+  function backendStepText(data) {
+    var order = Object.keys(BACKEND_STEPS);
+    var index = order.indexOf(data.backend_switch_step);
+    if (index < 0) return TEXT.detail_switching_backend;
+    return fill(TEXT.detail_backend_step, {
+      n: index + 1,
+      total: order.length,
+      step: TEXT[BACKEND_STEPS[data.backend_switch_step]]
+    });
+  }
+
+  // The daemon is about to stop the setup AP for this connect; further
+  // requests would only hit a dead server.
+  function apGoingDown(data) {
+    return data.status === 'Connecting' && data.ap_running && !KEEP_AP;
   }
 
   function statusDetailForSnapshot(data) {
-    if (!data || !data.status) return 'Waiting for wireless interfaces to become ready.';
-    if (data.status === 'Connected') return 'Connection is active. You can close this page now.';
-    if (data.status === 'Connecting') return 'Authentication and DHCP are still in progress.';
-    if (data.status === 'SwitchingBackend') return 'Restarting WiFi services. The setup AP may restart; reconnect if needed.';
-    if (data.status === 'Failed') return data.last_error || 'Unknown error while connecting.';
-    if (data.status === 'Scanning') return 'Searching for available access points...';
-    if (data.status === 'AwaitingCredentials') return 'Choose a network or enter credentials manually.';
-    if (data.status === 'Disconnected') return 'No active WiFi connection was detected.';
-    return 'Waiting for wireless interfaces to become ready.';
+    if (!data || !data.status) return TEXT.detail_initializing;
+    if (data.status === 'Connected') {
+      var quality = linkQualityText(data);
+      var next = data.ap_running ? TEXT.detail_connected_ap_kept : TEXT.detail_connected_done;
+      return fill(TEXT.detail_connected, { quality: quality ? ' (' + quality + ')' : '', next: next });
+    }
+    if (data.status === 'Connecting') return apGoingDown(data) ? TEXT.detail_ap_going_down : TEXT.detail_connecting;
+    if (data.status === 'SwitchingBackend') return backendStepText(data);
+    if (data.status === 'Failed') return data.last_error || TEXT.detail_failed;
+    if (data.status === 'ApUnsupported') return data.last_error || TEXT.detail_ap_unsupported;
+    if (data.status === 'RadioBlocked') return TEXT.detail_radio_blocked;
+    if (data.status === 'Scanning') return TEXT.detail_scanning;
+    if (data.status === 'AwaitingCredentials') return TEXT.detail_awaiting_credentials;
+    if (data.status === 'Disconnected') return TEXT.detail_disconnected;
+    return TEXT.detail_initializing;
   }
 
   function updateStatus(text, tone, detail) {
@@ -70,12 +141,47 @@ const PORTAL_BEHAVIOR_JS: &str = r#"
     }
   }
 
+  function showCancel(visible) {
+    byId('cancel-connect-btn').classList.toggle('hidden', !visible);
+  }
+
   function updateStatusFromSnapshot(data) {
+    showCancel(!!data && data.status === 'Connecting');
     updateStatus(
       statusTextForSnapshot(data),
       statusToneForState(data && data.status),
       statusDetailForSnapshot(data)
     );
+    updateScanAge(data);
+    updateApClients(data);
+  }
+
+  function updateApClients(data) {
+    var label = byId('ap-clients');
+    if (!label || !data || !data.ap_clients) return;
+    var count = data.ap_clients.length;
+    var clients = count === 0 ? TEXT.ap_clients_none : count === 1 ? TEXT.ap_clients_one : fill(TEXT.ap_clients_many, { count: count });
+    label.textContent = fill(TEXT.ap_clients, { clients: clients });
+  }
+
+  function updateScanAge(data) {
+    var label = byId('scan-age');
+    if (!label || !data) return;
+    var age = data.last_scan_secs_ago;
+    var staleAfter = parseInt(label.getAttribute('data-stale-after'), 10);
+    if (age == null) {
+      label.textContent = TEXT.scan_age_never;
+    } else if (age < 120) {
+      label.textContent = fill(TEXT.scan_age_secs, { secs: age });
+    } else {
+      label.textContent = fill(TEXT.scan_age_mins, { mins: Math.floor(age / 60) });
+    }
+    label.classList.toggle('stale', age == null || age >= staleAfter);
+  }
+
+  function isStaleSnapshot(data) {
+    if (currentAttemptId == null || !connectInProgress) return false;
+    return data.current_attempt_id == null || data.current_attempt_id < currentAttemptId;
   }
 
   function clearPoll() {
@@ -90,36 +196,44 @@ const PORTAL_BEHAVIOR_JS: &str = r#"
     pollTimer = setTimeout(pollStatus, delayMs);
   }
 
-  async function connect(ssid, password, save) {
+  async function connect(ssid, password, save, hidden, ssidBytes, useSaved, security) {
     connectInProgress = true;
+    showCancel(true);
     updateStatus(
-      'Connecting to ' + ssid + '...',
+      fill(TEXT.status_connecting, { ssid: ssid, remaining: '' }),
       'connecting',
-      'Applying credentials and starting authentication...'
+      TEXT.connect_applying
     );
-    schedulePoll(250);
+    schedulePoll(TIMING.fast);
 
     try {
       var response = await fetch('/api/connect', {
         method: 'POST',
         headers: { 'Content-Type': 'application/json' },
-        body: JSON.stringify({ ssid: ssid, password: password, save: save })
+        body: JSON.stringify({ ssid: ssid, password: password, save: save, hidden: !!hidden, ssid_bytes: ssidBytes || null, use_saved: !!useSaved, security: security || null })
       });
 
       var data = await response.json();
 
-      if (data.success) {
-        updateStatus('Connection requested', 'connecting', data.message || 'Waiting for daemon status...');
+      if (response.ok) {
+        if (data.attempt_id != null) currentAttemptId = data.attempt_id;
+        updateStatus(TEXT.connect_requested, 'connecting', data.message || TEXT.connect_waiting_daemon);
         schedulePoll(400);
+      } else if (response.status === 409) {
+        // Another connect is already running; follow it instead.
+        updateStatus(TEXT.connect_in_progress, 'connecting', TEXT.connect_following);
+        schedulePoll(1200);
       } else {
         connectInProgress = false;
+        showCancel(false);
         clearPoll();
-        updateStatus('Connection failed', 'failed', data.message);
+        updateStatus(TEXT.status_failed, 'failed', data.message);
       }
     } catch (err) {
       connectInProgress = false;
+      showCancel(false);
       clearPoll();
-      updateStatus('Connection error', 'failed', err.message || 'Unexpected network error.');
+      updateStatus(TEXT.connect_error, 'failed', err.message || TEXT.connect_unexpected_error);
     }
   }
 
@@ -130,11 +244,25 @@ const PORTAL_BEHAVIOR_JS: &str = r#"
         throw new Error('Status endpoint unavailable');
       }
       var data = await response.json();
+      if (isStaleSnapshot(data)) {
+        // Still describes an earlier attempt; ours hasn't started yet.
+        schedulePoll(400);
+        return;
+      }
       updateStatusFromSnapshot(data);
 
       if (data.status === 'Connected') {
         connectInProgress = false;
-        clearPoll();
+        if (SUCCESS_URL) {
+          window.location.href = SUCCESS_URL;
+          return;
+        }
+        // With the AP kept up, keep following the uplink.
+        if (data.ap_running) {
+          schedulePoll(TIMING.idle);
+        } else {
+          clearPoll();
+        }
         return;
       }
 
@@ -144,24 +272,42 @@ const PORTAL_BEHAVIOR_JS: &str = r#"
         return;
       }
 
+      if (apGoingDown(data)) {
+        clearPoll();
+        return;
+      }
+
       if (data.status === 'Connecting') {
-        schedulePoll(1000);
+        schedulePoll(TIMING.active);
         return;
       }
 
-      schedulePoll(connectInProgress ? 1200 : 2500);
+      schedulePoll(connectInProgress ? 1200 : TIMING.idle);
     } catch (err) {
       if (connectInProgress) {
-        updateStatus('Connection in progress', 'connecting', 'Still attempting to join the network...');
+        updateStatus(TEXT.connect_in_progress, 'connecting', TEXT.connect_still_trying);
         schedulePoll(1500);
         return;
       }
 
-      updateStatus('Waiting for portal status', 'waiting', 'Retrying status sync...');
-      schedulePoll(2500);
+      updateStatus(TEXT.status_sync_waiting, 'waiting', TEXT.status_sync_retrying);
+      schedulePoll(TIMING.idle);
     }
   }
 
+  var pendingOpen = null;
+
+  // Unencrypted networks need an explicit go-ahead unless the daemon waives it.
+  function connectOpen(ssid, hidden, ssidBytes) {
+    if (byId('network-list').getAttribute('data-confirm-open') !== 'true') {
+      connect(ssid, '', false, hidden, ssidBytes);
+      return;
+    }
+    pendingOpen = { ssid: ssid, hidden: hidden, ssidBytes: ssidBytes };
+    byId('open-modal-ssid').textContent = ssid;
+    showModal('open-modal');
+  }
+
   function bindNetworkRows() {
     var rows = document.querySelectorAll('.network-row');
     rows.forEach(function (row) {
@@ -169,23 +315,78 @@ const PORTAL_BEHAVIOR_JS: &str = r#"
         var ssid = row.getAttribute('data-ssid') || '';
         var secured = row.getAttribute('data-secured') === 'true';
         selectedSsid = ssid;
+        // The exact scanned bytes, so lookalike or space-padded SSIDs aren't re-derived from text.
+        try { selectedSsidBytes = JSON.parse(row.getAttribute('data-ssid-bytes')); } catch (err) { selectedSsidBytes = null; }
 
         if (secured) {
           byId('modal-ssid').textContent = ssid;
           byId('password-input').value = '';
           byId('save-password').checked = true;
+          byId('use-saved-btn').classList.toggle('hidden', row.getAttribute('data-saved') !== 'true');
           showModal('password-modal');
+        } else if (row.getAttribute('data-enhanced-open') === 'true') {
+          // OWE: no password, but the link is encrypted, so no warning either.
+          connect(ssid, '', false, false, selectedSsidBytes);
         } else {
-          connect(ssid, '', false);
+          connectOpen(ssid, false, selectedSsidBytes);
         }
       });
     });
   }
 
+  var showWeak = false;
+  var showAll = false;
+
+  function applyBandFilter() {
+    var band = byId('band-filter').value;
+    document.querySelectorAll('.network-row').forEach(function (row) {
+      var visible = band === 'all' || row.getAttribute('data-band') === band;
+      if (!showWeak && row.getAttribute('data-weak') === 'true') visible = false;
+      if (!showAll && row.getAttribute('data-overflow') === 'true') visible = false;
+      row.classList.toggle('hidden', !visible);
+    });
+  }
+
+  function updateWeakNote() {
+    var note = byId('weak-note');
+    if (!note) return;
+    var count = note.getAttribute('data-count');
+    var template = showWeak
+      ? (count === '1' ? TEXT.weak_shown_one : TEXT.weak_shown_many)
+      : (count === '1' ? TEXT.weak_hidden_one : TEXT.weak_hidden_many);
+    byId('weak-note-text').textContent = fill(template, { count: count });
+    byId('toggle-weak-btn').textContent = showWeak ? TEXT.hide : TEXT.show;
+  }
+
+  if (byId('toggle-weak-btn')) {
+    byId('toggle-weak-btn').addEventListener('click', function () {
+      showWeak = !showWeak;
+      try { sessionStorage.setItem('showWeak', showWeak ? 'true' : 'false'); } catch (err) {}
+      updateWeakNote();
+      applyBandFilter();
+    });
+  }
+
+  if (byId('show-all-btn')) {
+    byId('show-all-btn').addEventListener('click', function () {
+      showAll = true;
+      try { sessionStorage.setItem('showAllNetworks', 'true'); } catch (err) {}
+      byId('overflow-note').classList.add('hidden');
+      applyBandFilter();
+    });
+  }
+
+  byId('band-filter').addEventListener('change', function () {
+    // Remember the choice across the periodic list reloads.
+    try { sessionStorage.setItem('bandFilter', byId('band-filter').value); } catch (err) {}
+    applyBandFilter();
+  });
+
   byId('manual-entry-btn').addEventListener('click', function () {
     byId('manual-ssid').value = '';
     byId('manual-password').value = '';
     byId('manual-save-password').checked = true;
+    byId('manual-security').value = 'auto';
     showModal('manual-modal');
   });
 
@@ -196,11 +397,11 @@ const PORTAL_BEHAVIOR_JS: &str = r#"
       .then(function (response) { return response.json(); })
       .then(function (data) {
         var backend = (data && data.wifi_backend) ? data.wifi_backend : null;
-        var backendLabel = backend === 'iwd' ? 'iwd' : (backend === 'wpa_supplicant' ? 'wpa_supplicant' : 'unknown');
+        var backendLabel = backend === 'iwd' ? 'iwd' : (backend === 'wpa_supplicant' ? 'wpa_supplicant' : TEXT.backend_unknown);
         byId('backend-current').textContent = backendLabel;
       })
       .catch(function () {
-        byId('backend-current').textContent = 'unknown';
+        byId('backend-current').textContent = TEXT.backend_unknown;
       });
   });
 
@@ -216,7 +417,7 @@ const PORTAL_BEHAVIOR_JS: &str = r#"
     }
 
     hideModal('settings-modal');
-    updateStatus('Switching WiFi backend...', 'connecting', 'Restarting WiFi services and NetworkManager...');
+    updateStatus(TEXT.status_switching_backend, 'connecting', TEXT.backend_switch_restarting);
     connectInProgress = true;
 
     try {
@@ -226,12 +427,12 @@ const PORTAL_BEHAVIOR_JS: &str = r#"
         body: JSON.stringify({ backend: selected.value })
       });
       var data = await response.json();
-      updateStatus(data.success ? 'Backend switch requested' : 'Backend switch failed', data.success ? 'connecting' : 'failed', data.message || '');
+      updateStatus(response.ok ? TEXT.backend_switch_requested : TEXT.backend_switch_failed, response.ok ? 'connecting' : 'failed', data.message || '');
       schedulePoll(1200);
     } catch (err) {
       connectInProgress = false;
       clearPoll();
-      updateStatus('Backend switch error', 'failed', err.message || 'Unable to switch backend.');
+      updateStatus(TEXT.backend_switch_error, 'failed', err.message || TEXT.backend_switch_unreachable);
     }
   });
 
@@ -239,10 +440,24 @@ const PORTAL_BEHAVIOR_JS: &str = r#"
     try {
       var response = await fetch('/api/scan', { method: 'POST' });
       var data = await response.json();
-      updateStatus(data.success ? data.message : 'Scan failed', data.success ? 'waiting' : 'failed', 'Refreshing network list...');
+      updateStatus(response.ok ? data.message : TEXT.scan_failed, response.ok ? 'waiting' : 'failed', TEXT.scan_refreshing);
       setTimeout(function () { window.location.reload(); }, 800);
     } catch (err) {
-      updateStatus('Scan error', 'failed', err.message || 'Unable to trigger scan.');
+      updateStatus(TEXT.scan_error, 'failed', err.message || TEXT.scan_unreachable);
+    }
+  });
+
+  byId('cancel-connect-btn').addEventListener('click', async function () {
+    try {
+      var response = await fetch('/api/connect/cancel', { method: 'POST' });
+      if (response.ok) {
+        connectInProgress = false;
+        showCancel(false);
+        updateStatus(TEXT.cancel_requested, 'waiting', TEXT.cancel_restoring_ap);
+      }
+      schedulePoll(TIMING.active);
+    } catch (err) {
+      updateStatus(TEXT.cancel_failed, 'failed', err.message || TEXT.service_unreachable);
     }
   });
 
@@ -251,7 +466,7 @@ const PORTAL_BEHAVIOR_JS: &str = r#"
     var password = byId('password-input').value;
     var save = byId('save-password').checked;
     hideModal('password-modal');
-    connect(selectedSsid, password, save);
+    connect(selectedSsid, password, save, false, selectedSsidBytes);
   });
 
   byId('manual-form').addEventListener('submit', function (event) {
@@ -259,8 +474,32 @@ const PORTAL_BEHAVIOR_JS: &str = r#"
     var ssid = byId('manual-ssid').value;
     var password = byId('manual-password').value;
     var save = byId('manual-save-password').checked;
+    var security = byId('manual-security').value;
     hideModal('manual-modal');
-    connect(ssid, password, save);
+    // A blank password only means "open" unless a secured type was picked.
+    if (password === '' && (security === 'auto' || security === 'open')) {
+      connectOpen(ssid, true);
+    } else {
+      connect(ssid, password, save, true, null, false, security === 'auto' ? null : security);
+    }
+  });
+
+  byId('confirm-open-btn').addEventListener('click', function () {
+    hideModal('open-modal');
+    if (pendingOpen) {
+      connect(pendingOpen.ssid, '', false, pendingOpen.hidden, pendingOpen.ssidBytes);
+      pendingOpen = null;
+    }
+  });
+
+  byId('cancel-open-btn').addEventListener('click', function () {
+    pendingOpen = null;
+    hideModal('open-modal');
+  });
+
+  byId('use-saved-btn').addEventListener('click', function () {
+    hideModal('password-modal');
+    connect(selectedSsid, '', true, false, selectedSsidBytes, true);
   });
 
   byId('cancel-password-btn').addEventListener('click', function () {
@@ -283,6 +522,18 @@ const PORTAL_BEHAVIOR_JS: &str = r#"
 
   bindNetworkRows();
 
+  try {
+    var savedBand = sessionStorage.getItem('bandFilter');
+    if (savedBand) {
+      byId('band-filter').value = savedBand;
+    }
+    showWeak = sessionStorage.getItem('showWeak') === 'true';
+    showAll = sessionStorage.getItem('showAllNetworks') === 'true';
+  } catch (err) {}
+  if (showAll && byId('overflow-note')) byId('overflow-note').classList.add('hidden');
+  updateWeakNote();
+  applyBandFilter();
+
   setInterval(function () {
     if (connectInProgress) {
       return;
@@ -291,27 +542,115 @@ const PORTAL_BEHAVIOR_JS: &str = r#"
     if (!document.querySelector('.modal:not(.hidden)')) {
       window.location.reload();
     }
-  }, 30000);
+  }, TIMING.reload);
 
   pollStatus();
 })();
 "#;
 
-pub fn render_portal_page(snapshot: &WifiStateSnapshot) -> String {
-    let status_text = status_text(snapshot);
-    let status_detail = status_detail(snapshot);
+/// Poll intervals handed to the portal script, all scaled from `portal_poll_ms`.
+struct PortalTiming {
+    /// Status poll while nothing is happening
+    idle_ms: u64,
+    /// Status poll while a connect is running
+    active_ms: u64,
+    /// First poll right after a connect is submitted
+    fast_ms: u64,
+    /// Full page reload to pick up a fresh scan list
+    reload_ms: u64,
+}
+
+impl PortalTiming {
+    /// The defaults (2500ms idle) give 1s connecting polls and a 30s reload.
+    fn from_poll_ms(poll_ms: u64) -> Self {
+        Self {
+            idle_ms: poll_ms,
+            active_ms: poll_ms * 2 / 5,
+            fast_ms: poll_ms / 10,
+            reload_ms: poll_ms * 12,
+        }
+    }
+
+    fn to_js(&self) -> String {
+        format!(
+            "{{ idle: {}, active: {}, fast: {}, reload: {} }}",
+            self.idle_ms, self.active_ms, self.fast_ms, self.reload_ms
+        )
+    }
+}
+
+pub fn render_portal_page(
+    snapshot: &WifiStateSnapshot,
+    config: &DaemonConfig,
+    adapter_problems: &[String],
+    lang: Lang,
+) -> String {
+    let text = lang.messages();
+    let scan_ttl = config.scan_ttl;
+    let confirm_open = !config.allow_open_silently;
+    let portal_title = config.portal_title.clone();
+    let portal_subtitle = config.portal_subtitle.clone();
+    let status_text = status_text(snapshot, text);
+    let status_detail = status_detail(snapshot, text);
     let status_variant = status_variant(&snapshot.status);
     let status_tone = status_tone(&snapshot.status);
     let status_class = format!("portal-status state-{}", status_tone);
+    let connecting = snapshot.status == ConnectionStatus::Connecting;
     let networks = snapshot.available_networks.clone();
     let has_networks = !networks.is_empty();
+    // Nothing in range and a scan that never finished look alike otherwise.
+    let empty_text = match &snapshot.last_scan_error {
+        Some(reason) => format!("{}: {}", text.scan_failed, reason),
+        None => text.no_networks.to_string(),
+    };
+    let adapter_problems = adapter_problems.to_vec();
+    let min_signal = config.min_signal;
+    let weak_count = networks
+        .iter()
+        .filter(|n| !n.meets_min_signal(min_signal))
+        .count();
+    let overflow = overflow_rows(&networks, min_signal, config.portal_max_networks);
+    let saved: Vec<bool> = networks
+        .iter()
+        .map(|n| snapshot.has_saved(&n.ssid))
+        .collect();
+    let overflow_count = overflow.iter().filter(|&&o| o).count();
+    let ap_clients = snapshot.ap_running.then(|| {
+        fill(
+            text.ap_clients,
+            &[("clients", &ap_clients_text(&snapshot.ap_clients, text))],
+        )
+    });
+    let portal_address = snapshot.portal_hostname.as_ref().map(|hostname| {
+        fill(
+            text.portal_address,
+            &[("url", &format!("http://{}", hostname))],
+        )
+    });
+    let scan_age_class = match snapshot.last_scan_secs_ago {
+        Some(age) if age < scan_ttl => "scan-age",
+        _ => "scan-age stale",
+    };
+    let scan_age = scan_age_text(snapshot.last_scan_secs_ago, text);
+    let weak_note = fill(
+        if weak_count == 1 {
+            text.weak_hidden_one
+        } else {
+            text.weak_hidden_many
+        },
+        &[("count", &weak_count.to_string())],
+    );
 
     let body_html = view! {
             <div class="portal-root">
                 <Card class="portal-shell">
                     <CardHeader class="portal-header">
-                        <CardTitle class="portal-title">"Hyper Recovery"</CardTitle>
-                        <CardDescription class="portal-subtitle">"WiFi Setup Module"</CardDescription>
+                        <CardTitle class="portal-title">{portal_title}</CardTitle>
+                        <CardDescription class="portal-subtitle">{portal_subtitle}</CardDescription>
+                        {portal_address.map(|address| view! { <p class="portal-hint">{address}</p> })}
+                        {ap_clients.map(|clients| {
+                            view! { <p class="portal-hint" id="ap-clients">{clients}</p> }
+                        })}
                     </CardHeader>
 
                     <CardContent class="portal-content">
@@ -320,6 +659,15 @@ pub fn render_portal_page(snapshot: &WifiStateSnapshot) -> String {
                             <AlertDescription class="portal-status-detail" id="status-detail">{status_detail}</AlertDescription>
                         </Alert>
 
+                        <button
+                            class="plain-btn secondary"
+                            class:hidden=!connecting
+                            id="cancel-connect-btn"
+                            type="button"
+                        >
+                            {text.cancel_connection}
+                        </button>
+
                         <div class="portal-actions">
                             <Button
                                 variant=ButtonVariant::Outline
@@ -327,7 +675,7 @@ pub fn render_portal_page(snapshot: &WifiStateSnapshot) -> String {
                                 class="portal-action-btn"
                                 id="scan-networks-btn"
                             >
-                                "Scan Again"
+                                {text.scan_again}
                             </Button>
 
                             <Button
@@ -336,7 +684,7 @@ pub fn render_portal_page(snapshot: &WifiStateSnapshot) -> String {
                                 class="portal-action-btn"
                                 id="manual-entry-btn"
                             >
-                                "Enter Network Manually"
+                                {text.enter_manually}
                             </Button>
 
                             <Button
@@ -345,36 +693,81 @@ pub fn render_portal_page(snapshot: &WifiStateSnapshot) -> String {
                                 class="portal-action-btn"
                                 id="settings-btn"
                             >
-                                "Help / Settings"
+                                {text.help_settings}
                             </Button>
+
+                            <select class="portal-band-filter" id="band-filter" aria-label="Band">
+                                <option value="all">{text.all_bands}</option>
+                                {WifiBand::ALL
+                                    .iter()
+                                    .map(|band| view! { <option value=band.label()>{band.label()}</option> })
+                                    .collect_view()}
+                            </select>
                         </div>
 
-                        <section class="network-list" id="network-list">
+                        <p class=scan_age_class id="scan-age" data-stale-after=scan_ttl.to_string()>
+                            {scan_age}
+                        </p>
+
+                        {(weak_count > 0)
+                            .then(|| {
+                                view! {
+                                    <p class="weak-note" id="weak-note" data-count=weak_count.to_string()>
+                                        <span id="weak-note-text">{weak_note}</span>
+                                        <button type="button" class="weak-toggle" id="toggle-weak-btn">
+                                            {text.show}
+                                        </button>
+                                    </p>
+                                }
+                            })}
+
+                        <section
+                            class="network-list"
+                            id="network-list"
+                            data-confirm-open=if confirm_open { "true" } else { "false" }
+                        >
                             {if has_networks {
                                 networks
                                     .iter()
                                     .cloned()
-                                    .map(render_network_row)
+                                    .zip(overflow)
+                                    .zip(saved)
+                                    .map(|((network, overflow), saved)| {
+                                        render_network_row(network, min_signal, overflow, saved, text)
+                                    })
                                     .collect_view()
                                     .into_any()
                             } else {
                                 view! {
-                                    <p class="empty-state">
-                                        "No networks detected yet. Use Scan Again to refresh the list."
-                                    </p>
+                                    <p class="empty-state">{empty_text}</p>
+                                    {adapter_problems
+                                        .into_iter()
+                                        .map(|problem| view! { <p class="empty-state adapter-problem">{problem}</p> })
+                                        .collect_view()}
                                 }
                                 .into_any()
                             }}
                         </section>
+
+                        {(overflow_count > 0)
+                            .then(|| {
+                                view! {
+                                    <p class="weak-note" id="overflow-note">
+                                        <button type="button" class="weak-toggle" id="show-all-btn">
+                                            {fill(text.show_all, &[("count", &overflow_count.to_string())])}
+                                        </button>
+                                    </p>
+                                }
+                            })}
                     </CardContent>
                 </Card>
 
                 <div class="modal hidden" id="password-modal">
                     <Card class="modal-card">
                         <CardHeader class="modal-header">
-                            <CardTitle class="modal-title">"Enter Password"</CardTitle>
+                            <CardTitle class="modal-title">{text.password_title}</CardTitle>
                             <CardDescription class="modal-subtitle" id="modal-ssid">
-                                "Selected network"
+                                {text.selected_network}
                             </CardDescription>
                         </CardHeader>
 
@@ -385,61 +778,91 @@ pub fn render_portal_page(snapshot: &WifiStateSnapshot) -> String {
                                         class="portal-input"
                                         id="password-input"
                                         input_type="password"
-                                        placeholder="Password"
+                                        placeholder=text.password_placeholder
                                     />
-                                    <button class="toggle-btn" id="toggle-password-btn" type="button">"Show"</button>
+                                    <button class="toggle-btn" id="toggle-password-btn" type="button">{text.show}</button>
                                 </div>
 
                                 <label class="checkbox-row">
                                     <input checked=true id="save-password" type="checkbox"/>
-                                    <span>"Remember password for auto-connect"</span>
+                                    <span>{text.remember_password}</span>
                                 </label>
 
+                                <button class="plain-btn secondary hidden" id="use-saved-btn" type="button">
+                                    {text.use_saved_password}
+                                </button>
+
                                 <div class="modal-actions">
-                                    <button class="plain-btn secondary" id="cancel-password-btn" type="button">"Cancel"</button>
-                                    <button class="plain-btn primary" type="submit">"Connect"</button>
+                                    <button class="plain-btn secondary" id="cancel-password-btn" type="button">{text.cancel}</button>
+                                    <button class="plain-btn primary" type="submit">{text.connect}</button>
                                 </div>
                             </form>
                         </CardContent>
                     </Card>
                 </div>
 
-                <div class="modal hidden" id="manual-modal">
+                <div class="modal hidden" id="open-modal">
                     <Card class="modal-card">
                         <CardHeader class="modal-header">
-                            <CardTitle class="modal-title">"Manual Network Entry"</CardTitle>
-                            <CardDescription class="modal-subtitle">
-                                "Enter SSID and password for hidden network"
+                            <CardTitle class="modal-title">{text.open_title}</CardTitle>
+                            <CardDescription class="modal-subtitle" id="open-modal-ssid">
+                                {text.selected_network}
                             </CardDescription>
                         </CardHeader>
 
+                        <CardContent class="modal-content">
+                            <p class="modal-warning">{text.open_warning}</p>
+
+                            <div class="modal-actions">
+                                <button class="plain-btn secondary" id="cancel-open-btn" type="button">{text.cancel}</button>
+                                <button class="plain-btn primary" id="confirm-open-btn" type="button">{text.connect_anyway}</button>
+                            </div>
+                        </CardContent>
+                    </Card>
+                </div>
+
+                <div class="modal hidden" id="manual-modal">
+                    <Card class="modal-card">
+                        <CardHeader class="modal-header">
+                            <CardTitle class="modal-title">{text.manual_title}</CardTitle>
+                            <CardDescription class="modal-subtitle">{text.manual_subtitle}</CardDescription>
+                        </CardHeader>
+
                         <CardContent class="modal-content">
                             <form class="portal-form" id="manual-form">
                                 <Input
                                     class="portal-input"
                                     id="manual-ssid"
                                     input_type="text"
-                                    placeholder="Network Name (SSID)"
+                                    placeholder=text.ssid_placeholder
                                 />
 
+                                <select class="portal-band-filter" id="manual-security" aria-label="Security">
+                                    <option value="auto">{text.security_auto}</option>
+                                    <option value="open">{text.open}</option>
+                                    <option value="wpa2">"WPA2"</option>
+                                    <option value="wpa3">"WPA3"</option>
+                                    <option value="owe">"Enhanced Open"</option>
+                                </select>
+
                                 <div class="password-row">
                                     <Input
                                         class="portal-input"
                                         id="manual-password"
                                         input_type="password"
-                                        placeholder="Password (leave empty for open network)"
+                                        placeholder=text.manual_password_placeholder
                                     />
-                                    <button class="toggle-btn" id="toggle-manual-password-btn" type="button">"Show"</button>
+                                    <button class="toggle-btn" id="toggle-manual-password-btn" type="button">{text.show}</button>
                                 </div>
 
                                 <label class="checkbox-row">
                                     <input checked=true id="manual-save-password" type="checkbox"/>
-                                    <span>"Remember password for auto-connect"</span>
+                                    <span>{text.remember_password}</span>
                                 </label>
 
                                 <div class="modal-actions">
-                                    <button class="plain-btn secondary" id="cancel-manual-btn" type="button">"Cancel"</button>
-                                    <button class="plain-btn primary" type="submit">"Connect"</button>
+                                    <button class="plain-btn secondary" id="cancel-manual-btn" type="button">{text.cancel}</button>
+                                    <button class="plain-btn primary" type="submit">{text.connect}</button>
                                 </div>
                             </form>
                         </CardContent>
@@ -449,31 +872,29 @@ pub fn render_portal_page(snapshot: &WifiStateSnapshot) -> String {
                 <div class="modal hidden" id="settings-modal">
                     <Card class="modal-card">
                         <CardHeader class="modal-header">
-                            <CardTitle class="modal-title">"Help / Settings"</CardTitle>
-                            <CardDescription class="modal-subtitle">
-                                "If WiFi connection is unreliable, try switching the NetworkManager WiFi backend. This restarts WiFi services and may temporarily drop your connection to the setup AP."
-                            </CardDescription>
+                            <CardTitle class="modal-title">{text.help_settings}</CardTitle>
+                            <CardDescription class="modal-subtitle">{text.settings_help}</CardDescription>
                         </CardHeader>
 
                         <CardContent class="modal-content">
                             <div class="settings-row">
-                                <span class="settings-label">"Current backend"</span>
-                                <span class="settings-value" id="backend-current">"unknown"</span>
+                                <span class="settings-label">{text.current_backend}</span>
+                                <span class="settings-value" id="backend-current">{text.backend_unknown}</span>
                             </div>
 
                             <form class="portal-form" id="backend-form">
                                 <label class="radio-row">
                                     <input name="backend" type="radio" value="iwd"/>
-                                    <span>"iwd (recommended for Intel WiFi)"</span>
+                                    <span>{text.backend_iwd}</span>
                                 </label>
                                 <label class="radio-row">
                                     <input name="backend" type="radio" value="wpa_supplicant"/>
-                                    <span>"wpa_supplicant (max compatibility)"</span>
+                                    <span>{text.backend_wpa_supplicant}</span>
                                 </label>
 
                                 <div class="modal-actions">
-                                    <button class="plain-btn secondary" id="cancel-settings-btn" type="button">"Close"</button>
-                                    <button class="plain-btn primary" type="submit">"Apply & Restart WiFi"</button>
+                                    <button class="plain-btn secondary" id="cancel-settings-btn" type="button">{text.close}</button>
+                                    <button class="plain-btn primary" type="submit">{text.apply_backend}</button>
                                 </div>
                             </form>
                         </CardContent>
@@ -483,34 +904,163 @@ pub fn render_portal_page(snapshot: &WifiStateSnapshot) -> String {
     }
     .to_html();
 
+    let script = PORTAL_BEHAVIOR_JS
+        .replace(
+            "__PORTAL_TIMING__",
+            &PortalTiming::from_poll_ms(config.portal_poll_ms).to_js(),
+        )
+        .replace("__PORTAL_TEXT__", &text_to_js(text))
+        .replace("__KEEP_AP__", &config.keep_ap_after_connect.to_string())
+        .replace("__SUCCESS_URL__", &json_to_js(&config.success_url));
+    html_document(
+        config,
+        lang,
+        &body_html,
+        &format!("<script>{}</script>", script),
+    )
+}
+
+/// Stand-in for the portal while a connect takes the setup AP down: no
+/// controls and no script polling a server that is about to vanish.
+pub fn render_ap_going_down_page(
+    snapshot: &WifiStateSnapshot,
+    config: &DaemonConfig,
+    lang: Lang,
+) -> String {
+    let text = lang.messages();
+    let portal_title = config.portal_title.clone();
+    let portal_subtitle = config.portal_subtitle.clone();
+    let status_text = status_text(snapshot, text);
+
+    let body_html = view! {
+            <div class="portal-root">
+                <Card class="portal-shell">
+                    <CardHeader class="portal-header">
+                        <CardTitle class="portal-title">{portal_title}</CardTitle>
+                        <CardDescription class="portal-subtitle">{portal_subtitle}</CardDescription>
+                    </CardHeader>
+
+                    <CardContent class="portal-content">
+                        <Alert class="portal-status state-connecting" id="status" variant=AlertVariant::Warning>
+                            <AlertTitle class="portal-status-title" id="status-text">{status_text}</AlertTitle>
+                            <AlertDescription class="portal-status-detail" id="status-detail">{text.detail_ap_going_down}</AlertDescription>
+                        </Alert>
+                    </CardContent>
+                </Card>
+            </div>
+    }
+    .to_html();
+
+    html_document(config, lang, &body_html, "")
+}
+
+fn html_document(config: &DaemonConfig, lang: Lang, body_html: &str, script: &str) -> String {
     format!(
         r#"<!DOCTYPE html>
-<html class="dark" lang="en">
+<html class="dark" lang="{}">
 <head>
   <meta charset="UTF-8">
   <meta name="viewport" content="width=device-width, initial-scale=1.0, user-scalable=no">
   <meta name="color-scheme" content="dark">
-  <title>Hyper Recovery - WiFi Setup</title>
+  <title>{} - {}</title>
   <link rel="stylesheet" href="/style.css">
 </head>
 <body>
 {}
-<script>{}</script>
+{}
 </body>
 </html>"#,
-        body_html, PORTAL_BEHAVIOR_JS
+        lang.code(),
+        escape_html(&config.portal_title),
+        escape_html(&config.portal_subtitle),
+        body_html,
+        script
     )
 }
 
-fn render_network_row(network: NetworkInfo) -> impl IntoView {
-    let network_label = if network.is_secured {
+/// Escape text for interpolation into markup outside the Leptos view.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The catalog as a script literal.
+fn text_to_js(text: &Messages) -> String {
+    json_to_js(text)
+}
+
+/// `value` as a script literal; `<` is escaped so no string can close the
+/// surrounding `<script>` element.
+fn json_to_js<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value)
+        .unwrap_or_else(|_| "null".to_string())
+        .replace('<', "\\u003c")
+}
+
+fn scan_age_text(age: Option<u64>, text: &Messages) -> String {
+    match age {
+        None => text.scan_age_never.to_string(),
+        Some(secs) if secs < 120 => fill(text.scan_age_secs, &[("secs", &secs.to_string())]),
+        Some(secs) => fill(text.scan_age_mins, &[("mins", &(secs / 60).to_string())]),
+    }
+}
+
+fn ap_clients_text(clients: &[ApClient], text: &Messages) -> String {
+    match clients.len() {
+        0 => text.ap_clients_none.to_string(),
+        1 => text.ap_clients_one.to_string(),
+        n => fill(text.ap_clients_many, &[("count", &n.to_string())]),
+    }
+}
+
+/// Which rows fall outside the `max` strongest listed networks (weak ones are
+/// hidden separately and don't count). A `max` of 0 lists everything.
+fn overflow_rows(networks: &[NetworkInfo], min_signal: u8, max: usize) -> Vec<bool> {
+    let mut listed: Vec<usize> = (0..networks.len())
+        .filter(|&i| networks[i].meets_min_signal(min_signal))
+        .collect();
+    listed.sort_by_key(|&i| std::cmp::Reverse(networks[i].signal_strength));
+
+    let mut overflow = vec![false; networks.len()];
+    if max > 0 {
+        for &i in listed.iter().skip(max) {
+            overflow[i] = true;
+        }
+    }
+    overflow
+}
+
+fn render_network_row(
+    network: NetworkInfo,
+    min_signal: u8,
+    overflow: bool,
+    saved: bool,
+    text: &'static Messages,
+) -> impl IntoView {
+    let band = network.band();
+    let weak = !network.meets_min_signal(min_signal);
+    let ssid_bytes = serde_json::to_string(network.raw_ssid()).unwrap_or_default();
+    let enhanced_open = network.is_enhanced_open();
+    let network_label = if network.is_secured || enhanced_open {
         network.security_type
     } else {
-        "Open".to_string()
+        text.open.to_string()
     };
 
     let badge_variant = if network.is_secured {
         BadgeVariant::Secondary
+    } else if enhanced_open {
+        BadgeVariant::Outline
     } else {
         BadgeVariant::Default
     };
@@ -519,15 +1069,43 @@ fn render_network_row(network: NetworkInfo) -> impl IntoView {
         <button
             class="network-row"
             data-secured=if network.is_secured { "true" } else { "false" }
+            data-enhanced-open=if enhanced_open { "true" } else { "false" }
             data-ssid=network.ssid.clone()
+            data-band=band.map(|b| b.label()).unwrap_or("unknown")
+            data-weak=if weak { "true" } else { "false" }
+            data-overflow=if overflow { "true" } else { "false" }
+            data-ssid-bytes=ssid_bytes
+            data-saved=if saved { "true" } else { "false" }
+            class:hidden=weak || overflow
             type="button"
         >
             <div class="network-main">
                 <span class="network-ssid">{network.ssid.clone()}</span>
 
                 <div class="network-meta">
-                    <span class="network-channel">{format!("CH {}", network.channel)}</span>
+                    <span class="network-channel">
+                        {match band {
+                            Some(band) => format!("CH {} · {}", network.channel, band.label()),
+                            None => format!("CH {}", network.channel),
+                        }}
+                    </span>
                     <Badge class="network-badge" variant=badge_variant>{network_label}</Badge>
+                    {(network.bss_count > 1)
+                        .then(|| {
+                            view! {
+                                <Badge class="network-badge" variant=BadgeVariant::Outline>
+                                    {fill(text.access_points, &[("count", &network.bss_count.to_string())])}
+                                </Badge>
+                            }
+                        })}
+                    {saved
+                        .then(|| {
+                            view! {
+                                <Badge class="network-badge" variant=BadgeVariant::Outline>
+                                    {text.saved}
+                                </Badge>
+                            }
+                        })}
                 </div>
             </div>
 
@@ -547,6 +1125,8 @@ fn status_variant(status: &ConnectionStatus) -> AlertVariant {
         ConnectionStatus::Connecting => AlertVariant::Warning,
         ConnectionStatus::SwitchingBackend => AlertVariant::Warning,
         ConnectionStatus::Failed => AlertVariant::Destructive,
+        ConnectionStatus::ApUnsupported => AlertVariant::Destructive,
+        ConnectionStatus::RadioBlocked => AlertVariant::Destructive,
         _ => AlertVariant::Default,
     }
 }
@@ -557,47 +1137,130 @@ fn status_tone(status: &ConnectionStatus) -> &'static str {
         ConnectionStatus::Connecting => "connecting",
         ConnectionStatus::SwitchingBackend => "connecting",
         ConnectionStatus::Failed => "failed",
+        ConnectionStatus::ApUnsupported => "failed",
+        ConnectionStatus::RadioBlocked => "failed",
         _ => "waiting",
     }
 }
 
-fn status_text(state: &WifiStateSnapshot) -> String {
+fn status_text(state: &WifiStateSnapshot, text: &Messages) -> String {
     match state.status {
-        ConnectionStatus::Connected => format!(
-            "Connected to {}",
-            state.connected_ssid.as_deref().unwrap_or("network")
-        ),
-        ConnectionStatus::Connecting => format!(
-            "Connecting to {}...",
-            state.connecting_to.as_deref().unwrap_or("network")
+        ConnectionStatus::Connected => fill(
+            text.status_connected,
+            &[(
+                "ssid",
+                state.connected_ssid.as_deref().unwrap_or(text.some_network),
+            )],
         ),
-        ConnectionStatus::SwitchingBackend => "Switching WiFi backend...".to_string(),
-        ConnectionStatus::Failed => "Connection failed".to_string(),
-        ConnectionStatus::Scanning => "Scanning for nearby networks".to_string(),
-        ConnectionStatus::AwaitingCredentials => "Select a network to connect".to_string(),
-        ConnectionStatus::Initializing => "Preparing WiFi setup".to_string(),
-        ConnectionStatus::Disconnected => "Disconnected from WiFi".to_string(),
+        ConnectionStatus::Connecting => {
+            let remaining = state
+                .connect_deadline_secs
+                .map(|secs| fill(text.status_remaining, &[("secs", &secs.to_string())]))
+                .unwrap_or_default();
+            fill(
+                text.status_connecting,
+                &[
+                    (
+                        "ssid",
+                        state.connecting_to.as_deref().unwrap_or(text.some_network),
+                    ),
+                    ("remaining", &remaining),
+                ],
+            )
+        }
+        ConnectionStatus::SwitchingBackend => {
+            let remaining = state
+                .backend_switch_deadline_secs
+                .map(|secs| fill(text.status_remaining, &[("secs", &secs.to_string())]))
+                .unwrap_or_default();
+            fill(text.status_switching_backend, &[("remaining", &remaining)])
+        }
+        ConnectionStatus::Failed => failure_title(state.connect_error.as_ref(), text).to_string(),
+        ConnectionStatus::ApUnsupported => text.status_ap_unsupported.to_string(),
+        ConnectionStatus::RadioBlocked => text.status_radio_blocked.to_string(),
+        ConnectionStatus::Scanning => text.status_scanning.to_string(),
+        ConnectionStatus::AwaitingCredentials => text.status_awaiting_credentials.to_string(),
+        ConnectionStatus::Initializing => text.status_preparing.to_string(),
+        ConnectionStatus::Disconnected => text.status_disconnected.to_string(),
+    }
+}
+
+fn failure_title(error: Option<&ConnectError>, text: &Messages) -> &'static str {
+    match error {
+        Some(ConnectError::WrongPassword) => text.status_wrong_password,
+        Some(ConnectError::SsidNotFound) => text.status_ssid_not_found,
+        Some(ConnectError::AuthTimeout) => text.status_auth_timeout,
+        Some(ConnectError::DhcpFailed) => text.status_dhcp_failed,
+        Some(ConnectError::Other(_)) | None => text.status_failed,
     }
 }
 
-fn status_detail(state: &WifiStateSnapshot) -> String {
+fn backend_step_text(step: Option<BackendSwitchStep>, text: &Messages) -> String {
+    let Some(step) = step else {
+        return text.detail_switching_backend.to_string();
+    };
+    let n = BackendSwitchStep::ALL
+        .iter()
+        .position(|s| *s == step)
+        .unwrap_or(0)
+        + 1;
+    let label = match step {
+        BackendSwitchStep::StoppingOld => text.backend_step_stopping_old,
+        BackendSwitchStep::StartingNew => text.backend_step_starting_new,
+        BackendSwitchStep::RestartingNetworkManager => text.backend_step_restarting_nm,
+    };
+    fill(
+        text.detail_backend_step,
+        &[
+            ("n", &n.to_string()),
+            ("total", &BackendSwitchStep::ALL.len().to_string()),
+            ("step", label),
+        ],
+    )
+}
+
+fn status_detail(state: &WifiStateSnapshot, text: &Messages) -> String {
     match state.status {
-        ConnectionStatus::Connected => "Connection is active. You can continue setup.".to_string(),
-        ConnectionStatus::Connecting => "Attempting authentication and DHCP handshake.".to_string(),
-        ConnectionStatus::SwitchingBackend => {
-            "Restarting WiFi services. The setup AP may restart; reconnect if needed.".to_string()
+        ConnectionStatus::Connected => {
+            let quality: Vec<String> = state
+                .connected_signal
+                .map(|signal| fill(text.quality_signal, &[("signal", &signal.to_string())]))
+                .into_iter()
+                .chain(
+                    state
+                        .connected_rate
+                        .map(|rate| fill(text.quality_rate, &[("rate", &rate.to_string())])),
+                )
+                .collect();
+            let quality = if quality.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", quality.join(", "))
+            };
+            let next = if state.ap_running {
+                text.detail_connected_ap_kept
+            } else {
+                text.detail_connected_done
+            };
+            fill(
+                text.detail_connected,
+                &[("quality", &quality), ("next", next)],
+            )
         }
+        ConnectionStatus::Connecting => text.detail_connecting.to_string(),
+        ConnectionStatus::SwitchingBackend => backend_step_text(state.backend_switch_step, text),
         ConnectionStatus::Failed => state
             .last_error
             .clone()
-            .unwrap_or_else(|| "An unknown error occurred while connecting.".to_string()),
-        ConnectionStatus::Scanning => "Searching for available access points...".to_string(),
-        ConnectionStatus::AwaitingCredentials => {
-            "Choose a network or enter credentials manually.".to_string()
-        }
-        ConnectionStatus::Initializing => {
-            "Waiting for wireless interfaces to become ready.".to_string()
-        }
-        ConnectionStatus::Disconnected => "No active WiFi connection was detected.".to_string(),
+            .unwrap_or_else(|| text.detail_failed.to_string()),
+        ConnectionStatus::ApUnsupported => state
+            .last_error
+            .clone()
+            .unwrap_or_else(|| text.detail_ap_unsupported.to_string()),
+        ConnectionStatus::RadioBlocked => text.detail_radio_blocked.to_string(),
+        ConnectionStatus::Scanning => text.detail_scanning.to_string(),
+        ConnectionStatus::AwaitingCredentials => text.detail_awaiting_credentials.to_string(),
+        ConnectionStatus::Initializing => text.detail_initializing.to_string(),
+        ConnectionStatus::Disconnected => text.detail_disconnected.to_string(),
     }
 }
@@ -0,0 +1,507 @@
+//! Message catalog for the captive portal
+//!
+//! Every user-facing portal string lives in one [`Messages`] table per
+//! language. Placeholders are written `{name}` and filled by [`fill`]; the
+//! same table is serialized into the page so the portal script words status
+//! updates exactly like the server-rendered page. Adding a language means
+//! adding a [`Lang`] variant and its table.
+
+use serde::Serialize;
+
+/// Portal language.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+}
+
+impl Lang {
+    pub const ALL: [Lang; 2] = [Lang::En, Lang::De];
+
+    /// BCP 47 primary tag, as used in `--lang` and `<html lang>`.
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::De => "de",
+        }
+    }
+
+    pub fn messages(self) -> &'static Messages {
+        match self {
+            Lang::En => &EN,
+            Lang::De => &DE,
+        }
+    }
+
+    /// Best supported language in an `Accept-Language` header, by q-value;
+    /// English when nothing listed is supported.
+    pub fn negotiate(accept_language: Option<&str>) -> Lang {
+        let mut best: Option<(Lang, f32)> = None;
+        for entry in accept_language.unwrap_or_default().split(',') {
+            let mut parts = entry.split(';');
+            let tag = parts.next().unwrap_or_default().trim();
+            let primary = tag.split('-').next().unwrap_or_default();
+            let Some(lang) = Lang::ALL
+                .into_iter()
+                .find(|lang| lang.code().eq_ignore_ascii_case(primary))
+            else {
+                continue;
+            };
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())
+                .unwrap_or(0.0);
+            if quality > 0.0 && best.is_none_or(|(_, q)| quality > q) {
+                best = Some((lang, quality));
+            }
+        }
+        best.map(|(lang, _)| lang).unwrap_or_default()
+    }
+}
+
+impl std::str::FromStr for Lang {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        Lang::ALL
+            .into_iter()
+            .find(|lang| lang.code() == value)
+            .ok_or_else(|| "expected \"en\" or \"de\"".to_string())
+    }
+}
+
+/// Replace each `{name}` in `template` with its value, in one pass so a
+/// value that itself looks like a placeholder (an SSID, say) stays as typed.
+pub fn fill(template: &str, args: &[(&str, &str)]) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            args.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| (*value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                text.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                text.push('{');
+                rest = after;
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+/// One language's portal strings, keyed by field name in the page script.
+#[derive(Debug, Serialize)]
+pub struct Messages {
+    // Status headlines
+    pub status_preparing: &'static str,
+    pub status_connected: &'static str,
+    pub status_connecting: &'static str,
+    pub status_remaining: &'static str,
+    pub some_network: &'static str,
+    pub status_switching_backend: &'static str,
+    pub status_failed: &'static str,
+    pub status_wrong_password: &'static str,
+    pub status_ssid_not_found: &'static str,
+    pub status_auth_timeout: &'static str,
+    pub status_dhcp_failed: &'static str,
+    pub status_ap_unsupported: &'static str,
+    pub status_radio_blocked: &'static str,
+    pub status_scanning: &'static str,
+    pub status_awaiting_credentials: &'static str,
+    pub status_disconnected: &'static str,
+
+    // Status details
+    pub detail_connected: &'static str,
+    pub quality_signal: &'static str,
+    pub quality_rate: &'static str,
+    pub detail_connected_done: &'static str,
+    pub detail_connected_ap_kept: &'static str,
+    pub detail_connecting: &'static str,
+    pub detail_ap_going_down: &'static str,
+    pub detail_switching_backend: &'static str,
+    pub detail_backend_step: &'static str,
+    pub backend_step_stopping_old: &'static str,
+    pub backend_step_starting_new: &'static str,
+    pub backend_step_restarting_nm: &'static str,
+    pub detail_failed: &'static str,
+    pub detail_ap_unsupported: &'static str,
+    pub detail_radio_blocked: &'static str,
+    pub detail_scanning: &'static str,
+    pub detail_awaiting_credentials: &'static str,
+    pub detail_initializing: &'static str,
+    pub detail_disconnected: &'static str,
+
+    // Progress shown by the page script between polls
+    pub connect_applying: &'static str,
+    pub connect_requested: &'static str,
+    pub connect_waiting_daemon: &'static str,
+    pub connect_in_progress: &'static str,
+    pub connect_following: &'static str,
+    pub connect_still_trying: &'static str,
+    pub connect_error: &'static str,
+    pub connect_unexpected_error: &'static str,
+    pub status_sync_waiting: &'static str,
+    pub status_sync_retrying: &'static str,
+    pub backend_switch_restarting: &'static str,
+    pub backend_switch_requested: &'static str,
+    pub backend_switch_failed: &'static str,
+    pub backend_switch_error: &'static str,
+    pub backend_switch_unreachable: &'static str,
+    pub backend_unknown: &'static str,
+    pub scan_failed: &'static str,
+    pub scan_refreshing: &'static str,
+    pub scan_error: &'static str,
+    pub scan_unreachable: &'static str,
+    pub cancel_requested: &'static str,
+    pub cancel_restoring_ap: &'static str,
+    pub cancel_failed: &'static str,
+    pub service_unreachable: &'static str,
+
+    // Header and network list
+    pub portal_address: &'static str,
+    pub ap_clients: &'static str,
+    pub ap_clients_none: &'static str,
+    pub ap_clients_one: &'static str,
+    pub ap_clients_many: &'static str,
+    pub scan_age_never: &'static str,
+    pub scan_age_secs: &'static str,
+    pub scan_age_mins: &'static str,
+    pub weak_hidden_one: &'static str,
+    pub weak_hidden_many: &'static str,
+    pub weak_shown_one: &'static str,
+    pub weak_shown_many: &'static str,
+    pub show: &'static str,
+    pub hide: &'static str,
+    pub show_all: &'static str,
+    pub no_networks: &'static str,
+    pub all_bands: &'static str,
+    pub open: &'static str,
+    pub saved: &'static str,
+    pub access_points: &'static str,
+
+    // Buttons
+    pub cancel_connection: &'static str,
+    pub scan_again: &'static str,
+    pub enter_manually: &'static str,
+    pub help_settings: &'static str,
+    pub cancel: &'static str,
+    pub connect: &'static str,
+    pub close: &'static str,
+
+    // Modals
+    pub password_title: &'static str,
+    pub selected_network: &'static str,
+    pub password_placeholder: &'static str,
+    pub remember_password: &'static str,
+    pub use_saved_password: &'static str,
+    pub open_title: &'static str,
+    pub open_warning: &'static str,
+    pub connect_anyway: &'static str,
+    pub manual_title: &'static str,
+    pub manual_subtitle: &'static str,
+    pub ssid_placeholder: &'static str,
+    pub security_auto: &'static str,
+    pub manual_password_placeholder: &'static str,
+    pub settings_help: &'static str,
+    pub current_backend: &'static str,
+    pub backend_iwd: &'static str,
+    pub backend_wpa_supplicant: &'static str,
+    pub apply_backend: &'static str,
+}
+
+static EN: Messages = Messages {
+    status_preparing: "Preparing WiFi setup",
+    status_connected: "Connected to {ssid}",
+    status_connecting: "Connecting to {ssid}...{remaining}",
+    status_remaining: " ({secs}s remaining)",
+    some_network: "network",
+    status_switching_backend: "Switching WiFi backend...{remaining}",
+    status_failed: "Connection failed",
+    status_wrong_password: "Incorrect password",
+    status_ssid_not_found: "Network not found",
+    status_auth_timeout: "Authentication timed out",
+    status_dhcp_failed: "Could not obtain an IP address",
+    status_ap_unsupported: "Setup AP unavailable",
+    status_radio_blocked: "WiFi is disabled by hardware switch",
+    status_scanning: "Scanning for nearby networks",
+    status_awaiting_credentials: "Select a network to connect",
+    status_disconnected: "Disconnected from WiFi",
+
+    detail_connected: "Connection is active{quality}. {next}",
+    quality_signal: "signal {signal}%",
+    quality_rate: "{rate} Mbit/s",
+    detail_connected_done: "You can close this page now.",
+    detail_connected_ap_kept: "The setup AP stays up, so this page remains available.",
+    detail_connecting: "Authentication and DHCP are still in progress.",
+    detail_ap_going_down:
+        "The setup network will disappear shortly. Reconnect this device to your usual WiFi; if joining fails, the setup network comes back and you can reopen this page.",
+    detail_switching_backend:
+        "Restarting WiFi services. The setup AP may restart; reconnect if needed.",
+    detail_backend_step:
+        "Step {n} of {total}: {step}. The setup AP may restart; reconnect if needed.",
+    backend_step_stopping_old: "stopping the old WiFi service",
+    backend_step_starting_new: "starting the new WiFi service",
+    backend_step_restarting_nm: "restarting NetworkManager",
+    detail_failed: "Unknown error while connecting.",
+    detail_ap_unsupported: "This adapter cannot host a setup AP.",
+    detail_radio_blocked:
+        "Turn on the wireless switch or key on this device, then restart setup.",
+    detail_scanning: "Searching for available access points...",
+    detail_awaiting_credentials: "Choose a network or enter credentials manually.",
+    detail_initializing: "Waiting for wireless interfaces to become ready.",
+    detail_disconnected: "No active WiFi connection was detected.",
+
+    connect_applying: "Applying credentials and starting authentication...",
+    connect_requested: "Connection requested",
+    connect_waiting_daemon: "Waiting for daemon status...",
+    connect_in_progress: "Connection in progress",
+    connect_following: "Waiting for the current attempt to finish...",
+    connect_still_trying: "Still attempting to join the network...",
+    connect_error: "Connection error",
+    connect_unexpected_error: "Unexpected network error.",
+    status_sync_waiting: "Waiting for portal status",
+    status_sync_retrying: "Retrying status sync...",
+    backend_switch_restarting: "Restarting WiFi services and NetworkManager...",
+    backend_switch_requested: "Backend switch requested",
+    backend_switch_failed: "Backend switch failed",
+    backend_switch_error: "Backend switch error",
+    backend_switch_unreachable: "Unable to switch backend.",
+    backend_unknown: "unknown",
+    scan_failed: "Scan failed",
+    scan_refreshing: "Refreshing network list...",
+    scan_error: "Scan error",
+    scan_unreachable: "Unable to trigger scan.",
+    cancel_requested: "Cancelling connection...",
+    cancel_restoring_ap: "Bringing the setup network back up.",
+    cancel_failed: "Cancel failed",
+    service_unreachable: "Unable to reach the setup service.",
+
+    portal_address: "Portal address: {url}",
+    ap_clients: "Setup AP: {clients}",
+    ap_clients_none: "No devices connected",
+    ap_clients_one: "1 device connected",
+    ap_clients_many: "{count} devices connected",
+    scan_age_never: "Networks not scanned yet",
+    scan_age_secs: "Networks last scanned {secs}s ago",
+    scan_age_mins: "Networks last scanned {mins}m ago",
+    weak_hidden_one: "1 weak network hidden",
+    weak_hidden_many: "{count} weak networks hidden",
+    weak_shown_one: "1 weak network shown",
+    weak_shown_many: "{count} weak networks shown",
+    show: "Show",
+    hide: "Hide",
+    show_all: "Show all ({count} more)",
+    no_networks: "No networks found. Use Scan Again to refresh the list.",
+    all_bands: "All bands",
+    open: "Open",
+    saved: "Saved",
+    access_points: "{count} APs",
+
+    cancel_connection: "Cancel connection",
+    scan_again: "Scan Again",
+    enter_manually: "Enter Network Manually",
+    help_settings: "Help / Settings",
+    cancel: "Cancel",
+    connect: "Connect",
+    close: "Close",
+
+    password_title: "Enter Password",
+    selected_network: "Selected network",
+    password_placeholder: "Password",
+    remember_password: "Remember password for auto-connect",
+    use_saved_password: "Connect with saved password",
+    open_title: "Unencrypted Network",
+    open_warning: "This network is unencrypted. Anyone nearby can see the traffic you send over it, so avoid entering sensitive data until you are on a trusted connection.",
+    connect_anyway: "Connect Anyway",
+    manual_title: "Manual Network Entry",
+    manual_subtitle: "Enter SSID and password for hidden network",
+    ssid_placeholder: "Network Name (SSID)",
+    security_auto: "Security: from password",
+    manual_password_placeholder: "Password (leave empty for open network)",
+    settings_help: "If WiFi connection is unreliable, try switching the NetworkManager WiFi backend. This restarts WiFi services and may temporarily drop your connection to the setup AP.",
+    current_backend: "Current backend",
+    backend_iwd: "iwd (recommended for Intel WiFi)",
+    backend_wpa_supplicant: "wpa_supplicant (max compatibility)",
+    apply_backend: "Apply & Restart WiFi",
+};
+
+static DE: Messages = Messages {
+    status_preparing: "WLAN-Einrichtung wird vorbereitet",
+    status_connected: "Verbunden mit {ssid}",
+    status_connecting: "Verbinde mit {ssid}...{remaining}",
+    status_remaining: " (noch {secs} s)",
+    some_network: "Netzwerk",
+    status_switching_backend: "WLAN-Backend wird gewechselt...{remaining}",
+    status_failed: "Verbindung fehlgeschlagen",
+    status_wrong_password: "Falsches Passwort",
+    status_ssid_not_found: "Netzwerk nicht gefunden",
+    status_auth_timeout: "Zeitüberschreitung bei der Anmeldung",
+    status_dhcp_failed: "Keine IP-Adresse erhalten",
+    status_ap_unsupported: "Einrichtungs-WLAN nicht verfügbar",
+    status_radio_blocked: "WLAN ist per Hardwareschalter deaktiviert",
+    status_scanning: "Suche nach Netzwerken in der Nähe",
+    status_awaiting_credentials: "Netzwerk zum Verbinden auswählen",
+    status_disconnected: "Keine WLAN-Verbindung",
+
+    detail_connected: "Verbindung ist aktiv{quality}. {next}",
+    quality_signal: "Signal {signal} %",
+    quality_rate: "{rate} Mbit/s",
+    detail_connected_done: "Sie können diese Seite jetzt schließen.",
+    detail_connected_ap_kept:
+        "Das Einrichtungs-WLAN bleibt aktiv, diese Seite bleibt also erreichbar.",
+    detail_connecting: "Anmeldung und DHCP laufen noch.",
+    detail_ap_going_down: "Das Einrichtungs-WLAN verschwindet gleich. Verbinden Sie dieses Gerät wieder mit Ihrem üblichen WLAN; schlägt die Verbindung fehl, kommt das Einrichtungs-WLAN zurück und Sie können diese Seite erneut öffnen.",
+    detail_switching_backend: "WLAN-Dienste werden neu gestartet. Das Einrichtungs-WLAN startet eventuell neu; verbinden Sie sich bei Bedarf erneut.",
+    detail_backend_step: "Schritt {n} von {total}: {step}. Das Einrichtungs-WLAN startet eventuell neu; verbinden Sie sich bei Bedarf erneut.",
+    backend_step_stopping_old: "alter WLAN-Dienst wird beendet",
+    backend_step_starting_new: "neuer WLAN-Dienst wird gestartet",
+    backend_step_restarting_nm: "NetworkManager wird neu gestartet",
+    detail_failed: "Unbekannter Fehler beim Verbinden.",
+    detail_ap_unsupported: "Dieser Adapter kann kein Einrichtungs-WLAN bereitstellen.",
+    detail_radio_blocked: "Schalten Sie den WLAN-Schalter oder die WLAN-Taste dieses Geräts ein und starten Sie die Einrichtung neu.",
+    detail_scanning: "Suche nach verfügbaren Zugangspunkten...",
+    detail_awaiting_credentials:
+        "Wählen Sie ein Netzwerk oder geben Sie die Zugangsdaten manuell ein.",
+    detail_initializing: "Warte auf die WLAN-Schnittstellen.",
+    detail_disconnected: "Es wurde keine aktive WLAN-Verbindung erkannt.",
+
+    connect_applying: "Zugangsdaten werden übernommen, Anmeldung startet...",
+    connect_requested: "Verbindung angefordert",
+    connect_waiting_daemon: "Warte auf den Status des Dienstes...",
+    connect_in_progress: "Verbindung wird hergestellt",
+    connect_following: "Warte, bis der laufende Versuch abgeschlossen ist...",
+    connect_still_trying: "Versuche weiterhin, dem Netzwerk beizutreten...",
+    connect_error: "Verbindungsfehler",
+    connect_unexpected_error: "Unerwarteter Netzwerkfehler.",
+    status_sync_waiting: "Warte auf den Portalstatus",
+    status_sync_retrying: "Status wird erneut abgefragt...",
+    backend_switch_restarting: "WLAN-Dienste und NetworkManager werden neu gestartet...",
+    backend_switch_requested: "Backend-Wechsel angefordert",
+    backend_switch_failed: "Backend-Wechsel fehlgeschlagen",
+    backend_switch_error: "Fehler beim Backend-Wechsel",
+    backend_switch_unreachable: "Backend konnte nicht gewechselt werden.",
+    backend_unknown: "unbekannt",
+    scan_failed: "Suche fehlgeschlagen",
+    scan_refreshing: "Netzwerkliste wird aktualisiert...",
+    scan_error: "Fehler bei der Suche",
+    scan_unreachable: "Suche konnte nicht gestartet werden.",
+    cancel_requested: "Verbindung wird abgebrochen...",
+    cancel_restoring_ap: "Das Einrichtungs-WLAN wird wieder gestartet.",
+    cancel_failed: "Abbrechen fehlgeschlagen",
+    service_unreachable: "Der Einrichtungsdienst ist nicht erreichbar.",
+
+    portal_address: "Portaladresse: {url}",
+    ap_clients: "Einrichtungs-WLAN: {clients}",
+    ap_clients_none: "Keine Geräte verbunden",
+    ap_clients_one: "1 Gerät verbunden",
+    ap_clients_many: "{count} Geräte verbunden",
+    scan_age_never: "Noch nicht nach Netzwerken gesucht",
+    scan_age_secs: "Zuletzt vor {secs} s nach Netzwerken gesucht",
+    scan_age_mins: "Zuletzt vor {mins} min nach Netzwerken gesucht",
+    weak_hidden_one: "1 schwaches Netzwerk ausgeblendet",
+    weak_hidden_many: "{count} schwache Netzwerke ausgeblendet",
+    weak_shown_one: "1 schwaches Netzwerk angezeigt",
+    weak_shown_many: "{count} schwache Netzwerke angezeigt",
+    show: "Anzeigen",
+    hide: "Ausblenden",
+    show_all: "Alle anzeigen ({count} weitere)",
+    no_networks: "Keine Netzwerke gefunden. Mit „Erneut suchen“ die Liste aktualisieren.",
+    all_bands: "Alle Bänder",
+    open: "Offen",
+    saved: "Gespeichert",
+    access_points: "{count} APs",
+
+    cancel_connection: "Verbindung abbrechen",
+    scan_again: "Erneut suchen",
+    enter_manually: "Netzwerk manuell eingeben",
+    help_settings: "Hilfe / Einstellungen",
+    cancel: "Abbrechen",
+    connect: "Verbinden",
+    close: "Schließen",
+
+    password_title: "Passwort eingeben",
+    selected_network: "Ausgewähltes Netzwerk",
+    password_placeholder: "Passwort",
+    remember_password: "Passwort für automatisches Verbinden speichern",
+    use_saved_password: "Mit gespeichertem Passwort verbinden",
+    open_title: "Unverschlüsseltes Netzwerk",
+    open_warning: "Dieses Netzwerk ist unverschlüsselt. Jeder in der Nähe kann Ihren Datenverkehr mitlesen. Geben Sie keine sensiblen Daten ein, bis Sie über eine vertrauenswürdige Verbindung verfügen.",
+    connect_anyway: "Trotzdem verbinden",
+    manual_title: "Netzwerk manuell eingeben",
+    manual_subtitle: "SSID und Passwort für ein verborgenes Netzwerk eingeben",
+    ssid_placeholder: "Netzwerkname (SSID)",
+    security_auto: "Sicherheit: nach Passwort",
+    manual_password_placeholder: "Passwort (leer lassen für offenes Netzwerk)",
+    settings_help: "Falls die WLAN-Verbindung unzuverlässig ist, wechseln Sie das WLAN-Backend von NetworkManager. Dabei werden die WLAN-Dienste neu gestartet, und die Verbindung zum Einrichtungs-WLAN kann kurz abbrechen.",
+    current_backend: "Aktuelles Backend",
+    backend_iwd: "iwd (empfohlen für Intel-WLAN)",
+    backend_wpa_supplicant: "wpa_supplicant (maximale Kompatibilität)",
+    apply_backend: "Übernehmen & WLAN neu starten",
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn placeholders(template: &str) -> BTreeSet<&str> {
+        template
+            .split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+            .collect()
+    }
+
+    #[test]
+    fn test_translations_keep_placeholders() {
+        let english = serde_json::to_value(&EN).unwrap();
+        for lang in Lang::ALL {
+            let translated = serde_json::to_value(lang.messages()).unwrap();
+            for (key, text) in english.as_object().unwrap() {
+                let other = translated[key].as_str().unwrap();
+                assert!(!other.is_empty(), "{}: {} is empty", lang.code(), key);
+                assert_eq!(
+                    placeholders(text.as_str().unwrap()),
+                    placeholders(other),
+                    "{}: {}",
+                    lang.code(),
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_negotiate_accept_language() {
+        assert_eq!(Lang::negotiate(None), Lang::En);
+        assert_eq!(Lang::negotiate(Some("de-DE,de;q=0.9,en;q=0.8")), Lang::De);
+        assert_eq!(Lang::negotiate(Some("fr-FR, en;q=0.5, de;q=0.7")), Lang::De);
+        assert_eq!(Lang::negotiate(Some("fr, DE;q=0")), Lang::En);
+        assert_eq!(Lang::negotiate(Some("*")), Lang::En);
+        assert_eq!("de".parse::<Lang>(), Ok(Lang::De));
+        assert!("fr".parse::<Lang>().is_err());
+    }
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(
+            fill(EN.status_connecting, &[("ssid", "Home"), ("remaining", "")]),
+            "Connecting to Home..."
+        );
+        assert_eq!(
+            fill(EN.status_connected, &[("ssid", "{ssid} {x}")]),
+            "Connected to {ssid} {x}"
+        );
+    }
+}
@@ -2,76 +2,1021 @@
 
 mod assets;
 mod components;
+mod i18n;
 mod routes;
+mod tls;
 
-use crate::controller::{AppState, WifiState};
+use crate::controller::{
+    self, AppState, ConnectionStatus, DaemonConfig, OnboardingInfo, WifiState,
+};
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, StatusCode, Uri},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
     routing::{get, post},
-    Router,
+    Json, Router,
 };
+use axum_server::tls_rustls::RustlsConfig;
+use std::fmt::Write;
+use std::future::IntoFuture;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use tokio::net::TcpListener;
 use tokio::sync::watch;
 
+pub use i18n::Lang;
+
+/// The portal's bound sockets: plain HTTP, plus HTTPS with `--tls`
+pub struct PortalListeners {
+    http: TcpListener,
+    https: Option<(std::net::TcpListener, RustlsConfig)>,
+}
+
+/// Bind the portal's TCP listeners
+pub async fn bind(config: &DaemonConfig) -> anyhow::Result<PortalListeners> {
+    let addr = format!("0.0.0.0:{}", config.port);
+    let http = TcpListener::bind(&addr).await?;
+    tracing::info!(addr = %addr, "Starting web portal");
+
+    let https = if config.tls {
+        let tls_config = tls::rustls_config(config).await?;
+        let addr = format!("0.0.0.0:{}", config.tls_port);
+        let listener = TcpListener::bind(&addr).await?.into_std()?;
+        tracing::info!(addr = %addr, "Starting HTTPS web portal");
+        Some((listener, tls_config))
+    } else {
+        None
+    };
+
+    Ok(PortalListeners { http, https })
+}
+
 /// Run the web server
 pub async fn run_server(
+    listeners: PortalListeners,
     state: Arc<AppState>,
     _state_rx: watch::Receiver<WifiState>,
 ) -> anyhow::Result<()> {
-    let app = Router::new()
+    let Some((https, tls_config)) = listeners.https else {
+        axum::serve(
+            listeners.http,
+            router(state).into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    // Captive-portal checks only ever use HTTP; everything else moves to HTTPS.
+    let http = axum::serve(listeners.http, redirect_router(state.clone()));
+    let https = axum_server::from_tcp_rustls(https, tls_config)
+        .serve(router(state).into_make_service_with_connect_info::<SocketAddr>());
+    tokio::try_join!(http.into_future(), https)?;
+
+    Ok(())
+}
+
+/// What a connectivity probe expects back once the device is online
+#[derive(Debug, Clone, Copy)]
+enum ProbeReply {
+    NoContent,
+    Text(&'static str),
+    Html(&'static str),
+}
+
+/// OS connectivity-check paths and the "online" answer each one looks for.
+///
+/// Anything else still redirects to the portal through the fallback.
+const CAPTIVE_PROBES: &[(&str, ProbeReply)] = &[
+    // Android / ChromeOS
+    ("/generate_204", ProbeReply::NoContent),
+    ("/gen_204", ProbeReply::NoContent),
+    // Apple
+    ("/hotspot-detect.html", ProbeReply::Html(APPLE_SUCCESS_HTML)),
+    (
+        "/library/test/success.html",
+        ProbeReply::Html(APPLE_SUCCESS_HTML),
+    ),
+    // Windows
+    (
+        "/connecttest.txt",
+        ProbeReply::Text("Microsoft Connect Test"),
+    ),
+    ("/ncsi.txt", ProbeReply::Text("Microsoft NCSI")),
+    // Firefox
+    ("/success.txt", ProbeReply::Text("success\n")),
+];
+
+const APPLE_SUCCESS_HTML: &str =
+    "<HTML><HEAD><TITLE>Success</TITLE></HEAD><BODY>Success</BODY></HTML>";
+
+fn router(state: Arc<AppState>) -> Router {
+    let router = Router::new()
         // Main portal page
         .route("/", get(routes::index))
         // API endpoints
         .route("/api/status", get(routes::api_status))
         .route("/api/connect", post(routes::api_connect))
+        .route("/api/connect/cancel", post(routes::api_cancel_connect))
         .route("/api/scan", post(routes::api_scan))
         .route("/api/backend", post(routes::api_backend))
-        // Captive portal detection endpoints
-        .route("/generate_204", get(captive_check))
-        .route("/hotspot-detect.html", get(captive_redirect))
-        .route("/connecttest.txt", get(captive_redirect))
-        .route("/ncsi.txt", get(captive_redirect))
         // Static assets
-        .route("/style.css", get(assets::serve_css))
+        .route("/style.css", get(assets::serve_css));
+    with_common_routes(router, state)
+}
+
+/// Plain-HTTP side of `--tls`: no portal pages, only probes and redirects.
+fn redirect_router(state: Arc<AppState>) -> Router {
+    with_common_routes(Router::new(), state)
+}
+
+/// Routes served on every listener: captive checks, operational endpoints and
+/// the redirect fallback.
+fn with_common_routes(mut router: Router<Arc<AppState>>, state: Arc<AppState>) -> Router {
+    // Captive portal detection endpoints
+    for (path, _) in CAPTIVE_PROBES {
+        router = router.route(path, get(captive_probe));
+    }
+
+    router
+        // Operational endpoints (never redirected to the portal)
+        .route("/healthz", get(healthz))
+        .route("/info", get(info))
+        .route("/metrics", get(metrics))
         // Fallback - redirect everything to portal
         .fallback(get(captive_redirect))
-        .with_state(state.clone());
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            track_activity,
+        ))
+        .with_state(state)
+}
 
-    let addr = format!("0.0.0.0:{}", state.config.port);
-    tracing::info!(addr = %addr, "Starting web portal");
+/// Count portal traffic as AP activity for the idle timeout; probes don't count.
+async fn track_activity(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !matches!(request.uri().path(), "/healthz" | "/metrics" | "/info") {
+        state.touch_activity();
+    }
+    next.run(request).await
+}
 
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+/// Whether `url` can be handed to clients as `--success-url`.
+pub fn is_valid_success_url(url: &str) -> bool {
+    url.parse::<Uri>()
+        .map(|uri| matches!(uri.scheme_str(), Some("http" | "https")) && uri.host().is_some())
+        .unwrap_or(false)
+}
 
-    Ok(())
+/// `--success-url`, once the client has somewhere to go.
+pub(super) async fn success_url(state: &AppState) -> Option<&str> {
+    let url = state.config.success_url.as_deref()?;
+    let connected = state.wifi_state.read().await.status == ConnectionStatus::Connected;
+    connected.then_some(url)
 }
 
-/// Captive portal check - return 204 when connected, redirect when not
-async fn captive_check(State(state): State<Arc<AppState>>) -> Response {
-    let wifi_state = state.wifi_state.read().await;
+/// Captive portal check - answer like the real endpoint when connected, redirect when not
+async fn captive_probe(
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(url) = success_url(&state).await {
+        return redirect_to(url.to_string());
+    }
+    let connected = state.wifi_state.read().await.status == ConnectionStatus::Connected;
+    let reply = CAPTIVE_PROBES
+        .iter()
+        .find(|(path, _)| *path == uri.path())
+        .map(|(_, reply)| *reply);
 
-    if matches!(
-        wifi_state.status,
-        crate::controller::ConnectionStatus::Connected
-    ) {
-        StatusCode::NO_CONTENT.into_response()
-    } else {
-        // Redirect to portal
-        (
-            StatusCode::FOUND,
-            [("Location", format!("http://{}/", state.config.ap_ip))],
-        )
-            .into_response()
+    match reply {
+        Some(ProbeReply::NoContent) if connected => StatusCode::NO_CONTENT.into_response(),
+        Some(ProbeReply::Text(body)) if connected => {
+            ([(header::CONTENT_TYPE, "text/plain")], body).into_response()
+        }
+        Some(ProbeReply::Html(body)) if connected => {
+            ([(header::CONTENT_TYPE, "text/html")], body).into_response()
+        }
+        _ => captive_redirect(State(state), headers).await,
     }
 }
 
-/// Redirect to portal
-async fn captive_redirect(State(state): State<Arc<AppState>>) -> Response {
+/// Redirect to portal, or answer with the going-down page directly while a
+/// connect tears the AP down
+async fn captive_redirect(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    if state.ap_going_down().await {
+        return routes::ap_going_down_page(&state, &headers).await;
+    }
+    if let Some(url) = success_url(&state).await {
+        return redirect_to(url.to_string());
+    }
+    redirect_to(format!("{}/", state.config.portal_url()))
+}
+
+pub(super) fn redirect_to(location: String) -> Response {
     (
         StatusCode::FOUND,
-        [("Location", format!("http://{}/", state.config.ap_ip))],
+        [
+            (header::LOCATION, location),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
     )
         .into_response()
 }
+
+/// Liveness probe - 200 once the controller is past initialization
+async fn healthz(State(state): State<Arc<AppState>>) -> Response {
+    let wifi_state = state.wifi_state.read().await;
+    let body = serde_json::json!({ "status": wifi_state.status });
+
+    if wifi_state.status == ConnectionStatus::Initializing {
+        (StatusCode::SERVICE_UNAVAILABLE, Json(body)).into_response()
+    } else {
+        Json(body).into_response()
+    }
+}
+
+/// Join instructions for labels and secondary displays; available before the AP is up.
+///
+/// The passphrase is only given to clients already on the setup AP; the
+/// portal also listens on the upstream LAN.
+async fn info(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+) -> Json<OnboardingInfo> {
+    let info = state
+        .wifi_state
+        .read()
+        .await
+        .onboarding_info()
+        .unwrap_or_else(|| controller::onboarding_info(&state.config));
+    if on_ap_subnet(&state.config, peer.map(|ConnectInfo(addr)| addr)) {
+        Json(info)
+    } else {
+        Json(info.redacted())
+    }
+}
+
+/// Whether `peer` is on the setup AP's /24.
+fn on_ap_subnet(config: &DaemonConfig, peer: Option<SocketAddr>) -> bool {
+    let Ok(ap_ip) = config.ap_ip.parse::<Ipv4Addr>() else {
+        return false;
+    };
+    match peer.map(|addr| addr.ip()) {
+        Some(IpAddr::V4(ip)) => ip.octets()[..3] == ap_ip.octets()[..3],
+        _ => false,
+    }
+}
+
+/// Prometheus text-format metrics
+async fn metrics(State(state): State<Arc<AppState>>) -> Response {
+    let wifi_state = state.wifi_state.read().await;
+    let stats = &state.connect_stats;
+    let mut out = String::new();
+
+    write_metric(
+        &mut out,
+        "hyper_wifi_ap_running",
+        "gauge",
+        "Whether the setup access point is running.",
+        &[("", u64::from(wifi_state.ap_running))],
+    );
+    write_metric(
+        &mut out,
+        "hyper_wifi_networks_visible",
+        "gauge",
+        "Networks in the current scan list.",
+        &[("", wifi_state.available_networks.len() as u64)],
+    );
+
+    let states = ConnectionStatus::ALL.map(|status| {
+        (
+            format!("{{state=\"{:?}\"}}", status),
+            u64::from(status == wifi_state.status),
+        )
+    });
+    let states = states
+        .iter()
+        .map(|(labels, value)| (labels.as_str(), *value))
+        .collect::<Vec<_>>();
+    write_metric(
+        &mut out,
+        "hyper_wifi_status",
+        "gauge",
+        "Current connection state (1 for the active state).",
+        &states,
+    );
+
+    write_metric(
+        &mut out,
+        "hyper_wifi_connect_attempts_total",
+        "counter",
+        "Connection attempts started.",
+        &[("", stats.attempts.load(Ordering::Relaxed))],
+    );
+    write_metric(
+        &mut out,
+        "hyper_wifi_connect_failures_total",
+        "counter",
+        "Connection attempts that failed.",
+        &[("", stats.failures.load(Ordering::Relaxed))],
+    );
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out).into_response()
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(&str, u64)]) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{}{} {}", name, labels, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{test_app_state, test_app_state_with, ControlCommand, NetworkInfo};
+    use axum::body::{to_bytes, Body};
+    use tower::ServiceExt;
+
+    async fn post_json(app: Router, uri: &str, body: &str) -> StatusCode {
+        let request = Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    async fn post_json_body(app: Router, uri: &str, body: &str) -> (StatusCode, String) {
+        let request = Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    async fn get_body(app: Router, uri: &str) -> (StatusCode, String) {
+        let response = app
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_cache_headers() {
+        let (state, _rx) = test_app_state();
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        let response = router(state.clone()).oneshot(get("/")).await.unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+
+        let response = router(state.clone())
+            .oneshot(get("/generate_204_elsewhere"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+
+        let response = router(state.clone())
+            .oneshot(get("/style.css"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[header::CACHE_CONTROL]
+            .to_str()
+            .unwrap()
+            .contains("max-age="));
+        let etag = response.headers()[header::ETAG].clone();
+
+        let revalidate = Request::get("/style.css")
+            .header(header::IF_NONE_MATCH, etag)
+            .body(Body::empty())
+            .unwrap();
+        let response = router(state).oneshot(revalidate).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_healthz_waits_for_initialization() {
+        let (state, _rx) = test_app_state();
+        let (status, _) = get_body(router(state.clone()), "/healthz").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        state.wifi_state.write().await.status = ConnectionStatus::AwaitingCredentials;
+        let (status, body) = get_body(router(state), "/healthz").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("AwaitingCredentials"));
+    }
+
+    #[tokio::test]
+    async fn test_captive_probes() {
+        let (state, _rx) = test_app_state();
+        let portal = format!("http://{}/", state.config.ap_ip);
+        for (path, _) in CAPTIVE_PROBES {
+            let response = router(state.clone())
+                .oneshot(Request::get(*path).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::FOUND, "{path}");
+            assert_eq!(response.headers()[header::LOCATION], portal.as_str());
+        }
+
+        state.wifi_state.write().await.status = ConnectionStatus::Connected;
+        let expected = [
+            ("/generate_204", StatusCode::NO_CONTENT, ""),
+            ("/gen_204", StatusCode::NO_CONTENT, ""),
+            ("/hotspot-detect.html", StatusCode::OK, APPLE_SUCCESS_HTML),
+            (
+                "/library/test/success.html",
+                StatusCode::OK,
+                APPLE_SUCCESS_HTML,
+            ),
+            ("/connecttest.txt", StatusCode::OK, "Microsoft Connect Test"),
+            ("/ncsi.txt", StatusCode::OK, "Microsoft NCSI"),
+            ("/success.txt", StatusCode::OK, "success\n"),
+        ];
+        assert_eq!(expected.len(), CAPTIVE_PROBES.len());
+        for (path, status, body) in expected {
+            assert_eq!(
+                get_body(router(state.clone()), path).await,
+                (status, body.into())
+            );
+        }
+
+        // Unknown paths still land on the portal.
+        let (status, _) = get_body(router(state), "/anything").await;
+        assert_eq!(status, StatusCode::FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_success_url_redirects_once_connected() {
+        const NEXT: &str = "https://recovery.example.com/next";
+        let (state, _rx) = test_app_state_with(|config| config.success_url = Some(NEXT.into()));
+        let portal = format!("http://{}/", state.config.ap_ip);
+
+        // Still setting up: the portal as usual.
+        let response = router(state.clone())
+            .oneshot(Request::get("/generate_204").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[header::LOCATION], portal.as_str());
+        let (status, body) = get_body(router(state.clone()), "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(&format!("var SUCCESS_URL = \"{}\";", NEXT)));
+
+        state.wifi_state.write().await.status = ConnectionStatus::Connected;
+        for path in ["/", "/generate_204", "/hotspot-detect.html", "/anything"] {
+            let response = router(state.clone())
+                .oneshot(Request::get(path).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::FOUND, "{path}");
+            assert_eq!(response.headers()[header::LOCATION], NEXT, "{path}");
+            assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+        }
+
+        // Without one, connected probes keep answering as online.
+        let (state, _rx) = test_app_state();
+        state.wifi_state.write().await.status = ConnectionStatus::Connected;
+        let (status, _) = get_body(router(state.clone()), "/generate_204").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, body) = get_body(router(state), "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("var SUCCESS_URL = null;"));
+    }
+
+    #[test]
+    fn test_success_url_validation() {
+        assert!(is_valid_success_url("https://example.com/done"));
+        assert!(is_valid_success_url("http://10.0.0.1:8080/"));
+        assert!(!is_valid_success_url("ftp://example.com/"));
+        assert!(!is_valid_success_url("/relative"));
+        assert!(!is_valid_success_url("javascript:alert(1)"));
+    }
+
+    #[tokio::test]
+    async fn test_portal_urls_follow_port() {
+        let (state, _rx) = test_app_state_with(|config| config.port = 8080);
+        let response = router(state.clone())
+            .oneshot(Request::get("/generate_204").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "http://192.168.42.1:8080/"
+        );
+
+        let (_, body) = get_body(router(state), "/info").await;
+        let info: OnboardingInfo = serde_json::from_str(&body).unwrap();
+        assert_eq!(info.portal_url, "http://192.168.42.1:8080");
+    }
+
+    #[tokio::test]
+    async fn test_tls_moves_the_portal_off_plain_http() {
+        let (state, _rx) = test_app_state_with(|config| {
+            config.tls = true;
+            config.tls_port = 8443;
+        });
+        for path in ["/", "/api/status", "/generate_204"] {
+            let response = redirect_router(state.clone())
+                .oneshot(Request::get(path).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::FOUND, "{path}");
+            assert_eq!(
+                response.headers()[header::LOCATION],
+                "https://192.168.42.1:8443/"
+            );
+        }
+
+        // Probes still answer over HTTP once online.
+        state.wifi_state.write().await.status = ConnectionStatus::Connected;
+        let (status, _) = get_body(redirect_router(state.clone()), "/generate_204").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = get_body(redirect_router(state), "/healthz").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_connecting_shows_time_remaining() {
+        let (state, _rx) = test_app_state();
+        {
+            let mut wifi_state = state.wifi_state.write().await;
+            wifi_state.status = ConnectionStatus::Connecting;
+            wifi_state.connecting_to = Some("Home".to_string());
+            wifi_state.connect_deadline =
+                Some(std::time::Instant::now() + std::time::Duration::from_secs(90));
+        }
+        let (_, body) = get_body(router(state), "/").await;
+        assert!(body.contains("Connecting to Home... (89s remaining)"));
+    }
+
+    #[tokio::test]
+    async fn test_connected_page_notes_ap_kept_up() {
+        let (state, _rx) = test_app_state();
+        {
+            let mut wifi_state = state.wifi_state.write().await;
+            wifi_state.status = ConnectionStatus::Connected;
+            wifi_state.connected_ssid = Some("Home".to_string());
+            wifi_state.ap_running = true;
+        }
+        let (_, body) = get_body(router(state), "/").await;
+        assert!(body.contains("The setup AP stays up, so this page remains available."));
+    }
+
+    #[tokio::test]
+    async fn test_minimal_page_while_ap_goes_down() {
+        let connecting = |keep_ap: bool| async move {
+            let (state, rx) = test_app_state_with(|config| config.keep_ap_after_connect = keep_ap);
+            {
+                let mut wifi_state = state.wifi_state.write().await;
+                wifi_state.status = ConnectionStatus::Connecting;
+                wifi_state.connecting_to = Some("Home".to_string());
+                wifi_state.ap_running = true;
+            }
+            (state, rx)
+        };
+
+        let (state, _rx) = connecting(false).await;
+        let (status, body) = get_body(router(state.clone()), "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Connecting to Home..."));
+        assert!(body.contains("The setup network will disappear shortly."));
+        assert!(!body.contains("<script>"));
+        assert!(!body.contains("network-list"));
+
+        // Probes and stray URLs get the same page instead of a redirect.
+        let (status, body) = get_body(router(state), "/generate_204").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("The setup network will disappear shortly."));
+
+        // A kept AP stays reachable, so the full portal keeps polling.
+        let (state, _rx) = connecting(true).await;
+        let (_, body) = get_body(router(state), "/").await;
+        assert!(body.contains("var KEEP_AP = true;"));
+        assert!(body.contains("network-list"));
+    }
+
+    #[tokio::test]
+    async fn test_switching_page_shows_backend_step() {
+        let (state, _rx) = test_app_state();
+        {
+            let mut wifi_state = state.wifi_state.write().await;
+            wifi_state.status = ConnectionStatus::SwitchingBackend;
+            wifi_state.backend_switch_step =
+                Some(crate::controller::BackendSwitchStep::StartingNew);
+            wifi_state.backend_switch_deadline =
+                Some(std::time::Instant::now() + std::time::Duration::from_secs(30));
+        }
+        let (_, body) = get_body(router(state), "/").await;
+        assert!(body.contains("Switching WiFi backend... ("));
+        assert!(body.contains("Step 2 of 3: starting the new WiFi service."));
+    }
+
+    #[tokio::test]
+    async fn test_portal_language() {
+        let german = |state| async move {
+            let request = Request::get("/")
+                .header("accept-language", "de-DE,de;q=0.9,en;q=0.8")
+                .body(Body::empty())
+                .unwrap();
+            let response = router(state).oneshot(request).await.unwrap();
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        };
+
+        let (state, _rx) = test_app_state();
+        let body = german(state).await;
+        assert!(body.contains(r#"<html class="dark" lang="de">"#));
+        assert!(body.contains("Erneut suchen"));
+        assert!(body.contains("WLAN-Einrichtung wird vorbereitet"));
+        // The script gets the same catalog.
+        assert!(body.contains(r#"var TEXT = {"status_preparing":"WLAN-Einrichtung"#));
+        assert!(!body.contains("__PORTAL_TEXT__"));
+
+        let (_, body) = get_body(router(test_app_state().0), "/").await;
+        assert!(body.contains(r#"lang="en""#));
+        assert!(body.contains("Scan Again"));
+
+        // --lang wins over the browser.
+        let (state, _rx) = test_app_state_with(|config| config.portal_lang = Some(Lang::En));
+        assert!(german(state).await.contains("Scan Again"));
+    }
+
+    #[tokio::test]
+    async fn test_info_describes_setup_ap() {
+        let (state, _rx) = test_app_state();
+        let (status, body) = get_body(router(state.clone()), "/info").await;
+        assert_eq!(status, StatusCode::OK);
+        let info: OnboardingInfo = serde_json::from_str(&body).unwrap();
+        assert_eq!(info.ap_ssid, state.config.ssid);
+        assert_eq!(info.portal_url, format!("http://{}", state.config.ap_ip));
+        assert_eq!(info.ap_password, None);
+        assert!(info.wifi_qr.unwrap().starts_with("WIFI:T:nopass;"));
+
+        // A secured AP, before it is up: the passphrase goes to AP clients only.
+        let (state, _rx) =
+            test_app_state_with(|config| config.ap_password = Some("setup-pass".to_string()));
+        let info_from = |peer: &str| {
+            let mut request = Request::get("/info").body(Body::empty()).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+            let app = router(state.clone());
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<OnboardingInfo>(&body).unwrap()
+            }
+        };
+
+        let info = info_from("192.168.42.23:50000").await;
+        assert!(info.ap_secured);
+        assert_eq!(info.ap_password.as_deref(), Some("setup-pass"));
+        assert!(info
+            .wifi_qr
+            .unwrap()
+            .starts_with("WIFI:T:WPA;S:HyperRecovery;P:setup-pass;"));
+
+        let info = info_from("10.0.0.5:50000").await;
+        assert!(info.ap_secured);
+        assert_eq!(info.ap_password, None);
+        assert_eq!(info.wifi_qr, None);
+    }
+
+    #[tokio::test]
+    async fn test_portal_requests_reset_idle_timer() {
+        let (state, _rx) = test_app_state();
+        let stale = std::time::Instant::now() - std::time::Duration::from_secs(600);
+        *state.last_activity.lock().unwrap() = stale;
+
+        get_body(router(state.clone()), "/healthz").await;
+        get_body(router(state.clone()), "/metrics").await;
+        assert_eq!(*state.last_activity.lock().unwrap(), stale);
+
+        get_body(router(state.clone()), "/api/status").await;
+        assert!(state.last_activity.lock().unwrap().elapsed().as_secs() < 60);
+    }
+
+    #[tokio::test]
+    async fn test_open_network_confirmation_flag() {
+        let (state, _rx) = test_app_state();
+        let (_, body) = get_body(router(state), "/").await;
+        assert!(body.contains(r#"data-confirm-open="true""#));
+        assert!(body.contains(r#"id="open-modal""#));
+
+        let (state, _rx) = test_app_state_with(|config| config.allow_open_silently = true);
+        let (_, body) = get_body(router(state), "/").await;
+        assert!(body.contains(r#"data-confirm-open="false""#));
+    }
+
+    #[tokio::test]
+    async fn test_portal_branding_is_escaped() {
+        let (state, _rx) = test_app_state_with(|config| {
+            config.portal_title = "Acme <Rescue>".to_string();
+            config.portal_subtitle = "Fix & Go".to_string();
+        });
+        let (_, body) = get_body(router(state), "/").await;
+        assert!(body.contains("<title>Acme &lt;Rescue&gt; - Fix &amp; Go</title>"));
+        assert!(body.contains("Acme &lt;Rescue&gt;</"));
+        assert!(!body.contains("<Rescue>"));
+        assert!(!body.contains("Hyper Recovery"));
+    }
+
+    #[tokio::test]
+    async fn test_empty_list_tells_failed_scan_from_no_networks() {
+        let (state, _rx) = test_app_state();
+        let (_, body) = get_body(router(state.clone()), "/").await;
+        assert!(body.contains(r#"<p class="empty-state">No networks found."#));
+
+        state
+            .wifi_state
+            .write()
+            .await
+            .fail_scan(&anyhow::anyhow!("wlan0 is not ready"));
+        let (_, body) = get_body(router(state), "/").await;
+        assert!(body.contains(r#"<p class="empty-state">Scan failed: wlan0 is not ready"#));
+    }
+
+    #[tokio::test]
+    async fn test_weak_networks_hidden_below_min_signal() {
+        let (state, _rx) = test_app_state_with(|config| config.min_signal = 20);
+        state.wifi_state.write().await.apply_scan(vec![
+            NetworkInfo::test_fixture("Strong", 80),
+            NetworkInfo::test_fixture("Faint", 10),
+        ]);
+
+        let (_, body) = get_body(router(state), "/").await;
+        assert!(body.contains("1 weak network hidden"));
+        assert!(body.contains(r#"data-ssid="Faint" data-band="2.4GHz" data-weak="true""#));
+        assert!(body.contains(r#"data-ssid="Strong" data-band="2.4GHz" data-weak="false""#));
+        assert_eq!(body.matches("network-row hidden").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_network_list_capped_to_strongest() {
+        let (state, _rx) = test_app_state_with(|config| config.portal_max_networks = 2);
+        state.wifi_state.write().await.apply_scan(vec![
+            NetworkInfo::test_fixture("A", 90),
+            NetworkInfo::test_fixture("B", 70),
+            NetworkInfo::test_fixture("C", 50),
+            NetworkInfo::test_fixture("D", 30),
+        ]);
+
+        let (_, body) = get_body(router(state.clone()), "/").await;
+        assert!(body.contains("Show all (2 more)"));
+        assert!(body.contains(
+            r#"data-ssid="B" data-band="2.4GHz" data-weak="false" data-overflow="false""#
+        ));
+        assert!(body.contains(
+            r#"data-ssid="C" data-band="2.4GHz" data-weak="false" data-overflow="true""#
+        ));
+
+        // The API still sees every network.
+        let (_, status) = get_body(router(state), "/api/status").await;
+        assert!(status.contains(r#""ssid":"D""#));
+    }
+
+    #[tokio::test]
+    async fn test_portal_poll_intervals_follow_config() {
+        let (state, _rx) = test_app_state_with(|config| config.portal_poll_ms = 5000);
+        let (_, body) = get_body(router(state), "/").await;
+        assert!(
+            body.contains("var TIMING = { idle: 5000, active: 2000, fast: 500, reload: 60000 };")
+        );
+        assert!(!body.contains("__PORTAL_TIMING__"));
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let (state, _rx) = test_app_state();
+        {
+            let mut wifi_state = state.wifi_state.write().await;
+            wifi_state.ap_running = true;
+            wifi_state.status = ConnectionStatus::AwaitingCredentials;
+        }
+        state.connect_stats.attempts.fetch_add(3, Ordering::Relaxed);
+        state.connect_stats.failures.fetch_add(1, Ordering::Relaxed);
+
+        let (status, body) = get_body(router(state), "/metrics").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("hyper_wifi_ap_running 1\n"));
+        assert!(body.contains("hyper_wifi_networks_visible 0\n"));
+        assert!(body.contains("hyper_wifi_status{state=\"AwaitingCredentials\"} 1\n"));
+        assert!(body.contains("hyper_wifi_status{state=\"Connected\"} 0\n"));
+        assert!(body.contains("hyper_wifi_connect_attempts_total 3\n"));
+        assert!(body.contains("hyper_wifi_connect_failures_total 1\n"));
+    }
+
+    #[tokio::test]
+    async fn test_api_failures_use_status_codes() {
+        let (state, rx) = test_app_state();
+
+        let (status, body) = post_json_body(router(state.clone()), "/api/connect", "{").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let reply: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(reply["success"], false);
+
+        let bad_bssid = r#"{"ssid":"Home","password":"hunter22","bssid":"nope"}"#;
+        assert_eq!(
+            post_json(router(state.clone()), "/api/connect", bad_bssid).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            post_json(router(state.clone()), "/api/backend", r#"{"backend":"nm"}"#).await,
+            StatusCode::BAD_REQUEST
+        );
+
+        // The controller is gone: nothing can take the command.
+        drop(rx);
+        let body = r#"{"ssid":"Home","password":"hunter22"}"#;
+        assert_eq!(
+            post_json(router(state.clone()), "/api/connect", body).await,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert!(!state.connect_in_progress.load(Ordering::SeqCst));
+        assert_eq!(
+            post_json(router(state.clone()), "/api/scan", "").await,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        let (status, body) =
+            post_json_body(router(state), "/api/backend", r#"{"backend":"iwd"}"#).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(body.contains("Failed to send backend switch command"));
+    }
+
+    #[tokio::test]
+    async fn test_connect_rejected_while_in_progress() {
+        let (state, mut rx) = test_app_state();
+        let body = r#"{"ssid":"Home","password":"hunter22"}"#;
+
+        assert_eq!(
+            post_json(router(state.clone()), "/api/connect", body).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            post_json(router(state.clone()), "/api/connect", body).await,
+            StatusCode::CONFLICT
+        );
+        assert!(matches!(rx.try_recv(), Ok(ControlCommand::Connect { .. })));
+        assert!(rx.try_recv().is_err());
+
+        state.connect_in_progress.store(false, Ordering::SeqCst);
+        assert_eq!(
+            post_json(router(state), "/api/connect", body).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_password_rejected_for_secured_network() {
+        let (state, mut rx) = test_app_state();
+        state
+            .wifi_state
+            .write()
+            .await
+            .apply_scan(vec![NetworkInfo::test_fixture("Home", 70)]);
+
+        let (status, reply) = post_json_body(
+            router(state.clone()),
+            "/api/connect",
+            r#"{"ssid":"Home","password":""}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(reply.contains("Password required for Home"));
+        assert!(rx.try_recv().is_err());
+        assert!(!state.connect_in_progress.load(Ordering::SeqCst));
+
+        // Unlisted (hidden) networks are left for NetworkManager to judge.
+        assert_eq!(
+            post_json(
+                router(state),
+                "/api/connect",
+                r#"{"ssid":"Attic","password":"","hidden":true}"#
+            )
+            .await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_manual_security_checked_against_credentials() {
+        let (state, mut rx) = test_app_state();
+
+        let (status, reply) = post_json_body(
+            router(state.clone()),
+            "/api/connect",
+            r#"{"ssid":"Attic","password":"hunter22","hidden":true,"security":"open"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(reply.contains("Open networks take no password"));
+
+        // A picked WPA type allows probing with a blank password.
+        let status = post_json(
+            router(state),
+            "/api/connect",
+            r#"{"ssid":"Attic","password":"","hidden":true,"security":"wpa3"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        match rx.try_recv() {
+            Ok(ControlCommand::Connect { security, .. }) => {
+                assert_eq!(security, Some(crate::controller::ManualSecurity::Wpa3));
+            }
+            _ => panic!("expected Connect command"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sequential_connects_get_increasing_attempt_ids() {
+        let (state, mut rx) = test_app_state();
+        let body = r#"{"ssid":"Home","password":"hunter22"}"#;
+
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let (status, reply) = post_json_body(router(state.clone()), "/api/connect", body).await;
+            assert_eq!(status, StatusCode::OK);
+            let reply: serde_json::Value = serde_json::from_str(&reply).unwrap();
+            let id = reply["attempt_id"].as_u64().unwrap();
+            match rx.try_recv() {
+                Ok(ControlCommand::Connect { attempt_id, .. }) => assert_eq!(attempt_id, id),
+                other => panic!("expected Connect, got {:?}", other),
+            }
+            ids.push(id);
+            state.connect_in_progress.store(false, Ordering::SeqCst);
+        }
+        assert!(ids[1] > ids[0]);
+
+        // Rejected requests don't consume an id.
+        state.connect_in_progress.store(true, Ordering::SeqCst);
+        let (status, reply) = post_json_body(router(state.clone()), "/api/connect", body).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(!reply.contains("attempt_id"));
+        assert_eq!(state.allocate_attempt_id(), ids[1] + 1);
+    }
+
+    #[tokio::test]
+    async fn test_scan_is_debounced() {
+        let (state, mut rx) = test_app_state();
+
+        assert_eq!(
+            post_json(router(state.clone()), "/api/scan", "").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            post_json(router(state), "/api/scan", "").await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert!(matches!(rx.try_recv(), Ok(ControlCommand::Scan)));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_index_rescans_stale_list_in_background() {
+        let (state, mut rx) = test_app_state_with(|config| config.sta_interface = "wlan1".into());
+
+        let (status, body) = get_body(router(state.clone()), "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Networks not scanned yet"));
+        assert!(body.contains("scan-age stale"));
+        assert!(matches!(rx.try_recv(), Ok(ControlCommand::Scan)));
+
+        // Only one rescan is queued until the control loop finishes it.
+        get_body(router(state.clone()), "/").await;
+        assert!(rx.try_recv().is_err());
+
+        state.scan_in_progress.store(false, Ordering::SeqCst);
+        state.wifi_state.write().await.apply_scan(Vec::new());
+        let (_, body) = get_body(router(state), "/").await;
+        assert!(body.contains("Networks last scanned 0s ago"));
+        assert!(!body.contains("scan-age stale"));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_index_without_spare_radio_does_not_rescan() {
+        let (state, mut rx) = test_app_state();
+        get_body(router(state), "/").await;
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -1,21 +1,113 @@
 //! Web routes and handlers
 
 use super::components;
-use crate::controller::{AppState, ControlCommand, WifiBackend, WifiStateSnapshot};
+use super::i18n::Lang;
+use crate::controller::{
+    check_connect_cooldown, check_password_given, parse_bssid, saved_password, valid_ssid_bytes,
+    AdapterReport, AppState, ControlCommand, EapTls, ManualSecurity, WifiBackend,
+    WifiStateSnapshot,
+};
 use axum::{
-    extract::State,
-    response::{Html, IntoResponse},
+    extract::{rejection::JsonRejection, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-/// Main portal page (SSR)
-pub async fn index(State(state): State<Arc<AppState>>) -> Html<String> {
-    let wifi_state = state.wifi_state.read().await;
-    let snapshot = WifiStateSnapshot::from(&*wifi_state);
+/// Minimum spacing between portal-triggered rescans.
+const SCAN_DEBOUNCE: Duration = Duration::from_secs(5);
+
+/// Main portal page (SSR); never cached since it embeds live status
+pub async fn index(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    if state.ap_going_down().await {
+        return ap_going_down_page(&state, &headers).await;
+    }
+    if let Some(url) = super::success_url(&state).await {
+        return super::redirect_to(url.to_string());
+    }
+
+    let snapshot = {
+        let wifi_state = state.wifi_state.read().await;
+        WifiStateSnapshot::from(&*wifi_state)
+    };
+
+    let scan_ttl = state.config.scan_ttl;
+    let stale = snapshot
+        .last_scan_secs_ago
+        .is_none_or(|age| age >= scan_ttl);
+    if stale {
+        request_background_scan(&state);
+    }
+
+    // An empty list may mean the adapter never came up rather than no networks.
+    let adapter_problems = if snapshot.available_networks.is_empty() && !state.config.simulate {
+        tokio::task::spawn_blocking(|| AdapterReport::collect().problems())
+            .await
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    html_response(components::render_portal_page(
+        &snapshot,
+        &state.config,
+        &adapter_problems,
+        portal_lang(&state, &headers),
+    ))
+}
 
-    Html(components::render_portal_page(&snapshot))
+/// The minimal page served instead of the portal while the setup AP goes down.
+pub async fn ap_going_down_page(state: &AppState, headers: &HeaderMap) -> Response {
+    let snapshot = {
+        let wifi_state = state.wifi_state.read().await;
+        WifiStateSnapshot::from(&*wifi_state)
+    };
+    html_response(components::render_ap_going_down_page(
+        &snapshot,
+        &state.config,
+        portal_lang(state, headers),
+    ))
+}
+
+fn html_response(html: String) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/html; charset=utf-8"),
+            (header::CACHE_CONTROL, "no-store"),
+        ],
+        html,
+    )
+        .into_response()
+}
+
+/// `--lang` when given, otherwise the best match for the browser's Accept-Language.
+fn portal_lang(state: &AppState, headers: &HeaderMap) -> Lang {
+    state.config.portal_lang.unwrap_or_else(|| {
+        Lang::negotiate(
+            headers
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok()),
+        )
+    })
+}
+
+/// Queue a rescan for the next page load without waiting on it.
+///
+/// Only a spare station radio can scan while the AP is up, so without one
+/// this does nothing.
+fn request_background_scan(state: &AppState) {
+    if state.config.sta_interface().is_none() || state.scan_in_progress.swap(true, Ordering::SeqCst)
+    {
+        return;
+    }
+
+    if state.command_tx.try_send(ControlCommand::Scan).is_err() {
+        state.scan_in_progress.store(false, Ordering::SeqCst);
+    }
 }
 
 /// API: Get current status
@@ -30,6 +122,21 @@ pub struct ConnectRequest {
     password: String,
     #[serde(default = "default_save")]
     save: bool,
+    #[serde(default)]
+    hidden: bool,
+    #[serde(default)]
+    bssid: Option<String>,
+    #[serde(default)]
+    eap_tls: Option<EapTls>,
+    /// Raw SSID of the clicked row; absent for manual entry
+    #[serde(default)]
+    ssid_bytes: Option<Vec<u8>>,
+    /// Security chosen in the manual-entry form
+    #[serde(default)]
+    security: Option<ManualSecurity>,
+    /// Reuse the saved password instead of `password`
+    #[serde(default)]
+    use_saved: bool,
 }
 
 fn default_save() -> bool {
@@ -42,6 +149,37 @@ pub struct ApiResponse {
     message: String,
 }
 
+/// Reply to `/api/connect`
+#[derive(Debug, Serialize)]
+pub struct ConnectResponse {
+    success: bool,
+    message: String,
+    /// Matches the snapshot's `current_attempt_id` once the attempt starts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attempt_id: Option<u64>,
+}
+
+impl ConnectResponse {
+    fn rejected(status: StatusCode, message: String) -> (StatusCode, Json<ConnectResponse>) {
+        let body = ConnectResponse {
+            success: false,
+            message,
+            attempt_id: None,
+        };
+        (status, Json(body))
+    }
+}
+
+/// Failure reply with the status code a client can branch on; the JSON body
+/// still carries the message for the portal and for curl.
+fn api_error(status: StatusCode, message: String) -> (StatusCode, Json<ApiResponse>) {
+    let body = ApiResponse {
+        success: false,
+        message,
+    };
+    (status, Json(body))
+}
+
 #[derive(Debug, Deserialize)]
 pub struct BackendRequest {
     backend: WifiBackend,
@@ -50,42 +188,158 @@ pub struct BackendRequest {
 /// API: Connect to network
 pub async fn api_connect(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<ConnectRequest>,
+    req: Result<Json<ConnectRequest>, JsonRejection>,
 ) -> impl IntoResponse {
+    let req = match req {
+        Ok(Json(req)) => req,
+        // A body that doesn't parse is bad input too, answered in JSON
+        // instead of axum's plain-text rejection.
+        Err(rejection) => {
+            return ConnectResponse::rejected(StatusCode::BAD_REQUEST, rejection.body_text())
+        }
+    };
+
+    let bssid = match req.bssid.as_deref().map(parse_bssid) {
+        None => None,
+        Some(Some(bssid)) => Some(bssid),
+        Some(None) => {
+            return ConnectResponse::rejected(StatusCode::BAD_REQUEST, "Invalid BSSID".to_string());
+        }
+    };
+
+    if let Some(Err(e)) = req.eap_tls.as_ref().map(EapTls::validate) {
+        return ConnectResponse::rejected(StatusCode::BAD_REQUEST, format!("{:#}", e));
+    }
+
+    if req
+        .ssid_bytes
+        .as_deref()
+        .is_some_and(|b| !valid_ssid_bytes(b))
+    {
+        return ConnectResponse::rejected(
+            StatusCode::BAD_REQUEST,
+            "Invalid SSID bytes".to_string(),
+        );
+    }
+
+    let password = if req.use_saved {
+        match saved_password(&state.config, &req.ssid) {
+            Ok(password) => password,
+            Err(e) => {
+                return ConnectResponse::rejected(StatusCode::BAD_REQUEST, format!("{:#}", e));
+            }
+        }
+    } else {
+        req.password
+    };
+
+    if let Err(e) = check_password_given(
+        &*state.wifi_state.read().await,
+        &req.ssid,
+        &password,
+        req.eap_tls.is_some(),
+        req.security,
+    ) {
+        return ConnectResponse::rejected(StatusCode::BAD_REQUEST, format!("{:#}", e));
+    }
+
+    if let Err(e) = check_connect_cooldown(&*state.wifi_state.read().await, &req.ssid) {
+        return ConnectResponse::rejected(StatusCode::TOO_MANY_REQUESTS, format!("{:#}", e));
+    }
+
+    // Queued connects would each tear down and rebuild the AP in turn.
+    if state
+        .connect_in_progress
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return ConnectResponse::rejected(
+            StatusCode::CONFLICT,
+            "connection already in progress".to_string(),
+        );
+    }
+
+    let attempt_id = state.allocate_attempt_id();
     let result = state
         .command_tx
         .send(ControlCommand::Connect {
             ssid: req.ssid.clone(),
-            password: req.password,
+            password,
             save: req.save,
+            hidden: req.hidden,
+            bssid,
+            eap_tls: req.eap_tls,
+            dry_run: false,
+            ssid_bytes: req.ssid_bytes,
+            security: req.security,
+            attempt_id,
         })
         .await;
 
     match result {
-        Ok(()) => Json(ApiResponse {
-            success: true,
-            message: format!("Connecting to {}...", req.ssid),
-        }),
-        Err(e) => Json(ApiResponse {
-            success: false,
-            message: format!("Failed to send command: {}", e),
-        }),
+        Ok(()) => (
+            StatusCode::OK,
+            Json(ConnectResponse {
+                success: true,
+                message: format!("Connecting to {}...", req.ssid),
+                attempt_id: Some(attempt_id),
+            }),
+        ),
+        Err(e) => {
+            state.connect_in_progress.store(false, Ordering::SeqCst);
+            ConnectResponse::rejected(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to send command: {}", e),
+            )
+        }
+    }
+}
+
+/// API: Abort the connect attempt in progress
+pub async fn api_cancel_connect(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    if state.cancel_connect() {
+        (
+            StatusCode::OK,
+            Json(ApiResponse {
+                success: true,
+                message: "Cancelling connection...".to_string(),
+            }),
+        )
+    } else {
+        api_error(
+            StatusCode::CONFLICT,
+            "No connection attempt in progress".to_string(),
+        )
     }
 }
 
 /// API: Trigger rescan
 pub async fn api_scan(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    {
+        let mut last = state.last_scan_request.lock().unwrap();
+        if last.is_some_and(|at| at.elapsed() < SCAN_DEBOUNCE) {
+            return api_error(
+                StatusCode::TOO_MANY_REQUESTS,
+                "scan requested too recently".to_string(),
+            );
+        }
+        *last = Some(Instant::now());
+    }
+
     let result = state.command_tx.send(ControlCommand::Scan).await;
 
     match result {
-        Ok(()) => Json(ApiResponse {
-            success: true,
-            message: "Scan initiated".to_string(),
-        }),
-        Err(e) => Json(ApiResponse {
-            success: false,
-            message: format!("Failed to send command: {}", e),
-        }),
+        Ok(()) => (
+            StatusCode::OK,
+            Json(ApiResponse {
+                success: true,
+                message: "Scan initiated".to_string(),
+            }),
+        ),
+        Err(e) => api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to send command: {}", e),
+        ),
     }
 }
 
@@ -97,8 +351,12 @@ pub async fn api_scan(State(state): State<Arc<AppState>>) -> impl IntoResponse {
 /// general network/host control surface).
 pub async fn api_backend(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<BackendRequest>,
+    req: Result<Json<BackendRequest>, JsonRejection>,
 ) -> impl IntoResponse {
+    let req = match req {
+        Ok(Json(req)) => req,
+        Err(rejection) => return api_error(StatusCode::BAD_REQUEST, rejection.body_text()),
+    };
     let result = state
         .command_tx
         .send(ControlCommand::SwitchBackend {
@@ -107,16 +365,19 @@ pub async fn api_backend(
         .await;
 
     match result {
-        Ok(()) => Json(ApiResponse {
-            success: true,
-            message: format!(
-                "Switching WiFi backend to {}. The setup AP may restart; reconnect if needed.",
-                req.backend.as_nm_value()
-            ),
-        }),
-        Err(e) => Json(ApiResponse {
-            success: false,
-            message: format!("Failed to send backend switch command: {}", e),
-        }),
+        Ok(()) => (
+            StatusCode::OK,
+            Json(ApiResponse {
+                success: true,
+                message: format!(
+                    "Switching WiFi backend to {}. The setup AP may restart; reconnect if needed.",
+                    req.backend.as_nm_value()
+                ),
+            }),
+        ),
+        Err(e) => api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to send backend switch command: {}", e),
+        ),
     }
 }